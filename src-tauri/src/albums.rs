@@ -0,0 +1,769 @@
+use crate::artists::ArtistCredits;
+use crate::import;
+use crate::search;
+use crate::settings;
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use uuid::Uuid;
+
+/// Setting holding the album artist shown for compilations
+pub const VARIOUS_ARTISTS_SETTING: &str = "albums.various_artists_name";
+pub const DEFAULT_VARIOUS_ARTISTS: &str = "Various Artists";
+/// Album artist tags that already mean "no single artist"
+const VARIOUS_ARTISTS_SPELLINGS: [&str; 3] = ["various artists", "various", "va"];
+/// Stands in for the album artist in keys of compilations; normalised names
+/// never contain it
+const COMPILATION_KEY: &str = "*";
+
+#[derive(Debug, Serialize, Clone)]
+pub struct AlbumSummary {
+    pub id: String,
+    pub title: String,
+    pub album_artist: Option<String>,
+    pub year: Option<i32>,
+    pub is_compilation: bool,
+    pub track_count: i64,
+    pub disc_count: i64,
+    pub duration_seconds: f64,
+    /// Cover of the first track in album order that has one
+    pub cover_art_path: Option<String>,
+    pub cover_art_thumb_path: Option<String>,
+    /// Library tracks ordered by disc, then track number
+    pub track_ids: Vec<String>,
+}
+
+/// The fields of a track that decide which album it belongs to
+struct AlbumFields {
+    album: Option<String>,
+    /// None for compilations grouped under the "Various Artists" name
+    album_artist: Option<String>,
+    year: Option<i32>,
+    musicbrainz_albumid: Option<String>,
+    is_compilation: bool,
+}
+
+pub fn ensure_album_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS albums (
+            id TEXT PRIMARY KEY,
+            album_key TEXT NOT NULL UNIQUE,
+            title TEXT NOT NULL,
+            album_artist TEXT,
+            year INTEGER,
+            musicbrainz_albumid TEXT,
+            is_compilation INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL
+        );",
+    )
+    .map_err(|error| error.to_string())?;
+
+    let _ = conn.execute(
+        "ALTER TABLE albums ADD COLUMN is_compilation INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+
+    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN album_id TEXT", []);
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_tracks_album_id ON tracks(album_id)",
+        [],
+    )
+    .map_err(|error| error.to_string())?;
+    // Matches the lookup in `untagged_folder_tracks`, which runs per import
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_tracks_album_title
+         ON tracks(TRIM(album) COLLATE NOCASE)",
+        [],
+    )
+    .map_err(|error| error.to_string())?;
+
+    Ok(())
+}
+
+/// Link a track to its album from its stored tags, creating the album when
+/// it is the first track seen and dropping the one it leaves when that is now
+/// empty. Tracks without an album title are unlinked.
+/// When the track turns out to share its folder and album title with tracks
+/// by other artists, those tracks move to the same compilation.
+/// `various_artists` is the `various_artists_name` setting, read once by
+/// callers that link many tracks.
+pub fn assign_album(
+    conn: &Connection,
+    track_id: &str,
+    various_artists: &str,
+) -> Result<Option<String>, String> {
+    let Some((fields, folder_tracks)) = album_fields(conn, track_id, various_artists)? else {
+        return Ok(None);
+    };
+
+    let album_id = match album_key(&fields) {
+        Some(key) => Some(album_id(conn, &key, &fields)?),
+        None => None,
+    };
+    for id in std::iter::once(track_id).chain(folder_tracks.iter().map(String::as_str)) {
+        let previous: Option<String> = conn
+            .prepare_cached("SELECT album_id FROM tracks WHERE id = ?1")
+            .and_then(|mut stmt| stmt.query_row([id], |row| row.get(0)))
+            .map_err(|error| error.to_string())?;
+        conn.execute(
+            "UPDATE tracks SET album_id = ?1 WHERE id = ?2",
+            (&album_id, id),
+        )
+        .map_err(|error| error.to_string())?;
+        // An album left without tracks would keep claiming its key
+        if let Some(previous) = previous.filter(|previous| Some(previous) != album_id.as_ref()) {
+            conn.prepare_cached(
+                "DELETE FROM albums WHERE id = ?1
+                   AND NOT EXISTS (SELECT 1 FROM tracks WHERE album_id = ?1)",
+            )
+            .and_then(|mut stmt| stmt.execute([&previous]))
+            .map_err(|error| error.to_string())?;
+        }
+    }
+
+    Ok(album_id)
+}
+
+/// Album artist shown for compilations, falling back to
+/// `DEFAULT_VARIOUS_ARTISTS`
+pub fn various_artists_name(conn: &Connection) -> Result<String, String> {
+    Ok(settings::get_setting(conn, VARIOUS_ARTISTS_SETTING)?
+        .unwrap_or_else(|| DEFAULT_VARIOUS_ARTISTS.to_string()))
+}
+
+/// Albums with at least one library track, for the album grid
+pub fn load_albums(db_path: &str) -> Result<Vec<AlbumSummary>, String> {
+    if !Path::new(db_path).exists() {
+        return Ok(Vec::new());
+    }
+
+    let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    import::ensure_schema(&conn)?;
+
+    let various_artists = various_artists_name(&conn)?;
+    let mut albums = Vec::new();
+    let mut index_by_id = HashMap::new();
+    {
+        let mut stmt = conn
+            .prepare(
+                "SELECT a.id, a.title, COALESCE(a.album_artist, ?2), a.year, a.is_compilation,
+                        COUNT(t.id),
+                        COUNT(DISTINCT COALESCE(t.disc_number, 1)),
+                        SUM(COALESCE(t.duration_seconds, 0))
+                 FROM albums a
+                 JOIN tracks t ON t.album_id = a.id
+                 WHERE t.import_status = ?1
+                 GROUP BY a.id
+                 ORDER BY COALESCE(a.album_artist, ?2) COLLATE NOCASE, a.year,
+                          a.title COLLATE NOCASE",
+            )
+            .map_err(|error| error.to_string())?;
+        let rows = stmt
+            .query_map((import::STATUS_ACCEPTED, &various_artists), |row| {
+                Ok(AlbumSummary {
+                    id: row.get(0)?,
+                    title: row.get(1)?,
+                    album_artist: row.get(2)?,
+                    year: row.get(3)?,
+                    is_compilation: row.get(4)?,
+                    track_count: row.get(5)?,
+                    disc_count: row.get(6)?,
+                    duration_seconds: row.get(7)?,
+                    cover_art_path: None,
+                    cover_art_thumb_path: None,
+                    track_ids: Vec::new(),
+                })
+            })
+            .map_err(|error| error.to_string())?;
+        for row in rows {
+            let album = row.map_err(|error| error.to_string())?;
+            index_by_id.insert(album.id.clone(), albums.len());
+            albums.push(album);
+        }
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT album_id, id, cover_art_path, cover_art_thumb_path
+             FROM tracks
+             WHERE album_id IS NOT NULL AND import_status = ?1
+             ORDER BY COALESCE(disc_number, 1), track_number IS NULL, track_number,
+                      title COLLATE NOCASE",
+        )
+        .map_err(|error| error.to_string())?;
+    let rows = stmt
+        .query_map([import::STATUS_ACCEPTED], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })
+        .map_err(|error| error.to_string())?;
+    for row in rows {
+        let (album_id, track_id, cover_art_path, cover_art_thumb_path) =
+            row.map_err(|error| error.to_string())?;
+        let Some(album) = index_by_id.get(&album_id).map(|index| &mut albums[*index]) else {
+            continue;
+        };
+        if album.cover_art_path.is_none() && cover_art_path.is_some() {
+            album.cover_art_path = cover_art_path;
+            album.cover_art_thumb_path = cover_art_thumb_path;
+        }
+        album.track_ids.push(track_id);
+    }
+
+    Ok(albums)
+}
+
+/// Rebuild albums from every track's tags, for tracks imported before albums
+/// existed or grouped under an older album key. Album ids change. Returns the
+/// number of tracks linked.
+pub fn backfill_albums(conn: &Connection) -> Result<usize, String> {
+    let track_ids: Vec<String> = conn
+        .prepare(
+            "SELECT id FROM tracks
+             WHERE album IS NOT NULL AND album != '' AND album != ?1",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map([import::UNKNOWN_ALBUM], |row| row.get(0))?
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .map_err(|error| error.to_string())?;
+
+    let various_artists = various_artists_name(conn)?;
+    conn.execute("UPDATE tracks SET album_id = NULL", [])
+        .map_err(|error| error.to_string())?;
+    conn.execute("DELETE FROM albums", [])
+        .map_err(|error| error.to_string())?;
+    let mut linked = 0;
+    for track_id in &track_ids {
+        if assign_album(conn, track_id, &various_artists)?.is_some() {
+            linked += 1;
+        }
+    }
+
+    Ok(linked)
+}
+
+/// Grouping fields for a track, with the other tracks of its folder that
+/// belong to the same compilation when that is how it was detected.
+///
+/// Without an album artist the first main artist stands in, so "A feat. B"
+/// and "A" tracks still share an album. Compilations are recognised from the
+/// iTunes compilation flag, the MusicBrainz release type, a "Various
+/// Artists" album artist, or untagged tracks by different artists sharing an
+/// album title in one folder.
+fn album_fields(
+    conn: &Connection,
+    track_id: &str,
+    various_artists: &str,
+) -> Result<Option<(AlbumFields, Vec<String>)>, String> {
+    let row = conn
+        .query_row(
+            "SELECT album, album_artist, year, musicbrainz_albumid, musicbrainz_albumtype,
+                    is_compilation, artist, source_path
+             FROM tracks WHERE id = ?1",
+            [track_id],
+            |row| {
+                Ok((
+                    row.get::<_, Option<String>>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<i32>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, Option<bool>>(5)?,
+                    row.get::<_, Option<String>>(6)?,
+                    row.get::<_, Option<String>>(7)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(|error| error.to_string())?;
+    let Some((album, album_artist, year, mbid, album_type, flag, artist, source_path)) = row else {
+        return Ok(None);
+    };
+
+    let album = album
+        .map(|album| album.trim().to_string())
+        .filter(|album| !album.is_empty() && album != import::UNKNOWN_ALBUM);
+    let album_artist = album_artist
+        .map(|artist| artist.trim().to_string())
+        .filter(|artist| !artist.is_empty() && artist != import::UNKNOWN_ARTIST);
+    let tagged_various = album_artist
+        .as_deref()
+        .is_some_and(|name| is_various_artists(name, various_artists));
+    let main_artist = first_main_artist(artist.as_deref());
+
+    let mut is_compilation = flag.unwrap_or(false)
+        || tagged_various
+        || album_type
+            .as_deref()
+            .is_some_and(|value| value.to_ascii_lowercase().contains("compilation"));
+    let mut folder_tracks = Vec::new();
+    let untagged_album = album.as_deref().filter(|_| album_artist.is_none());
+    if let Some(album) = untagged_album.filter(|_| !is_compilation) {
+        let others = untagged_folder_tracks(conn, track_id, album, source_path.as_deref())?;
+        let own_key = main_artist.as_deref().map(search::normalize_fragment);
+        let differs = |other: &Option<String>| {
+            let other = other.as_deref().map(search::normalize_fragment);
+            own_key.is_some() && other.is_some() && other != own_key
+        };
+        if others.iter().any(|(_, other)| differs(other)) {
+            is_compilation = true;
+            folder_tracks = others.into_iter().map(|(id, _)| id).collect();
+        }
+    }
+
+    let album_artist = if is_compilation && (album_artist.is_none() || tagged_various) {
+        None
+    } else {
+        album_artist.or(main_artist)
+    };
+
+    Ok(Some((
+        AlbumFields {
+            album,
+            album_artist,
+            year,
+            musicbrainz_albumid: mbid,
+            is_compilation,
+        },
+        folder_tracks,
+    )))
+}
+
+/// Other tracks without an album artist in the same folder and with the same
+/// album title, with their first main artist
+fn untagged_folder_tracks(
+    conn: &Connection,
+    track_id: &str,
+    album: &str,
+    source_path: Option<&str>,
+) -> Result<Vec<(String, Option<String>)>, String> {
+    let Some(folder) = source_path.and_then(|path| Path::new(path).parent()) else {
+        return Ok(Vec::new());
+    };
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT id, artist, source_path FROM tracks
+             WHERE id != ?1 AND TRIM(album) = ?2 COLLATE NOCASE
+               AND (album_artist IS NULL OR TRIM(album_artist) = '')",
+        )
+        .map_err(|error| error.to_string())?;
+    let rows = stmt
+        .query_map((track_id, album), |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })
+        .map_err(|error| error.to_string())?;
+
+    let mut tracks = Vec::new();
+    for row in rows {
+        let (id, artist, path) = row.map_err(|error| error.to_string())?;
+        if path.as_deref().and_then(|path| Path::new(path).parent()) == Some(folder) {
+            tracks.push((id, first_main_artist(artist.as_deref())));
+        }
+    }
+    Ok(tracks)
+}
+
+fn first_main_artist(artist: Option<&str>) -> Option<String> {
+    ArtistCredits::from_tags(artist, &[], None, &[], None)
+        .main
+        .into_iter()
+        .next()
+}
+
+fn is_various_artists(name: &str, various_artists: &str) -> bool {
+    let key = search::normalize_fragment(name);
+    VARIOUS_ARTISTS_SPELLINGS.contains(&key.as_str())
+        || key == search::normalize_fragment(various_artists)
+}
+
+/// The album artist and title, compared without case or punctuation so small
+/// spelling differences between tracks do not split the album. The year is
+/// left out: reissues and tracks tagged with a single's year would otherwise
+/// end up on albums of their own.
+fn album_key(fields: &AlbumFields) -> Option<String> {
+    let album = fields.album.as_deref()?;
+    let normalize = |value: &str| {
+        let key = search::normalize_fragment(value);
+        if key.is_empty() {
+            value.trim().to_lowercase()
+        } else {
+            key
+        }
+    };
+    let artist = match (&fields.album_artist, fields.is_compilation) {
+        (Some(artist), _) => normalize(artist),
+        (None, true) => COMPILATION_KEY.to_string(),
+        (None, false) => String::new(),
+    };
+    Some(format!("tag:{}|{}", artist, normalize(album)))
+}
+
+fn musicbrainz_albumid(fields: &AlbumFields) -> Option<String> {
+    fields
+        .musicbrainz_albumid
+        .as_deref()
+        .map(str::trim)
+        .filter(|mbid| !mbid.is_empty())
+        .map(str::to_ascii_lowercase)
+}
+
+/// The album for `key`, or the one carrying the track's MusicBrainz release
+/// id. Tracks with and without a release id share an album when their tags
+/// agree; only a different release id under the same artist and title gets
+/// an album of its own, keyed by that id. The album's shown title and artist
+/// follow the most recently linked track, so fixing the spelling on every
+/// track updates the album too.
+fn album_id(conn: &Connection, key: &str, fields: &AlbumFields) -> Result<String, String> {
+    let mbid = musicbrainz_albumid(fields);
+    let by_mbid = match &mbid {
+        Some(mbid) => conn
+            .prepare_cached("SELECT id FROM albums WHERE musicbrainz_albumid = ?1")
+            .and_then(|mut stmt| stmt.query_row([mbid], |row| row.get(0)).optional())
+            .map_err(|error| error.to_string())?,
+        None => None,
+    };
+    let by_key: Option<(String, Option<String>)> = conn
+        .prepare_cached("SELECT id, musicbrainz_albumid FROM albums WHERE album_key = ?1")
+        .and_then(|mut stmt| {
+            stmt.query_row([key], |row| Ok((row.get(0)?, row.get(1)?)))
+                .optional()
+        })
+        .map_err(|error| error.to_string())?;
+
+    let existing = by_mbid.or_else(|| match &by_key {
+        Some((id, other)) if mbid.is_none() || other.is_none() || *other == mbid => {
+            Some(id.clone())
+        }
+        _ => None,
+    });
+    if let Some(id) = existing {
+        conn.prepare_cached(
+            "UPDATE albums SET
+                title = ?1,
+                album_artist = ?2,
+                year = ?3,
+                musicbrainz_albumid = COALESCE(musicbrainz_albumid, ?4),
+                is_compilation = MAX(is_compilation, ?5)
+             WHERE id = ?6",
+        )
+        .and_then(|mut stmt| {
+            stmt.execute((
+                fields.album.as_deref().unwrap_or_default(),
+                &fields.album_artist,
+                fields.year,
+                &mbid,
+                fields.is_compilation,
+                &id,
+            ))
+        })
+        .map_err(|error| error.to_string())?;
+        return Ok(id);
+    }
+
+    // The tag key belongs to another release
+    let key = match (&by_key, &mbid) {
+        (Some(_), Some(mbid)) => format!("mb:{}", mbid),
+        _ => key.to_string(),
+    };
+    let id = Uuid::new_v4().to_string();
+    conn.prepare_cached(
+        "INSERT INTO albums (
+            id, album_key, title, album_artist, year, musicbrainz_albumid, is_compilation,
+            created_at
+         ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+    )
+    .and_then(|mut stmt| {
+        stmt.execute((
+            &id,
+            key,
+            fields.album.as_deref().unwrap_or_default(),
+            &fields.album_artist,
+            fields.year,
+            &mbid,
+            fields.is_compilation,
+            import::current_timestamp(),
+        ))
+    })
+    .map_err(|error| error.to_string())?;
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(album_artist: Option<&str>, album: &str, year: Option<i32>) -> AlbumFields {
+        AlbumFields {
+            album: Some(album.to_string()),
+            album_artist: album_artist.map(str::to_string),
+            year,
+            musicbrainz_albumid: None,
+            is_compilation: false,
+        }
+    }
+
+    fn insert_track(conn: &Connection, id: &str, album: &str, year: i32, mbid: Option<&str>) {
+        conn.execute(
+            "INSERT INTO tracks (id, album, album_artist, year, musicbrainz_albumid, source_path)
+             VALUES (?1, ?2, 'The Band', ?3, ?4, ?5)",
+            rusqlite::params![
+                id,
+                album,
+                year,
+                mbid,
+                format!("/music/{}/{}.flac", year, id)
+            ],
+        )
+        .unwrap();
+    }
+
+    fn album_of(conn: &Connection, track_id: &str) -> Option<String> {
+        conn.query_row(
+            "SELECT album_id FROM tracks WHERE id = ?1",
+            [track_id],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn album_keys_ignore_year_case_and_punctuation() {
+        let key = album_key(&fields(Some("The Band"), "Songs, Vol. 1", Some(1999)));
+        assert_eq!(
+            key,
+            album_key(&fields(Some("the band"), "Songs Vol 1", Some(2011)))
+        );
+        assert_eq!(
+            key,
+            album_key(&fields(Some("The Band"), "Songs, Vol. 1", None))
+        );
+        assert_ne!(
+            key,
+            album_key(&fields(Some("Other Band"), "Songs, Vol. 1", None))
+        );
+
+        let mut compilation = fields(None, "Hits", None);
+        assert_eq!(album_key(&compilation).as_deref(), Some("tag:|hits"));
+        compilation.is_compilation = true;
+        assert_eq!(album_key(&compilation).as_deref(), Some("tag:*|hits"));
+
+        let mut untitled = fields(Some("The Band"), "", None);
+        untitled.album = None;
+        assert_eq!(album_key(&untitled), None);
+    }
+
+    #[test]
+    fn tracks_with_and_without_a_release_id_share_an_album() {
+        let conn = Connection::open_in_memory().unwrap();
+        import::ensure_schema(&conn).unwrap();
+        insert_track(&conn, "tagged", "Songs", 1999, None);
+        insert_track(&conn, "reissue", "Songs", 2011, None);
+        insert_track(&conn, "release", "Songs", 1999, Some("ABC-123"));
+        insert_track(&conn, "same-release", "Songs", 1999, Some("abc-123"));
+        insert_track(&conn, "other-release", "Songs", 1999, Some("def-456"));
+        for id in [
+            "tagged",
+            "reissue",
+            "release",
+            "same-release",
+            "other-release",
+        ] {
+            assign_album(&conn, id, DEFAULT_VARIOUS_ARTISTS).unwrap();
+        }
+
+        let album = album_of(&conn, "tagged").unwrap();
+        assert_eq!(album_of(&conn, "reissue").as_ref(), Some(&album));
+        assert_eq!(album_of(&conn, "release").as_ref(), Some(&album));
+        assert_eq!(album_of(&conn, "same-release").as_ref(), Some(&album));
+        let other = album_of(&conn, "other-release").unwrap();
+        assert_ne!(other, album);
+
+        let keys: Vec<(String, Option<String>)> = conn
+            .prepare("SELECT album_key, musicbrainz_albumid FROM albums ORDER BY album_key")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            keys,
+            [
+                ("mb:def-456".to_string(), Some("def-456".to_string())),
+                (
+                    "tag:the band|songs".to_string(),
+                    Some("abc-123".to_string())
+                ),
+            ]
+        );
+
+        // A track arriving after the release id was recorded joins it too
+        insert_track(&conn, "late", "Songs", 2020, None);
+        assign_album(&conn, "late", DEFAULT_VARIOUS_ARTISTS).unwrap();
+        assert_eq!(album_of(&conn, "late"), Some(album));
+    }
+
+    #[test]
+    fn retagged_tracks_leave_no_empty_album_behind() {
+        let conn = Connection::open_in_memory().unwrap();
+        import::ensure_schema(&conn).unwrap();
+        insert_track(&conn, "t", "Songs", 1999, None);
+        assign_album(&conn, "t", DEFAULT_VARIOUS_ARTISTS).unwrap();
+        conn.execute("UPDATE tracks SET album = 'Other Songs' WHERE id = 't'", [])
+            .unwrap();
+        assign_album(&conn, "t", DEFAULT_VARIOUS_ARTISTS).unwrap();
+
+        let titles: Vec<String> = conn
+            .prepare("SELECT title FROM albums")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(titles, ["Other Songs"]);
+    }
+
+    #[test]
+    fn backfill_regroups_albums_under_older_keys() {
+        let conn = Connection::open_in_memory().unwrap();
+        import::ensure_schema(&conn).unwrap();
+        insert_track(&conn, "a", "Songs", 1999, None);
+        insert_track(&conn, "b", "Songs", 2011, None);
+        insert_track(&conn, "unlinked", "Songs", 2011, None);
+        // Albums keyed by year, as older versions stored them
+        conn.execute_batch(
+            "INSERT INTO albums (id, album_key, title, created_at)
+             VALUES ('old-1', 'tag:the band|songs|1999', 'Songs', 0),
+                    ('old-2', 'tag:the band|songs|2011', 'Songs', 0);
+             UPDATE tracks SET album_id = 'old-1' WHERE id = 'a';
+             UPDATE tracks SET album_id = 'old-2' WHERE id = 'b';",
+        )
+        .unwrap();
+
+        assert_eq!(backfill_albums(&conn).unwrap(), 3);
+        let album = album_of(&conn, "a").unwrap();
+        assert_eq!(album_of(&conn, "b").as_ref(), Some(&album));
+        assert_eq!(album_of(&conn, "unlinked").as_ref(), Some(&album));
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM albums", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    /// Inserts a track by `artist` in `folder` and links it
+    fn add(conn: &Connection, id: &str, artist: &str, album_artist: Option<&str>, folder: &str) {
+        conn.execute(
+            "INSERT INTO tracks (id, artist, album_artist, album, source_path)
+             VALUES (?1, ?2, ?3, ' Hits ', ?4)",
+            rusqlite::params![id, artist, album_artist, format!("{}/{}.mp3", folder, id)],
+        )
+        .unwrap();
+        assign_album(conn, id, "VA").unwrap();
+    }
+
+    fn album_row(conn: &Connection, track_id: &str) -> (Option<String>, bool) {
+        conn.query_row(
+            "SELECT a.album_artist, a.is_compilation FROM albums a
+             JOIN tracks t ON t.album_id = a.id WHERE t.id = ?1",
+            [track_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn untagged_folders_by_several_artists_become_compilations() {
+        let conn = Connection::open_in_memory().unwrap();
+        import::ensure_schema(&conn).unwrap();
+        add(&conn, "a1", "Artist A", None, "/mix");
+        add(&conn, "a2", "Artist A feat. Artist C", None, "/mix");
+        // One main artist so far, credited with and without a guest
+        assert_eq!(
+            album_row(&conn, "a1"),
+            (Some("Artist A".to_string()), false)
+        );
+        assert_eq!(album_of(&conn, "a1"), album_of(&conn, "a2"));
+
+        add(&conn, "b1", "Artist B", None, "/mix");
+        let album = album_of(&conn, "b1");
+        for id in ["a1", "a2"] {
+            assert_eq!(album_of(&conn, id), album);
+        }
+        assert_eq!(album_row(&conn, "b1"), (None, true));
+
+        // The same title in another folder is another album
+        add(&conn, "c1", "Artist C", None, "/other");
+        assert_ne!(album_of(&conn, "c1"), album);
+        assert_eq!(
+            album_row(&conn, "c1"),
+            (Some("Artist C".to_string()), false)
+        );
+    }
+
+    #[test]
+    fn compilation_tags_are_recognised() {
+        let conn = Connection::open_in_memory().unwrap();
+        import::ensure_schema(&conn).unwrap();
+        // The configured name and the usual spellings both count
+        add(&conn, "named", "Artist A", Some("va"), "/one");
+        add(
+            &conn,
+            "spelled",
+            "Artist B",
+            Some("Various Artists"),
+            "/two",
+        );
+        assert_eq!(album_row(&conn, "named"), (None, true));
+        assert_eq!(album_of(&conn, "named"), album_of(&conn, "spelled"));
+
+        conn.execute_batch(
+            "INSERT INTO tracks (id, artist, album, is_compilation, source_path)
+             VALUES ('flagged', 'Artist C', 'Flagged', 1, '/three/flagged.mp3');
+             INSERT INTO tracks (id, artist, album, musicbrainz_albumtype, source_path)
+             VALUES ('typed', 'Artist D', 'Typed', 'album; Compilation', '/four/typed.mp3');",
+        )
+        .unwrap();
+        assign_album(&conn, "flagged", "VA").unwrap();
+        assign_album(&conn, "typed", "VA").unwrap();
+        assert_eq!(album_row(&conn, "flagged"), (None, true));
+        assert_eq!(album_row(&conn, "typed"), (None, true));
+
+        // A real album artist keeps the album out of the compilations
+        add(&conn, "owned", "Artist E", Some("Artist E"), "/five");
+        assert_eq!(
+            album_row(&conn, "owned"),
+            (Some("Artist E".to_string()), false)
+        );
+    }
+
+    #[test]
+    fn folder_lookup_uses_the_album_title_index() {
+        let conn = Connection::open_in_memory().unwrap();
+        import::ensure_schema(&conn).unwrap();
+        let plan: Vec<String> = conn
+            .prepare(
+                "EXPLAIN QUERY PLAN SELECT id FROM tracks
+                 WHERE id != ?1 AND TRIM(album) = ?2 COLLATE NOCASE",
+            )
+            .unwrap()
+            .query_map(["t", "Hits"], |row| row.get(3))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert!(
+            plan.iter()
+                .any(|step| step.contains("idx_tracks_album_title")),
+            "{:?}",
+            plan
+        );
+    }
+}
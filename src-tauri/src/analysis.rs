@@ -0,0 +1,154 @@
+//! Audio for the BPM and key analyzer in the frontend. The webview decodes
+//! most formats itself, but WebAudio cannot read DSF/DFF, WavPack or Monkey's
+//! Audio, so those are decoded here and handed over as a mono 16-bit WAV.
+
+use crate::formats;
+use std::fs::File;
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{SeekMode, SeekTo};
+use symphonia::core::units::Time;
+
+/// Decode up to `max_seconds` from the middle of the file, mixed down to
+/// mono, as the bytes of a WAV file at the decoder's output rate
+pub fn decode_mono_wav(path: &Path, max_seconds: f64) -> Result<Vec<u8>, String> {
+    let file = File::open(path).map_err(|error| error.to_string())?;
+    let mut probed = formats::open_stream(file, path)?;
+    let format = probed.format.as_mut();
+    let track = format
+        .default_track()
+        .ok_or_else(|| "No audio track found".to_string())?;
+    let track_id = track.id;
+    let params = track.codec_params.clone();
+    let mut decoder = formats::codecs()
+        .make(&params, &DecoderOptions::default())
+        .map_err(|error| error.to_string())?;
+
+    let duration = match (params.n_frames, params.time_base) {
+        (Some(frames), Some(time_base)) => {
+            let time = time_base.calc_time(frames);
+            time.seconds as f64 + time.frac
+        }
+        _ => 0.0,
+    };
+    let start = (duration - max_seconds) / 2.0;
+    if start > 0.0 {
+        // Starting at the top only costs a longer decode, so a failed seek is
+        // not an error
+        let _ = format.seek(
+            SeekMode::Coarse,
+            SeekTo::Time {
+                time: Time::from(start),
+                track_id: Some(track_id),
+            },
+        );
+    }
+
+    let mut samples: Vec<f32> = Vec::new();
+    let mut sample_rate = params.sample_rate.unwrap_or(0);
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(_)) => break,
+            Err(error) => return Err(error.to_string()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(error) => return Err(error.to_string()),
+        };
+        let spec = *decoded.spec();
+        sample_rate = spec.rate;
+        let channels = spec.channels.count().max(1);
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        samples.extend(
+            buffer
+                .samples()
+                .chunks_exact(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+        );
+
+        let max_frames = (max_seconds * sample_rate as f64) as usize;
+        if samples.len() >= max_frames {
+            samples.truncate(max_frames);
+            break;
+        }
+    }
+
+    if sample_rate == 0 || samples.is_empty() {
+        return Err("No audio could be decoded".to_string());
+    }
+    Ok(wav_bytes(&samples, sample_rate))
+}
+
+/// A canonical 16-bit PCM WAV holding `samples`
+fn wav_bytes(samples: &[f32], sample_rate: u32) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use std::fs;
+
+    #[test]
+    fn decodes_a_window_of_the_track_as_mono_wav() {
+        let dir = std::env::temp_dir().join(format!("muro-analysis-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("track.wav");
+        fs::write(&path, test_support::wav_bytes()).unwrap();
+
+        let bytes = decode_mono_wav(&path, 0.5).unwrap();
+        let info = formats::read_stream_info(&path).unwrap();
+        assert!((info.duration_seconds - 1.0).abs() < 0.01);
+        fs::write(&path, &bytes).unwrap();
+        let decoded = formats::ProbedFile::open(&path).unwrap();
+        assert_eq!(decoded.params.sample_rate, Some(8000));
+        assert_eq!(
+            decoded.params.channels.map(|channels| channels.count()),
+            Some(1)
+        );
+        assert_eq!(bytes.len(), 44 + 4000 * 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn encodes_clipped_samples_at_full_scale() {
+        let bytes = wav_bytes(&[0.0, 2.0, -2.0], 44100);
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(&bytes[24..28], &44100u32.to_le_bytes());
+        assert_eq!(
+            &bytes[44..],
+            [0i16, i16::MAX, -i16::MAX]
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect::<Vec<u8>>()
+        );
+    }
+}
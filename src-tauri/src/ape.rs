@@ -0,0 +1,1172 @@
+use std::io::{Read, Seek, SeekFrom};
+use symphonia::core::audio::{
+    AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec,
+};
+use symphonia::core::codecs::{
+    CodecDescriptor, CodecParameters, Decoder, DecoderOptions, FinalizeResult,
+    CODEC_TYPE_MONKEYS_AUDIO,
+};
+use symphonia::core::errors::{decode_error, seek_error, unsupported_error, Result, SeekErrorKind};
+use symphonia::core::formats::{
+    Cue, FormatOptions, FormatReader, Packet, SeekMode, SeekTo, SeekedTo, Track,
+};
+use symphonia::core::io::{MediaSource, MediaSourceStream, ReadBytes};
+use symphonia::core::meta::{Metadata, MetadataLog};
+use symphonia::core::probe::{Descriptor, Instantiate, QueryDescriptor};
+use symphonia::core::units::TimeBase;
+use symphonia::core::{support_codec, support_format};
+
+/// Oldest encoder version whose frames this decoder understands (Monkey's
+/// Audio 3.95, from 2002)
+const MIN_VERSION: u16 = 3950;
+/// Versions from here on describe the stream in a separate descriptor
+const DESCRIPTOR_VERSION: u16 = 3980;
+/// Versions from here on use the pivot-based entropy coder
+const PIVOT_VERSION: u16 = 3990;
+
+const FLAG_8_BIT: u16 = 0x1;
+const FLAG_PEAK_LEVEL: u16 = 0x4;
+const FLAG_24_BIT: u16 = 0x8;
+const FLAG_SEEK_ELEMENTS: u16 = 0x10;
+const FLAG_CREATE_WAV_HEADER: u16 = 0x20;
+
+/// Blocks per frame for files without a descriptor
+const OLD_BLOCKS_PER_FRAME: u32 = 73728 * 4;
+
+const FRAME_MONO_SILENCE: u32 = 1;
+const FRAME_STEREO_SILENCE: u32 = 3;
+const FRAME_PSEUDO_STEREO: u32 = 4;
+
+fn channel_layout(count: u16) -> Option<Channels> {
+    match count {
+        1 => Some(Channels::FRONT_LEFT),
+        2 => Some(Channels::FRONT_LEFT | Channels::FRONT_RIGHT),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Frame {
+    /// Rounded down to the 32-bit word grid the frame data is stored on
+    offset: u64,
+    size: u64,
+    /// Bytes between `offset` and the frame's real start
+    skip: u32,
+    blocks: u32,
+}
+
+/// Demuxer for Monkey's Audio (.ape) files
+pub struct ApeReader {
+    reader: MediaSourceStream,
+    tracks: Vec<Track>,
+    cues: Vec<Cue>,
+    metadata: MetadataLog,
+    frames: Vec<Frame>,
+    blocks_per_frame: u32,
+    /// Index of the next frame to return
+    next: usize,
+}
+
+impl QueryDescriptor for ApeReader {
+    fn query() -> &'static [Descriptor] {
+        &[support_format!(
+            "ape",
+            "Monkey's Audio",
+            &["ape"],
+            &["audio/ape", "audio/x-ape"],
+            &[b"MAC "]
+        )]
+    }
+
+    fn score(_context: &[u8]) -> u8 {
+        255
+    }
+}
+
+struct ApeHeader {
+    version: u16,
+    compression: u16,
+    flags: u16,
+    channels: u16,
+    sample_rate: u32,
+    bits_per_sample: u32,
+    blocks_per_frame: u32,
+    final_frame_blocks: u32,
+    total_frames: u32,
+    seek_table_start: u64,
+    seek_table_entries: u32,
+    first_frame: u64,
+    wav_tail_bytes: u64,
+}
+
+fn read_header(source: &mut MediaSourceStream, start: u64) -> Result<ApeHeader> {
+    if source.read_quad_bytes()? != *b"MAC " {
+        return decode_error("ape: missing MAC header");
+    }
+    let version = source.read_u16()?;
+    if version < MIN_VERSION {
+        return unsupported_error("ape: files from before Monkey's Audio 3.95 are not supported");
+    }
+
+    if version >= DESCRIPTOR_VERSION {
+        let _padding = source.read_u16()?;
+        let descriptor_bytes = u64::from(source.read_u32()?);
+        let header_bytes = u64::from(source.read_u32()?);
+        let seek_table_bytes = source.read_u32()?;
+        let wav_header_bytes = u64::from(source.read_u32()?);
+        let _audio_bytes = source.read_u64()?;
+        let wav_tail_bytes = u64::from(source.read_u32()?);
+        source.seek(SeekFrom::Start(start + descriptor_bytes))?;
+
+        let compression = source.read_u16()?;
+        let flags = source.read_u16()?;
+        let blocks_per_frame = source.read_u32()?;
+        let final_frame_blocks = source.read_u32()?;
+        let total_frames = source.read_u32()?;
+        let bits_per_sample = u32::from(source.read_u16()?);
+        let channels = source.read_u16()?;
+        let sample_rate = source.read_u32()?;
+
+        let seek_table_start = start + descriptor_bytes + header_bytes;
+        Ok(ApeHeader {
+            version,
+            compression,
+            flags,
+            channels,
+            sample_rate,
+            bits_per_sample,
+            blocks_per_frame,
+            final_frame_blocks,
+            total_frames,
+            seek_table_start,
+            seek_table_entries: seek_table_bytes / 4,
+            first_frame: seek_table_start + u64::from(seek_table_bytes) + wav_header_bytes,
+            wav_tail_bytes,
+        })
+    } else {
+        let compression = source.read_u16()?;
+        let flags = source.read_u16()?;
+        let channels = source.read_u16()?;
+        let sample_rate = source.read_u32()?;
+        let wav_header_bytes = u64::from(source.read_u32()?);
+        let wav_tail_bytes = u64::from(source.read_u32()?);
+        let total_frames = source.read_u32()?;
+        let final_frame_blocks = source.read_u32()?;
+        if flags & FLAG_PEAK_LEVEL != 0 {
+            let _peak = source.read_u32()?;
+        }
+        let seek_table_entries = if flags & FLAG_SEEK_ELEMENTS != 0 {
+            source.read_u32()?
+        } else {
+            total_frames
+        };
+        // The stored WAV header comes before the seek table in these files
+        let header_end = source.pos();
+        let stored_wav_header = if flags & FLAG_CREATE_WAV_HEADER == 0 {
+            wav_header_bytes
+        } else {
+            0
+        };
+        let bits_per_sample = if flags & FLAG_8_BIT != 0 {
+            8
+        } else if flags & FLAG_24_BIT != 0 {
+            24
+        } else {
+            16
+        };
+
+        let seek_table_start = header_end + stored_wav_header;
+        Ok(ApeHeader {
+            version,
+            compression,
+            flags,
+            channels,
+            sample_rate,
+            bits_per_sample,
+            blocks_per_frame: OLD_BLOCKS_PER_FRAME,
+            final_frame_blocks,
+            total_frames,
+            seek_table_start,
+            seek_table_entries,
+            first_frame: seek_table_start + u64::from(seek_table_entries) * 4,
+            wav_tail_bytes,
+        })
+    }
+}
+
+/// Lays out the frames from the seek table. Frames only start on byte
+/// boundaries, but the decoder reads them as 32-bit words counted from the
+/// first frame, so each one is widened back to that grid.
+fn frame_table(
+    header: &ApeHeader,
+    seek_table: &[u32],
+    start: u64,
+    file_size: Option<u64>,
+) -> Result<Vec<Frame>> {
+    let total = header.total_frames as usize;
+    let mut starts = Vec::with_capacity(total);
+    starts.push(header.first_frame);
+    for &entry in seek_table.iter().take(total).skip(1) {
+        let offset = start + u64::from(entry);
+        if offset <= starts[starts.len() - 1] {
+            return decode_error("ape: seek table is out of order");
+        }
+        starts.push(offset);
+    }
+    if starts.len() != total {
+        return decode_error("ape: seek table is too short");
+    }
+
+    let last = starts[total - 1];
+    let final_size = file_size
+        .map(|size| size.saturating_sub(last + header.wav_tail_bytes))
+        .map(|size| size - (size & 3))
+        .filter(|size| *size > 0)
+        .unwrap_or(u64::from(header.final_frame_blocks) * 8);
+
+    Ok(starts
+        .iter()
+        .enumerate()
+        .map(|(index, &offset)| {
+            let size = starts
+                .get(index + 1)
+                .map_or(final_size, |next| next - offset);
+            let skip = ((offset - header.first_frame) & 3) as u32;
+            Frame {
+                offset: offset - u64::from(skip),
+                size: (size + u64::from(skip) + 3) & !3,
+                skip,
+                blocks: if index + 1 == total {
+                    header.final_frame_blocks
+                } else {
+                    header.blocks_per_frame
+                },
+            }
+        })
+        .collect())
+}
+
+impl FormatReader for ApeReader {
+    fn try_new(mut source: MediaSourceStream, _options: &FormatOptions) -> Result<Self> {
+        let start = source.pos();
+        let header = read_header(&mut source, start)?;
+        if !matches!(header.bits_per_sample, 8 | 16 | 24) {
+            return unsupported_error("ape: only 8, 16 and 24-bit audio is supported");
+        }
+        let Some(channels) = channel_layout(header.channels) else {
+            return unsupported_error("ape: only mono and stereo are supported");
+        };
+        if header.total_frames == 0 || header.blocks_per_frame == 0 || header.sample_rate == 0 {
+            return decode_error("ape: empty stream");
+        }
+        if header.seek_table_entries < header.total_frames {
+            return decode_error("ape: seek table is too short");
+        }
+
+        source.seek(SeekFrom::Start(header.seek_table_start))?;
+        let seek_table = (0..header.total_frames)
+            .map(|_| source.read_u32())
+            .collect::<std::io::Result<Vec<u32>>>()?;
+        let frames = frame_table(&header, &seek_table, start, source.byte_len())?;
+
+        let n_frames = u64::from(header.total_frames - 1) * u64::from(header.blocks_per_frame)
+            + u64::from(header.final_frame_blocks);
+        let mut extra_data = Vec::with_capacity(6);
+        for value in [header.version, header.compression, header.flags] {
+            extra_data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let mut codec_params = CodecParameters::new();
+        codec_params
+            .for_codec(CODEC_TYPE_MONKEYS_AUDIO)
+            .with_sample_rate(header.sample_rate)
+            .with_time_base(TimeBase::new(1, header.sample_rate))
+            .with_n_frames(n_frames)
+            .with_channels(channels)
+            .with_bits_per_sample(header.bits_per_sample)
+            .with_max_frames_per_packet(u64::from(header.blocks_per_frame))
+            // The decoder needs the version, compression level and flags
+            .with_extra_data(extra_data.into_boxed_slice());
+
+        source.seek(SeekFrom::Start(frames[0].offset))?;
+        Ok(Self {
+            reader: source,
+            tracks: vec![Track::new(0, codec_params)],
+            cues: Vec::new(),
+            metadata: MetadataLog::default(),
+            frames,
+            blocks_per_frame: header.blocks_per_frame,
+            next: 0,
+        })
+    }
+
+    fn cues(&self) -> &[Cue] {
+        &self.cues
+    }
+
+    fn metadata(&mut self) -> Metadata<'_> {
+        self.metadata.metadata()
+    }
+
+    fn seek(&mut self, _mode: SeekMode, to: SeekTo) -> Result<SeekedTo> {
+        let required_ts = match to {
+            SeekTo::TimeStamp { ts, .. } => ts,
+            SeekTo::Time { time, .. } => {
+                let Some(rate) = self.tracks[0].codec_params.sample_rate else {
+                    return seek_error(SeekErrorKind::Unseekable);
+                };
+                TimeBase::new(1, rate).calc_timestamp(time)
+            }
+        };
+        if required_ts > self.tracks[0].codec_params.n_frames.unwrap_or(0) {
+            return seek_error(SeekErrorKind::OutOfRange);
+        }
+
+        // Frames decode independently; the caller discards the samples before
+        // the target
+        self.next =
+            ((required_ts / u64::from(self.blocks_per_frame)) as usize).min(self.frames.len() - 1);
+        self.reader
+            .seek(SeekFrom::Start(self.frames[self.next].offset))?;
+
+        Ok(SeekedTo {
+            track_id: 0,
+            required_ts,
+            actual_ts: self.frame_ts(self.next),
+        })
+    }
+
+    fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    fn next_packet(&mut self) -> Result<Packet> {
+        let Some(frame) = self.frames.get(self.next).copied() else {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        };
+        if self.reader.pos() != frame.offset {
+            self.reader.seek(SeekFrom::Start(frame.offset))?;
+        }
+
+        // Block count and skip, then the frame; the last frame's size is only
+        // an estimate, so it may come up short
+        let mut data = Vec::with_capacity(8 + frame.size as usize);
+        data.extend_from_slice(&frame.blocks.to_le_bytes());
+        data.extend_from_slice(&frame.skip.to_le_bytes());
+        (&mut self.reader).take(frame.size).read_to_end(&mut data)?;
+
+        let ts = self.frame_ts(self.next);
+        self.next += 1;
+        Ok(Packet::new_from_boxed_slice(
+            0,
+            ts,
+            u64::from(frame.blocks),
+            data.into_boxed_slice(),
+        ))
+    }
+
+    fn into_inner(self: Box<Self>) -> MediaSourceStream {
+        self.reader
+    }
+}
+
+impl ApeReader {
+    fn frame_ts(&self, index: usize) -> u64 {
+        index as u64 * u64::from(self.blocks_per_frame)
+    }
+}
+
+const CODE_BITS: u32 = 32;
+const TOP_VALUE: u32 = 1 << (CODE_BITS - 1);
+const EXTRA_BITS: u32 = 7;
+const BOTTOM_VALUE: u32 = TOP_VALUE >> 8;
+/// Symbol that escapes to an explicitly coded overflow
+const MODEL_ELEMENTS: u32 = 64;
+
+/// Cumulative frequencies of the overflow symbols before version 3.99
+const COUNTS_3970: [u32; 22] = [
+    0, 14824, 28224, 39348, 47855, 53994, 58171, 60926, 62682, 63786, 64463, 64878, 65126, 65276,
+    65365, 65419, 65450, 65469, 65480, 65487, 65491, 65493,
+];
+/// Cumulative frequencies of the overflow symbols from version 3.98 on
+const COUNTS_3980: [u32; 22] = [
+    0, 19578, 36160, 48417, 56323, 60899, 63265, 64435, 64971, 65232, 65351, 65416, 65447, 65466,
+    65476, 65482, 65485, 65488, 65490, 65491, 65492, 65493,
+];
+
+/// Adaptive Rice parameter, one per channel
+struct Rice {
+    k: u32,
+    ksum: u32,
+}
+
+impl Rice {
+    fn new() -> Self {
+        Self {
+            k: 10,
+            ksum: 16 << 10,
+        }
+    }
+
+    fn update(&mut self, value: u32) {
+        let limit = if self.k > 0 { 1 << (self.k + 4) } else { 0 };
+        self.ksum = self
+            .ksum
+            .wrapping_add(value.div_ceil(2))
+            .wrapping_sub((self.ksum + 16) >> 5);
+        if self.ksum < limit {
+            self.k -= 1;
+        } else if self.ksum >= 1 << (self.k + 5) && self.k < 24 {
+            self.k += 1;
+        }
+    }
+}
+
+/// Maps the coder's unsigned values back onto signed residuals
+fn to_signed(value: u32) -> i32 {
+    ((value >> 1) ^ (value & 1).wrapping_sub(1)).wrapping_add(1) as i32
+}
+
+/// The range decoder the entropy coder runs on. Running past the end of the
+/// frame or off the end of a frequency table marks the frame as corrupt, which
+/// is checked once the whole frame has been decoded.
+struct RangeDecoder<'a> {
+    data: &'a [u8],
+    position: usize,
+    low: u32,
+    range: u32,
+    buffer: u32,
+    help: u32,
+    corrupt: bool,
+}
+
+impl<'a> RangeDecoder<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        let buffer = u32::from(data.first().copied().unwrap_or(0));
+        Self {
+            data,
+            position: 1,
+            low: buffer >> (8 - EXTRA_BITS),
+            range: 1 << EXTRA_BITS,
+            buffer,
+            help: 0,
+            corrupt: data.is_empty(),
+        }
+    }
+
+    fn normalize(&mut self) {
+        while self.range <= BOTTOM_VALUE {
+            self.buffer <<= 8;
+            match self.data.get(self.position) {
+                Some(byte) => self.buffer += u32::from(*byte),
+                None => self.corrupt = true,
+            }
+            self.position += 1;
+            self.low = (self.low << 8) | ((self.buffer >> 1) & 0xff);
+            self.range <<= 8;
+        }
+    }
+
+    fn cumulative_frequency(&mut self, total: u32) -> u32 {
+        self.normalize();
+        self.help = self.range / total;
+        self.low / self.help
+    }
+
+    fn cumulative_shift(&mut self, shift: u32) -> u32 {
+        self.normalize();
+        self.help = self.range >> shift;
+        self.low / self.help
+    }
+
+    fn update(&mut self, frequency: u32, cumulative: u32) {
+        self.low = self.low.wrapping_sub(self.help.wrapping_mul(cumulative));
+        self.range = self.help.wrapping_mul(frequency);
+    }
+
+    fn bits(&mut self, count: u32) -> u32 {
+        let value = self.cumulative_shift(count);
+        self.update(1, value);
+        value
+    }
+
+    fn symbol(&mut self, counts: &[u32; 22]) -> u32 {
+        let frequency = self.cumulative_shift(16);
+        if frequency > 65492 {
+            self.update(1, frequency);
+            if frequency > 65535 {
+                self.corrupt = true;
+            }
+            return (frequency + 63).saturating_sub(65535);
+        }
+        let symbol = counts.partition_point(|count| *count <= frequency) - 1;
+        self.update(counts[symbol + 1] - counts[symbol], counts[symbol]);
+        symbol as u32
+    }
+
+    /// Residual coding used from version 3.99 on
+    fn value_3990(&mut self, rice: &mut Rice) -> i32 {
+        let pivot = (rice.ksum >> 5).max(1);
+        let mut overflow = self.symbol(&COUNTS_3980);
+        if overflow == MODEL_ELEMENTS - 1 {
+            overflow = self.bits(16) << 16;
+            overflow |= self.bits(16);
+        }
+
+        let base = if pivot < 0x10000 {
+            let base = self.cumulative_frequency(pivot);
+            self.update(1, base);
+            base
+        } else {
+            let mut high = pivot;
+            let mut bits = 0;
+            while high & !0xffff != 0 {
+                high >>= 1;
+                bits += 1;
+            }
+            let high = self.cumulative_frequency(high + 1);
+            self.update(1, high);
+            let low = self.cumulative_frequency(1 << bits);
+            self.update(1, low);
+            (high << bits).wrapping_add(low)
+        };
+
+        let value = base.wrapping_add(overflow.wrapping_mul(pivot));
+        rice.update(value);
+        to_signed(value)
+    }
+
+    /// Residual coding used by versions 3.95 to 3.98
+    fn value_3900(&mut self, rice: &mut Rice) -> i32 {
+        let mut overflow = self.symbol(&COUNTS_3970);
+        let bits = if overflow == MODEL_ELEMENTS - 1 {
+            overflow = 0;
+            self.bits(5)
+        } else {
+            rice.k.saturating_sub(1)
+        };
+
+        let value = if bits <= 16 {
+            self.bits(bits)
+        } else {
+            let low = self.bits(16);
+            low | (self.bits(bits - 16) << 16)
+        };
+        let value = value.wrapping_add(overflow.wrapping_shl(bits));
+        rice.update(value);
+        to_signed(value)
+    }
+}
+
+/// `-1` for positive values and `1` for negative ones, as the adaptive filters
+/// step against the sign of their input
+fn ape_sign(value: i32) -> i32 {
+    i32::from(value < 0) - i32::from(value > 0)
+}
+
+const HISTORY_SIZE: usize = 512;
+
+/// NN filter orders and fraction bits per compression level, applied in order
+const FILTERS: [&[(usize, u32)]; 5] = [
+    &[],
+    &[(16, 11)],
+    &[(64, 11)],
+    &[(32, 10), (256, 13)],
+    &[(16, 11), (256, 13), (1280, 15)],
+];
+
+/// Sign-adaptive FIR filter. Delayed outputs and adaption steps share one
+/// rolling buffer, the steps trailing the outputs by `order` samples.
+struct NnFilter {
+    order: usize,
+    fraction_bits: u32,
+    coefficients: Vec<i16>,
+    history: Vec<i16>,
+    delay: usize,
+    adapt: usize,
+    average: u32,
+}
+
+impl NnFilter {
+    fn new(order: usize, fraction_bits: u32) -> Self {
+        Self {
+            order,
+            fraction_bits,
+            coefficients: vec![0; order],
+            history: vec![0; HISTORY_SIZE + order * 2],
+            delay: order * 2,
+            adapt: order,
+            average: 0,
+        }
+    }
+
+    fn apply(&mut self, version: u16, data: &mut [i32]) {
+        let order = self.order;
+        for sample in data.iter_mut() {
+            let input = *sample;
+            let step = ape_sign(input) as i16;
+            let mut dot = 0i32;
+            for index in 0..order {
+                let coefficient = &mut self.coefficients[index];
+                dot = dot.wrapping_add(
+                    i32::from(*coefficient) * i32::from(self.history[self.delay - order + index]),
+                );
+                *coefficient = coefficient
+                    .wrapping_add(step.wrapping_mul(self.history[self.adapt - order + index]));
+            }
+            let rounded = (i64::from(dot) + (1 << (self.fraction_bits - 1))) >> self.fraction_bits;
+            let output = (rounded as i32).wrapping_add(input);
+            *sample = output;
+
+            self.history[self.delay] = output.clamp(i16::MIN.into(), i16::MAX.into()) as i16;
+            self.delay += 1;
+
+            let adapt = self.adapt;
+            if version < DESCRIPTOR_VERSION {
+                self.history[adapt] = if output == 0 {
+                    0
+                } else {
+                    (((output >> 28) & 8) - 4) as i16
+                };
+                self.history[adapt - 4] >>= 1;
+                self.history[adapt - 8] >>= 1;
+            } else {
+                let magnitude = output.unsigned_abs();
+                self.history[adapt] = if magnitude == 0 {
+                    0
+                } else {
+                    let average = u64::from(self.average);
+                    let scale = u32::from(u64::from(magnitude) > average * 3)
+                        + u32::from(magnitude > self.average + self.average / 3);
+                    (ape_sign(output) * (8 << scale)) as i16
+                };
+                self.average = self
+                    .average
+                    .wrapping_add((magnitude.wrapping_sub(self.average) as i32 / 16) as u32);
+                self.history[adapt - 1] >>= 1;
+                self.history[adapt - 2] >>= 1;
+                self.history[adapt - 8] >>= 1;
+            }
+            self.adapt += 1;
+
+            if self.delay == self.history.len() {
+                self.history.copy_within(self.delay - order * 2.., 0);
+                self.delay = order * 2;
+                self.adapt = order;
+            }
+        }
+    }
+}
+
+const PREDICTOR_SIZE: usize = 50;
+const Y_DELAY_A: usize = 50;
+const Y_DELAY_B: usize = 42;
+const X_DELAY_A: usize = 34;
+const X_DELAY_B: usize = 26;
+const Y_ADAPT_A: usize = 18;
+const X_ADAPT_A: usize = 14;
+const Y_ADAPT_B: usize = 10;
+const X_ADAPT_B: usize = 5;
+const INITIAL_COEFFICIENTS: [i32; 4] = [360, 317, -109, 98];
+
+/// Slots of the shared history one channel's predictor uses
+struct Slots {
+    delay_a: usize,
+    delay_b: usize,
+    adapt_a: usize,
+    adapt_b: usize,
+}
+
+const Y_SLOTS: Slots = Slots {
+    delay_a: Y_DELAY_A,
+    delay_b: Y_DELAY_B,
+    adapt_a: Y_ADAPT_A,
+    adapt_b: Y_ADAPT_B,
+};
+const X_SLOTS: Slots = Slots {
+    delay_a: X_DELAY_A,
+    delay_b: X_DELAY_B,
+    adapt_a: X_ADAPT_A,
+    adapt_b: X_ADAPT_B,
+};
+
+/// The final, per-sample prediction stage. Channel 0 (Y) also predicts from
+/// channel 1's previous output and channel 1 (X) from channel 0's current one.
+struct Predictor {
+    history: Vec<i32>,
+    position: usize,
+    last_a: [i32; 2],
+    filter_a: [i32; 2],
+    filter_b: [i32; 2],
+    coefficients_a: [[i32; 4]; 2],
+    coefficients_b: [[i32; 5]; 2],
+}
+
+impl Predictor {
+    fn new() -> Self {
+        Self {
+            history: vec![0; HISTORY_SIZE + PREDICTOR_SIZE],
+            position: 0,
+            last_a: [0; 2],
+            filter_a: [0; 2],
+            filter_b: [0; 2],
+            coefficients_a: [INITIAL_COEFFICIENTS; 2],
+            coefficients_b: [[0; 5]; 2],
+        }
+    }
+
+    fn update(&mut self, decoded: i32, channel: usize, slots: &Slots) -> i32 {
+        let buf = &mut self.history[self.position..];
+        let (delay_a, delay_b) = (slots.delay_a, slots.delay_b);
+        let (adapt_a, adapt_b) = (slots.adapt_a, slots.adapt_b);
+
+        buf[delay_a] = self.last_a[channel];
+        buf[adapt_a] = ape_sign(buf[delay_a]);
+        buf[delay_a - 1] = buf[delay_a].wrapping_sub(buf[delay_a - 1]);
+        buf[adapt_a - 1] = ape_sign(buf[delay_a - 1]);
+        let prediction_a = (0..4).fold(0i32, |sum, index| {
+            sum.wrapping_add(buf[delay_a - index].wrapping_mul(self.coefficients_a[channel][index]))
+        });
+
+        // A scaled first-order filter over the other channel
+        buf[delay_b] =
+            self.filter_a[channel ^ 1].wrapping_sub(self.filter_b[channel].wrapping_mul(31) >> 5);
+        buf[adapt_b] = ape_sign(buf[delay_b]);
+        buf[delay_b - 1] = buf[delay_b].wrapping_sub(buf[delay_b - 1]);
+        buf[adapt_b - 1] = ape_sign(buf[delay_b - 1]);
+        self.filter_b[channel] = self.filter_a[channel ^ 1];
+        let prediction_b = (0..5).fold(0i32, |sum, index| {
+            sum.wrapping_add(buf[delay_b - index].wrapping_mul(self.coefficients_b[channel][index]))
+        });
+
+        self.last_a[channel] =
+            decoded.wrapping_add(prediction_a.wrapping_add(prediction_b >> 1) >> 10);
+        self.filter_a[channel] =
+            self.last_a[channel].wrapping_add(self.filter_a[channel].wrapping_mul(31) >> 5);
+
+        let sign = ape_sign(decoded);
+        for index in 0..4 {
+            self.coefficients_a[channel][index] += buf[adapt_a - index] * sign;
+        }
+        for index in 0..5 {
+            self.coefficients_b[channel][index] += buf[adapt_b - index] * sign;
+        }
+        self.filter_a[channel]
+    }
+
+    fn advance(&mut self) {
+        self.position += 1;
+        if self.position == HISTORY_SIZE {
+            self.history
+                .copy_within(HISTORY_SIZE..HISTORY_SIZE + PREDICTOR_SIZE, 0);
+            self.position = 0;
+        }
+    }
+}
+
+/// Decodes Monkey's Audio 3.95 and later at every compression level
+pub struct ApeDecoder {
+    params: CodecParameters,
+    version: u16,
+    filters: &'static [(usize, u32)],
+    bits_per_sample: u32,
+    buf: AudioBuffer<i32>,
+}
+
+impl ApeDecoder {
+    fn decode_frame(&self, data: &[u8], blocks: usize, channels: usize) -> Result<[Vec<i32>; 2]> {
+        if data.len() < 4 {
+            return decode_error("ape: truncated frame");
+        }
+        // A CRC, whose top bit says frame flags follow
+        let mut position = 4;
+        let mut frame_flags = 0;
+        if data[0] & 0x80 != 0 {
+            let Some(flags) = data.get(4..8) else {
+                return decode_error("ape: truncated frame");
+            };
+            frame_flags = u32::from_be_bytes([flags[0], flags[1], flags[2], flags[3]]);
+            position = 8;
+        }
+
+        let mono = channels == 1 || frame_flags & FRAME_PSEUDO_STEREO != 0;
+        let silent = if mono {
+            frame_flags & FRAME_MONO_SILENCE != 0
+        } else {
+            frame_flags & FRAME_STEREO_SILENCE == FRAME_STEREO_SILENCE
+        };
+        let mut y = vec![0i32; blocks];
+        let mut x = vec![0i32; blocks];
+        if silent {
+            return Ok([y, x]);
+        }
+
+        // The first byte after the header is not part of the range coder
+        let mut range = RangeDecoder::new(data.get(position + 1..).unwrap_or_default());
+        let (mut rice_y, mut rice_x) = (Rice::new(), Rice::new());
+        for index in 0..blocks {
+            if self.version >= PIVOT_VERSION {
+                y[index] = range.value_3990(&mut rice_y);
+                if !mono {
+                    x[index] = range.value_3990(&mut rice_x);
+                }
+            } else {
+                y[index] = range.value_3900(&mut rice_y);
+                if !mono {
+                    x[index] = range.value_3900(&mut rice_x);
+                }
+            }
+        }
+        if range.corrupt {
+            return decode_error("ape: corrupt frame");
+        }
+
+        let coded = if mono { 1 } else { 2 };
+        for data in [&mut y, &mut x].into_iter().take(coded) {
+            for &(order, fraction_bits) in self.filters {
+                NnFilter::new(order, fraction_bits).apply(self.version, data);
+            }
+        }
+
+        let mut predictor = Predictor::new();
+        if mono {
+            for sample in y.iter_mut() {
+                *sample = predictor.update(*sample, 0, &Y_SLOTS);
+                predictor.advance();
+            }
+            if channels == 2 {
+                x.copy_from_slice(&y);
+            }
+        } else {
+            for (y, x) in y.iter_mut().zip(x.iter_mut()) {
+                *y = predictor.update(*y, 0, &Y_SLOTS);
+                *x = predictor.update(*x, 1, &X_SLOTS);
+                predictor.advance();
+                let left = x.wrapping_sub(*y / 2);
+                let right = left.wrapping_add(*y);
+                (*y, *x) = (left, right);
+            }
+        }
+        Ok([y, x])
+    }
+}
+
+impl Decoder for ApeDecoder {
+    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self> {
+        let (version, compression) = match params.extra_data.as_deref() {
+            Some([a, b, c, d, ..]) => (u16::from_le_bytes([*a, *b]), u16::from_le_bytes([*c, *d])),
+            _ => return decode_error("ape: missing stream header"),
+        };
+        if version < MIN_VERSION {
+            return unsupported_error(
+                "ape: files from before Monkey's Audio 3.95 are not supported",
+            );
+        }
+        let level = usize::from(compression / 1000);
+        if compression % 1000 != 0 || !(1..=FILTERS.len()).contains(&level) {
+            return decode_error("ape: unknown compression level");
+        }
+        let Some(channels) = params.channels else {
+            return decode_error("ape: missing channel layout");
+        };
+        let Some(sample_rate) = params.sample_rate else {
+            return decode_error("ape: missing sample rate");
+        };
+        let Some(bits_per_sample) = params.bits_per_sample else {
+            return decode_error("ape: missing bit depth");
+        };
+        let max_frames = params
+            .max_frames_per_packet
+            .unwrap_or(u64::from(OLD_BLOCKS_PER_FRAME));
+
+        Ok(Self {
+            params: params.clone(),
+            version,
+            filters: FILTERS[level - 1],
+            bits_per_sample,
+            buf: AudioBuffer::new(max_frames, SignalSpec::new(sample_rate, channels)),
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        &[support_codec!(
+            CODEC_TYPE_MONKEYS_AUDIO,
+            "ape",
+            "Monkey's Audio"
+        )]
+    }
+
+    fn reset(&mut self) {}
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>> {
+        self.buf.clear();
+
+        let packet = packet.buf();
+        if packet.len() < 8 {
+            return decode_error("ape: malformed packet");
+        }
+        let blocks = u32::from_le_bytes([packet[0], packet[1], packet[2], packet[3]]) as usize;
+        let skip = u32::from_le_bytes([packet[4], packet[5], packet[6], packet[7]]) as usize;
+        if blocks > self.buf.capacity() || skip > 3 {
+            return decode_error("ape: malformed packet");
+        }
+
+        // Frames are stored as little-endian 32-bit words of a big-endian stream
+        let data: Vec<u8> = packet[8..]
+            .chunks_exact(4)
+            .flat_map(|word| [word[3], word[2], word[1], word[0]])
+            .collect();
+        let Some(frame) = data.get(skip..) else {
+            return decode_error("ape: truncated frame");
+        };
+
+        let channels = self.buf.spec().channels.count();
+        let decoded = self.decode_frame(frame, blocks, channels)?;
+        self.buf.render_reserved(Some(blocks));
+        let shift = 32 - self.bits_per_sample;
+        for (channel, samples) in decoded.iter().take(channels).enumerate() {
+            for (out, sample) in self.buf.chan_mut(channel).iter_mut().zip(samples) {
+                *out = sample.wrapping_shl(shift);
+            }
+        }
+
+        Ok(self.buf.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        FinalizeResult::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_> {
+        self.buf.as_audio_buffer_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use symphonia::core::errors::Error;
+
+    fn open(bytes: Vec<u8>) -> ApeReader {
+        let source = MediaSourceStream::new(Box::new(Cursor::new(bytes)), Default::default());
+        ApeReader::try_new(source, &FormatOptions::default()).unwrap()
+    }
+
+    /// A silent frame: a CRC flagged as followed by frame flags, then the
+    /// stereo silence flags, as the big-endian stream the decoder sees
+    fn silent_frame(padding: usize) -> Vec<u8> {
+        let mut frame = 0x8000_0000u32.to_be_bytes().to_vec();
+        frame.extend_from_slice(&FRAME_STEREO_SILENCE.to_be_bytes());
+        frame.extend(std::iter::repeat_n(0, padding));
+        frame
+    }
+
+    /// A 16-bit stereo 3.99 file with frames of four blocks. The second frame
+    /// starts two bytes off the 32-bit grid.
+    fn ape_file() -> Vec<u8> {
+        let first = silent_frame(6);
+        let second = silent_frame(2);
+        let seek_table_bytes = 8u32;
+        let first_frame = 52 + 24 + seek_table_bytes;
+
+        let mut bytes = b"MAC ".to_vec();
+        bytes.extend_from_slice(&PIVOT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        for value in [52u32, 24, seek_table_bytes, 0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&((first.len() + second.len()) as u64).to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 16]);
+
+        bytes.extend_from_slice(&2000u16.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        for value in [4u32, 3, 2] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&44_100u32.to_le_bytes());
+
+        for offset in [first_frame, first_frame + first.len() as u32] {
+            bytes.extend_from_slice(&offset.to_le_bytes());
+        }
+
+        // Stored as little-endian words counted from the first frame
+        let mut stream = first;
+        stream.extend(second);
+        stream.resize(stream.len().div_ceil(4) * 4, 0);
+        for word in stream.chunks_exact(4) {
+            bytes.extend([word[3], word[2], word[1], word[0]]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn shared_probe_and_registry_handle_the_format() {
+        let source = MediaSourceStream::new(Box::new(Cursor::new(ape_file())), Default::default());
+        let probed = crate::formats::probe()
+            .format(
+                &Default::default(),
+                source,
+                &FormatOptions::default(),
+                &Default::default(),
+            )
+            .unwrap();
+        let params = &probed.format.tracks()[0].codec_params;
+        let codec = crate::formats::codecs().get_codec(params.codec).unwrap();
+        assert_eq!(codec.short_name, "ape");
+        assert!(crate::formats::codecs()
+            .make(params, &DecoderOptions::default())
+            .is_ok());
+    }
+
+    #[test]
+    fn lays_frames_out_on_the_word_grid() {
+        let mut reader = open(ape_file());
+        let params = &reader.tracks()[0].codec_params;
+        assert_eq!(params.codec, CODEC_TYPE_MONKEYS_AUDIO);
+        assert_eq!(params.sample_rate, Some(44_100));
+        assert_eq!(params.n_frames, Some(7));
+        assert_eq!(params.bits_per_sample, Some(16));
+
+        assert_eq!(reader.frames[1].skip, 2);
+        assert_eq!(reader.frames[1].offset, reader.frames[0].offset + 12);
+
+        let first = reader.next_packet().unwrap();
+        assert_eq!((first.ts(), first.dur()), (0, 4));
+        let second = reader.next_packet().unwrap();
+        assert_eq!((second.ts(), second.dur()), (4, 3));
+        assert!(reader.next_packet().is_err());
+
+        let seeked = reader
+            .seek(SeekMode::Accurate, SeekTo::TimeStamp { ts: 5, track_id: 0 })
+            .unwrap();
+        assert_eq!(seeked.actual_ts, 4);
+        assert_eq!(reader.next_packet().unwrap().ts(), 4);
+    }
+
+    #[test]
+    fn decodes_silent_frames_at_their_block_counts() {
+        let mut reader = open(ape_file());
+        let params = reader.tracks()[0].codec_params.clone();
+        let mut decoder = ApeDecoder::try_new(&params, &DecoderOptions::default()).unwrap();
+        for blocks in [4, 3] {
+            let packet = reader.next_packet().unwrap();
+            let AudioBufferRef::S32(buffer) = decoder.decode(&packet).unwrap() else {
+                panic!("expected integer samples");
+            };
+            assert_eq!(buffer.frames(), blocks);
+            assert!(buffer
+                .chan(0)
+                .iter()
+                .chain(buffer.chan(1))
+                .all(|sample| *sample == 0));
+        }
+    }
+
+    #[test]
+    fn rice_parameter_follows_the_running_magnitude() {
+        let mut rice = Rice::new();
+        for _ in 0..64 {
+            rice.update(0);
+        }
+        assert!(rice.k < 10, "{}", rice.k);
+        let mut rice = Rice::new();
+        for _ in 0..64 {
+            rice.update(1 << 16);
+        }
+        assert!(rice.k > 10, "{}", rice.k);
+        assert_eq!([1, 2, 3, 4].map(to_signed), [1, -1, 2, -2]);
+    }
+
+    fn noise(seed: u32, count: usize) -> Vec<u32> {
+        let mut state = seed;
+        (0..count)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state
+            })
+            .collect()
+    }
+
+    #[test]
+    fn corrupt_frames_fail_without_panicking() {
+        // A CRC without frame flags, then bytes that are not a valid stream
+        let mut frame = vec![0u8; 4];
+        frame.extend(noise(0x2545_f491, 1 << 16).iter().map(|value| *value as u8));
+        let mut packet = 2048u32.to_le_bytes().to_vec();
+        packet.extend_from_slice(&0u32.to_le_bytes());
+        packet.extend(
+            frame
+                .chunks_exact(4)
+                .flat_map(|word| [word[3], word[2], word[1], word[0]]),
+        );
+        let packet = Packet::new_from_slice(0, 0, 2048, &packet);
+
+        for version in [MIN_VERSION, DESCRIPTOR_VERSION, PIVOT_VERSION] {
+            for compression in [1000u16, 2000, 3000, 4000, 5000] {
+                let mut extra_data = version.to_le_bytes().to_vec();
+                extra_data.extend_from_slice(&compression.to_le_bytes());
+                let mut params = CodecParameters::new();
+                params
+                    .for_codec(CODEC_TYPE_MONKEYS_AUDIO)
+                    .with_sample_rate(44_100)
+                    .with_channels(Channels::FRONT_LEFT | Channels::FRONT_RIGHT)
+                    .with_bits_per_sample(16)
+                    .with_max_frames_per_packet(4096)
+                    .with_extra_data(extra_data.into_boxed_slice());
+                let mut decoder = ApeDecoder::try_new(&params, &DecoderOptions::default()).unwrap();
+                match decoder.decode(&packet) {
+                    Ok(decoded) => assert_eq!(decoded.frames(), 2048),
+                    Err(error) => assert!(matches!(error, Error::DecodeError(_)), "{}", error),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn filters_wrap_their_history_under_both_adaption_rules() {
+        let residuals: Vec<i32> = noise(0x9e37_79b9, 3000)
+            .iter()
+            .map(|value| (*value as i32) >> 16)
+            .collect();
+        for version in [MIN_VERSION, DESCRIPTOR_VERSION] {
+            for filters in FILTERS {
+                let mut data = residuals.clone();
+                for &(order, fraction_bits) in filters {
+                    NnFilter::new(order, fraction_bits).apply(version, &mut data);
+                }
+                let mut predictor = Predictor::new();
+                for sample in &data {
+                    predictor.update(*sample, 0, &Y_SLOTS);
+                    predictor.advance();
+                }
+            }
+        }
+        // Silence stays silent through every stage
+        let mut data = vec![0; 1000];
+        NnFilter::new(16, 11).apply(PIVOT_VERSION, &mut data);
+        let mut predictor = Predictor::new();
+        assert!(data.iter().all(|sample| {
+            let output = predictor.update(*sample, 0, &Y_SLOTS);
+            predictor.advance();
+            output == 0
+        }));
+    }
+
+    #[test]
+    fn rejects_versions_before_3_95() {
+        let mut bytes = ape_file();
+        bytes[4..6].copy_from_slice(&3930u16.to_le_bytes());
+        let source = MediaSourceStream::new(Box::new(Cursor::new(bytes)), Default::default());
+        assert!(ApeReader::try_new(source, &FormatOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,433 @@
+use crate::import;
+use crate::search;
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+use std::path::Path;
+
+pub const ROLE_MAIN: &str = "main";
+pub const ROLE_FEATURED: &str = "featured";
+pub const ROLE_ALBUM_ARTIST: &str = "album_artist";
+pub const ROLE_REMIXER: &str = "remixer";
+pub const ALL_ROLES: [&str; 4] = [ROLE_MAIN, ROLE_FEATURED, ROLE_ALBUM_ARTIST, ROLE_REMIXER];
+
+/// Words that mark a "(... Remix)" group as a version rather than a person
+const GENERIC_REMIX_NAMES: [&str; 7] = [
+    "original",
+    "extended",
+    "radio",
+    "club",
+    "dub",
+    "instrumental",
+    "official",
+];
+const FEATURING_MARKERS: [&str; 5] = ["featuring ", "feat. ", "feat ", "ft. ", "ft "];
+
+/// The people credited on one track, by role, in tag order
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ArtistCredits {
+    pub main: Vec<String>,
+    pub featured: Vec<String>,
+    pub album_artists: Vec<String>,
+    pub remixers: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ArtistSummary {
+    pub id: i64,
+    pub name: String,
+    /// Library tracks crediting the artist in any role
+    pub track_count: i64,
+    pub main_count: i64,
+    pub featured_count: i64,
+    pub album_artist_count: i64,
+    pub remixer_count: i64,
+}
+
+impl ArtistCredits {
+    /// Work out credits from tag values. A multi-value ARTISTS tag names the
+    /// main artists exactly; otherwise the display string is split on "&", ","
+    /// and ";". Featured artists come from "feat." in the artist or title, and
+    /// remixers from a REMIXER tag or a "(Name Remix)" title suffix.
+    pub fn from_tags(
+        artist: Option<&str>,
+        artists: &[String],
+        album_artist: Option<&str>,
+        remixers: &[String],
+        title: Option<&str>,
+    ) -> Self {
+        let mut credits = ArtistCredits::default();
+        let artist = artist.filter(|value| *value != import::UNKNOWN_ARTIST);
+
+        let (main, featured) = artist
+            .map(|value| split_featuring(value, false))
+            .unwrap_or(("", None));
+        let title_featured = title.and_then(|value| split_featuring(value, true).1);
+        for value in featured.into_iter().chain(title_featured) {
+            push_names(&mut credits.featured, search::split_artist_tokens(value));
+        }
+
+        if artists.is_empty() {
+            push_names(&mut credits.main, search::split_artist_tokens(main));
+        } else {
+            push_names(&mut credits.main, artists.iter().map(String::as_str));
+        }
+        // ARTISTS lists everyone on the recording, featured artists included
+        let featured_keys: Vec<String> =
+            credits.featured.iter().map(|name| name_key(name)).collect();
+        credits
+            .main
+            .retain(|name| !featured_keys.contains(&name_key(name)));
+
+        if let Some(value) = album_artist.filter(|value| *value != import::UNKNOWN_ARTIST) {
+            push_names(
+                &mut credits.album_artists,
+                search::split_artist_tokens(value),
+            );
+        }
+
+        if remixers.is_empty() {
+            if let Some(title) = title {
+                push_names(&mut credits.remixers, remixers_from_title(title));
+            }
+        } else {
+            for value in remixers {
+                push_names(&mut credits.remixers, search::split_artist_tokens(value));
+            }
+        }
+
+        credits
+    }
+
+    /// Credits derived from a track's stored artist, album artist and title,
+    /// used when there are no tags to read (edits in the app, older rows)
+    pub fn from_row(conn: &Connection, track_id: &str) -> Result<Option<Self>, String> {
+        let row = conn
+            .query_row(
+                "SELECT artist, album_artist, title FROM tracks WHERE id = ?1",
+                [track_id],
+                |row| {
+                    Ok((
+                        row.get::<_, Option<String>>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        row.get::<_, Option<String>>(2)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(|error| error.to_string())?;
+
+        Ok(row.map(|(artist, album_artist, title)| {
+            Self::from_tags(
+                artist.as_deref(),
+                &[],
+                album_artist.as_deref(),
+                &[],
+                title.as_deref(),
+            )
+        }))
+    }
+
+    fn names(&self, role: &str) -> &[String] {
+        match role {
+            ROLE_MAIN => &self.main,
+            ROLE_FEATURED => &self.featured,
+            ROLE_ALBUM_ARTIST => &self.album_artists,
+            ROLE_REMIXER => &self.remixers,
+            _ => &[],
+        }
+    }
+}
+
+pub fn ensure_artist_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS artists (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            name_key TEXT NOT NULL UNIQUE
+        );
+        CREATE TABLE IF NOT EXISTS track_artists (
+            track_id TEXT NOT NULL,
+            artist_id INTEGER NOT NULL,
+            role TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            PRIMARY KEY (track_id, artist_id, role)
+        );
+        CREATE INDEX IF NOT EXISTS idx_track_artists_artist ON track_artists(artist_id);",
+    )
+    .map_err(|error| error.to_string())?;
+
+    Ok(())
+}
+
+/// Replace the track's credits for `roles` with the ones in `credits`.
+/// Artists are shared between tracks by their normalised name; the first
+/// spelling seen is the one shown.
+pub fn set_track_credits(
+    conn: &Connection,
+    track_id: &str,
+    credits: &ArtistCredits,
+    roles: &[&str],
+) -> Result<(), String> {
+    for role in roles {
+        conn.execute(
+            "DELETE FROM track_artists WHERE track_id = ?1 AND role = ?2",
+            (track_id, role),
+        )
+        .map_err(|error| error.to_string())?;
+
+        for (position, name) in credits.names(role).iter().enumerate() {
+            let artist_id = artist_id(conn, name)?;
+            conn.prepare_cached(
+                "INSERT OR IGNORE INTO track_artists (track_id, artist_id, role, position)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )
+            .and_then(|mut stmt| stmt.execute((track_id, artist_id, role, position as i64)))
+            .map_err(|error| error.to_string())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Artists credited on library tracks, with how many tracks credit them in
+/// each role
+pub fn list_artists(db_path: &str) -> Result<Vec<ArtistSummary>, String> {
+    if !Path::new(db_path).exists() {
+        return Ok(Vec::new());
+    }
+
+    let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    import::ensure_schema(&conn)?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT a.id, a.name, COUNT(DISTINCT ta.track_id),
+                    COUNT(DISTINCT CASE WHEN ta.role = ?2 THEN ta.track_id END),
+                    COUNT(DISTINCT CASE WHEN ta.role = ?3 THEN ta.track_id END),
+                    COUNT(DISTINCT CASE WHEN ta.role = ?4 THEN ta.track_id END),
+                    COUNT(DISTINCT CASE WHEN ta.role = ?5 THEN ta.track_id END)
+             FROM artists a
+             JOIN track_artists ta ON ta.artist_id = a.id
+             JOIN tracks t ON t.id = ta.track_id
+             WHERE t.import_status = ?1
+             GROUP BY a.id
+             ORDER BY a.name COLLATE NOCASE",
+        )
+        .map_err(|error| error.to_string())?;
+    let rows = stmt
+        .query_map(
+            (
+                import::STATUS_ACCEPTED,
+                ROLE_MAIN,
+                ROLE_FEATURED,
+                ROLE_ALBUM_ARTIST,
+                ROLE_REMIXER,
+            ),
+            |row| {
+                Ok(ArtistSummary {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    track_count: row.get(2)?,
+                    main_count: row.get(3)?,
+                    featured_count: row.get(4)?,
+                    album_artist_count: row.get(5)?,
+                    remixer_count: row.get(6)?,
+                })
+            },
+        )
+        .map_err(|error| error.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|error| error.to_string())
+}
+
+/// Drop credits of tracks that were removed, and artists no track credits
+pub fn remove_orphaned_credits(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "DELETE FROM track_artists WHERE track_id NOT IN (SELECT id FROM tracks);
+         DELETE FROM artists WHERE id NOT IN (SELECT artist_id FROM track_artists);",
+    )
+    .map_err(|error| error.to_string())
+}
+
+/// Credit tracks imported before artists were tracked, from their stored
+/// artist, album artist and title
+pub fn backfill_credits(conn: &Connection) -> Result<usize, String> {
+    let track_ids: Vec<String> = conn
+        .prepare("SELECT id FROM tracks WHERE id NOT IN (SELECT track_id FROM track_artists)")
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| row.get(0))?
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .map_err(|error| error.to_string())?;
+
+    for track_id in &track_ids {
+        if let Some(credits) = ArtistCredits::from_row(conn, track_id)? {
+            set_track_credits(conn, track_id, &credits, &ALL_ROLES)?;
+        }
+    }
+
+    Ok(track_ids.len())
+}
+
+fn artist_id(conn: &Connection, name: &str) -> Result<i64, String> {
+    let key = name_key(name);
+    conn.prepare_cached("INSERT OR IGNORE INTO artists (name, name_key) VALUES (?1, ?2)")
+        .and_then(|mut stmt| stmt.execute((name, &key)))
+        .map_err(|error| error.to_string())?;
+    conn.prepare_cached("SELECT id FROM artists WHERE name_key = ?1")
+        .and_then(|mut stmt| stmt.query_row([&key], |row| row.get(0)))
+        .map_err(|error| error.to_string())
+}
+
+/// Names that only differ in case, accents or punctuation are one artist.
+/// Names without any latin letters or digits are compared as written.
+fn name_key(name: &str) -> String {
+    let key = search::normalize_fragment(name);
+    if key.is_empty() {
+        name.trim().to_lowercase()
+    } else {
+        key
+    }
+}
+
+fn push_names<'a>(names: &mut Vec<String>, values: impl IntoIterator<Item = &'a str>) {
+    for value in values {
+        let value = value.trim();
+        if value.is_empty() || names.iter().any(|name| name_key(name) == name_key(value)) {
+            continue;
+        }
+        names.push(value.to_string());
+    }
+}
+
+/// Split "A & B feat. C" into the main part and the featured part. A marker
+/// must follow a space or an opening bracket; the bracketed form ends at the
+/// closing bracket. Titles only use the bracketed form.
+fn split_featuring(value: &str, bracketed_only: bool) -> (&str, Option<&str>) {
+    let lower = value.to_ascii_lowercase();
+    let mut found = None;
+    for (index, _) in lower.char_indices() {
+        let rest = &lower[index..];
+        let bracketed = rest.starts_with('(') || rest.starts_with('[');
+        if !bracketed && (bracketed_only || !rest.starts_with(' ')) {
+            continue;
+        }
+        if let Some(marker) = FEATURING_MARKERS
+            .iter()
+            .find(|marker| rest[1..].starts_with(*marker))
+        {
+            found = Some((index, index + 1 + marker.len(), bracketed));
+            break;
+        }
+    }
+
+    let Some((start, content, bracketed)) = found else {
+        return (value, None);
+    };
+    let mut featured = &value[content..];
+    if bracketed {
+        if let Some(end) = featured.find([')', ']']) {
+            featured = &featured[..end];
+        }
+    }
+    (
+        value[..start].trim(),
+        Some(featured.trim()).filter(|value| !value.is_empty()),
+    )
+}
+
+/// Names from "(Name Remix)" or "[Name Remix]" groups in a title
+fn remixers_from_title(title: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = title;
+    while let Some(open) = rest.find(['(', '[']) {
+        let inner = &rest[open + 1..];
+        let Some(close) = inner.find([')', ']']) else {
+            break;
+        };
+        let group = inner[..close].trim();
+        if let Some(name_len) = group
+            .to_ascii_lowercase()
+            .strip_suffix(" remix")
+            .map(str::len)
+        {
+            let name = group[..name_len].trim();
+            if !GENERIC_REMIX_NAMES.contains(&search::normalize_fragment(name).as_str()) {
+                names.extend(search::split_artist_tokens(name));
+            }
+        }
+        rest = &inner[close + 1..];
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_display_string_by_role() {
+        let credits = ArtistCredits::from_tags(
+            Some("Alpha & Beta feat. Gamma"),
+            &[],
+            Some("Alpha"),
+            &[],
+            Some("Song (Delta Remix)"),
+        );
+        assert_eq!(credits.main, ["Alpha", "Beta"]);
+        assert_eq!(credits.featured, ["Gamma"]);
+        assert_eq!(credits.album_artists, ["Alpha"]);
+        assert_eq!(credits.remixers, ["Delta"]);
+    }
+
+    #[test]
+    fn multi_value_artists_tag_wins_over_splitting() {
+        let credits = ArtistCredits::from_tags(
+            Some("Simon & Garfunkel (feat. Guest)"),
+            &["Simon & Garfunkel".to_string(), "Guest".to_string()],
+            None,
+            &[],
+            Some("Song [feat. Other] (Extended Remix)"),
+        );
+        assert_eq!(credits.main, ["Simon & Garfunkel"]);
+        assert_eq!(credits.featured, ["Guest", "Other"]);
+        assert!(credits.remixers.is_empty());
+    }
+
+    #[test]
+    fn placeholder_artist_is_not_credited() {
+        let credits =
+            ArtistCredits::from_tags(Some(import::UNKNOWN_ARTIST), &[], None, &[], Some("Song"));
+        assert_eq!(credits, ArtistCredits::default());
+    }
+
+    fn artist_names(conn: &Connection) -> Vec<String> {
+        conn.prepare("SELECT name FROM artists ORDER BY name")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn backfill_credits_old_tracks_and_cleanup_drops_removed_ones() {
+        let conn = Connection::open_in_memory().unwrap();
+        import::ensure_schema(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO tracks (id, title, artist) VALUES ('1', 'One', 'Alpha feat. Beta');
+             INSERT INTO tracks (id, title, artist) VALUES ('2', 'Two', 'Gamma');",
+        )
+        .unwrap();
+
+        assert_eq!(backfill_credits(&conn).unwrap(), 2);
+        assert_eq!(artist_names(&conn), ["Alpha", "Beta", "Gamma"]);
+        assert_eq!(backfill_credits(&conn).unwrap(), 0);
+
+        conn.execute("DELETE FROM tracks WHERE id = '1'", [])
+            .unwrap();
+        remove_orphaned_credits(&conn).unwrap();
+        assert_eq!(artist_names(&conn), ["Gamma"]);
+    }
+}
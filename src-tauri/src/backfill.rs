@@ -1,13 +1,18 @@
+use crate::albums;
+use crate::artists;
 use crate::cover_art;
+use crate::formats::{self, AudioContainer};
+use crate::import;
 use crate::search;
+use lofty::file::AudioFile;
 use lofty::probe::Probe;
-use rusqlite::Connection;
+use rusqlite::{params, Connection};
 use serde_json::Value;
 use std::path::Path;
 
 #[derive(Debug)]
 struct TrackSearchRow {
-    id: i64,
+    id: String,
     title: Option<String>,
     artist: Option<String>,
     album: Option<String>,
@@ -122,6 +127,8 @@ pub fn run_cover_art_backfill(db_path: &str, cache_dir: &Path) -> Result<usize,
     std::fs::create_dir_all(cache_dir).map_err(|e| e.to_string())?;
 
     let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    import::ensure_schema(&conn)?;
+    let sidecar_patterns = cover_art::sidecar_patterns(&conn)?;
 
     // Find tracks without cover art but with source path
     let mut pending: Vec<(String, String)> = Vec::new();
@@ -157,16 +164,18 @@ pub fn run_cover_art_backfill(db_path: &str, cache_dir: &Path) -> Result<usize,
             continue;
         }
 
-        // Try to read the file and extract cover art
-        let tagged = match Probe::open(path).and_then(|p| p.read()) {
-            Ok(t) => t,
-            Err(_) => continue,
-        };
+        // Embedded art first, then images in the track's folder; files lofty
+        // cannot read may still have a sidecar
+        let tagged = Probe::open(path).and_then(|p| p.read()).ok();
 
-        if let Some(cached) = cover_art::process_cover_art(&tagged, cache_dir) {
+        if let Some((cached, source)) =
+            cover_art::resolve_cover_art(tagged.as_ref(), path, &sidecar_patterns, cache_dir)
+        {
             tx.execute(
-                "UPDATE tracks SET cover_art_path = ?1, cover_art_thumb_path = ?2 WHERE id = ?3",
-                (&cached.full_path, &cached.thumb_path, &id),
+                "UPDATE tracks SET cover_art_path = ?1, cover_art_thumb_path = ?2,
+                    cover_art_source = ?3
+                 WHERE id = ?4",
+                (&cached.full_path, &cached.thumb_path, source.as_str(), &id),
             )
             .map_err(|e| e.to_string())?;
             updated += 1;
@@ -176,3 +185,105 @@ pub fn run_cover_art_backfill(db_path: &str, cache_dir: &Path) -> Result<usize,
     tx.commit().map_err(|e| e.to_string())?;
     Ok(updated)
 }
+
+/// Backfill technical audio properties for tracks imported before they were
+/// recorded
+pub fn run_audio_properties_backfill(db_path: &str) -> Result<usize, String> {
+    let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    import::ensure_schema(&conn)?;
+
+    let mut pending: Vec<(String, String)> = Vec::new();
+    {
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, source_path FROM tracks
+                 WHERE source_path IS NOT NULL
+                 AND source_path != ''
+                 AND container IS NULL",
+            )
+            .map_err(|e| e.to_string())?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                let id: String = row.get(0)?;
+                let source_path: String = row.get(1)?;
+                Ok((id, source_path))
+            })
+            .map_err(|e| e.to_string())?;
+
+        for row in rows {
+            pending.push(row.map_err(|e| e.to_string())?);
+        }
+    }
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut updated = 0;
+
+    for (id, source_path) in pending {
+        let path = Path::new(&source_path);
+        let Some(container) = formats::sniff(path) else {
+            continue;
+        };
+
+        let tagged = match container {
+            AudioContainer::Tagged(_) => Probe::open(path)
+                .ok()
+                .and_then(|p| p.guess_file_type().ok())
+                .and_then(|p| p.read().ok()),
+            AudioContainer::Stream(_) => None,
+        };
+        let audio = formats::read_audio_properties(
+            path,
+            Some(container),
+            tagged.as_ref().map(|file| file.properties()),
+        );
+        let file_size = import::file_signature(path).map(|(size, _)| size);
+
+        tx.execute(
+            "UPDATE tracks SET sample_rate = ?1, bit_depth = ?2, channels = ?3, codec = ?4,
+                container = ?5, is_lossless = ?6, bitrate_mode = ?7,
+                file_size = COALESCE(file_size, ?8)
+             WHERE id = ?9",
+            params![
+                audio.sample_rate,
+                audio.bit_depth,
+                audio.channels,
+                audio.codec,
+                audio.container,
+                audio.lossless,
+                audio.bitrate_mode,
+                file_size,
+                &id
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        updated += 1;
+    }
+
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(updated)
+}
+
+/// Credit artists on tracks imported before credits were recorded
+pub fn run_artist_credits_backfill(db_path: &str) -> Result<usize, String> {
+    let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    import::ensure_schema(&conn)?;
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    artists::remove_orphaned_credits(&tx)?;
+    let updated = artists::backfill_credits(&tx)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(updated)
+}
+
+/// Group tracks into albums again, for libraries imported before albums were
+/// recorded or while album keys still included the year
+pub fn run_album_backfill(db_path: &str) -> Result<usize, String> {
+    let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    import::ensure_schema(&conn)?;
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let linked = albums::backfill_albums(&tx)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(linked)
+}
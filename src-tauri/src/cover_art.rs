@@ -1,17 +1,26 @@
+use crate::glob;
+use crate::settings;
 use image::imageops::FilterType;
 use image::ImageFormat;
 use lofty::file::TaggedFile;
 use lofty::picture::{MimeType, PictureType};
 use lofty::prelude::*;
+use rusqlite::Connection;
 use sha2::{Digest, Sha256};
 use std::fs;
 use std::io::Cursor;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Size for full cover art (sidebar/now playing)
 const FULL_SIZE: u32 = 512;
 /// Size for thumbnail (bottom bar/lists)
 const THUMB_SIZE: u32 = 128;
+/// Setting holding the ordered sidecar file name patterns
+pub const SIDECAR_PATTERNS_SETTING: &str = "cover_art.sidecar_patterns";
+/// Sidecar file names tried when a file has no embedded picture, in order
+pub const DEFAULT_SIDECAR_PATTERNS: [&str; 5] =
+    ["cover.*", "folder.*", "front.*", "album.*", "albumart*.*"];
+const IMAGE_EXTENSIONS: [&str; 6] = ["jpg", "jpeg", "png", "webp", "gif", "bmp"];
 
 /// Result of caching cover art to disk
 #[derive(Debug, Clone)]
@@ -21,6 +30,24 @@ pub struct CachedCover {
     pub hash: String,
 }
 
+/// Where a track's cover art was found, stored in `tracks.cover_art_source`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverSource {
+    /// Embedded picture
+    Tag,
+    /// Image file next to the audio file
+    Sidecar,
+}
+
+impl CoverSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Tag => "tag",
+            Self::Sidecar => "sidecar",
+        }
+    }
+}
+
 /// Extract cover art bytes and mime type from a tagged file
 /// Prefers CoverFront, falls back to first available picture
 pub fn extract_cover_bytes(tagged: &TaggedFile) -> Option<(Vec<u8>, MimeType)> {
@@ -123,3 +150,113 @@ pub fn process_cover_art(tagged: &TaggedFile, cache_dir: &Path) -> Option<Cached
     let (bytes, _mime) = extract_cover_bytes(tagged)?;
     cache_cover_art(&bytes, cache_dir)
 }
+
+/// Cover art for an audio file: the embedded picture if there is one,
+/// otherwise the first image in the file's folder matching `patterns`
+pub fn resolve_cover_art(
+    tagged: Option<&TaggedFile>,
+    path: &Path,
+    patterns: &[String],
+    cache_dir: &Path,
+) -> Option<(CachedCover, CoverSource)> {
+    if let Some(cached) = tagged.and_then(|tagged| process_cover_art(tagged, cache_dir)) {
+        return Some((cached, CoverSource::Tag));
+    }
+
+    let sidecar = find_sidecar_image(path, patterns)?;
+    let bytes = fs::read(&sidecar).ok()?;
+    let cached = cache_cover_art(&bytes, cache_dir)?;
+    Some((cached, CoverSource::Sidecar))
+}
+
+/// Configured sidecar patterns, falling back to `DEFAULT_SIDECAR_PATTERNS`
+pub fn sidecar_patterns(conn: &Connection) -> Result<Vec<String>, String> {
+    Ok(
+        settings::get_setting(conn, SIDECAR_PATTERNS_SETTING)?.unwrap_or_else(|| {
+            DEFAULT_SIDECAR_PATTERNS
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect()
+        }),
+    )
+}
+
+/// First image next to `path` matching one of `patterns`, tried in order.
+/// Patterns match file names case-insensitively (see `glob::matches`).
+pub fn find_sidecar_image(path: &Path, patterns: &[String]) -> Option<PathBuf> {
+    let dir = path.parent()?;
+    let mut images: Vec<(String, PathBuf)> = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|candidate| {
+            candidate
+                .extension()
+                .and_then(|value| value.to_str())
+                .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+                && candidate.is_file()
+        })
+        .filter_map(|candidate| {
+            let name = candidate.file_name()?.to_str()?.to_lowercase();
+            Some((name, candidate))
+        })
+        .collect();
+    // Stable choice when several files match the same pattern
+    images.sort();
+
+    patterns.iter().find_map(|pattern| {
+        images
+            .iter()
+            .find(|(name, _)| glob::matches(pattern, name))
+            .map(|(_, candidate)| candidate.clone())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> Vec<String> {
+        DEFAULT_SIDECAR_PATTERNS
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn sidecar_images_follow_pattern_order() {
+        let dir = std::env::temp_dir().join(format!("muro-sidecar-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let track = dir.join("01 - Song.flac");
+        fs::write(&track, b"").unwrap();
+        fs::write(dir.join("scan01.jpg"), b"").unwrap();
+        fs::write(dir.join("cover.txt"), b"").unwrap();
+        // Folders are never picked, whatever their name
+        fs::create_dir_all(dir.join("front.jpg")).unwrap();
+
+        // Arbitrary images such as booklet scans are not covers by default
+        assert_eq!(find_sidecar_image(&track, &defaults()), None);
+
+        fs::write(dir.join("Folder.PNG"), b"").unwrap();
+        assert_eq!(
+            find_sidecar_image(&track, &defaults()),
+            Some(dir.join("Folder.PNG"))
+        );
+
+        // An earlier pattern wins over an earlier file name
+        fs::write(dir.join("Cover.jpg"), b"").unwrap();
+        assert_eq!(
+            find_sidecar_image(&track, &defaults()),
+            Some(dir.join("Cover.jpg"))
+        );
+
+        let custom = ["scan*.jpg".to_string(), "cover.*".to_string()];
+        assert_eq!(
+            find_sidecar_image(&track, &custom),
+            Some(dir.join("scan01.jpg"))
+        );
+        assert_eq!(find_sidecar_image(&track, &[]), None);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,494 @@
+use crate::backfill;
+use crate::import;
+use crate::playlist_io::{self, UnresolvedEntry};
+use crate::rekordbox;
+use crate::tag_write;
+use crate::traktor;
+use quick_xml::events::BytesStart;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Key names indexed the way Traktor numbers them: majors 0-11 from C, then
+/// minors 12-23 from C minor. Rekordbox uses the same spelling for tonality.
+pub const KEY_NAMES: [&str; 24] = [
+    "C", "Db", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B", "Cm", "C#m", "Dm", "Ebm",
+    "Em", "Fm", "F#m", "Gm", "G#m", "Am", "Bbm", "Bm",
+];
+
+/// Rating scale used by both Rekordbox and Traktor: 51 per star
+const RATING_STEP: f32 = 51.0;
+
+/// A track as described by a DJ application's collection file
+#[derive(Debug, Default)]
+pub struct CollectionTrack {
+    /// Identifier the collection's playlists refer to the track by
+    pub key: String,
+    pub location: String,
+    /// Whether `location` is a `file://` URI rather than a plain path
+    pub location_is_uri: bool,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub duration_seconds: Option<f64>,
+    pub bpm: Option<f64>,
+    pub musical_key: Option<String>,
+    /// 0-5 stars, like `tracks.rating`
+    pub rating: Option<f32>,
+    pub comment: Option<String>,
+    pub play_count: Option<i32>,
+    pub cues: Vec<CuePoint>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CuePoint {
+    /// "cue", "hotcue", "loop", "fade_in", "fade_out", "load" or "grid"
+    pub kind: String,
+    /// Hot cue pad, counted from 0
+    pub slot: Option<i32>,
+    pub start_seconds: f64,
+    pub end_seconds: Option<f64>,
+    pub name: Option<String>,
+    /// "#rrggbb"
+    pub color: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum CollectionNode {
+    Folder {
+        name: String,
+        children: Vec<CollectionNode>,
+    },
+    Playlist {
+        name: String,
+        track_keys: Vec<String>,
+    },
+}
+
+#[derive(Debug, Default)]
+pub struct Collection {
+    pub tracks: Vec<CollectionTrack>,
+    pub nodes: Vec<CollectionNode>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct CollectionImportReport {
+    pub source: String,
+    pub total: usize,
+    /// Tracks that were already in the library
+    pub matched: usize,
+    /// Tracks imported into the library from the collection
+    pub imported: usize,
+    pub cue_points: usize,
+    pub playlists: usize,
+    pub folders: usize,
+    pub unresolved: Vec<UnresolvedEntry>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct StoredCuePoint {
+    pub id: String,
+    pub source: String,
+    #[serde(flatten)]
+    pub cue: CuePoint,
+}
+
+pub fn ensure_cue_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS cue_points (
+            id TEXT PRIMARY KEY,
+            track_id TEXT NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+            source TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            slot INTEGER,
+            start_seconds REAL NOT NULL,
+            end_seconds REAL,
+            name TEXT,
+            color TEXT
+        );
+        CREATE INDEX IF NOT EXISTS cue_points_track_idx ON cue_points (track_id, start_seconds);",
+    )
+    .map_err(|error| error.to_string())
+}
+
+pub fn load_cue_points(db_path: &str, track_id: &str) -> Result<Vec<StoredCuePoint>, String> {
+    if !Path::new(db_path).exists() {
+        return Ok(Vec::new());
+    }
+
+    let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    ensure_cue_schema(&conn)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, source, kind, slot, start_seconds, end_seconds, name, color
+             FROM cue_points WHERE track_id = ?1 ORDER BY start_seconds ASC",
+        )
+        .map_err(|error| error.to_string())?;
+    let rows = stmt
+        .query_map([track_id], |row| {
+            Ok(StoredCuePoint {
+                id: row.get(0)?,
+                source: row.get(1)?,
+                cue: CuePoint {
+                    kind: row.get(2)?,
+                    slot: row.get(3)?,
+                    start_seconds: row.get(4)?,
+                    end_seconds: row.get(5)?,
+                    name: row.get(6)?,
+                    color: row.get(7)?,
+                },
+            })
+        })
+        .map_err(|error| error.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|error| error.to_string())
+}
+
+/// Import a Rekordbox `collection.xml` or Traktor `collection.nml`
+pub fn import_collection_file(
+    db_path: &str,
+    cache_dir: &Path,
+    path: &Path,
+) -> Result<CollectionImportReport, String> {
+    let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+    if text.contains("<DJ_PLAYLISTS") {
+        let collection = rekordbox::parse_collection(&text)?;
+        apply_collection(db_path, cache_dir, "rekordbox", collection)
+    } else if text.contains("<NML") {
+        let collection = traktor::parse_collection(&text)?;
+        apply_collection(db_path, cache_dir, "traktor", collection)
+    } else {
+        Err("Not a Rekordbox or Traktor collection".to_string())
+    }
+}
+
+/// Bring a parsed collection into the library. Tracks are matched by path and
+/// files not in the library yet are imported; BPM, key, rating, comment and
+/// play count from the collection then overwrite the library values, and all
+/// but the play count are written to the files' tags. The track's cue points
+/// from an earlier import of the same `source` are replaced. Playlists and
+/// folders are always created anew.
+pub fn apply_collection(
+    db_path: &str,
+    cache_dir: &Path,
+    source: &str,
+    collection: Collection,
+) -> Result<CollectionImportReport, String> {
+    let mut report = CollectionImportReport {
+        source: source.to_string(),
+        total: collection.tracks.len(),
+        ..Default::default()
+    };
+
+    if let Some(parent) = Path::new(db_path).parent() {
+        std::fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+    }
+    let mut conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    import::ensure_schema(&conn)?;
+    import::ensure_playlist_schema(&conn)?;
+    ensure_cue_schema(&conn)?;
+
+    let mut resolved: Vec<(&CollectionTrack, Result<String, PathBuf>)> = Vec::new();
+    for track in &collection.tracks {
+        match playlist_io::resolve_location(&track.location, Path::new(""), track.location_is_uri) {
+            Ok(path) => match playlist_io::find_track_id(&conn, &path)? {
+                Some(track_id) => resolved.push((track, Ok(track_id))),
+                None => resolved.push((track, Err(path))),
+            },
+            Err(reason) => report.unresolved.push(unresolved_entry(track, reason)),
+        }
+    }
+    report.matched = resolved.iter().filter(|(_, item)| item.is_ok()).count();
+
+    let mut to_import: Vec<String> = resolved
+        .iter()
+        .filter_map(|(_, item)| item.as_ref().err())
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    to_import.sort();
+    to_import.dedup();
+
+    let mut import_issues: HashMap<String, String> = HashMap::new();
+    if !to_import.is_empty() {
+        let import_report = import::import_files(to_import, db_path, cache_dir)?;
+        report.imported = import_report.imported.len();
+        for issue in import_report
+            .unsupported
+            .into_iter()
+            .chain(import_report.failed)
+        {
+            import_issues.insert(issue.path, issue.reason);
+        }
+    }
+
+    let mut track_ids: HashMap<&str, String> = HashMap::new();
+    let mut matched_tracks = Vec::new();
+    for (track, item) in resolved {
+        let track_id = match item {
+            Ok(track_id) => track_id,
+            Err(path) => match playlist_io::find_track_id(&conn, &path)? {
+                Some(track_id) => track_id,
+                None => {
+                    let reason = import_issues
+                        .get(path.to_string_lossy().as_ref())
+                        .map(String::as_str)
+                        .unwrap_or("Could not be imported");
+                    report.unresolved.push(unresolved_entry(track, reason));
+                    continue;
+                }
+            },
+        };
+        track_ids.insert(track.key.as_str(), track_id.clone());
+        matched_tracks.push((track, track_id));
+    }
+
+    let now = import::current_timestamp();
+    let tx = conn.transaction().map_err(|error| error.to_string())?;
+    for (track, track_id) in &matched_tracks {
+        let comment_json = track
+            .comment
+            .as_ref()
+            .map(|comment| serde_json::to_string(&[comment]).unwrap_or_else(|_| "[]".to_string()));
+        // Clearing search_text lets the backfill below pick up the new comment
+        tx.execute(
+            "UPDATE tracks SET
+                bpm = COALESCE(?1, bpm),
+                key = COALESCE(?2, key),
+                rating = COALESCE(?3, rating),
+                comment_json = COALESCE(?4, comment_json),
+                search_text = CASE WHEN ?4 IS NULL THEN search_text ELSE NULL END,
+                play_count = MAX(COALESCE(play_count, 0), COALESCE(?5, 0)),
+                updated_at = ?6
+             WHERE id = ?7",
+            rusqlite::params![
+                track.bpm,
+                track.musical_key,
+                track.rating,
+                comment_json,
+                track.play_count,
+                now,
+                track_id
+            ],
+        )
+        .map_err(|error| error.to_string())?;
+
+        if track.cues.is_empty() {
+            continue;
+        }
+        tx.execute(
+            "DELETE FROM cue_points WHERE track_id = ?1 AND source = ?2",
+            (track_id, source),
+        )
+        .map_err(|error| error.to_string())?;
+        for cue in &track.cues {
+            tx.execute(
+                "INSERT INTO cue_points (
+                    id, track_id, source, kind, slot, start_seconds, end_seconds, name, color
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                rusqlite::params![
+                    Uuid::new_v4().to_string(),
+                    track_id,
+                    source,
+                    cue.kind,
+                    cue.slot,
+                    cue.start_seconds,
+                    cue.end_seconds,
+                    cue.name,
+                    cue.color
+                ],
+            )
+            .map_err(|error| error.to_string())?;
+            report.cue_points += 1;
+        }
+    }
+
+    for node in &collection.nodes {
+        insert_node(&tx, node, None, &track_ids, now, &mut report)?;
+    }
+    tx.commit().map_err(|error| error.to_string())?;
+
+    // A failed write is kept for `retry_tag_writes` and shown as unsynced
+    for (track, track_id) in &matched_tracks {
+        let keys: Vec<String> = [
+            ("bpm", track.bpm.is_some()),
+            ("key", track.musical_key.is_some()),
+            ("rating", track.rating.is_some()),
+            ("comment", track.comment.is_some()),
+        ]
+        .into_iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(key, _)| key.to_string())
+        .collect();
+        let _ = tag_write::sync_tags_to_file(&conn, track_id, &keys);
+    }
+
+    backfill::run_backfill(db_path)?;
+    Ok(report)
+}
+
+fn insert_node(
+    conn: &Connection,
+    node: &CollectionNode,
+    parent_id: Option<&str>,
+    track_ids: &HashMap<&str, String>,
+    now: i64,
+    report: &mut CollectionImportReport,
+) -> Result<(), String> {
+    let id = Uuid::new_v4().to_string();
+    let (name, is_folder) = match node {
+        CollectionNode::Folder { name, .. } => (name, true),
+        CollectionNode::Playlist { name, .. } => (name, false),
+    };
+    conn.execute(
+        "INSERT INTO playlists (id, name, created_at, parent_id, is_folder) VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![id, name, now, parent_id, is_folder],
+    )
+    .map_err(|error| error.to_string())?;
+
+    match node {
+        CollectionNode::Folder { children, .. } => {
+            report.folders += 1;
+            for child in children {
+                insert_node(conn, child, Some(&id), track_ids, now, report)?;
+            }
+        }
+        CollectionNode::Playlist { track_keys, .. } => {
+            report.playlists += 1;
+            let mut position = 0i64;
+            for key in track_keys {
+                // Entries for tracks that could not be resolved are dropped
+                let Some(track_id) = track_ids.get(key.as_str()) else {
+                    continue;
+                };
+                conn.execute(
+                    "INSERT INTO playlist_tracks (playlist_id, track_id, position) VALUES (?1, ?2, ?3)",
+                    (&id, track_id, position),
+                )
+                .map_err(|error| error.to_string())?;
+                position += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn unresolved_entry(track: &CollectionTrack, reason: &str) -> UnresolvedEntry {
+    let title = match (&track.artist, &track.title) {
+        (Some(artist), Some(title)) => Some(format!("{} - {}", artist, title)),
+        (None, Some(title)) => Some(title.clone()),
+        _ => None,
+    };
+    UnresolvedEntry {
+        location: track.location.clone(),
+        title,
+        duration_seconds: track.duration_seconds,
+        reason: reason.to_string(),
+    }
+}
+
+/// Position of a key in `KEY_NAMES`. Accepts note names ("F#m", "Ebmin",
+/// "A minor"), Camelot ("8A") and Open Key ("1m") notation.
+pub fn key_index(key: &str) -> Option<usize> {
+    let key = key.trim();
+    let digits = key.chars().take_while(|ch| ch.is_ascii_digit()).count();
+    if digits > 0 {
+        let number = key[..digits]
+            .parse::<usize>()
+            .ok()
+            .filter(|n| (1..=12).contains(n))?;
+        let (camelot, minor) = match key[digits..].trim().to_ascii_lowercase().as_str() {
+            "a" => (number, true),
+            "b" => (number, false),
+            // Open Key 1m/1d is Camelot 8A/8B
+            "m" => ((number + 6) % 12 + 1, true),
+            "d" => ((number + 6) % 12 + 1, false),
+            _ => return None,
+        };
+        // Camelot 8B is C major and each step adds a fifth (7 semitones);
+        // the minor keys are the relative minors, three semitones down
+        let major = (camelot + 4) * 7 % 12;
+        return Some(if minor { (major + 9) % 12 + 12 } else { major });
+    }
+
+    let mut chars = key.chars();
+    let base = match chars.next()?.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+    let rest = chars.as_str();
+    let (semitone, rest) = match rest.chars().next() {
+        Some('#' | '♯') => (base + 1, &rest[rest.chars().next()?.len_utf8()..]),
+        Some('b' | '♭') => (base + 11, &rest[rest.chars().next()?.len_utf8()..]),
+        _ => (base, rest),
+    };
+    let minor = match rest.trim().to_ascii_lowercase().as_str() {
+        "" | "maj" | "major" => false,
+        "m" | "min" | "minor" => true,
+        _ => return None,
+    };
+    Some(semitone % 12 + if minor { 12 } else { 0 })
+}
+
+/// Attributes of an XML element by local name, unescaped
+pub(crate) fn attributes(element: &BytesStart) -> HashMap<String, String> {
+    element
+        .attributes()
+        .flatten()
+        .filter_map(|attribute| {
+            let key = String::from_utf8_lossy(attribute.key.local_name().as_ref()).to_string();
+            let value = attribute.unescape_value().ok()?.to_string();
+            Some((key, value))
+        })
+        .collect()
+}
+
+/// Convert a 0-255 rating (POPM style, as Rekordbox and Traktor store it) to
+/// 0-5 stars
+pub(crate) fn rating_from_byte(value: &str) -> Option<f32> {
+    let value = value.trim().parse::<f32>().ok()?;
+    Some((value / RATING_STEP).round().clamp(0.0, 5.0))
+}
+
+pub(crate) fn non_empty(value: Option<&String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_index_reads_common_notations() {
+        let name = |key: &str| key_index(key).map(|index| KEY_NAMES[index]);
+        assert_eq!(name("Am"), Some("Am"));
+        assert_eq!(name("F#m"), Some("F#m"));
+        assert_eq!(name("Gb"), Some("F#"));
+        assert_eq!(name("Ebmin"), Some("Ebm"));
+        assert_eq!(name("C major"), Some("C"));
+        assert_eq!(name("8A"), Some("Am"));
+        assert_eq!(name("8B"), Some("C"));
+        assert_eq!(name("1A"), Some("G#m"));
+        assert_eq!(name("12B"), Some("E"));
+        assert_eq!(name("1m"), Some("Am"));
+        assert_eq!(name("6d"), Some("B"));
+        assert_eq!(name("13A"), None);
+        assert_eq!(name("unknown"), None);
+    }
+
+    #[test]
+    fn key_names_read_back_as_themselves() {
+        for (index, name) in KEY_NAMES.iter().enumerate() {
+            assert_eq!(key_index(name), Some(index), "{}", name);
+        }
+    }
+}
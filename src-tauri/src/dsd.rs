@@ -0,0 +1,810 @@
+use std::f64::consts::PI;
+use std::io::{Seek, SeekFrom};
+use symphonia::core::audio::{
+    AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec,
+};
+use symphonia::core::codecs::{
+    decl_codec_type, CodecDescriptor, CodecParameters, CodecType, Decoder, DecoderOptions,
+    FinalizeResult,
+};
+use symphonia::core::errors::{decode_error, seek_error, unsupported_error, Result, SeekErrorKind};
+use symphonia::core::formats::{
+    Cue, FormatOptions, FormatReader, Packet, SeekMode, SeekTo, SeekedTo, Track,
+};
+use symphonia::core::io::{MediaSourceStream, ReadBytes};
+use symphonia::core::meta::{Metadata, MetadataBuilder, MetadataLog, StandardTagKey, Tag, Value};
+use symphonia::core::probe::{Descriptor, Instantiate, QueryDescriptor};
+use symphonia::core::units::TimeBase;
+use symphonia::core::{support_codec, support_format};
+
+/// 1-bit DSD audio. Packets hold each channel's bytes back to back, MSB first.
+pub const CODEC_TYPE_DSD: CodecType = decl_codec_type(b"dsd");
+
+/// Bytes per channel in one packet; DSF files store audio in blocks of this size
+const BLOCK_BYTES: u64 = 4096;
+/// Lowest PCM rate DSD is decimated to (88.2 kHz for the 44.1 kHz family)
+const MIN_PCM_RATE: u32 = 88_200;
+/// DSD "digital silence" idle pattern, used to prime the filters without a DC step
+const DSD_SILENCE: u8 = 0x69;
+/// Bytes of history in the first, bit-level filter stage (8 taps each). At
+/// 256 taps everything that folds into the output band when decimating by 8
+/// is attenuated by over 110 dB, where 128 taps only reach about 97 dB.
+const STAGE1_BYTES: usize = 32;
+/// Taps per unit of decimation in the second stage
+const STAGE2_TAPS_PER_FACTOR: usize = 32;
+/// DSD's 0 dB reference is 50% modulation, so full-scale PCM is -6 dB of it
+const OUTPUT_GAIN: f32 = 0.5;
+
+/// Total decimation from the DSD bit rate to the PCM output rate: a power of
+/// two, at least 8, leaving a PCM rate of 88.2 kHz or more.
+fn decimation_for(dsd_rate: u32) -> u32 {
+    let mut factor = 8;
+    while dsd_rate / (factor * 2) >= MIN_PCM_RATE {
+        factor *= 2;
+    }
+    factor
+}
+
+fn channel_layout(count: usize) -> Option<Channels> {
+    match count {
+        1 => Some(Channels::FRONT_LEFT),
+        2 => Some(Channels::FRONT_LEFT | Channels::FRONT_RIGHT),
+        3..=8 => Some(Channels::from_bits_truncate((1 << count) - 1)),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Interleave {
+    /// DSF: `BLOCK_BYTES` of one channel, then the next channel
+    Block,
+    /// DSDIFF: one byte per channel in turn
+    Byte,
+}
+
+/// Demuxer for Sony DSF and Philips DSDIFF (DFF) files
+pub struct DsdReader {
+    reader: MediaSourceStream,
+    tracks: Vec<Track>,
+    cues: Vec<Cue>,
+    metadata: MetadataLog,
+    interleave: Interleave,
+    lsb_first: bool,
+    channels: usize,
+    decimation: u32,
+    data_start: u64,
+    /// Audio bytes per channel, excluding block padding
+    channel_bytes: u64,
+    /// Bytes per channel already returned as packets
+    position: u64,
+}
+
+impl QueryDescriptor for DsdReader {
+    fn query() -> &'static [Descriptor] {
+        &[
+            support_format!(
+                "dsf",
+                "DSD Stream File",
+                &["dsf"],
+                &["audio/dsf", "audio/x-dsf"],
+                &[b"DSD "]
+            ),
+            support_format!(
+                "dff",
+                "DSD Interchange File Format",
+                &["dff"],
+                &["audio/dff", "audio/x-dff"],
+                &[b"FRM8"]
+            ),
+        ]
+    }
+
+    fn score(_context: &[u8]) -> u8 {
+        255
+    }
+}
+
+struct DsdLayout {
+    interleave: Interleave,
+    lsb_first: bool,
+    channels: usize,
+    dsd_rate: u32,
+    data_start: u64,
+    channel_bytes: u64,
+}
+
+fn read_dsf(source: &mut MediaSourceStream, metadata: &mut MetadataLog) -> Result<DsdLayout> {
+    // "DSD " chunk: size, total file size, offset of the ID3v2 tag (0 if none)
+    let _chunk_size = source.read_u64()?;
+    let _file_size = source.read_u64()?;
+    let metadata_offset = source.read_u64()?;
+
+    if source.read_quad_bytes()? != *b"fmt " {
+        return decode_error("dsf: missing fmt chunk");
+    }
+    let fmt_size = source.read_u64()?;
+    let _version = source.read_u32()?;
+    if source.read_u32()? != 0 {
+        return unsupported_error("dsf: only raw DSD is supported");
+    }
+    let _channel_type = source.read_u32()?;
+    let channels = source.read_u32()? as usize;
+    let dsd_rate = source.read_u32()?;
+    let bits_per_sample = source.read_u32()?;
+    let sample_count = source.read_u64()?;
+    let block_size = source.read_u32()?;
+    let _reserved = source.read_u32()?;
+    // fmt is 52 bytes including its 12-byte header
+    source.ignore_bytes(fmt_size.saturating_sub(52))?;
+
+    if u64::from(block_size) != BLOCK_BYTES {
+        return unsupported_error("dsf: unexpected block size");
+    }
+
+    if source.read_quad_bytes()? != *b"data" {
+        return decode_error("dsf: missing data chunk");
+    }
+    let _data_size = source.read_u64()?;
+    let data_start = source.pos();
+
+    if metadata_offset > 0 {
+        source.seek(SeekFrom::Start(metadata_offset))?;
+        let mut builder = MetadataBuilder::new();
+        if symphonia_metadata::id3v2::read_id3v2(source, &mut builder).is_ok() {
+            metadata.push(builder.metadata());
+        }
+        source.seek(SeekFrom::Start(data_start))?;
+    }
+
+    Ok(DsdLayout {
+        interleave: Interleave::Block,
+        lsb_first: bits_per_sample == 1,
+        channels,
+        dsd_rate,
+        data_start,
+        channel_bytes: sample_count / 8,
+    })
+}
+
+fn read_dff(source: &mut MediaSourceStream, metadata: &mut MetadataLog) -> Result<DsdLayout> {
+    let form_size = source.read_be_u64()?;
+    if source.read_quad_bytes()? != *b"DSD " {
+        return unsupported_error("dff: not a DSD form");
+    }
+    let form_end = 12 + form_size;
+
+    let mut channels = 0usize;
+    let mut dsd_rate = 0u32;
+    let mut compression = *b"DSD ";
+    let mut data: Option<(u64, u64)> = None;
+    let mut tags = Vec::new();
+
+    while source.pos() + 12 <= form_end {
+        let id = source.read_quad_bytes()?;
+        let size = source.read_be_u64()?;
+        let start = source.pos();
+        // Chunks are padded to an even length
+        let end = start + size + (size & 1);
+
+        match &id {
+            b"PROP" => {
+                if source.read_quad_bytes()? != *b"SND " {
+                    source.seek(SeekFrom::Start(end))?;
+                    continue;
+                }
+                while source.pos() + 12 <= start + size {
+                    let prop_id = source.read_quad_bytes()?;
+                    let prop_size = source.read_be_u64()?;
+                    let prop_end = source.pos() + prop_size + (prop_size & 1);
+                    match &prop_id {
+                        b"FS  " => dsd_rate = source.read_be_u32()?,
+                        b"CHNL" => channels = source.read_be_u16()? as usize,
+                        b"CMPR" => compression = source.read_quad_bytes()?,
+                        _ => {}
+                    }
+                    source.seek(SeekFrom::Start(prop_end))?;
+                }
+            }
+            b"DSD " => data = Some((start, size)),
+            b"DST " => return unsupported_error("dff: DST compression is not supported"),
+            b"DIIN" => {
+                while source.pos() + 12 <= start + size {
+                    let info_id = source.read_quad_bytes()?;
+                    let info_size = source.read_be_u64()?;
+                    let info_end = source.pos() + info_size + (info_size & 1);
+                    let key = match &info_id {
+                        b"DITI" => Some((StandardTagKey::TrackTitle, "TITLE")),
+                        b"DIAR" => Some((StandardTagKey::Artist, "ARTIST")),
+                        _ => None,
+                    };
+                    if let Some((std_key, name)) = key {
+                        let length = source.read_be_u32()? as usize;
+                        let text = source.read_boxed_slice_exact(length)?;
+                        let value = String::from_utf8_lossy(&text).trim().to_string();
+                        tags.push(Tag::new(Some(std_key), name, Value::from(value)));
+                    }
+                    source.seek(SeekFrom::Start(info_end))?;
+                }
+            }
+            // Not part of the DSDIFF spec, but commonly written by taggers
+            b"ID3 " => {
+                let mut builder = MetadataBuilder::new();
+                if symphonia_metadata::id3v2::read_id3v2(source, &mut builder).is_ok() {
+                    metadata.push(builder.metadata());
+                }
+            }
+            _ => {}
+        }
+
+        source.seek(SeekFrom::Start(end))?;
+    }
+
+    if compression != *b"DSD " {
+        return unsupported_error("dff: DST compression is not supported");
+    }
+
+    if !tags.is_empty() {
+        let mut builder = MetadataBuilder::new();
+        for tag in tags {
+            builder.add_tag(tag);
+        }
+        metadata.push(builder.metadata());
+    }
+
+    let Some((data_start, data_size)) = data else {
+        return decode_error("dff: missing DSD sound data chunk");
+    };
+    if channels == 0 {
+        return decode_error("dff: missing channel count");
+    }
+    source.seek(SeekFrom::Start(data_start))?;
+
+    Ok(DsdLayout {
+        interleave: Interleave::Byte,
+        lsb_first: false,
+        channels,
+        dsd_rate,
+        data_start,
+        channel_bytes: data_size / channels as u64,
+    })
+}
+
+impl DsdReader {
+    fn frames_for(&self, bytes: u64) -> u64 {
+        bytes * 8 / u64::from(self.decimation)
+    }
+}
+
+impl FormatReader for DsdReader {
+    fn try_new(mut source: MediaSourceStream, _options: &FormatOptions) -> Result<Self> {
+        let mut metadata = MetadataLog::default();
+        let layout = match &source.read_quad_bytes()? {
+            b"DSD " => read_dsf(&mut source, &mut metadata)?,
+            b"FRM8" => read_dff(&mut source, &mut metadata)?,
+            _ => return unsupported_error("dsd: not a DSF or DFF file"),
+        };
+
+        let Some(channels) = channel_layout(layout.channels) else {
+            return unsupported_error("dsd: unsupported channel count");
+        };
+        if layout.dsd_rate == 0 {
+            return decode_error("dsd: missing sample rate");
+        }
+
+        let decimation = decimation_for(layout.dsd_rate);
+        let pcm_rate = layout.dsd_rate / decimation;
+        let mut codec_params = CodecParameters::new();
+        codec_params
+            .for_codec(CODEC_TYPE_DSD)
+            .with_sample_rate(pcm_rate)
+            .with_time_base(TimeBase::new(1, pcm_rate))
+            .with_n_frames(layout.channel_bytes * 8 / u64::from(decimation))
+            .with_channels(channels)
+            .with_max_frames_per_packet(BLOCK_BYTES * 8 / u64::from(decimation))
+            // The decoder needs the bit rate to derive its decimation
+            .with_extra_data(Box::from(layout.dsd_rate.to_le_bytes()));
+
+        Ok(Self {
+            reader: source,
+            tracks: vec![Track::new(0, codec_params)],
+            cues: Vec::new(),
+            metadata,
+            interleave: layout.interleave,
+            lsb_first: layout.lsb_first,
+            channels: layout.channels,
+            decimation,
+            data_start: layout.data_start,
+            channel_bytes: layout.channel_bytes,
+            position: 0,
+        })
+    }
+
+    fn cues(&self) -> &[Cue] {
+        &self.cues
+    }
+
+    fn metadata(&mut self) -> Metadata<'_> {
+        self.metadata.metadata()
+    }
+
+    fn seek(&mut self, _mode: SeekMode, to: SeekTo) -> Result<SeekedTo> {
+        let required_ts = match to {
+            SeekTo::TimeStamp { ts, .. } => ts,
+            SeekTo::Time { time, .. } => {
+                let Some(rate) = self.tracks[0].codec_params.sample_rate else {
+                    return seek_error(SeekErrorKind::Unseekable);
+                };
+                TimeBase::new(1, rate).calc_timestamp(time)
+            }
+        };
+
+        // Packets start on block boundaries so timestamps stay deterministic
+        let byte = required_ts * u64::from(self.decimation) / 8;
+        if byte > self.channel_bytes {
+            return seek_error(SeekErrorKind::OutOfRange);
+        }
+        let block = byte / BLOCK_BYTES;
+        self.position = block * BLOCK_BYTES;
+        self.reader.seek(SeekFrom::Start(
+            self.data_start + self.position * self.channels as u64,
+        ))?;
+
+        Ok(SeekedTo {
+            track_id: 0,
+            required_ts,
+            actual_ts: self.frames_for(self.position),
+        })
+    }
+
+    fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    fn next_packet(&mut self) -> Result<Packet> {
+        let remaining = self.channel_bytes.saturating_sub(self.position);
+        if remaining == 0 {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+
+        let valid = remaining.min(BLOCK_BYTES) as usize;
+        // DSF pads the final block of every channel up to the full block size
+        let stored = match self.interleave {
+            Interleave::Block => BLOCK_BYTES as usize,
+            Interleave::Byte => valid,
+        };
+        let raw = self.reader.read_boxed_slice_exact(stored * self.channels)?;
+
+        let mut data = vec![0u8; valid * self.channels];
+        for channel in 0..self.channels {
+            let out = &mut data[channel * valid..(channel + 1) * valid];
+            for (index, byte) in out.iter_mut().enumerate() {
+                let value = match self.interleave {
+                    Interleave::Block => raw[channel * stored + index],
+                    Interleave::Byte => raw[index * self.channels + channel],
+                };
+                *byte = if self.lsb_first {
+                    value.reverse_bits()
+                } else {
+                    value
+                };
+            }
+        }
+
+        let ts = self.frames_for(self.position);
+        self.position += valid as u64;
+        let dur = self.frames_for(self.position) - ts;
+        Ok(Packet::new_from_boxed_slice(
+            0,
+            ts,
+            dur,
+            data.into_boxed_slice(),
+        ))
+    }
+
+    fn into_inner(self: Box<Self>) -> MediaSourceStream {
+        self.reader
+    }
+}
+
+/// Blackman-windowed sinc low-pass; `cutoff` is in cycles per input sample
+fn lowpass(taps: usize, cutoff: f64) -> Vec<f64> {
+    let center = (taps - 1) as f64 / 2.0;
+    let mut coefficients: Vec<f64> = (0..taps)
+        .map(|index| {
+            let x = index as f64 - center;
+            let sinc = if x == 0.0 {
+                2.0 * cutoff
+            } else {
+                (2.0 * PI * cutoff * x).sin() / (PI * x)
+            };
+            let phase = 2.0 * PI * index as f64 / (taps - 1) as f64;
+            let window = 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+            sinc * window
+        })
+        .collect();
+    let sum: f64 = coefficients.iter().sum();
+    for value in &mut coefficients {
+        *value /= sum;
+    }
+    coefficients
+}
+
+struct ChannelFilter {
+    /// Most recent DSD bytes, newest first
+    bytes: Vec<u8>,
+    /// Most recent stage 1 outputs, ring buffer
+    history: Vec<f32>,
+    head: usize,
+    phase: usize,
+}
+
+impl ChannelFilter {
+    fn new(stage2_taps: usize) -> Self {
+        Self {
+            bytes: vec![DSD_SILENCE; STAGE1_BYTES],
+            history: vec![0.0; stage2_taps],
+            head: 0,
+            phase: 0,
+        }
+    }
+}
+
+/// Converts DSD to PCM in two stages: a bit-level FIR evaluated a byte at a time
+/// through lookup tables (decimating by 8), then a windowed-sinc FIR that
+/// decimates the rest of the way to the output rate.
+pub struct DsdDecoder {
+    params: CodecParameters,
+    /// `STAGE1_BYTES` tables mapping a byte to its filtered contribution
+    stage1: Vec<[f32; 256]>,
+    stage2: Vec<f32>,
+    stage2_factor: usize,
+    filters: Vec<ChannelFilter>,
+    buf: AudioBuffer<f32>,
+}
+
+impl DsdDecoder {
+    fn stage1_tables() -> Vec<[f32; 256]> {
+        // Pass band up to a quarter of the stage 1 output rate
+        let taps = lowpass(STAGE1_BYTES * 8, 1.0 / 32.0);
+        (0..STAGE1_BYTES)
+            .map(|byte_index| {
+                let mut table = [0f32; 256];
+                for (value, entry) in table.iter_mut().enumerate() {
+                    // Bit 7 is the oldest sample of the byte
+                    let sum: f64 = (0..8)
+                        .map(|bit| {
+                            let tap = taps[byte_index * 8 + bit];
+                            if value & (1 << bit) != 0 {
+                                tap
+                            } else {
+                                -tap
+                            }
+                        })
+                        .sum();
+                    *entry = sum as f32;
+                }
+                table
+            })
+            .collect()
+    }
+}
+
+impl Decoder for DsdDecoder {
+    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self> {
+        let dsd_rate = match params.extra_data.as_deref() {
+            Some([a, b, c, d, ..]) => u32::from_le_bytes([*a, *b, *c, *d]),
+            _ => return decode_error("dsd: missing bit rate"),
+        };
+        let Some(channels) = params.channels else {
+            return decode_error("dsd: missing channel layout");
+        };
+
+        let decimation = decimation_for(dsd_rate) as usize;
+        let stage2_factor = decimation / 8;
+        let stage2_taps = STAGE2_TAPS_PER_FACTOR * stage2_factor;
+        // Keep the band below 90% of the output Nyquist frequency
+        let stage2 = lowpass(stage2_taps, 0.45 / stage2_factor as f64)
+            .into_iter()
+            .map(|value| value as f32)
+            .collect();
+
+        let pcm_rate = dsd_rate / decimation as u32;
+        let max_frames = params
+            .max_frames_per_packet
+            .unwrap_or(BLOCK_BYTES * 8 / decimation as u64);
+
+        Ok(Self {
+            params: params.clone(),
+            stage1: Self::stage1_tables(),
+            stage2,
+            stage2_factor,
+            filters: (0..channels.count())
+                .map(|_| ChannelFilter::new(stage2_taps))
+                .collect(),
+            buf: AudioBuffer::new(max_frames, SignalSpec::new(pcm_rate, channels)),
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        &[support_codec!(
+            CODEC_TYPE_DSD,
+            "dsd",
+            "Direct Stream Digital"
+        )]
+    }
+
+    fn reset(&mut self) {
+        let taps = self.stage2.len();
+        for filter in &mut self.filters {
+            *filter = ChannelFilter::new(taps);
+        }
+    }
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>> {
+        self.buf.clear();
+
+        let channels = self.filters.len();
+        let data = packet.buf();
+        if channels == 0 || !data.len().is_multiple_of(channels) {
+            return decode_error("dsd: malformed packet");
+        }
+        let bytes_per_channel = data.len() / channels;
+        let frames = bytes_per_channel / self.stage2_factor;
+        self.buf.render_reserved(Some(frames));
+
+        for (channel, filter) in self.filters.iter_mut().enumerate() {
+            let input = &data[channel * bytes_per_channel..(channel + 1) * bytes_per_channel];
+            let output = self.buf.chan_mut(channel);
+            let mut written = 0;
+
+            for &byte in input {
+                filter.bytes.rotate_right(1);
+                filter.bytes[0] = byte;
+                let stage1: f32 = filter
+                    .bytes
+                    .iter()
+                    .zip(&self.stage1)
+                    .map(|(value, table)| table[*value as usize])
+                    .sum();
+
+                filter.history[filter.head] = stage1;
+                filter.head = (filter.head + 1) % filter.history.len();
+                filter.phase += 1;
+                if filter.phase < self.stage2_factor {
+                    continue;
+                }
+                filter.phase = 0;
+
+                if written < output.len() {
+                    let taps = filter.history.len();
+                    let mut sum = 0.0;
+                    for (offset, coefficient) in self.stage2.iter().enumerate() {
+                        sum += coefficient * filter.history[(filter.head + offset) % taps];
+                    }
+                    output[written] = (sum * OUTPUT_GAIN).clamp(-1.0, 1.0);
+                    written += 1;
+                }
+            }
+        }
+
+        Ok(self.buf.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        FinalizeResult::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_> {
+        self.buf.as_audio_buffer_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const DSD64: u32 = 2_822_400;
+
+    fn open(bytes: Vec<u8>) -> DsdReader {
+        let source = MediaSourceStream::new(Box::new(Cursor::new(bytes)), Default::default());
+        DsdReader::try_new(source, &FormatOptions::default()).unwrap()
+    }
+
+    /// A stereo DSF file whose channels hold `left` and `right` in every byte,
+    /// stored LSB first
+    fn dsf(channel_bytes: usize, left: u8, right: u8) -> Vec<u8> {
+        let blocks = channel_bytes.div_ceil(BLOCK_BYTES as usize);
+        let data_size = 12 + (blocks * BLOCK_BYTES as usize * 2) as u64;
+        let mut bytes = b"DSD ".to_vec();
+        bytes.extend_from_slice(&28u64.to_le_bytes());
+        bytes.extend_from_slice(&(28 + 52 + data_size).to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(b"fmt ");
+        bytes.extend_from_slice(&52u64.to_le_bytes());
+        for value in [1u32, 0, 2, 2, DSD64, 1] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(channel_bytes as u64 * 8).to_le_bytes());
+        bytes.extend_from_slice(&(BLOCK_BYTES as u32).to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_size.to_le_bytes());
+        for block in 0..blocks {
+            let valid = (channel_bytes - block * BLOCK_BYTES as usize).min(BLOCK_BYTES as usize);
+            for value in [left, right] {
+                bytes.extend(std::iter::repeat_n(value.reverse_bits(), valid));
+                bytes.extend(std::iter::repeat_n(0, BLOCK_BYTES as usize - valid));
+            }
+        }
+        bytes
+    }
+
+    fn chunk(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend_from_slice(&(body.len() as u64).to_be_bytes());
+        bytes.extend_from_slice(body);
+        if body.len() % 2 == 1 {
+            bytes.push(0);
+        }
+        bytes
+    }
+
+    /// A stereo DSDIFF file with a title, sample bytes interleaved per channel
+    fn dff(channel_bytes: usize, left: u8, right: u8) -> Vec<u8> {
+        let mut prop = b"SND ".to_vec();
+        prop.extend(chunk(b"FS  ", &DSD64.to_be_bytes()));
+        prop.extend(chunk(b"CHNL", b"\0\x02SLFTSRGT"));
+        prop.extend(chunk(b"CMPR", b"DSD \x0enot compressed"));
+        let mut title = 5u32.to_be_bytes().to_vec();
+        title.extend_from_slice(b"Intro");
+        let samples: Vec<u8> = (0..channel_bytes).flat_map(|_| [left, right]).collect();
+
+        let mut form = b"DSD ".to_vec();
+        form.extend(chunk(b"FVER", &[1, 5, 0, 0]));
+        form.extend(chunk(b"PROP", &prop));
+        form.extend(chunk(b"DIIN", &chunk(b"DITI", &title)));
+        form.extend(chunk(b"DSD ", &samples));
+        let mut bytes = b"FRM8".to_vec();
+        bytes.extend_from_slice(&(form.len() as u64).to_be_bytes());
+        bytes.extend(form);
+        bytes
+    }
+
+    fn packet_channels(packet: &Packet) -> (Vec<u8>, Vec<u8>) {
+        let half = packet.buf().len() / 2;
+        (packet.buf()[..half].to_vec(), packet.buf()[half..].to_vec())
+    }
+
+    #[test]
+    fn decimation_keeps_the_output_at_88_2_khz_or_more() {
+        assert_eq!(decimation_for(DSD64), 32);
+        assert_eq!(decimation_for(DSD64 * 2), 64);
+        assert_eq!(decimation_for(DSD64 * 4), 128);
+        // 48 kHz family: DSD64 at 3.072 MHz gives 96 kHz
+        assert_eq!(decimation_for(3_072_000), 32);
+        // Never below the first stage's factor
+        assert_eq!(decimation_for(705_600), 8);
+    }
+
+    #[test]
+    fn reads_dsf_blocks_and_drops_the_padding() {
+        let mut reader = open(dsf(5000, 0x01, 0x02));
+        let params = &reader.tracks()[0].codec_params;
+        assert_eq!(params.sample_rate, Some(88_200));
+        assert_eq!(params.n_frames, Some(5000 * 8 / 32));
+        assert_eq!(params.channels.map(|channels| channels.count()), Some(2));
+
+        let first = reader.next_packet().unwrap();
+        assert_eq!((first.ts(), first.dur()), (0, 1024));
+        let (left, right) = packet_channels(&first);
+        assert_eq!(left, vec![0x01; 4096]);
+        assert_eq!(right, vec![0x02; 4096]);
+
+        let last = reader.next_packet().unwrap();
+        assert_eq!((last.ts(), last.dur()), (1024, 226));
+        let (left, right) = packet_channels(&last);
+        assert_eq!(left, vec![0x01; 904]);
+        assert_eq!(right, vec![0x02; 904]);
+        assert!(reader.next_packet().is_err());
+
+        let seeked = reader
+            .seek(
+                SeekMode::Accurate,
+                SeekTo::TimeStamp {
+                    ts: 1100,
+                    track_id: 0,
+                },
+            )
+            .unwrap();
+        assert_eq!(seeked.actual_ts, 1024);
+        assert_eq!(reader.next_packet().unwrap().ts(), 1024);
+    }
+
+    #[test]
+    fn reads_dff_chunks_and_interleaved_bytes() {
+        let mut reader = open(dff(100, 0x0F, 0xF0));
+        let params = &reader.tracks()[0].codec_params;
+        assert_eq!(params.sample_rate, Some(88_200));
+        assert_eq!(params.n_frames, Some(100 * 8 / 32));
+
+        let metadata = reader.metadata();
+        let tags = metadata.current().unwrap().tags();
+        assert_eq!(tags[0].std_key, Some(StandardTagKey::TrackTitle));
+        assert_eq!(tags[0].value.to_string(), "Intro");
+
+        let packet = reader.next_packet().unwrap();
+        let (left, right) = packet_channels(&packet);
+        assert_eq!(left, vec![0x0F; 100]);
+        assert_eq!(right, vec![0xF0; 100]);
+    }
+
+    #[test]
+    fn rejects_dst_compressed_dff() {
+        let mut bytes = dff(16, 0, 0);
+        let at = bytes
+            .windows(4)
+            .position(|window| window == b"CMPR")
+            .unwrap()
+            + 12;
+        bytes[at..at + 4].copy_from_slice(b"DST ");
+        let source = MediaSourceStream::new(Box::new(Cursor::new(bytes)), Default::default());
+        assert!(DsdReader::try_new(source, &FormatOptions::default()).is_err());
+    }
+
+    #[test]
+    fn decoder_outputs_one_frame_per_decimation() {
+        let mut reader = open(dsf(8192, 0xFF, DSD_SILENCE));
+        let params = reader.tracks()[0].codec_params.clone();
+        let mut decoder = DsdDecoder::try_new(&params, &DecoderOptions::default()).unwrap();
+
+        let mut last = (0.0, 0.0);
+        for _ in 0..2 {
+            let packet = reader.next_packet().unwrap();
+            let decoded = decoder.decode(&packet).unwrap();
+            assert_eq!(decoded.frames(), 4096 * 8 / 32);
+            let AudioBufferRef::F32(buffer) = decoded else {
+                panic!("expected f32 samples");
+            };
+            last = (buffer.chan(0)[1000], buffer.chan(1)[1000]);
+        }
+        // All ones is full positive modulation, the idle pattern is silence
+        assert!((last.0 - OUTPUT_GAIN).abs() < 1e-3, "{}", last.0);
+        assert!(last.1.abs() < 1e-3, "{}", last.1);
+    }
+
+    /// Gain of the stage 1 filter at `frequency` cycles per DSD bit
+    fn stage1_gain_db(taps: &[f64], frequency: f64) -> f64 {
+        let (re, im) = taps
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(re, im), (index, tap)| {
+                let angle = 2.0 * PI * frequency * index as f64;
+                (re + tap * angle.cos(), im - tap * angle.sin())
+            });
+        20.0 * (re * re + im * im).sqrt().log10()
+    }
+
+    #[test]
+    fn stage1_rejects_what_folds_into_the_output_band() {
+        let taps = lowpass(STAGE1_BYTES * 8, 1.0 / 32.0);
+        // After decimating by 8, content near multiples of 1/8 lands below
+        // the lowest output Nyquist, 1/64 of the DSD rate
+        for multiple in 1..=4 {
+            for step in -50..=50 {
+                let frequency = multiple as f64 / 8.0 + step as f64 / 50.0 / 64.0;
+                let gain = stage1_gain_db(&taps, frequency);
+                assert!(gain < -110.0, "{} dB at {}", gain, frequency);
+            }
+        }
+        // Flat through the audible band at DSD64
+        for hz in [1_000.0, 10_000.0, 20_000.0] {
+            let gain = stage1_gain_db(&taps, hz / f64::from(DSD64));
+            assert!(gain.abs() < 0.01, "{} dB at {} Hz", gain, hz);
+        }
+    }
+}
@@ -0,0 +1,619 @@
+use crate::artists;
+use crate::dj_collection;
+use crate::import;
+use crate::search;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Maximum difference in seconds for two tracks to count as the same recording
+const DURATION_TOLERANCE: f64 = 2.0;
+
+/// One copy of a recording, with what the quality ranking looked at
+#[derive(Debug, Serialize, Clone)]
+pub struct DuplicateCopy {
+    pub track_id: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub source_path: String,
+    pub import_status: String,
+    pub duration_seconds: Option<f64>,
+    pub bitrate_kbps: Option<i32>,
+    pub codec: Option<String>,
+    pub is_lossless: bool,
+    pub sample_rate: Option<u32>,
+    pub bit_depth: Option<u32>,
+    /// How many of the descriptive tags are filled in
+    pub tag_score: u32,
+    pub rating: Option<f64>,
+    pub play_count: i32,
+    pub is_missing: bool,
+}
+
+/// Tracks that look like the same recording, best copy first
+#[derive(Debug, Serialize, Clone)]
+pub struct DuplicateCluster {
+    /// Normalised `artist|title` of the best copy
+    pub key: String,
+    /// True when at least two copies have the same audio hash and length
+    pub exact: bool,
+    pub copies: Vec<DuplicateCopy>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct DuplicateResolution {
+    pub kept_id: String,
+    pub removed: usize,
+    pub play_count: i32,
+    pub rating: Option<f64>,
+    /// Playlist entries moved over to the kept track
+    pub playlist_entries: usize,
+}
+
+struct Row {
+    copy: DuplicateCopy,
+    key: Option<String>,
+    audio_hash: Option<String>,
+    added_at: Option<i64>,
+}
+
+/// Group the library into clusters of likely duplicates: same normalised
+/// artist and title, or the same audio hash whatever the tags say, with
+/// durations within `DURATION_TOLERANCE` either way. The hash only covers the
+/// first packets, so edits sharing an intro are told apart by their length.
+/// Tracks without a real artist or title are only matched by hash.
+pub fn find_duplicates(db_path: &str) -> Result<Vec<DuplicateCluster>, String> {
+    if !Path::new(db_path).exists() {
+        return Ok(Vec::new());
+    }
+    let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    import::ensure_schema(&conn)?;
+
+    let rows = load_rows(&conn)?;
+    Ok(cluster_rows(&rows))
+}
+
+fn cluster_rows(rows: &[Row]) -> Vec<DuplicateCluster> {
+    let mut parents: Vec<usize> = (0..rows.len()).collect();
+
+    let mut by_key: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut by_hash: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, row) in rows.iter().enumerate() {
+        if let Some(key) = row.key.as_deref() {
+            by_key.entry(key).or_default().push(index);
+        }
+        if let Some(hash) = row.audio_hash.as_deref() {
+            by_hash.entry(hash).or_default().push(index);
+        }
+    }
+
+    for group in by_key.into_values() {
+        union_runs(rows, group, &mut parents);
+    }
+    let mut hash_matched = vec![false; rows.len()];
+    for group in by_hash.into_values() {
+        for index in union_runs(rows, group, &mut parents) {
+            hash_matched[index] = true;
+        }
+    }
+
+    let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+    for index in 0..rows.len() {
+        let root = find(&mut parents, index);
+        members.entry(root).or_default().push(index);
+    }
+
+    let mut clusters = Vec::new();
+    for mut indices in members.into_values().filter(|indices| indices.len() > 1) {
+        indices.sort_by(|a, b| compare_quality(&rows[*a], &rows[*b]));
+        let exact = indices.iter().any(|index| hash_matched[*index]);
+        let key = indices
+            .iter()
+            .find_map(|index| rows[*index].key.clone())
+            .unwrap_or_default();
+        clusters.push(DuplicateCluster {
+            key,
+            exact,
+            copies: indices
+                .into_iter()
+                .map(|index| rows[index].copy.clone())
+                .collect(),
+        });
+    }
+    clusters.sort_by(|a, b| a.key.cmp(&b.key));
+    clusters
+}
+
+/// Join tracks of `group` whose durations match. Each run starts at its
+/// shortest track so a chain of small differences cannot pull in a different
+/// edit. Returns the tracks that were joined to another.
+fn union_runs(rows: &[Row], mut group: Vec<usize>, parents: &mut [usize]) -> Vec<usize> {
+    let mut joined = Vec::new();
+    if group.len() < 2 {
+        return joined;
+    }
+    group.sort_by(|a, b| compare_durations(&rows[*a], &rows[*b]));
+    let mut start = group[0];
+    for &index in &group[1..] {
+        if durations_match(&rows[start], &rows[index]) {
+            union(parents, start, index);
+            joined.extend([start, index]);
+        } else {
+            start = index;
+        }
+    }
+    joined
+}
+
+/// Keep `keep_id` and delete the other copies from the library. Play counts
+/// are summed, the highest rating and latest play win, date added takes the
+/// earliest, and playlist entries point at the kept track. Lyrics and cue
+/// points move over only when the kept track has none. Files stay on disk.
+pub fn resolve_duplicates(
+    db_path: &str,
+    keep_id: &str,
+    remove_ids: &[String],
+) -> Result<DuplicateResolution, String> {
+    let remove_ids: Vec<&String> = remove_ids.iter().filter(|id| *id != keep_id).collect();
+    if !Path::new(db_path).exists() {
+        return Err("Database not found".to_string());
+    }
+    let mut conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    import::ensure_schema(&conn)?;
+    import::ensure_playlist_schema(&conn)?;
+    dj_collection::ensure_cue_schema(&conn)?;
+
+    let tx = conn.transaction().map_err(|error| error.to_string())?;
+    let mut resolution = DuplicateResolution {
+        kept_id: keep_id.to_string(),
+        ..Default::default()
+    };
+
+    let mut stats = read_stats(&tx, keep_id)?.ok_or("Track to keep not found")?;
+    for id in &remove_ids {
+        let Some(other) = read_stats(&tx, id)? else {
+            continue;
+        };
+        stats.play_count += other.play_count;
+        if other.rating.unwrap_or(0.0) > stats.rating.unwrap_or(0.0) {
+            stats.rating = other.rating;
+        }
+        // ISO 8601 timestamps sort as text
+        if other.last_played_at > stats.last_played_at {
+            stats.last_played_at = other.last_played_at;
+        }
+        stats.added_at = match (stats.added_at, other.added_at) {
+            (Some(ours), Some(theirs)) => Some(ours.min(theirs)),
+            (ours, theirs) => ours.or(theirs),
+        };
+
+        // A playlist holding both copies keeps the entry of the kept track
+        tx.execute(
+            "DELETE FROM playlist_tracks WHERE track_id = ?1
+             AND playlist_id IN (SELECT playlist_id FROM playlist_tracks WHERE track_id = ?2)",
+            params![id, keep_id],
+        )
+        .map_err(|error| error.to_string())?;
+        resolution.playlist_entries += tx
+            .execute(
+                "UPDATE playlist_tracks SET track_id = ?1 WHERE track_id = ?2",
+                params![keep_id, id],
+            )
+            .map_err(|error| error.to_string())?;
+
+        tx.execute(
+            "UPDATE OR IGNORE lyrics SET track_id = ?1 WHERE track_id = ?2",
+            params![keep_id, id],
+        )
+        .map_err(|error| error.to_string())?;
+        let kept_cues: i64 = tx
+            .query_row(
+                "SELECT COUNT(*) FROM cue_points WHERE track_id = ?1",
+                [keep_id],
+                |row| row.get(0),
+            )
+            .map_err(|error| error.to_string())?;
+        if kept_cues == 0 {
+            tx.execute(
+                "UPDATE cue_points SET track_id = ?1 WHERE track_id = ?2",
+                params![keep_id, id],
+            )
+            .map_err(|error| error.to_string())?;
+        }
+
+        for sql in [
+            "DELETE FROM lyrics WHERE track_id = ?1",
+            "DELETE FROM cue_points WHERE track_id = ?1",
+            "DELETE FROM track_artists WHERE track_id = ?1",
+            "DELETE FROM tracks WHERE id = ?1",
+        ] {
+            tx.execute(sql, [id]).map_err(|error| error.to_string())?;
+        }
+        resolution.removed += 1;
+    }
+    artists::remove_orphaned_credits(&tx)?;
+
+    tx.execute(
+        "UPDATE tracks SET rating = ?1, play_count = ?2, last_played_at = ?3, added_at = ?4,
+            updated_at = ?5
+         WHERE id = ?6",
+        params![
+            stats.rating,
+            stats.play_count,
+            stats.last_played_at,
+            stats.added_at,
+            import::current_timestamp(),
+            keep_id
+        ],
+    )
+    .map_err(|error| error.to_string())?;
+    tx.commit().map_err(|error| error.to_string())?;
+
+    resolution.play_count = stats.play_count;
+    resolution.rating = stats.rating;
+    Ok(resolution)
+}
+
+struct TrackStats {
+    rating: Option<f64>,
+    play_count: i32,
+    last_played_at: Option<String>,
+    added_at: Option<i64>,
+}
+
+fn read_stats(conn: &Connection, track_id: &str) -> Result<Option<TrackStats>, String> {
+    conn.query_row(
+        "SELECT rating, play_count, last_played_at, added_at FROM tracks WHERE id = ?1",
+        [track_id],
+        |row| {
+            Ok(TrackStats {
+                rating: row.get(0)?,
+                play_count: row.get::<_, Option<i32>>(1)?.unwrap_or(0),
+                last_played_at: row.get(2)?,
+                added_at: row.get(3)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(|error| error.to_string())
+}
+
+fn load_rows(conn: &Connection) -> Result<Vec<Row>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, title, artist, album, source_path, import_status, duration_seconds,
+                    bitrate_kbps, codec, is_lossless, sample_rate, bit_depth, rating,
+                    play_count, is_missing, audio_hash, added_at,
+                    album_artist, year, track_number, genre_json, key, bpm, label,
+                    cover_art_path, musicbrainz_trackid
+             FROM tracks WHERE source_path IS NOT NULL AND source_path != ''",
+        )
+        .map_err(|error| error.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            let title: Option<String> = row.get(1)?;
+            let artist: Option<String> = row.get(2)?;
+            let album: Option<String> = row.get(3)?;
+            let genre_json: Option<String> = row.get(20)?;
+            let tag_score = [
+                has_text(album.as_deref()) && album.as_deref() != Some(import::UNKNOWN_ALBUM),
+                has_text(row.get::<_, Option<String>>(17)?.as_deref()),
+                row.get::<_, Option<i32>>(18)?.is_some(),
+                row.get::<_, Option<i32>>(19)?.is_some(),
+                genre_json.is_some_and(|json| json.trim() != "[]" && !json.is_empty()),
+                has_text(row.get::<_, Option<String>>(21)?.as_deref()),
+                row.get::<_, Option<f64>>(22)?.is_some_and(|bpm| bpm > 0.0),
+                has_text(row.get::<_, Option<String>>(23)?.as_deref()),
+                has_text(row.get::<_, Option<String>>(24)?.as_deref()),
+                has_text(row.get::<_, Option<String>>(25)?.as_deref()),
+            ]
+            .into_iter()
+            .filter(|filled| *filled)
+            .count() as u32;
+
+            Ok(Row {
+                key: tag_key(artist.as_deref(), title.as_deref()),
+                audio_hash: row.get(15)?,
+                added_at: row.get(16)?,
+                copy: DuplicateCopy {
+                    track_id: row.get(0)?,
+                    title,
+                    artist,
+                    album,
+                    source_path: row.get(4)?,
+                    import_status: row
+                        .get::<_, Option<String>>(5)?
+                        .unwrap_or_else(|| import::STATUS_ACCEPTED.to_string()),
+                    duration_seconds: row.get(6)?,
+                    bitrate_kbps: row.get(7)?,
+                    codec: row.get(8)?,
+                    is_lossless: row.get::<_, Option<bool>>(9)?.unwrap_or(false),
+                    sample_rate: row.get(10)?,
+                    bit_depth: row.get(11)?,
+                    tag_score,
+                    rating: row.get(12)?,
+                    play_count: row.get::<_, Option<i32>>(13)?.unwrap_or(0),
+                    is_missing: row.get::<_, Option<i32>>(14)?.unwrap_or(0) != 0,
+                },
+            })
+        })
+        .map_err(|error| error.to_string())?;
+
+    rows.collect::<Result<_, _>>()
+        .map_err(|error| error.to_string())
+}
+
+fn has_text(value: Option<&str>) -> bool {
+    value.is_some_and(|value| !value.trim().is_empty())
+}
+
+/// Normalised `artist|title`, or None when either is missing or a placeholder
+fn tag_key(artist: Option<&str>, title: Option<&str>) -> Option<String> {
+    let artist = artist.filter(|artist| *artist != import::UNKNOWN_ARTIST)?;
+    let artist = search::normalize_fragment(artist);
+    let title = search::normalize_fragment(title?);
+    if artist.is_empty() || title.is_empty() {
+        return None;
+    }
+    Some(format!("{}|{}", artist, title))
+}
+
+/// Shortest first; tracks without a duration last
+fn compare_durations(a: &Row, b: &Row) -> Ordering {
+    match (a.copy.duration_seconds, b.copy.duration_seconds) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn durations_match(a: &Row, b: &Row) -> bool {
+    match (a.copy.duration_seconds, b.copy.duration_seconds) {
+        (Some(a), Some(b)) => (a - b).abs() <= DURATION_TOLERANCE,
+        _ => false,
+    }
+}
+
+/// Best copy first: files that exist, then lossless, then bitrate, sample
+/// rate and bit depth, then tag completeness; ties go to the most played and
+/// the earliest added
+fn compare_quality(a: &Row, b: &Row) -> Ordering {
+    let (x, y) = (&a.copy, &b.copy);
+    x.is_missing
+        .cmp(&y.is_missing)
+        .then(y.is_lossless.cmp(&x.is_lossless))
+        .then(
+            y.bitrate_kbps
+                .unwrap_or(0)
+                .cmp(&x.bitrate_kbps.unwrap_or(0)),
+        )
+        .then(y.sample_rate.unwrap_or(0).cmp(&x.sample_rate.unwrap_or(0)))
+        .then(y.bit_depth.unwrap_or(0).cmp(&x.bit_depth.unwrap_or(0)))
+        .then(y.tag_score.cmp(&x.tag_score))
+        .then(y.play_count.cmp(&x.play_count))
+        .then(
+            a.added_at
+                .unwrap_or(i64::MAX)
+                .cmp(&b.added_at.unwrap_or(i64::MAX)),
+        )
+}
+
+fn find(parents: &mut [usize], index: usize) -> usize {
+    let mut root = index;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    let mut current = index;
+    while parents[current] != root {
+        let next = parents[current];
+        parents[current] = root;
+        current = next;
+    }
+    root
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parents, a), find(parents, b));
+    if a != b {
+        parents[b] = a;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(id: &str, key: Option<&str>, hash: Option<&str>, duration: Option<f64>) -> Row {
+        Row {
+            copy: DuplicateCopy {
+                track_id: id.to_string(),
+                title: None,
+                artist: None,
+                album: None,
+                source_path: format!("/music/{}.mp3", id),
+                import_status: import::STATUS_ACCEPTED.to_string(),
+                duration_seconds: duration,
+                bitrate_kbps: None,
+                codec: None,
+                is_lossless: false,
+                sample_rate: None,
+                bit_depth: None,
+                tag_score: 0,
+                rating: None,
+                play_count: 0,
+                is_missing: false,
+            },
+            key: key.map(str::to_string),
+            audio_hash: hash.map(str::to_string),
+            added_at: None,
+        }
+    }
+
+    fn cluster_ids(clusters: &[DuplicateCluster]) -> Vec<Vec<String>> {
+        let mut ids: Vec<Vec<String>> = clusters
+            .iter()
+            .map(|cluster| {
+                let mut ids: Vec<String> = cluster
+                    .copies
+                    .iter()
+                    .map(|copy| copy.track_id.clone())
+                    .collect();
+                ids.sort();
+                ids
+            })
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn tag_key_normalises_and_skips_placeholders() {
+        assert_eq!(
+            tag_key(Some("The Beatles"), Some("Hey Jude!")).as_deref(),
+            Some("the beatles|hey jude")
+        );
+        assert_eq!(
+            tag_key(Some("the  BEATLES"), Some("hey jude")),
+            tag_key(Some("The Beatles"), Some("Hey Jude!"))
+        );
+        assert_eq!(tag_key(Some(import::UNKNOWN_ARTIST), Some("Song")), None);
+        assert_eq!(tag_key(Some("Artist"), None), None);
+        assert_eq!(tag_key(Some("!!!"), Some("Song")), None);
+    }
+
+    #[test]
+    fn quality_ranks_present_lossless_and_high_bitrate_first() {
+        let mut missing = row("missing", None, None, None);
+        missing.copy.is_missing = true;
+        missing.copy.is_lossless = true;
+        let mut flac = row("flac", None, None, None);
+        flac.copy.is_lossless = true;
+        flac.copy.bitrate_kbps = Some(900);
+        let mut mp3_320 = row("mp3_320", None, None, None);
+        mp3_320.copy.bitrate_kbps = Some(320);
+        let mut mp3_128 = row("mp3_128", None, None, None);
+        mp3_128.copy.bitrate_kbps = Some(128);
+        mp3_128.copy.play_count = 50;
+
+        let mut rows = [mp3_128, missing, mp3_320, flac];
+        rows.sort_by(compare_quality);
+        let order: Vec<&str> = rows.iter().map(|row| row.copy.track_id.as_str()).collect();
+        assert_eq!(order, ["flac", "mp3_320", "mp3_128", "missing"]);
+    }
+
+    #[test]
+    fn quality_ties_go_to_tags_then_plays_then_earliest_added() {
+        let mut tagged = row("tagged", None, None, None);
+        tagged.copy.tag_score = 5;
+        let mut played = row("played", None, None, None);
+        played.copy.play_count = 3;
+        let mut early = row("early", None, None, None);
+        early.added_at = Some(100);
+        let mut late = row("late", None, None, None);
+        late.added_at = Some(200);
+
+        let mut rows = [late, early, played, tagged];
+        rows.sort_by(compare_quality);
+        let order: Vec<&str> = rows.iter().map(|row| row.copy.track_id.as_str()).collect();
+        assert_eq!(order, ["tagged", "played", "early", "late"]);
+    }
+
+    #[test]
+    fn duration_runs_start_at_the_shortest_track() {
+        let key = Some("artist|song");
+        let rows = [
+            row("a", key, None, Some(203.0)),
+            row("b", key, None, Some(200.0)),
+            row("c", key, None, Some(201.5)),
+            row("d", key, None, Some(204.5)),
+            row("e", key, None, None),
+        ];
+        let clusters = cluster_rows(&rows);
+        assert_eq!(cluster_ids(&clusters), [["a", "d"], ["b", "c"]]);
+        assert!(clusters.iter().all(|cluster| !cluster.exact));
+    }
+
+    #[test]
+    fn hash_matches_need_matching_durations() {
+        let rows = [
+            row("radio", None, Some("intro"), Some(180.0)),
+            row("extended", None, Some("intro"), Some(420.0)),
+            row("copy", None, Some("intro"), Some(181.0)),
+            row("other", Some("artist|song"), Some("other"), Some(180.0)),
+        ];
+        let clusters = cluster_rows(&rows);
+        assert_eq!(cluster_ids(&clusters), [["copy", "radio"]]);
+        assert!(clusters[0].exact);
+    }
+
+    #[test]
+    fn resolving_merges_stats_playlists_lyrics_and_cues() {
+        let dir = std::env::temp_dir().join(format!("muro-dups-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("library.db");
+        let db = db_path.to_str().unwrap();
+        {
+            let conn = Connection::open(db).unwrap();
+            import::ensure_schema(&conn).unwrap();
+            import::ensure_playlist_schema(&conn).unwrap();
+            dj_collection::ensure_cue_schema(&conn).unwrap();
+            conn.execute_batch(
+                "INSERT INTO tracks (id, title, source_path, rating, play_count, added_at)
+                 VALUES ('keep', 'Song', '/a.flac', 3.0, 2, 200);
+                 INSERT INTO tracks (id, title, source_path, rating, play_count, added_at,
+                     last_played_at)
+                 VALUES ('dup', 'Song', '/a.mp3', 4.5, 5, 100, '2024-01-01T00:00:00Z');
+                 INSERT INTO playlists (id, name, created_at) VALUES ('only_dup', 'A', 0);
+                 INSERT INTO playlists (id, name, created_at) VALUES ('both', 'B', 0);
+                 INSERT INTO playlist_tracks VALUES ('only_dup', 'dup', 0);
+                 INSERT INTO playlist_tracks VALUES ('both', 'keep', 0);
+                 INSERT INTO playlist_tracks VALUES ('both', 'dup', 1);
+                 INSERT INTO lyrics VALUES ('dup', 'La la', NULL, 'tag', 0);
+                 INSERT INTO cue_points (id, track_id, source, kind, start_seconds)
+                 VALUES ('cue', 'dup', 'rekordbox', 'hot', 12.5);",
+            )
+            .unwrap();
+        }
+
+        let resolution = resolve_duplicates(db, "keep", &["dup".to_string()]).unwrap();
+        assert_eq!(resolution.removed, 1);
+        assert_eq!(resolution.play_count, 7);
+        assert_eq!(resolution.rating, Some(4.5));
+        assert_eq!(resolution.playlist_entries, 1);
+
+        let conn = Connection::open(db).unwrap();
+        let entries: Vec<(String, String)> = conn
+            .prepare("SELECT playlist_id, track_id FROM playlist_tracks ORDER BY playlist_id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            entries,
+            [
+                ("both".to_string(), "keep".to_string()),
+                ("only_dup".to_string(), "keep".to_string())
+            ]
+        );
+        let owner = |sql: &str| -> String { conn.query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_eq!(owner("SELECT track_id FROM lyrics"), "keep");
+        assert_eq!(owner("SELECT track_id FROM cue_points"), "keep");
+        assert_eq!(owner("SELECT id FROM tracks"), "keep");
+        let (added_at, last_played): (i64, String) = conn
+            .query_row("SELECT added_at, last_played_at FROM tracks", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(added_at, 100);
+        assert_eq!(last_played, "2024-01-01T00:00:00Z");
+
+        drop(conn);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,59 @@
+use crate::formats;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::path::Path;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Number of compressed packets fed into the audio hash
+const HASH_PACKETS: usize = 64;
+
+/// Hash the first packets of a file's audio stream, see `hash_packets`
+pub fn audio_hash(path: &Path) -> Option<String> {
+    let file = File::open(path).ok()?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let probed = formats::probe()
+        .format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .ok()?;
+    let mut format = probed.format;
+    hash_packets(format.as_mut())
+}
+
+/// Hash the first packets of the default track, returning the first 16 hex
+/// characters of the SHA-256. Tags and embedded pictures are not part of the
+/// packets, so the hash survives retagging, renaming and moving the file.
+pub fn hash_packets(format: &mut dyn FormatReader) -> Option<String> {
+    let track_id = format.default_track()?.id;
+
+    let mut hasher = Sha256::new();
+    let mut packets = 0;
+    while packets < HASH_PACKETS {
+        let Ok(packet) = format.next_packet() else {
+            break;
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        hasher.update(packet.buf());
+        packets += 1;
+    }
+
+    if packets == 0 {
+        return None;
+    }
+
+    let result = hasher.finalize();
+    Some(hex::encode(&result[..8]))
+}
@@ -0,0 +1,679 @@
+use crate::ape;
+use crate::dsd;
+use crate::fingerprint;
+use crate::opus;
+use crate::wavpack;
+use lofty::config::ParseOptions;
+use lofty::error::Result as LoftyResult;
+use lofty::file::{AudioFile, FileType, TaggedFile};
+use lofty::id3::v2::Id3v2Tag;
+use lofty::iff::aiff::AiffFile;
+use lofty::iff::wav::WavFile;
+use lofty::mpeg::MpegFile;
+use lofty::probe::Probe;
+use lofty::properties::FileProperties;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+use symphonia::core::codecs::{
+    CodecParameters, CodecRegistry, CodecType, DecoderOptions, CODEC_TYPE_NULL, CODEC_TYPE_OPUS,
+};
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, StandardTagKey, Tag};
+use symphonia::core::probe::{Hint, Probe as FormatProbe, ProbeResult};
+
+/// Extensions that are worth a full stream probe when the content sniff fails,
+/// e.g. MP3 files with junk before the first frame
+const AUDIO_EXTENSIONS: [&str; 18] = [
+    "mp3", "flac", "wav", "m4a", "m4b", "aac", "ogg", "oga", "opus", "aif", "aiff", "alac", "mka",
+    "caf", "dsf", "dff", "wv", "ape",
+];
+
+const EBML_MAGIC: [u8; 4] = [0x1A, 0x45, 0xDF, 0xA3];
+const CAF_MAGIC: &[u8; 4] = b"caff";
+const DSF_MAGIC: &[u8; 4] = b"DSD ";
+const DFF_MAGIC: &[u8; 4] = b"FRM8";
+/// How far past the ID3v2 tag to look for the first MPEG frame
+const MP3_FRAME_SCAN_BYTES: u64 = 8192;
+
+/// How an audio file was recognized
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioContainer {
+    /// A format lofty can read tags and properties from
+    Tagged(FileType),
+    /// A container only symphonia understands (Matroska, CAF, ...)
+    Stream(&'static str),
+}
+
+impl AudioContainer {
+    pub fn name(&self) -> String {
+        match self {
+            AudioContainer::Tagged(FileType::Mpeg) => "MP3".to_string(),
+            AudioContainer::Tagged(FileType::Mp4) => "MP4".to_string(),
+            AudioContainer::Tagged(file_type) => format!("{:?}", file_type),
+            AudioContainer::Stream(name) => name.to_string(),
+        }
+    }
+}
+
+static CODECS: OnceLock<CodecRegistry> = OnceLock::new();
+static PROBE: OnceLock<FormatProbe> = OnceLock::new();
+
+/// Codec registry shared by import checks and playback: symphonia's built-in
+/// decoders plus the ones it lacks
+pub fn codecs() -> &'static CodecRegistry {
+    CODECS.get_or_init(|| {
+        let mut registry = CodecRegistry::new();
+        symphonia::default::register_enabled_codecs(&mut registry);
+        registry.register_all::<opus::OpusDecoder>();
+        registry.register_all::<dsd::DsdDecoder>();
+        registry.register_all::<wavpack::WavPackDecoder>();
+        registry.register_all::<ape::ApeDecoder>();
+        registry
+    })
+}
+
+/// Format probe shared by import checks and playback: symphonia's built-in
+/// demuxers plus DSF/DFF, WavPack and Monkey's Audio
+pub fn probe() -> &'static FormatProbe {
+    PROBE.get_or_init(|| {
+        let mut probe = FormatProbe::default();
+        symphonia::default::register_enabled_formats(&mut probe);
+        probe.register_all::<dsd::DsdReader>();
+        probe.register_all::<wavpack::WavPackReader>();
+        probe.register_all::<ape::ApeReader>();
+        probe
+    })
+}
+
+/// Audio to decode and discard before a seek target so the decoder output has
+/// settled by the time playback resumes
+pub fn seek_preroll(codec: CodecType) -> Duration {
+    if codec == CODEC_TYPE_OPUS {
+        Duration::from_millis(opus::SEEK_PREROLL_MS)
+    } else {
+        Duration::ZERO
+    }
+}
+
+/// Detect an audio file from its content. The extension is only consulted as a
+/// last resort for streams that start with junk.
+pub fn sniff(path: &Path) -> Option<AudioContainer> {
+    if !path.is_file() {
+        return None;
+    }
+
+    let mut file = File::open(path).ok()?;
+    sniff_content(&mut file).or_else(|| {
+        has_audio_extension(path)
+            .then(|| open_stream(file, path).ok())
+            .flatten()
+            .map(|_| AudioContainer::Stream("Audio stream"))
+    })
+}
+
+pub fn is_audio_file(path: &Path) -> bool {
+    sniff(path).is_some()
+}
+
+/// Recognise the container from the first bytes, leaving the file rewound
+fn sniff_content(file: &mut File) -> Option<AudioContainer> {
+    let file_type = Probe::new(BufReader::new(&mut *file))
+        .guess_file_type()
+        .ok()
+        .and_then(|probe| probe.file_type());
+    file.rewind().ok()?;
+    if let Some(file_type) = file_type {
+        return Some(AudioContainer::Tagged(file_type));
+    }
+
+    let mut magic = [0u8; 4];
+    let read = file.read_exact(&mut magic).is_ok();
+    file.rewind().ok()?;
+    if !read {
+        return None;
+    }
+    match &magic {
+        magic if *magic == EBML_MAGIC => Some(AudioContainer::Stream("Matroska")),
+        magic if magic == CAF_MAGIC => Some(AudioContainer::Stream("CAF")),
+        magic if magic == DSF_MAGIC => Some(AudioContainer::Stream("DSF")),
+        magic if magic == DFF_MAGIC => Some(AudioContainer::Stream("DSDIFF")),
+        _ => None,
+    }
+}
+
+fn has_audio_extension(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .and_then(|value| value.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    AUDIO_EXTENSIONS.contains(&extension.as_str())
+}
+
+/// Formats lofty recognises but no registered decoder plays
+fn unsupported_codec(file_type: FileType) -> Option<&'static str> {
+    match file_type {
+        FileType::Mpc => Some("Musepack"),
+        _ => None,
+    }
+}
+
+pub(crate) fn open_stream(file: File, path: &Path) -> Result<ProbeResult, String> {
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    probe()
+        .format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|error| error.to_string())
+}
+
+/// An audio file opened once for import. The content sniff, the playability
+/// check, the stream's tags and the audio hash all come from one handle and
+/// one symphonia probe, and lofty reads tags through the same handle.
+pub struct ProbedFile {
+    pub container: AudioContainer,
+    /// Default track parameters, as the decoder sees them
+    pub params: CodecParameters,
+    /// Tags and duration found by symphonia
+    pub stream: StreamInfo,
+    /// See `fingerprint::hash_packets`
+    pub audio_hash: Option<String>,
+    file: File,
+}
+
+impl ProbedFile {
+    /// Open and check a file the player must be able to decode. The error
+    /// says why it cannot be imported, naming the format when it is known.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if !path.is_file() {
+            return Err("Not an audio file".to_string());
+        }
+        let mut file = File::open(path).map_err(|error| error.to_string())?;
+        let sniffed = sniff_content(&mut file);
+        let name = sniffed
+            .map(|container| container.name())
+            .unwrap_or_else(|| "Unknown".to_string());
+        if let Some(AudioContainer::Tagged(file_type)) = sniffed {
+            if let Some(codec) = unsupported_codec(file_type) {
+                return Err(format!("{} decoding is not supported", codec));
+            }
+        }
+        if sniffed.is_none() && !has_audio_extension(path) {
+            return Err("Not an audio file".to_string());
+        }
+
+        let stream_file = file.try_clone().map_err(|error| error.to_string())?;
+        let mut probed = open_stream(stream_file, path)
+            .map_err(|_| format!("{} audio: No decoder for this container", name))?;
+        let params = probed
+            .format
+            .default_track()
+            .map(|track| track.codec_params.clone())
+            .ok_or_else(|| format!("{} audio: No audio track found", name))?;
+        if params.codec == CODEC_TYPE_NULL {
+            return Err(format!("{} audio: Unknown codec", name));
+        }
+        if codecs().make(&params, &DecoderOptions::default()).is_err() {
+            let codec = codecs()
+                .get_codec(params.codec)
+                .map(|descriptor| descriptor.short_name.to_string())
+                .unwrap_or_else(|| format!("{}", params.codec));
+            return Err(format!("{} audio: No decoder for codec {}", name, codec));
+        }
+
+        let stream = stream_info(&mut probed);
+        let audio_hash = fingerprint::hash_packets(probed.format.as_mut());
+        Ok(ProbedFile {
+            container: sniffed.unwrap_or(AudioContainer::Stream("Audio stream")),
+            params,
+            stream,
+            audio_hash,
+            file,
+        })
+    }
+
+    /// Read tags and properties with lofty through the open handle
+    pub fn read_tags(&mut self) -> Result<FileTags, String> {
+        let AudioContainer::Tagged(file_type) = self.container else {
+            return Err(format!(
+                "{} has no tags lofty can read",
+                self.container.name()
+            ));
+        };
+        self.file.rewind().map_err(|error| error.to_string())?;
+        read_tags(&mut BufReader::new(&mut self.file), file_type).map_err(|error| error.to_string())
+    }
+
+    /// Stream properties of the probed track, reusing the open handle.
+    /// `properties` are lofty's, for what symphonia leaves out.
+    pub fn audio_properties(&mut self, properties: Option<&FileProperties>) -> AudioProperties {
+        audio_properties(
+            Some(&self.params),
+            Some(self.container),
+            properties,
+            Some(&mut self.file),
+        )
+    }
+}
+
+/// Tags lofty read from a file. The generic tags drop ID3v2 frames they
+/// cannot map, such as comments with a description, so the ID3v2 tag is
+/// also kept as it was read.
+pub struct FileTags {
+    pub tagged: TaggedFile,
+    pub id3v2: Option<Id3v2Tag>,
+}
+
+/// Read a file's tags as `file_type`
+pub fn read_tags<R: Read + Seek>(reader: &mut R, file_type: FileType) -> LoftyResult<FileTags> {
+    match file_type {
+        FileType::Mpeg => read_with_id3v2(reader, MpegFile::id3v2),
+        FileType::Wav => read_with_id3v2(reader, WavFile::id3v2),
+        FileType::Aiff => read_with_id3v2(reader, AiffFile::id3v2),
+        _ => Ok(FileTags {
+            tagged: Probe::with_file_type(reader, file_type).read()?,
+            id3v2: None,
+        }),
+    }
+}
+
+fn read_with_id3v2<F, R>(
+    reader: &mut R,
+    id3v2: fn(&F) -> Option<&Id3v2Tag>,
+) -> LoftyResult<FileTags>
+where
+    F: AudioFile + Into<TaggedFile>,
+    R: Read + Seek,
+{
+    let file = F::read_from(reader, ParseOptions::new())?;
+    let id3v2 = id3v2(&file).cloned();
+    Ok(FileTags {
+        tagged: file.into(),
+        id3v2,
+    })
+}
+
+/// Technical properties of the audio stream, stored with each track
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AudioProperties {
+    pub sample_rate: Option<u32>,
+    pub bit_depth: Option<u32>,
+    pub channels: Option<u32>,
+    /// Symphonia short name, e.g. `mp3`, `flac`, `pcm_s24le`
+    pub codec: Option<String>,
+    pub container: Option<String>,
+    pub lossless: Option<bool>,
+    /// `cbr` or `vbr`; only detected for MP3
+    pub bitrate_mode: Option<&'static str>,
+}
+
+/// Read stream properties from the file's default track. What symphonia
+/// cannot open (Musepack) comes from lofty's `properties`.
+pub fn read_audio_properties(
+    path: &Path,
+    container: Option<AudioContainer>,
+    properties: Option<&FileProperties>,
+) -> AudioProperties {
+    let mut file = File::open(path).ok();
+    let params = file
+        .as_ref()
+        .and_then(|file| file.try_clone().ok())
+        .and_then(|stream_file| open_stream(stream_file, path).ok())
+        .and_then(|probed| {
+            probed
+                .format
+                .default_track()
+                .map(|track| track.codec_params.clone())
+        });
+    audio_properties(params.as_ref(), container, properties, file.as_mut())
+}
+
+/// Build the stored properties from the default track's parameters. `reader`
+/// is the open file, rewound to look for an MP3 VBR header.
+fn audio_properties<R: Read + Seek>(
+    params: Option<&CodecParameters>,
+    container: Option<AudioContainer>,
+    properties: Option<&FileProperties>,
+    reader: Option<&mut R>,
+) -> AudioProperties {
+    let mut audio = AudioProperties {
+        container: container.map(|container| container.name()),
+        ..AudioProperties::default()
+    };
+
+    if let Some(params) = params {
+        audio.sample_rate = params.sample_rate;
+        audio.bit_depth = params.bits_per_sample.or(params.bits_per_coded_sample);
+        audio.channels = params.channels.map(|channels| channels.count() as u32);
+        audio.codec = codecs()
+            .get_codec(params.codec)
+            .map(|descriptor| descriptor.short_name.to_string());
+        if params.codec == dsd::CODEC_TYPE_DSD {
+            // The reader reports the decimated PCM rate; the DSD rate is
+            // passed to the decoder in extra_data
+            audio.sample_rate = params
+                .extra_data
+                .as_deref()
+                .and_then(|data| data.try_into().ok())
+                .map(u32::from_le_bytes)
+                .or(audio.sample_rate);
+            audio.bit_depth = Some(1);
+        }
+    }
+
+    if let Some(properties) = properties {
+        audio.sample_rate = audio.sample_rate.or(properties.sample_rate());
+        audio.bit_depth = audio.bit_depth.or(properties.bit_depth().map(u32::from));
+        audio.channels = audio.channels.or(properties.channels().map(u32::from));
+    }
+    if audio.codec.is_none() {
+        audio.codec = match container {
+            Some(AudioContainer::Tagged(file_type)) => file_type_codec(file_type),
+            _ => None,
+        }
+        .map(str::to_string);
+    }
+
+    audio.lossless = audio.codec.as_deref().and_then(is_lossless_codec);
+    if audio.lossless == Some(false) {
+        // Lossy decoders report the output sample format, not a source depth
+        audio.bit_depth = None;
+    }
+    if audio.codec.as_deref() == Some("mp3") {
+        audio.bitrate_mode = reader.and_then(mp3_bitrate_mode);
+    }
+
+    audio
+}
+
+/// Codec implied by a container that only ever holds one
+fn file_type_codec(file_type: FileType) -> Option<&'static str> {
+    match file_type {
+        FileType::Aac => Some("aac"),
+        FileType::Ape => Some("ape"),
+        FileType::Flac => Some("flac"),
+        FileType::Mpc => Some("musepack"),
+        FileType::Mpeg => Some("mp3"),
+        FileType::Opus => Some("opus"),
+        FileType::Speex => Some("speex"),
+        FileType::Vorbis => Some("vorbis"),
+        FileType::WavPack => Some("wavpack"),
+        _ => None,
+    }
+}
+
+fn is_lossless_codec(codec: &str) -> Option<bool> {
+    match codec {
+        "flac" | "alac" | "ape" | "wavpack" | "tta" | "dsd" => Some(true),
+        "mp1" | "mp2" | "mp3" | "aac" | "vorbis" | "opus" | "musepack" | "speex" => Some(false),
+        _ if codec.starts_with("pcm") => Some(true),
+        _ if codec.starts_with("adpcm") => Some(false),
+        _ => None,
+    }
+}
+
+/// Tell VBR from CBR MP3s by the header in the first frame: encoders write
+/// `Xing` or `VBRI` for VBR, LAME writes `Info` for CBR, and files without
+/// either are CBR
+fn mp3_bitrate_mode<R: Read + Seek>(file: &mut R) -> Option<&'static str> {
+    let mut header = [0u8; 10];
+    file.rewind().ok()?;
+    file.read_exact(&mut header).ok()?;
+    let mut start = 0;
+    if &header[..3] == b"ID3" {
+        let size = header[6..10]
+            .iter()
+            .fold(0u64, |size, byte| (size << 7) | u64::from(byte & 0x7F));
+        let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+        start = 10 + size + footer;
+    }
+
+    file.seek(SeekFrom::Start(start)).ok()?;
+    let mut buffer = Vec::new();
+    file.take(MP3_FRAME_SCAN_BYTES)
+        .read_to_end(&mut buffer)
+        .ok()?;
+    let sync = buffer
+        .windows(2)
+        .position(|pair| pair[0] == 0xFF && pair[1] & 0xE0 == 0xE0)?;
+    let frame = buffer.get(sync..sync + 4)?;
+
+    // The Xing/Info header follows the side info, whose size depends on the
+    // MPEG version and channel mode; VBRI always sits at offset 36
+    let mpeg1 = frame[1] & 0x18 == 0x18;
+    let mono = frame[3] >> 6 == 3;
+    let side_info = match (mpeg1, mono) {
+        (true, false) => 32,
+        (true, true) | (false, false) => 17,
+        (false, true) => 9,
+    };
+    let marker = |offset: usize| buffer.get(sync + 4 + offset..sync + 8 + offset);
+    match marker(side_info) {
+        Some(b"Xing") => Some("vbr"),
+        Some(b"Info") => Some("cbr"),
+        _ if marker(32) == Some(&b"VBRI"[..]) => Some("vbr"),
+        _ => Some("cbr"),
+    }
+}
+
+/// Properties and tags read through symphonia for containers lofty cannot parse
+pub struct StreamInfo {
+    pub duration_seconds: f64,
+    pub tags: Vec<StreamTag>,
+}
+
+pub struct StreamTag {
+    pub std_key: Option<StandardTagKey>,
+    pub key: String,
+    pub value: String,
+}
+
+impl StreamInfo {
+    /// First value for a standard key, falling back to the raw tag name
+    pub fn get(&self, std_key: StandardTagKey, raw_key: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|tag| tag.std_key == Some(std_key) || tag.key.eq_ignore_ascii_case(raw_key))
+            .map(|tag| tag.value.as_str())
+            .filter(|value| !value.trim().is_empty())
+    }
+
+    /// First value of a tag symphonia has no standard key for
+    pub fn get_raw(&self, raw_key: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|tag| tag.key.eq_ignore_ascii_case(raw_key))
+            .map(|tag| tag.value.as_str())
+            .filter(|value| !value.trim().is_empty())
+    }
+
+    pub fn get_all(&self, std_key: StandardTagKey, raw_key: &str) -> Vec<String> {
+        self.tags
+            .iter()
+            .filter(|tag| tag.std_key == Some(std_key) || tag.key.eq_ignore_ascii_case(raw_key))
+            .map(|tag| tag.value.clone())
+            .filter(|value| !value.trim().is_empty())
+            .collect()
+    }
+}
+
+pub fn read_stream_info(path: &Path) -> Result<StreamInfo, String> {
+    let file = File::open(path).map_err(|error| error.to_string())?;
+    let mut probed = open_stream(file, path)?;
+    Ok(stream_info(&mut probed))
+}
+
+fn stream_info(probed: &mut ProbeResult) -> StreamInfo {
+    let mut tags = Vec::new();
+    if let Some(metadata) = probed.metadata.get() {
+        if let Some(revision) = metadata.current() {
+            tags.extend(revision.tags().iter().map(stream_tag));
+        }
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        tags.extend(revision.tags().iter().map(stream_tag));
+    }
+
+    let duration_seconds = probed
+        .format
+        .default_track()
+        .and_then(|track| {
+            let frames = track.codec_params.n_frames?;
+            let time = track.codec_params.time_base?.calc_time(frames);
+            Some(time.seconds as f64 + time.frac)
+        })
+        .unwrap_or(0.0);
+
+    StreamInfo {
+        duration_seconds,
+        tags,
+    }
+}
+
+fn stream_tag(tag: &Tag) -> StreamTag {
+    StreamTag {
+        std_key: tag.std_key,
+        key: tag.key.clone(),
+        value: tag.value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::wav_bytes;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("muro-formats-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn probes_misnamed_audio_by_content() {
+        let dir = temp_dir();
+        let path = dir.join("track.dat");
+        fs::write(&path, wav_bytes()).unwrap();
+
+        assert_eq!(sniff(&path), Some(AudioContainer::Tagged(FileType::Wav)));
+        let mut probed = ProbedFile::open(&path).unwrap();
+        assert_eq!(probed.container, AudioContainer::Tagged(FileType::Wav));
+        assert_eq!(probed.params.sample_rate, Some(8000));
+        assert!(probed.audio_hash.is_some());
+        assert_eq!(probed.audio_hash, fingerprint::audio_hash(&path));
+        let tags = probed.read_tags().unwrap();
+        assert_eq!(tags.tagged.properties().sample_rate(), Some(8000));
+        let audio = probed.audio_properties(Some(tags.tagged.properties()));
+        assert_eq!(audio.codec.as_deref(), Some("pcm_s16le"));
+        assert_eq!(audio.lossless, Some(true));
+        assert_eq!(
+            audio,
+            read_audio_properties(&path, Some(probed.container), None)
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_formats_without_a_decoder() {
+        let dir = temp_dir();
+        let musepack = dir.join("track.mpc");
+        let mut bytes = b"MPCK".to_vec();
+        bytes.extend_from_slice(&[0; 60]);
+        fs::write(&musepack, bytes).unwrap();
+        let text = dir.join("notes.mp3");
+        fs::write(&text, "not audio").unwrap();
+
+        assert_eq!(
+            ProbedFile::open(&musepack).err().as_deref(),
+            Some("Musepack decoding is not supported")
+        );
+        assert_eq!(
+            ProbedFile::open(&text).err().as_deref(),
+            Some("Unknown audio: No decoder for this container")
+        );
+        assert!(ProbedFile::open(&dir).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// An MPEG audio frame header followed by `marker` at `offset` past it
+    fn mp3_frame(header: [u8; 4], offset: usize, marker: &[u8]) -> Vec<u8> {
+        let mut bytes = header.to_vec();
+        bytes.resize(4 + offset, 0);
+        bytes.extend_from_slice(marker);
+        bytes.resize(256, 0);
+        bytes
+    }
+
+    fn bitrate_mode(bytes: Vec<u8>) -> Option<&'static str> {
+        mp3_bitrate_mode(&mut std::io::Cursor::new(bytes))
+    }
+
+    #[test]
+    fn detects_mp3_bitrate_mode_from_the_first_frame() {
+        const MPEG1_STEREO: [u8; 4] = [0xFF, 0xFB, 0x90, 0x00];
+        const MPEG1_MONO: [u8; 4] = [0xFF, 0xFB, 0x90, 0xC0];
+        const MPEG2_STEREO: [u8; 4] = [0xFF, 0xF3, 0x90, 0x00];
+        const MPEG2_MONO: [u8; 4] = [0xFF, 0xF3, 0x90, 0xC0];
+
+        assert_eq!(
+            bitrate_mode(mp3_frame(MPEG1_STEREO, 32, b"Xing")),
+            Some("vbr")
+        );
+        assert_eq!(
+            bitrate_mode(mp3_frame(MPEG1_MONO, 17, b"Xing")),
+            Some("vbr")
+        );
+        assert_eq!(
+            bitrate_mode(mp3_frame(MPEG2_STEREO, 17, b"Info")),
+            Some("cbr")
+        );
+        assert_eq!(bitrate_mode(mp3_frame(MPEG2_MONO, 9, b"Xing")), Some("vbr"));
+        // A marker at another channel mode's offset does not count
+        assert_eq!(
+            bitrate_mode(mp3_frame(MPEG1_MONO, 32, b"Xing")),
+            Some("cbr")
+        );
+        // VBRI sits at offset 32 whatever the channel mode
+        assert_eq!(
+            bitrate_mode(mp3_frame(MPEG1_MONO, 32, b"VBRI")),
+            Some("vbr")
+        );
+        assert_eq!(bitrate_mode(mp3_frame(MPEG1_STEREO, 0, b"")), Some("cbr"));
+
+        // The frame is looked for after the ID3v2 tag and any junk
+        let mut tagged = b"ID3\x04\x00\x00\x00\x00\x01\x00".to_vec();
+        tagged.extend_from_slice(&[0xFF; 128]);
+        tagged.extend_from_slice(&[0x00; 5]);
+        tagged.extend(mp3_frame(MPEG1_STEREO, 32, b"Xing"));
+        assert_eq!(bitrate_mode(tagged), Some("vbr"));
+
+        assert_eq!(bitrate_mode(vec![0; 256]), None);
+    }
+
+    #[test]
+    fn classifies_codecs_as_lossless_or_lossy() {
+        for codec in ["flac", "alac", "wavpack", "dsd", "pcm_s24le", "pcm_f32be"] {
+            assert_eq!(is_lossless_codec(codec), Some(true), "{}", codec);
+        }
+        for codec in ["mp3", "aac", "vorbis", "opus", "adpcm_ima_wav"] {
+            assert_eq!(is_lossless_codec(codec), Some(false), "{}", codec);
+        }
+        assert_eq!(is_lossless_codec("unknown"), None);
+    }
+}
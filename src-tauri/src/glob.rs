@@ -0,0 +1,16 @@
+/// Case-insensitive wildcard match of a whole string: `*` stands for any run
+/// of characters (including `/`) and `?` for a single one
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.trim().to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    matches_chars(&pattern, &text)
+}
+
+fn matches_chars(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|skip| matches_chars(rest, &text[skip..])),
+        Some(('?', rest)) => !text.is_empty() && matches_chars(rest, &text[1..]),
+        Some((ch, rest)) => text.first() == Some(ch) && matches_chars(rest, &text[1..]),
+    }
+}
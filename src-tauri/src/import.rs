@@ -1,29 +1,57 @@
+use crate::albums;
+use crate::artists::{self, ArtistCredits};
 use crate::cover_art;
+use crate::formats::{self, AudioContainer, AudioProperties};
+use crate::glob;
+use crate::inbox_rules;
+use crate::lyrics::{self, FileLyrics};
+use crate::organize;
 use crate::search;
+use crate::settings;
 use chrono::{DateTime, Utc};
-use lofty::file::FileType;
-use lofty::file::TaggedFile;
-use lofty::id3::v2::Popularimeter;
+use lofty::file::{FileType, TaggedFile};
+use lofty::id3::v2::{CommentFrame, FrameValue, Id3v2Tag, Popularimeter};
 use lofty::prelude::*;
 use lofty::probe::Probe;
 use lofty::tag::{ItemValue, Tag, TagItem, TagType};
+use parking_lot::Mutex;
 use rusqlite::{params, Connection};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::Cursor;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
+use symphonia::core::meta::StandardTagKey;
 use uuid::Uuid;
 
-const AUDIO_EXTENSIONS: [&str; 8] = ["mp3", "flac", "wav", "m4a", "aac", "ogg", "aiff", "alac"];
-const STATUS_STAGED: &str = "staged";
-const STATUS_ACCEPTED: &str = "accepted";
+pub(crate) const STATUS_STAGED: &str = "staged";
+pub(crate) const STATUS_ACCEPTED: &str = "accepted";
 const DEFAULT_DURATION: &str = "--:--";
 const DEFAULT_BITRATE: &str = "--";
 const UNKNOWN_TITLE: &str = "Unknown Title";
-const UNKNOWN_ARTIST: &str = "Unknown Artist";
-const UNKNOWN_ALBUM: &str = "Unknown Album";
+pub(crate) const UNKNOWN_ARTIST: &str = "Unknown Artist";
+pub(crate) const UNKNOWN_ALBUM: &str = "Unknown Album";
+/// Keys lofty leaves the multi-value ARTISTS tag under: Vorbis, APE and ID3v2
+/// TXXX use the plain name, MP4 a freeform atom
+const ARTISTS_KEYS: [&str; 2] = ["ARTISTS", "----:com.apple.iTunes:ARTISTS"];
+/// Files imported per transaction; progress survives in steps of this size
+const IMPORT_BATCH_SIZE: usize = 50;
+const JOB_RUNNING: &str = "running";
+const JOB_CANCELLED: &str = "cancelled";
+const JOB_COMPLETED: &str = "completed";
+/// Setting holding the `ScanOptions` used when walking folders
+pub const SCAN_OPTIONS_SETTING: &str = "import.scan_options";
+/// NAS metadata and trash folders that never hold music worth importing
+const DEFAULT_EXCLUDES: [&str; 5] = [
+    "@eaDir",
+    "#recycle",
+    "$RECYCLE.BIN",
+    "System Volume Information",
+    "lost+found",
+];
 
 #[derive(Debug, Serialize, Clone)]
 pub struct ImportedTrack {
@@ -54,12 +82,205 @@ pub struct ImportedTrack {
     pub disc_total: Option<i32>,
     pub last_played_at: Option<String>,
     pub play_count: i32,
+    pub is_missing: bool,
+    pub import_status: String,
+    pub sample_rate: Option<u32>,
+    pub bit_depth: Option<u32>,
+    pub channels: Option<u32>,
+    pub codec: Option<String>,
+    pub container: Option<String>,
+    pub is_lossless: Option<bool>,
+    pub bitrate_mode: Option<String>,
+    pub file_size: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Clone)]
 pub struct ImportProgress {
+    pub job_id: String,
     pub imported: usize,
     pub total: usize,
+    pub current_file: Option<String>,
+}
+
+/// A file that was not imported, with the reason why
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportIssue {
+    pub path: String,
+    pub reason: String,
+}
+
+impl ImportIssue {
+    pub(crate) fn new(path: &Path, reason: &str) -> Self {
+        Self {
+            path: path.to_string_lossy().to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// Which files and folders a folder walk skips
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ScanOptions {
+    /// Skip dot-files and dot-folders (and hidden entries on Windows)
+    pub skip_hidden: bool,
+    /// Globs (see `glob::matches`) matched against the name of each file and
+    /// folder, or against the full path when the glob contains a separator
+    pub exclude: Vec<String>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            skip_hidden: true,
+            exclude: DEFAULT_EXCLUDES
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl ScanOptions {
+    pub fn load(conn: &Connection) -> Result<Self, String> {
+        Ok(settings::get_setting(conn, SCAN_OPTIONS_SETTING)?.unwrap_or_default())
+    }
+
+    /// Options stored in the database at `db_path`, or the defaults when it
+    /// does not exist yet
+    pub fn load_from(db_path: &str) -> Result<Self, String> {
+        if !Path::new(db_path).exists() {
+            return Ok(Self::default());
+        }
+        let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+        Self::load(&conn)
+    }
+
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|value| value.to_str()) else {
+            return false;
+        };
+        if self.skip_hidden && is_hidden(path, name) {
+            return true;
+        }
+        let full_path = path.to_string_lossy().replace('\\', "/");
+        self.exclude.iter().any(|pattern| {
+            if pattern.contains('/') || pattern.contains('\\') {
+                glob::matches(&pattern.replace('\\', "/"), &full_path)
+            } else {
+                glob::matches(pattern, name)
+            }
+        })
+    }
+
+    /// Whether `path` or any folder between `root` and it is excluded
+    pub fn is_excluded_below(&self, root: &Path, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(root) else {
+            return false;
+        };
+        let mut current = root.to_path_buf();
+        relative.components().any(|component| {
+            current.push(component);
+            self.is_excluded(&current)
+        })
+    }
+}
+
+#[cfg(windows)]
+fn is_hidden(path: &Path, name: &str) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    name.starts_with('.')
+        || std::fs::symlink_metadata(path)
+            .map(|metadata| metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+            .unwrap_or(false)
+}
+
+#[cfg(not(windows))]
+fn is_hidden(_path: &Path, name: &str) -> bool {
+    name.starts_with('.')
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ImportReport {
+    pub job_id: String,
+    pub cancelled: bool,
+    pub imported: Vec<ImportedTrack>,
+    pub skipped_duplicates: Vec<ImportIssue>,
+    pub unsupported: Vec<ImportIssue>,
+    pub failed: Vec<ImportIssue>,
+}
+
+/// Persisted form of an `ImportReport`; imported tracks are only counted
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportReportSummary {
+    #[serde(default)]
+    pub job_id: String,
+    #[serde(default)]
+    pub cancelled: bool,
+    pub created_at: i64,
+    pub imported: usize,
+    pub skipped_duplicates: Vec<ImportIssue>,
+    pub unsupported: Vec<ImportIssue>,
+    pub failed: Vec<ImportIssue>,
+}
+
+impl ImportReport {
+    pub fn summary(&self, created_at: i64) -> ImportReportSummary {
+        ImportReportSummary {
+            job_id: self.job_id.clone(),
+            cancelled: self.cancelled,
+            created_at,
+            imported: self.imported.len(),
+            skipped_duplicates: self.skipped_duplicates.clone(),
+            unsupported: self.unsupported.clone(),
+            failed: self.failed.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ImportJobRow {
+    pub id: String,
+    pub created_at: i64,
+    pub status: String,
+    pub total: usize,
+    pub pending: usize,
+}
+
+/// Cancellation flags of the imports currently running, keyed by job id
+#[derive(Default)]
+pub struct ImportJobs {
+    active: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl ImportJobs {
+    pub fn register(&self, job_id: &str) -> Arc<AtomicBool> {
+        let flag = Arc::new(AtomicBool::new(false));
+        self.active
+            .lock()
+            .insert(job_id.to_string(), Arc::clone(&flag));
+        flag
+    }
+
+    /// Request cancellation; returns false if no such import is running
+    pub fn cancel(&self, job_id: &str) -> bool {
+        match self.active.lock().get(job_id) {
+            Some(flag) => {
+                flag.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn finish(&self, job_id: &str) {
+        self.active.lock().remove(job_id);
+    }
+
+    pub fn is_active(&self, job_id: &str) -> bool {
+        self.active.lock().contains_key(job_id)
+    }
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -77,6 +298,8 @@ pub struct PlaylistSnapshot {
 pub struct PlaylistRow {
     pub id: String,
     pub name: String,
+    pub parent_id: Option<String>,
+    pub is_folder: bool,
     pub track_ids: Vec<String>,
 }
 
@@ -86,6 +309,15 @@ struct NormalizedMetadata {
     artist: Option<String>,
     album: Option<String>,
     album_artist: Option<String>,
+    /// Multi-value ARTISTS tag
+    artists: Vec<String>,
+    remixers: Vec<String>,
+    /// iTunes compilation flag (TCMP, COMPILATION, cpil)
+    compilation: bool,
+    /// Unsynchronised lyrics (USLT, LYRICS), possibly in LRC format
+    lyrics: Option<String>,
+    /// Timed lines of an ID3v2 SYLT frame
+    synced_lyrics: Option<Vec<lyrics::LyricLine>>,
     genres: Vec<String>,
     comments: Vec<String>,
     label: Option<String>,
@@ -116,11 +348,61 @@ struct NormalizedMetadata {
     musicbrainz_albumtype: Option<String>,
 }
 
+/// Everything read from an audio file that ends up in its `tracks` row
+struct TrackFileData {
+    metadata: NormalizedMetadata,
+    title: String,
+    artist: String,
+    album: String,
+    credits: ArtistCredits,
+    lyrics: Option<FileLyrics>,
+    search_text: String,
+    genre_json: String,
+    comment_json: String,
+    isrc_json: String,
+    raw_tags_json: String,
+    duration_seconds: f32,
+    bitrate: i32,
+    audio: AudioProperties,
+    cover_art_path: Option<String>,
+    cover_art_thumb_path: Option<String>,
+    cover_art_source: Option<&'static str>,
+    file_size: Option<i64>,
+    file_mtime: Option<i64>,
+    audio_hash: Option<String>,
+}
+
+/// Settings every imported file needs, read once per import rather than for
+/// each file
+pub(crate) struct ImportSettings {
+    pub various_artists: String,
+    pub sidecar_patterns: Vec<String>,
+    pub inbox_rules: Vec<inbox_rules::InboxRule>,
+}
+
+impl ImportSettings {
+    pub(crate) fn load(conn: &Connection) -> Result<Self, String> {
+        Ok(Self {
+            various_artists: albums::various_artists_name(conn)?,
+            sidecar_patterns: cover_art::sidecar_patterns(conn)?,
+            inbox_rules: inbox_rules::load_rules(conn)?,
+        })
+    }
+}
+
+/// Tag fields and duration used to recognise a track at a different path
+#[derive(Debug, Clone)]
+pub(crate) struct TrackFingerprint {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub duration_seconds: f64,
+}
+
 pub fn import_files(
     paths: Vec<String>,
     db_path: &str,
     cache_dir: &Path,
-) -> Result<Vec<ImportedTrack>, String> {
+) -> Result<ImportReport, String> {
     import_files_with_progress(paths, db_path, cache_dir, |_| {})
 }
 
@@ -128,57 +410,512 @@ pub fn import_files_with_progress<F>(
     paths: Vec<String>,
     db_path: &str,
     cache_dir: &Path,
-    mut on_progress: F,
-) -> Result<Vec<ImportedTrack>, String>
+    on_progress: F,
+) -> Result<ImportReport, String>
+where
+    F: FnMut(ImportProgress),
+{
+    let job_id = Uuid::new_v4().to_string();
+    run_background_import(
+        &job_id,
+        paths,
+        db_path,
+        cache_dir,
+        &AtomicBool::new(false),
+        on_progress,
+    )
+}
+
+/// Settings of one pass of `process_import_job` over a list of files
+#[derive(Clone, Copy)]
+struct ImportRun<'a> {
+    cache_dir: &'a Path,
+    cancel: &'a AtomicBool,
+    /// Persisted runs record their progress in `import_job_paths` so they
+    /// can be resumed and save their report for the import report view;
+    /// background imports leave neither behind
+    persisted: bool,
+    /// Files of the job imported by earlier runs, when resuming
+    already_done: usize,
+    total: usize,
+}
+
+/// Import `paths` as a persisted job. Files are committed in batches so an
+/// interrupted or cancelled job keeps what it already imported and can be
+/// picked up again with `resume_import_job`. Only imports the user started
+/// are run this way.
+pub fn run_import_job<F>(
+    job_id: &str,
+    paths: Vec<String>,
+    db_path: &str,
+    cache_dir: &Path,
+    cancel: &AtomicBool,
+    on_progress: F,
+) -> Result<ImportReport, String>
+where
+    F: FnMut(ImportProgress),
+{
+    start_import(job_id, paths, db_path, cache_dir, cancel, true, on_progress)
+}
+
+/// Import `paths` without recording a job, for imports the user did not start
+/// directly: watched folders, playlists and DJ collections. A cancelled or
+/// interrupted background import is not offered for resuming.
+pub fn run_background_import<F>(
+    job_id: &str,
+    paths: Vec<String>,
+    db_path: &str,
+    cache_dir: &Path,
+    cancel: &AtomicBool,
+    on_progress: F,
+) -> Result<ImportReport, String>
+where
+    F: FnMut(ImportProgress),
+{
+    start_import(
+        job_id,
+        paths,
+        db_path,
+        cache_dir,
+        cancel,
+        false,
+        on_progress,
+    )
+}
+
+fn start_import<F>(
+    job_id: &str,
+    paths: Vec<String>,
+    db_path: &str,
+    cache_dir: &Path,
+    cancel: &AtomicBool,
+    persisted: bool,
+    on_progress: F,
+) -> Result<ImportReport, String>
 where
     F: FnMut(ImportProgress),
 {
+    let mut report = ImportReport {
+        job_id: job_id.to_string(),
+        ..Default::default()
+    };
+    let options = ScanOptions::load_from(db_path)?;
     let mut file_paths = Vec::new();
     for path in paths {
-        collect_audio_paths(Path::new(&path), &mut file_paths)?;
+        let path = Path::new(&path);
+        if path.is_file() && !formats::is_audio_file(path) {
+            // Explicitly selected files are reported; non-audio files found
+            // while walking folders (covers, cue sheets, ...) are not
+            report
+                .unsupported
+                .push(ImportIssue::new(path, "Not an audio file"));
+            continue;
+        }
+        let issues = collect_audio_paths(path, &options, &mut file_paths);
+        report.failed.extend(issues);
     }
 
     if file_paths.is_empty() {
-        return Ok(Vec::new());
+        return Ok(report);
     }
 
     if let Some(parent) = Path::new(db_path).parent() {
         std::fs::create_dir_all(parent).map_err(|error| error.to_string())?;
     }
 
-    // Ensure cover art cache directory exists
-    std::fs::create_dir_all(cache_dir).map_err(|error| error.to_string())?;
+    let mut conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    ensure_schema(&conn)?;
+
+    if persisted {
+        ensure_import_job_schema(&conn)?;
+        let tx = conn.transaction().map_err(|error| error.to_string())?;
+        tx.execute(
+            "INSERT INTO import_jobs (id, created_at, status, total) VALUES (?1, ?2, ?3, ?4)",
+            params![job_id, current_timestamp(), JOB_RUNNING, file_paths.len()],
+        )
+        .map_err(|error| error.to_string())?;
+        for (position, path) in file_paths.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO import_job_paths (job_id, path, position, done) VALUES (?1, ?2, ?3, 0)",
+                params![job_id, path.to_string_lossy().to_string(), position],
+            )
+            .map_err(|error| error.to_string())?;
+        }
+        tx.commit().map_err(|error| error.to_string())?;
+    }
+
+    let run = ImportRun {
+        cache_dir,
+        cancel,
+        persisted,
+        already_done: 0,
+        total: file_paths.len(),
+    };
+    process_import_job(&mut conn, report, file_paths, &run, on_progress)
+}
+
+/// Continue an interrupted or cancelled import job from its pending paths
+pub fn resume_import_job<F>(
+    job_id: &str,
+    db_path: &str,
+    cache_dir: &Path,
+    cancel: &AtomicBool,
+    on_progress: F,
+) -> Result<ImportReport, String>
+where
+    F: FnMut(ImportProgress),
+{
+    if !Path::new(db_path).exists() {
+        return Err("Database not found".to_string());
+    }
 
     let mut conn = Connection::open(db_path).map_err(|error| error.to_string())?;
     ensure_schema(&conn)?;
+    ensure_import_job_schema(&conn)?;
 
-    let tx = conn.transaction().map_err(|error| error.to_string())?;
-    let mut imported = Vec::new();
+    let total: usize = conn
+        .query_row(
+            "SELECT total FROM import_jobs WHERE id = ?1 AND status != ?2",
+            params![job_id, JOB_COMPLETED],
+            |row| row.get(0),
+        )
+        .map_err(|_| format!("No resumable import job {}", job_id))?;
+
+    let mut pending = Vec::new();
+    {
+        let mut stmt = conn
+            .prepare(
+                "SELECT path FROM import_job_paths
+                 WHERE job_id = ?1 AND done = 0 ORDER BY position ASC",
+            )
+            .map_err(|error| error.to_string())?;
+        let rows = stmt
+            .query_map([job_id], |row| row.get::<_, String>(0))
+            .map_err(|error| error.to_string())?;
+        for row in rows {
+            pending.push(PathBuf::from(row.map_err(|error| error.to_string())?));
+        }
+    }
+
+    conn.execute(
+        "UPDATE import_jobs SET status = ?1 WHERE id = ?2",
+        params![JOB_RUNNING, job_id],
+    )
+    .map_err(|error| error.to_string())?;
+
+    let report = ImportReport {
+        job_id: job_id.to_string(),
+        ..Default::default()
+    };
+    let run = ImportRun {
+        cache_dir,
+        cancel,
+        persisted: true,
+        already_done: total.saturating_sub(pending.len()),
+        total,
+    };
+    process_import_job(&mut conn, report, pending, &run, on_progress)
+}
+
+fn process_import_job<F>(
+    conn: &mut Connection,
+    mut report: ImportReport,
+    file_paths: Vec<PathBuf>,
+    run: &ImportRun,
+    mut on_progress: F,
+) -> Result<ImportReport, String>
+where
+    F: FnMut(ImportProgress),
+{
+    let ImportRun {
+        cache_dir,
+        cancel,
+        persisted,
+        already_done,
+        total,
+    } = *run;
+
+    // Ensure cover art cache directory exists
+    std::fs::create_dir_all(cache_dir).map_err(|error| error.to_string())?;
+    ensure_import_report_schema(conn)?;
+
+    let job_id = report.job_id.clone();
     let now = current_timestamp();
-    let total = file_paths.len();
-    let mut processed = 0;
+    let settings = ImportSettings::load(conn)?;
+    let mut processed = already_done;
+
+    for batch in file_paths.chunks(IMPORT_BATCH_SIZE) {
+        if cancel.load(Ordering::Relaxed) {
+            report.cancelled = true;
+            break;
+        }
+
+        let tx = conn.transaction().map_err(|error| error.to_string())?;
+        for path in batch {
+            if cancel.load(Ordering::Relaxed) {
+                report.cancelled = true;
+                break;
+            }
+
+            on_progress(ImportProgress {
+                job_id: job_id.clone(),
+                imported: processed,
+                total,
+                current_file: path
+                    .file_name()
+                    .map(|value| value.to_string_lossy().to_string()),
+            });
+
+            import_job_path(&tx, path, now, cache_dir, &settings, &mut report)?;
+            if persisted {
+                tx.execute(
+                    "UPDATE import_job_paths SET done = 1 WHERE job_id = ?1 AND path = ?2",
+                    params![job_id, path.to_string_lossy().to_string()],
+                )
+                .map_err(|error| error.to_string())?;
+            }
+            processed += 1;
+        }
+        tx.commit().map_err(|error| error.to_string())?;
+    }
+
+    organize_accepted_imports(conn, &mut report);
+
     on_progress(ImportProgress {
-        imported: processed,
+        job_id: job_id.clone(),
+        imported: if report.cancelled { processed } else { total },
         total,
+        current_file: None,
     });
 
-    for path in file_paths {
-        match import_single(&tx, &path, now, cache_dir) {
-            Ok(Some(track)) => imported.push(track),
-            Ok(None) => {} // Duplicate, silently skipped
-            Err(error) => {
-                eprintln!("Import failed for {}: {}", path.display(), error);
+    let tx = conn.transaction().map_err(|error| error.to_string())?;
+    if persisted && report.cancelled {
+        tx.execute(
+            "UPDATE import_jobs SET status = ?1 WHERE id = ?2",
+            params![JOB_CANCELLED, job_id],
+        )
+        .map_err(|error| error.to_string())?;
+    } else if persisted {
+        // Finished jobs have nothing left to resume
+        tx.execute("DELETE FROM import_job_paths WHERE job_id = ?1", [&job_id])
+            .map_err(|error| error.to_string())?;
+        tx.execute("DELETE FROM import_jobs WHERE id = ?1", [&job_id])
+            .map_err(|error| error.to_string())?;
+    }
+    if persisted {
+        save_import_report(&tx, &report.summary(now))?;
+    }
+    tx.commit().map_err(|error| error.to_string())?;
+
+    Ok(report)
+}
+
+/// Tracks the inbox rules accepted are filed into the library folder like
+/// manually accepted ones. Failures leave the file where it was.
+fn organize_accepted_imports(conn: &Connection, report: &mut ImportReport) {
+    let accepted: Vec<String> = report
+        .imported
+        .iter()
+        .filter(|track| track.import_status == STATUS_ACCEPTED)
+        .map(|track| track.id.clone())
+        .collect();
+    match organize::organize_accepted(conn, &accepted) {
+        Ok(Some(organized)) => {
+            for moved in organized.moved {
+                if let Some(track) = report
+                    .imported
+                    .iter_mut()
+                    .find(|track| track.id == moved.track_id)
+                {
+                    track.source_path = moved.new_path;
+                }
             }
+            report
+                .failed
+                .extend(organized.failed.into_iter().map(|failure| ImportIssue {
+                    path: failure.path,
+                    reason: format!("Could not be organised: {}", failure.reason),
+                }));
+        }
+        Ok(None) => {}
+        Err(error) => {
+            let reason = format!("Could not be organised: {}", error);
+            let failed: Vec<ImportIssue> = report
+                .imported
+                .iter()
+                .filter(|track| accepted.contains(&track.id))
+                .map(|track| ImportIssue::new(Path::new(&track.source_path), &reason))
+                .collect();
+            report.failed.extend(failed);
         }
-        processed += 1;
-        on_progress(ImportProgress {
-            imported: processed,
-            total,
-        });
     }
+}
 
-    tx.commit().map_err(|error| error.to_string())?;
-    Ok(imported)
+fn import_job_path(
+    conn: &Connection,
+    path: &Path,
+    now: i64,
+    cache_dir: &Path,
+    settings: &ImportSettings,
+    report: &mut ImportReport,
+) -> Result<(), String> {
+    if is_known_path(conn, path)? {
+        report
+            .skipped_duplicates
+            .push(ImportIssue::new(path, "Already in library"));
+        return Ok(());
+    }
+
+    // Audio the player cannot decode is reported instead of imported
+    let mut probed = match formats::ProbedFile::open(path) {
+        Ok(probed) => probed,
+        Err(error) => {
+            report.unsupported.push(ImportIssue::new(path, &error));
+            return Ok(());
+        }
+    };
+
+    match import_single(conn, &mut probed, path, now, cache_dir, settings) {
+        Ok(Some(track)) => report.imported.push(track),
+        Ok(None) => report
+            .skipped_duplicates
+            .push(ImportIssue::new(path, "Already in library")),
+        Err(error) => report.failed.push(ImportIssue::new(path, &error)),
+    }
+    Ok(())
+}
+
+fn ensure_import_job_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS import_jobs (
+            id TEXT PRIMARY KEY,
+            created_at INTEGER NOT NULL,
+            status TEXT NOT NULL,
+            total INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS import_job_paths (
+            job_id TEXT NOT NULL REFERENCES import_jobs(id) ON DELETE CASCADE,
+            path TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            done INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE INDEX IF NOT EXISTS import_job_paths_job_idx ON import_job_paths (job_id, done, position);",
+    )
+    .map_err(|error| error.to_string())?;
+
+    // Completed jobs used to be kept around
+    conn.execute("DELETE FROM import_jobs WHERE status = ?1", [JOB_COMPLETED])
+        .map_err(|error| error.to_string())?;
+    Ok(())
+}
+
+/// Import jobs that did not finish, either because they were cancelled or
+/// because the app closed while they were running
+pub fn load_unfinished_import_jobs(db_path: &str) -> Result<Vec<ImportJobRow>, String> {
+    if !Path::new(db_path).exists() {
+        return Ok(Vec::new());
+    }
+
+    let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    ensure_import_job_schema(&conn)?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT j.id, j.created_at, j.status, j.total,
+                    (SELECT COUNT(*) FROM import_job_paths p WHERE p.job_id = j.id AND p.done = 0)
+             FROM import_jobs j
+             WHERE j.status != ?1
+             ORDER BY j.created_at DESC",
+        )
+        .map_err(|error| error.to_string())?;
+
+    let rows = stmt
+        .query_map([JOB_COMPLETED], |row| {
+            Ok(ImportJobRow {
+                id: row.get(0)?,
+                created_at: row.get(1)?,
+                status: row.get(2)?,
+                total: row.get(3)?,
+                pending: row.get(4)?,
+            })
+        })
+        .map_err(|error| error.to_string())?;
+
+    let mut jobs = Vec::new();
+    for row in rows {
+        jobs.push(row.map_err(|error| error.to_string())?);
+    }
+    Ok(jobs)
+}
+
+pub fn discard_import_job(db_path: &str, job_id: &str) -> Result<(), String> {
+    let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    ensure_import_job_schema(&conn)?;
+    conn.execute("DELETE FROM import_job_paths WHERE job_id = ?1", [job_id])
+        .map_err(|error| error.to_string())?;
+    conn.execute("DELETE FROM import_jobs WHERE id = ?1", [job_id])
+        .map_err(|error| error.to_string())?;
+    Ok(())
+}
+
+fn is_known_path(conn: &Connection, path: &Path) -> Result<bool, String> {
+    let mut stmt = conn
+        .prepare_cached("SELECT 1 FROM tracks WHERE source_path = ?1")
+        .map_err(|error| error.to_string())?;
+    stmt.exists([path.to_string_lossy().as_ref()])
+        .map_err(|error| error.to_string())
+}
+
+fn ensure_import_report_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS import_reports (
+            id INTEGER PRIMARY KEY,
+            created_at INTEGER NOT NULL,
+            report_json TEXT NOT NULL
+        )",
+        [],
+    )
+    .map_err(|error| error.to_string())?;
+
+    Ok(())
+}
+
+/// Store the summary of an import, keeping only the most recent one
+fn save_import_report(conn: &Connection, summary: &ImportReportSummary) -> Result<(), String> {
+    let json = serde_json::to_string(summary).map_err(|error| error.to_string())?;
+    conn.execute("DELETE FROM import_reports", [])
+        .map_err(|error| error.to_string())?;
+    conn.execute(
+        "INSERT INTO import_reports (created_at, report_json) VALUES (?1, ?2)",
+        params![summary.created_at, json],
+    )
+    .map_err(|error| error.to_string())?;
+
+    Ok(())
+}
+
+pub fn load_last_import_report(db_path: &str) -> Result<Option<ImportReportSummary>, String> {
+    if !Path::new(db_path).exists() {
+        return Ok(None);
+    }
+
+    let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    ensure_import_report_schema(&conn)?;
+
+    let json: Option<String> = conn
+        .query_row(
+            "SELECT report_json FROM import_reports ORDER BY created_at DESC LIMIT 1",
+            [],
+            |row| row.get(0),
+        )
+        .ok();
+
+    match json {
+        Some(json) => serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|error| error.to_string()),
+        None => Ok(None),
+    }
 }
 
 pub fn load_tracks(db_path: &str) -> Result<LibrarySnapshot, String> {
@@ -198,7 +935,9 @@ pub fn load_tracks(db_path: &str) -> Result<LibrarySnapshot, String> {
                     key, bpm, year, date, added_at, updated_at, rating, duration_seconds,
                     bitrate_kbps, import_status, source_path, cover_art_path,
                     cover_art_thumb_path, last_played_at, play_count,
-                    genre_json, comment_json, label, disc_number, disc_total
+                    genre_json, comment_json, label, disc_number, disc_total,
+                    is_missing, sample_rate, bit_depth, channels, codec, container,
+                    is_lossless, bitrate_mode, file_size
              FROM tracks ORDER BY added_at DESC",
         )
         .map_err(|error| error.to_string())?;
@@ -221,7 +960,9 @@ pub fn load_tracks(db_path: &str) -> Result<LibrarySnapshot, String> {
             let rating: Option<f64> = row.get(13)?;
             let duration_seconds: Option<f64> = row.get(14)?;
             let bitrate_kbps: Option<i32> = row.get(15)?;
-            let import_status: Option<String> = row.get(16)?;
+            let import_status = row
+                .get::<_, Option<String>>(16)?
+                .unwrap_or_else(|| STATUS_ACCEPTED.to_string());
             let source_path: Option<String> = row.get(17)?;
             let cover_art_path: Option<String> = row.get(18)?;
             let cover_art_thumb_path: Option<String> = row.get(19)?;
@@ -232,6 +973,8 @@ pub fn load_tracks(db_path: &str) -> Result<LibrarySnapshot, String> {
             let label: Option<String> = row.get(24)?;
             let disc_number: Option<i32> = row.get(25)?;
             let disc_total: Option<i32> = row.get(26)?;
+            let is_missing: Option<i32> = row.get(27)?;
+            let is_lossless: Option<bool> = row.get(33)?;
 
             let duration = duration_seconds
                 .map(|value| format_duration(value as f32))
@@ -267,14 +1010,24 @@ pub fn load_tracks(db_path: &str) -> Result<LibrarySnapshot, String> {
                     cover_art_path,
                     cover_art_thumb_path,
                     genre: json_array_to_csv(&genre_json),
-                    comment: json_array_to_csv(&comment_json),
+                    comment: json_array_to_lines(&comment_json),
                     label,
                     disc_number,
                     disc_total,
                     last_played_at,
                     play_count: play_count.unwrap_or(0),
+                    is_missing: is_missing.unwrap_or(0) != 0,
+                    import_status: import_status.clone(),
+                    sample_rate: row.get(28)?,
+                    bit_depth: row.get(29)?,
+                    channels: row.get(30)?,
+                    codec: row.get(31)?,
+                    container: row.get(32)?,
+                    is_lossless,
+                    bitrate_mode: row.get(34)?,
+                    file_size: row.get(35)?,
                 },
-                import_status.unwrap_or_else(|| STATUS_ACCEPTED.to_string()),
+                import_status,
             ))
         })
         .map_err(|error| error.to_string())?;
@@ -321,6 +1074,16 @@ pub fn ensure_playlist_schema(conn: &Connection) -> Result<(), String> {
     )
     .map_err(|error| error.to_string())?;
 
+    // Folders group playlists (and other folders) and hold no tracks themselves
+    let _ = conn.execute(
+        "ALTER TABLE playlists ADD COLUMN parent_id TEXT REFERENCES playlists(id) ON DELETE CASCADE",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE playlists ADD COLUMN is_folder INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+
     Ok(())
 }
 
@@ -338,10 +1101,10 @@ pub fn load_playlists(db_path: &str) -> Result<PlaylistSnapshot, String> {
     // This avoids the N+1 query problem
     let mut stmt = conn
         .prepare(
-            "SELECT p.id, p.name, p.created_at, pt.track_id
+            "SELECT p.id, p.name, p.parent_id, p.is_folder, pt.track_id
              FROM playlists p
              LEFT JOIN playlist_tracks pt ON p.id = pt.playlist_id
-             ORDER BY p.created_at DESC, pt.position ASC",
+             ORDER BY p.created_at DESC, p.rowid ASC, pt.position ASC",
         )
         .map_err(|error| error.to_string())?;
 
@@ -349,8 +1112,10 @@ pub fn load_playlists(db_path: &str) -> Result<PlaylistSnapshot, String> {
         .query_map([], |row| {
             let id: String = row.get(0)?;
             let name: String = row.get(1)?;
-            let track_id: Option<String> = row.get(3)?;
-            Ok((id, name, track_id))
+            let parent_id: Option<String> = row.get(2)?;
+            let is_folder: bool = row.get(3)?;
+            let track_id: Option<String> = row.get(4)?;
+            Ok((id, name, parent_id, is_folder, track_id))
         })
         .map_err(|error| error.to_string())?;
 
@@ -360,13 +1125,15 @@ pub fn load_playlists(db_path: &str) -> Result<PlaylistSnapshot, String> {
     let mut playlist_order: Vec<String> = Vec::new();
 
     for row in rows {
-        let (id, name, track_id) = row.map_err(|error| error.to_string())?;
+        let (id, name, parent_id, is_folder, track_id) = row.map_err(|error| error.to_string())?;
 
         let playlist = playlist_map.entry(id.clone()).or_insert_with(|| {
             playlist_order.push(id.clone());
             PlaylistRow {
                 id,
                 name,
+                parent_id,
+                is_folder,
                 track_ids: Vec::new(),
             }
         });
@@ -392,7 +1159,9 @@ pub fn load_recently_played(conn: &Connection, limit: i32) -> Result<Vec<Importe
                     key, bpm, year, date, added_at, updated_at, rating, duration_seconds,
                     bitrate_kbps, import_status, source_path, cover_art_path,
                     cover_art_thumb_path, last_played_at, play_count,
-                    genre_json, comment_json, label, disc_number, disc_total
+                    genre_json, comment_json, label, disc_number, disc_total,
+                    is_missing, sample_rate, bit_depth, channels, codec, container,
+                    is_lossless, bitrate_mode, file_size
              FROM tracks
              WHERE last_played_at IS NOT NULL
              ORDER BY last_played_at DESC
@@ -428,6 +1197,8 @@ pub fn load_recently_played(conn: &Connection, limit: i32) -> Result<Vec<Importe
             let label: Option<String> = row.get(24)?;
             let disc_number: Option<i32> = row.get(25)?;
             let disc_total: Option<i32> = row.get(26)?;
+            let is_missing: Option<i32> = row.get(27)?;
+            let is_lossless: Option<bool> = row.get(33)?;
 
             let duration = duration_seconds
                 .map(|value| format_duration(value as f32))
@@ -462,12 +1233,24 @@ pub fn load_recently_played(conn: &Connection, limit: i32) -> Result<Vec<Importe
                 cover_art_path,
                 cover_art_thumb_path,
                 genre: json_array_to_csv(&genre_json),
-                comment: json_array_to_csv(&comment_json),
+                comment: json_array_to_lines(&comment_json),
                 label,
                 disc_number,
                 disc_total,
                 last_played_at,
                 play_count: play_count.unwrap_or(0),
+                is_missing: is_missing.unwrap_or(0) != 0,
+                import_status: row
+                    .get::<_, Option<String>>(16)?
+                    .unwrap_or_else(|| STATUS_ACCEPTED.to_string()),
+                sample_rate: row.get(28)?,
+                bit_depth: row.get(29)?,
+                channels: row.get(30)?,
+                codec: row.get(31)?,
+                container: row.get(32)?,
+                is_lossless,
+                bitrate_mode: row.get(34)?,
+                file_size: row.get(35)?,
             })
         })
         .map_err(|error| error.to_string())?;
@@ -500,43 +1283,129 @@ pub fn clear_tracks(db_path: &str, cache_dir: &Path) -> Result<(), String> {
 
     let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
     ensure_schema(&conn)?;
-    conn.execute("DELETE FROM tracks", [])
-        .map_err(|error| error.to_string())?;
+    conn.execute_batch(
+        "DELETE FROM tracks; DELETE FROM track_artists; DELETE FROM artists; DELETE FROM albums;
+         DELETE FROM lyrics;",
+    )
+    .map_err(|error| error.to_string())?;
     Ok(())
 }
 
-fn collect_audio_paths(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
-    if path.is_dir() {
-        for entry in std::fs::read_dir(path).map_err(|error| error.to_string())? {
-            let entry = entry.map_err(|error| error.to_string())?;
-            collect_audio_paths(&entry.path(), files)?;
+/// Walk `path` for audio files. Folders reached twice through symlinks are
+/// only read once, entries matching `options` are skipped (except `path`
+/// itself, which was picked explicitly), and folders that cannot be read are
+/// returned as issues instead of ending the walk.
+pub(crate) fn collect_audio_paths(
+    path: &Path,
+    options: &ScanOptions,
+    files: &mut Vec<PathBuf>,
+) -> Vec<ImportIssue> {
+    let mut issues = Vec::new();
+    if !path.exists() {
+        issues.push(ImportIssue::new(path, "File or folder not found"));
+    } else if path.is_dir() {
+        walk_dir(path, options, &mut HashSet::new(), files, &mut issues);
+    } else if formats::is_audio_file(path) {
+        files.push(path.to_path_buf());
+    }
+    issues
+}
+
+fn walk_dir(
+    dir: &Path,
+    options: &ScanOptions,
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<PathBuf>,
+    issues: &mut Vec<ImportIssue>,
+) {
+    let canonical = match dir.canonicalize() {
+        Ok(canonical) => canonical,
+        Err(error) => {
+            issues.push(ImportIssue::new(dir, &error.to_string()));
+            return;
         }
-        return Ok(());
+    };
+    if !visited.insert(canonical) {
+        return;
     }
 
-    let extension = path
-        .extension()
-        .and_then(|value| value.to_str())
-        .unwrap_or("")
-        .to_ascii_lowercase();
-    if AUDIO_EXTENSIONS.iter().any(|item| *item == extension) {
-        files.push(path.to_path_buf());
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(error) => {
+            issues.push(ImportIssue::new(
+                dir,
+                &format!("Could not read folder: {}", error),
+            ));
+            return;
+        }
+    };
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(error) => {
+                issues.push(ImportIssue::new(
+                    dir,
+                    &format!("Could not read folder: {}", error),
+                ));
+                continue;
+            }
+        };
+        if options.is_excluded(&path) {
+            continue;
+        }
+        if path.is_dir() {
+            walk_dir(&path, options, visited, files, issues);
+        } else if formats::is_audio_file(&path) {
+            files.push(path);
+        }
     }
-    Ok(())
 }
 
-fn import_single(
-    conn: &Connection,
+/// Size in bytes and modification time (unix seconds) used to detect changed files
+pub fn file_signature(path: &Path) -> Option<(i64, i64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_secs() as i64;
+    Some((metadata.len() as i64, modified))
+}
+
+fn read_track_file(
+    probed: &mut formats::ProbedFile,
     path: &Path,
-    now: i64,
     cache_dir: &Path,
-) -> Result<Option<ImportedTrack>, String> {
-    let tagged = Probe::open(path)
-        .map_err(|error| error.to_string())?
-        .read()
-        .map_err(|error| error.to_string())?;
-    let properties = tagged.properties();
-    let metadata = normalize_metadata(&tagged, path)?;
+    sidecar_patterns: &[String],
+) -> Result<TrackFileData, String> {
+    let container = probed.container;
+    let (metadata, duration_seconds, bitrate, audio, cached_cover) = match container {
+        AudioContainer::Tagged(_) => {
+            let formats::FileTags { tagged, id3v2 } = probed.read_tags()?;
+            let properties = tagged.properties();
+            (
+                normalize_metadata(&tagged, id3v2.as_ref(), path)?,
+                properties.duration().as_secs_f32(),
+                properties.audio_bitrate().unwrap_or(0) as i32,
+                probed.audio_properties(Some(properties)),
+                // Extract and cache cover art
+                cover_art::resolve_cover_art(Some(&tagged), path, sidecar_patterns, cache_dir),
+            )
+        }
+        // Containers lofty cannot parse (Matroska, CAF) are read through symphonia
+        AudioContainer::Stream(_) => {
+            let audio = probed.audio_properties(None);
+            let info = &probed.stream;
+            (
+                normalize_stream_metadata(info, path),
+                info.duration_seconds as f32,
+                stream_bitrate(path, info.duration_seconds),
+                audio,
+                cover_art::resolve_cover_art(None, path, sidecar_patterns, cache_dir),
+            )
+        }
+    };
 
     let title = metadata
         .title
@@ -550,23 +1419,19 @@ fn import_single(
         .album
         .clone()
         .unwrap_or_else(|| UNKNOWN_ALBUM.to_string());
-    let rating = metadata.rating.unwrap_or(0.0);
 
-    // Extract and cache cover art
-    let cached_cover = cover_art::process_cover_art(&tagged, cache_dir);
-    let cover_art_path = cached_cover.as_ref().map(|c| c.full_path.clone());
-    let cover_art_thumb_path = cached_cover.as_ref().map(|c| c.thumb_path.clone());
+    let credits = ArtistCredits::from_tags(
+        metadata.artist.as_deref(),
+        &metadata.artists,
+        metadata.album_artist.as_deref(),
+        &metadata.remixers,
+        Some(&title),
+    );
 
-    let duration_seconds = properties.duration().as_secs_f32();
-    let bitrate = properties.audio_bitrate().unwrap_or(0) as i32;
-    let duration_text = format_duration(duration_seconds);
-    let bitrate_text = if bitrate > 0 {
-        format!("{} kbps", bitrate)
-    } else {
-        DEFAULT_BITRATE.to_string()
-    };
+    let cover_art_path = cached_cover.as_ref().map(|(c, _)| c.full_path.clone());
+    let cover_art_thumb_path = cached_cover.as_ref().map(|(c, _)| c.thumb_path.clone());
+    let cover_art_source = cached_cover.as_ref().map(|(_, source)| source.as_str());
 
-    let id = Uuid::new_v4().to_string();
     let genre_refs: Vec<&str> = metadata.genres.iter().map(|value| value.as_str()).collect();
     let comment_refs: Vec<&str> = metadata
         .comments
@@ -601,6 +1466,89 @@ fn import_single(
     let isrc_json = serde_json::to_string(&metadata.isrc).unwrap_or_else(|_| "[]".to_string());
     let raw_tags_json =
         serde_json::to_string(&metadata.raw_tags).unwrap_or_else(|_| "{}".to_string());
+    let signature = file_signature(path);
+    let lyrics = lyrics::read_file_lyrics(
+        metadata.lyrics.as_deref(),
+        metadata.synced_lyrics.clone(),
+        path,
+    );
+
+    Ok(TrackFileData {
+        metadata,
+        title,
+        artist,
+        album,
+        credits,
+        lyrics,
+        search_text,
+        genre_json,
+        comment_json,
+        isrc_json,
+        raw_tags_json,
+        duration_seconds,
+        bitrate,
+        audio,
+        cover_art_path,
+        cover_art_thumb_path,
+        cover_art_source,
+        file_size: signature.map(|(size, _)| size),
+        file_mtime: signature.map(|(_, modified)| modified),
+        audio_hash: probed.audio_hash.clone(),
+    })
+}
+
+pub(crate) fn read_fingerprint(path: &Path) -> Result<TrackFingerprint, String> {
+    let tagged = match read_tagged_file(path) {
+        Ok(tagged) => tagged,
+        Err(error) => {
+            if !matches!(formats::sniff(path), Some(AudioContainer::Stream(_))) {
+                return Err(error);
+            }
+            let info = formats::read_stream_info(path)?;
+            return Ok(TrackFingerprint {
+                title: info
+                    .get(StandardTagKey::TrackTitle, "TITLE")
+                    .map(str::to_string),
+                artist: info
+                    .get(StandardTagKey::Artist, "ARTIST")
+                    .map(str::to_string),
+                duration_seconds: info.duration_seconds,
+            });
+        }
+    };
+    let tag = tagged.primary_tag().or_else(|| tagged.first_tag());
+
+    Ok(TrackFingerprint {
+        title: tag
+            .and_then(|tag| tag.get_string(&ItemKey::TrackTitle))
+            .map(str::to_string),
+        artist: tag
+            .and_then(|tag| tag.get_string(&ItemKey::TrackArtist))
+            .map(str::to_string),
+        duration_seconds: tagged.properties().duration().as_secs_f64(),
+    })
+}
+
+pub(crate) fn import_single(
+    conn: &Connection,
+    probed: &mut formats::ProbedFile,
+    path: &Path,
+    now: i64,
+    cache_dir: &Path,
+    settings: &ImportSettings,
+) -> Result<Option<ImportedTrack>, String> {
+    let data = read_track_file(probed, path, cache_dir, &settings.sidecar_patterns)?;
+    let metadata = &data.metadata;
+    let rating = metadata.rating.unwrap_or(0.0);
+
+    let duration_text = format_duration(data.duration_seconds);
+    let bitrate_text = if data.bitrate > 0 {
+        format!("{} kbps", data.bitrate)
+    } else {
+        DEFAULT_BITRATE.to_string()
+    };
+
+    let id = Uuid::new_v4().to_string();
 
     conn.execute(
         "INSERT OR IGNORE INTO tracks (
@@ -611,23 +1559,27 @@ fn import_single(
             musicbrainz_releasegroupid, musicbrainz_trackid, musicbrainz_releasetrackid,
             musicbrainz_albumstatus, musicbrainz_albumtype, source_path, search_text,
             import_status, duration_seconds, bitrate_kbps, added_at, updated_at, is_missing,
-            cover_art_path, cover_art_thumb_path
+            cover_art_path, cover_art_thumb_path, file_size, file_mtime, audio_hash,
+            cover_art_source, is_compilation, sample_rate, bit_depth, channels, codec,
+            container, is_lossless, bitrate_mode
         ) VALUES (
             ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9,
             ?10, ?11, ?12, ?13, ?14, ?15, ?16,
             ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25,
             ?26, ?27, ?28, ?29, ?30, ?31,
             ?32, ?33, ?34, ?35,
-            ?36, ?37, ?38, ?39, ?40, ?41, ?42, ?43
+            ?36, ?37, ?38, ?39, ?40, ?41, ?42, ?43, ?44, ?45, ?46,
+            ?47, ?48, ?49, ?50, ?51, ?52,
+            ?53, ?54, ?55
         )",
         params![
             id,
-            title,
-            artist,
-            album,
+            data.title,
+            data.artist,
+            data.album,
             metadata.album_artist,
-            genre_json,
-            comment_json,
+            data.genre_json,
+            data.comment_json,
             metadata.label,
             metadata.filename,
             metadata.year,
@@ -641,11 +1593,11 @@ fn import_single(
             metadata.key,
             metadata.bpm,
             rating,
-            isrc_json,
+            data.isrc_json,
             metadata.encoder,
             metadata.encoder_tag,
             metadata.encoder_tool,
-            raw_tags_json,
+            data.raw_tags_json,
             metadata.musicbrainz_albumid,
             metadata.musicbrainz_artistid,
             metadata.musicbrainz_albumartistid,
@@ -655,15 +1607,27 @@ fn import_single(
             metadata.musicbrainz_albumstatus,
             metadata.musicbrainz_albumtype,
             path.to_string_lossy().to_string(),
-            search_text,
+            data.search_text,
             STATUS_STAGED,
-            duration_seconds,
-            bitrate,
+            data.duration_seconds,
+            data.bitrate,
             now,
             now,
             0,
-            cover_art_path,
-            cover_art_thumb_path
+            data.cover_art_path,
+            data.cover_art_thumb_path,
+            data.file_size,
+            data.file_mtime,
+            data.audio_hash,
+            data.cover_art_source,
+            metadata.compilation,
+            data.audio.sample_rate,
+            data.audio.bit_depth,
+            data.audio.channels,
+            data.audio.codec,
+            data.audio.container,
+            data.audio.lossless,
+            data.audio.bitrate_mode
         ],
     )
     .map_err(|error| error.to_string())?;
@@ -672,6 +1636,14 @@ fn import_single(
     if conn.changes() == 0 {
         return Ok(None);
     }
+    artists::set_track_credits(conn, &id, &data.credits, &artists::ALL_ROLES)?;
+    albums::assign_album(conn, &id, &settings.various_artists)?;
+    lyrics::store_file_lyrics(conn, &id, data.lyrics.as_ref())?;
+    let import_status = if inbox_rules::apply_to_track(conn, &settings.inbox_rules, &id)? {
+        STATUS_ACCEPTED
+    } else {
+        STATUS_STAGED
+    };
 
     let date_added = Some(format_timestamp(now));
 
@@ -688,10 +1660,10 @@ fn import_single(
 
     Ok(Some(ImportedTrack {
         id,
-        title,
-        artist,
+        title: data.title.clone(),
+        artist: data.artist.clone(),
         artists: metadata.album_artist.clone(),
-        album,
+        album: data.album.clone(),
         track_number: metadata.track_number,
         track_total: metadata.track_total,
         key: metadata.key.clone(),
@@ -701,12 +1673,12 @@ fn import_single(
         date_added: date_added.clone(),
         date_modified: date_added,
         duration: duration_text,
-        duration_seconds: duration_seconds as f64,
+        duration_seconds: data.duration_seconds as f64,
         bitrate: bitrate_text,
         rating,
         source_path: path.to_string_lossy().to_string(),
-        cover_art_path,
-        cover_art_thumb_path,
+        cover_art_path: data.cover_art_path.clone(),
+        cover_art_thumb_path: data.cover_art_thumb_path.clone(),
         genre: genre_csv,
         comment: comment_csv,
         label: metadata.label.clone(),
@@ -714,10 +1686,235 @@ fn import_single(
         disc_total: metadata.disc_total,
         last_played_at: None,
         play_count: 0,
+        is_missing: false,
+        import_status: import_status.to_string(),
+        sample_rate: data.audio.sample_rate,
+        bit_depth: data.audio.bit_depth,
+        channels: data.audio.channels,
+        codec: data.audio.codec.clone(),
+        container: data.audio.container.clone(),
+        is_lossless: data.audio.lossless,
+        bitrate_mode: data.audio.bitrate_mode.map(str::to_string),
+        file_size: data.file_size,
     }))
 }
 
-fn normalize_metadata(tagged: &TaggedFile, path: &Path) -> Result<NormalizedMetadata, String> {
+/// Re-read tags for an existing track and update its row in place.
+/// Values the app may have set without touching the file (key, BPM, rating,
+/// cover art) are only replaced when the file provides them; play statistics
+/// and playlist memberships are left alone.
+pub(crate) fn refresh_track_from_file(
+    conn: &Connection,
+    track_id: &str,
+    path: &Path,
+    now: i64,
+    cache_dir: &Path,
+    settings: &ImportSettings,
+) -> Result<(), String> {
+    let mut probed = formats::ProbedFile::open(path)?;
+    let data = read_track_file(&mut probed, path, cache_dir, &settings.sidecar_patterns)?;
+    let metadata = &data.metadata;
+
+    conn.execute(
+        "UPDATE tracks SET
+            title = ?1, artist = ?2, album = ?3, album_artist = ?4, genre_json = ?5,
+            comment_json = ?6, label = ?7, filename = ?8, year = ?9, date = ?10,
+            original_date = ?11, original_year = ?12, track_number = ?13, track_total = ?14,
+            disc_number = ?15, disc_total = ?16, key = COALESCE(?17, key),
+            bpm = COALESCE(?18, bpm), rating = COALESCE(?19, rating), isrc_json = ?20,
+            encoder = ?21, encoder_tag = ?22, encoder_tool = ?23, raw_tags_json = ?24,
+            musicbrainz_albumid = ?25, musicbrainz_artistid = ?26,
+            musicbrainz_albumartistid = ?27, musicbrainz_releasegroupid = ?28,
+            musicbrainz_trackid = ?29, musicbrainz_releasetrackid = ?30,
+            musicbrainz_albumstatus = ?31, musicbrainz_albumtype = ?32, search_text = ?33,
+            duration_seconds = ?34, bitrate_kbps = ?35, updated_at = ?36, is_missing = 0,
+            cover_art_path = COALESCE(?37, cover_art_path),
+            cover_art_thumb_path = COALESCE(?38, cover_art_thumb_path),
+            cover_art_source = CASE WHEN ?37 IS NULL THEN cover_art_source ELSE ?43 END,
+            file_size = ?39, file_mtime = ?40, audio_hash = COALESCE(?41, audio_hash),
+            is_compilation = ?44, sample_rate = ?45, bit_depth = ?46, channels = ?47,
+            codec = ?48, container = ?49, is_lossless = ?50, bitrate_mode = ?51
+         WHERE id = ?42",
+        params![
+            data.title,
+            data.artist,
+            data.album,
+            metadata.album_artist,
+            data.genre_json,
+            data.comment_json,
+            metadata.label,
+            metadata.filename,
+            metadata.year,
+            metadata.date,
+            metadata.original_date,
+            metadata.original_year,
+            metadata.track_number,
+            metadata.track_total,
+            metadata.disc_number,
+            metadata.disc_total,
+            metadata.key,
+            metadata.bpm,
+            metadata.rating,
+            data.isrc_json,
+            metadata.encoder,
+            metadata.encoder_tag,
+            metadata.encoder_tool,
+            data.raw_tags_json,
+            metadata.musicbrainz_albumid,
+            metadata.musicbrainz_artistid,
+            metadata.musicbrainz_albumartistid,
+            metadata.musicbrainz_releasegroupid,
+            metadata.musicbrainz_trackid,
+            metadata.musicbrainz_releasetrackid,
+            metadata.musicbrainz_albumstatus,
+            metadata.musicbrainz_albumtype,
+            data.search_text,
+            data.duration_seconds,
+            data.bitrate,
+            now,
+            data.cover_art_path,
+            data.cover_art_thumb_path,
+            data.file_size,
+            data.file_mtime,
+            data.audio_hash,
+            track_id,
+            data.cover_art_source,
+            metadata.compilation,
+            data.audio.sample_rate,
+            data.audio.bit_depth,
+            data.audio.channels,
+            data.audio.codec,
+            data.audio.container,
+            data.audio.lossless,
+            data.audio.bitrate_mode
+        ],
+    )
+    .map_err(|error| error.to_string())?;
+    artists::set_track_credits(conn, track_id, &data.credits, &artists::ALL_ROLES)?;
+    albums::assign_album(conn, track_id, &settings.various_artists)?;
+    lyrics::store_file_lyrics(conn, track_id, data.lyrics.as_ref())?;
+
+    Ok(())
+}
+
+/// Read tags with lofty, detecting the format from the content so mis-named
+/// files still parse
+fn read_tagged_file(path: &Path) -> Result<TaggedFile, String> {
+    Probe::open(path)
+        .map_err(|error| error.to_string())?
+        .guess_file_type()
+        .map_err(|error| error.to_string())?
+        .read()
+        .map_err(|error| error.to_string())
+}
+
+fn stream_bitrate(path: &Path, duration_seconds: f64) -> i32 {
+    match file_signature(path) {
+        Some((size, _)) if duration_seconds > 0.0 => {
+            (size as f64 * 8.0 / duration_seconds / 1000.0).round() as i32
+        }
+        _ => 0,
+    }
+}
+
+fn normalize_stream_metadata(info: &formats::StreamInfo, path: &Path) -> NormalizedMetadata {
+    let text = |key: StandardTagKey, raw: &str| info.get(key, raw).map(str::to_string);
+
+    let mut meta = NormalizedMetadata {
+        filename: path
+            .file_name()
+            .and_then(|value| value.to_str())
+            .unwrap_or_default()
+            .to_string(),
+        ..Default::default()
+    };
+    meta.title = text(StandardTagKey::TrackTitle, "TITLE");
+    meta.artist = text(StandardTagKey::Artist, "ARTIST");
+    meta.album = text(StandardTagKey::Album, "ALBUM");
+    meta.album_artist = text(StandardTagKey::AlbumArtist, "ALBUM_ARTIST");
+    meta.remixers = info.get_all(StandardTagKey::Remixer, "REMIXED_BY");
+    meta.compilation = info
+        .get(StandardTagKey::Compilation, "COMPILATION")
+        .is_some_and(parse_flag);
+    meta.lyrics = text(StandardTagKey::Lyrics, "LYRICS");
+    meta.label = text(StandardTagKey::Label, "LABEL");
+    meta.date = text(StandardTagKey::Date, "DATE_RELEASED")
+        .or_else(|| text(StandardTagKey::ReleaseDate, "DATE_RECORDED"));
+    meta.year = meta.date.as_ref().and_then(|value| parse_year(value));
+    meta.original_date = text(StandardTagKey::OriginalDate, "ORIGINAL_DATE");
+    meta.original_year = meta
+        .original_date
+        .as_ref()
+        .and_then(|value| parse_year(value));
+    meta.key = info
+        .get_raw("INITIAL_KEY")
+        .or_else(|| info.get_raw("TKEY"))
+        .map(|value| value.trim().to_string());
+    meta.bpm = info
+        .get(StandardTagKey::Bpm, "BPM")
+        .and_then(|value| value.trim().parse::<f64>().ok());
+    meta.encoder_tag = text(StandardTagKey::Encoder, "ENCODER");
+    meta.encoder = meta.encoder_tag.clone();
+
+    for value in info.get_all(StandardTagKey::Genre, "GENRE") {
+        meta.genres.extend(split_genres(&value));
+    }
+    for value in info.get_all(StandardTagKey::Comment, "COMMENT") {
+        meta.comments.extend(split_comments(&value));
+    }
+    meta.isrc = info.get_all(StandardTagKey::IdentIsrc, "ISRC");
+
+    let (track_number, track_total) = parse_number_pair(
+        info.get(StandardTagKey::TrackNumber, "PART_NUMBER")
+            .unwrap_or(""),
+    );
+    meta.track_number = track_number;
+    meta.track_total = info
+        .get(StandardTagKey::TrackTotal, "TOTAL_PARTS")
+        .and_then(|value| value.trim().parse::<i32>().ok())
+        .or(track_total);
+    let (disc_number, disc_total) =
+        parse_number_pair(info.get(StandardTagKey::DiscNumber, "DISC").unwrap_or(""));
+    meta.disc_number = disc_number;
+    meta.disc_total = info
+        .get(StandardTagKey::DiscTotal, "DISCTOTAL")
+        .and_then(|value| value.trim().parse::<i32>().ok())
+        .or(disc_total);
+
+    meta.musicbrainz_albumid = text(StandardTagKey::MusicBrainzAlbumId, "MUSICBRAINZ_ALBUMID");
+    meta.musicbrainz_artistid = text(StandardTagKey::MusicBrainzArtistId, "MUSICBRAINZ_ARTISTID");
+    meta.musicbrainz_albumartistid = text(
+        StandardTagKey::MusicBrainzAlbumArtistId,
+        "MUSICBRAINZ_ALBUMARTISTID",
+    );
+    meta.musicbrainz_releasegroupid = text(
+        StandardTagKey::MusicBrainzReleaseGroupId,
+        "MUSICBRAINZ_RELEASEGROUPID",
+    );
+    meta.musicbrainz_trackid = text(
+        StandardTagKey::MusicBrainzRecordingId,
+        "MUSICBRAINZ_TRACKID",
+    );
+    meta.musicbrainz_releasetrackid = text(
+        StandardTagKey::MusicBrainzReleaseTrackId,
+        "MUSICBRAINZ_RELEASETRACKID",
+    );
+
+    let mut raw = BTreeMap::new();
+    for tag in &info.tags {
+        raw.entry(tag.key.clone())
+            .or_insert_with(Vec::new)
+            .push(tag.value.clone());
+    }
+    meta.raw_tags = json!({ "stream": raw });
+    meta
+}
+
+fn normalize_metadata(
+    tagged: &TaggedFile,
+    id3v2: Option<&Id3v2Tag>,
+    path: &Path,
+) -> Result<NormalizedMetadata, String> {
     let tag = tagged.primary_tag().or_else(|| tagged.first_tag());
     let filename = path
         .file_name()
@@ -730,7 +1927,20 @@ fn normalize_metadata(tagged: &TaggedFile, path: &Path) -> Result<NormalizedMeta
         meta.title = tag.get_string(&ItemKey::TrackTitle).map(str::to_string);
         meta.artist = tag.get_string(&ItemKey::TrackArtist).map(str::to_string);
         meta.album = tag.get_string(&ItemKey::AlbumTitle).map(str::to_string);
-        meta.album_artist = tag.get_string(&ItemKey::AlbumArtist).map(str::to_string);
+        let split = multi_value_split(tag.tag_type());
+        let album_artists = collect_values(tag, ItemKey::AlbumArtist, split);
+        meta.album_artist = (!album_artists.is_empty()).then(|| album_artists.join("; "));
+        meta.artists = ARTISTS_KEYS
+            .iter()
+            .map(|key| collect_values(tag, ItemKey::Unknown(key.to_string()), split))
+            .find(|values| !values.is_empty())
+            .unwrap_or_default();
+        meta.remixers = collect_values(tag, ItemKey::Remixer, split_passthrough);
+        meta.compilation = tag
+            .get_string(&ItemKey::FlagCompilation)
+            .is_some_and(parse_flag);
+        meta.lyrics = tag.get_string(&ItemKey::Lyrics).map(str::to_string);
+        meta.synced_lyrics = id3v2.and_then(lyrics::sylt_lines);
         meta.label = tag
             .get_string(&ItemKey::Label)
             .or_else(|| tag.get_string(&ItemKey::Publisher))
@@ -771,7 +1981,10 @@ fn normalize_metadata(tagged: &TaggedFile, path: &Path) -> Result<NormalizedMeta
         meta.encoder = meta.encoder_tag.clone();
 
         meta.genres = collect_values(tag, ItemKey::Genre, split_genres);
-        meta.comments = collect_values(tag, ItemKey::Comment, split_comments);
+        meta.comments = match id3v2.filter(|_| tag.tag_type() == TagType::Id3v2) {
+            Some(id3v2) => id3v2_comments(id3v2),
+            None => collect_values(tag, ItemKey::Comment, split_comments),
+        };
         meta.isrc = collect_values(tag, ItemKey::Isrc, split_passthrough);
 
         let track_value = tag.get_string(&ItemKey::TrackNumber).unwrap_or("");
@@ -869,7 +2082,11 @@ fn item_value_to_string(item: &TagItem) -> String {
     }
 }
 
-fn collect_values(tag: &Tag, key: ItemKey, split: fn(&str) -> Vec<String>) -> Vec<String> {
+pub(crate) fn collect_values(
+    tag: &Tag,
+    key: ItemKey,
+    split: fn(&str) -> Vec<String>,
+) -> Vec<String> {
     let mut values = Vec::new();
     for item in tag.items().filter(|item| item.key() == &key) {
         let value = item_value_to_string(item);
@@ -878,22 +2095,42 @@ fn collect_values(tag: &Tag, key: ItemKey, split: fn(&str) -> Vec<String>) -> Ve
     values
 }
 
-fn split_genres(value: &str) -> Vec<String> {
+pub(crate) fn split_genres(value: &str) -> Vec<String> {
     value
-        .split(['/', ';', ','])
+        .split(['/', ';', ',', '\0'])
         .map(|item| item.trim())
         .filter(|item| !item.is_empty())
         .map(|item| item.to_string())
         .collect()
 }
 
-fn split_comments(value: &str) -> Vec<String> {
-    let trimmed = value.trim();
-    if trimmed.is_empty() {
-        Vec::new()
-    } else {
-        vec![trimmed.to_string()]
+/// Every COMM frame's text. lofty's generic tag only keeps the frames
+/// without a description, and muro writes one frame per comment.
+pub(crate) fn id3v2_comments(tag: &Id3v2Tag) -> Vec<String> {
+    let mut values = Vec::new();
+    for frame in tag {
+        if let FrameValue::Comment(comment) = frame.content() {
+            if !is_machine_comment(comment) {
+                values.extend(split_comments(&comment.content));
+            }
+        }
     }
+    values
+}
+
+/// iTunes keeps loudness and gapless data in described comments
+pub(crate) fn is_machine_comment(comment: &CommentFrame) -> bool {
+    comment.description.starts_with("iTun")
+}
+
+/// Comments are free text, so only ID3v2's NUL separator splits them
+pub(crate) fn split_comments(value: &str) -> Vec<String> {
+    value
+        .split('\0')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .map(|item| item.to_string())
+        .collect()
 }
 
 fn split_passthrough(value: &str) -> Vec<String> {
@@ -905,6 +2142,34 @@ fn split_passthrough(value: &str) -> Vec<String> {
     }
 }
 
+/// Vorbis comments, MP4, APE and ID3v2 hold one item per value, so a ";" in
+/// a name is part of the name. Only tags without multiple values fall back to
+/// a "; " list.
+fn multi_value_split(tag_type: TagType) -> fn(&str) -> Vec<String> {
+    match tag_type {
+        TagType::Id3v1 | TagType::RiffInfo | TagType::AiffText => split_multi_value,
+        _ => split_passthrough,
+    }
+}
+
+/// Split a list joined with NUL or "; ", as stored in the library and written
+/// by taggers for formats without multiple values
+pub(crate) fn split_multi_value(value: &str) -> Vec<String> {
+    value
+        .split(['\0', ';'])
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .map(|item| item.to_string())
+        .collect()
+}
+
+fn parse_flag(value: &str) -> bool {
+    matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "1" | "true" | "yes"
+    )
+}
+
 fn parse_number_pair(value: &str) -> (Option<i32>, Option<i32>) {
     let trimmed = value.trim();
     if trimmed.is_empty() {
@@ -968,13 +2233,13 @@ fn format_duration(seconds: f32) -> String {
     format!("{}:{:02}", minutes, secs)
 }
 
-fn format_timestamp(timestamp: i64) -> String {
+pub(crate) fn format_timestamp(timestamp: i64) -> String {
     DateTime::<Utc>::from_timestamp(timestamp, 0)
         .map(|dt| dt.format("%Y-%m-%dT%H:%M:%SZ").to_string())
         .unwrap_or_default()
 }
 
-fn fallback_title(path: &Path) -> String {
+pub(crate) fn fallback_title(path: &Path) -> String {
     path.file_stem()
         .and_then(|value| value.to_str())
         .map(search::strip_leading_track_number)
@@ -982,7 +2247,7 @@ fn fallback_title(path: &Path) -> String {
         .to_string()
 }
 
-fn current_timestamp() -> i64 {
+pub(crate) fn current_timestamp() -> i64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .map(|value| value.as_secs() as i64)
@@ -1000,6 +2265,38 @@ fn json_array_to_csv(json: &Option<String>) -> Option<String> {
     })
 }
 
+/// Comments are shown one per line, since they may contain commas
+fn json_array_to_lines(json: &Option<String>) -> Option<String> {
+    json.as_ref().and_then(|s| {
+        let items: Vec<String> = serde_json::from_str(s).ok()?;
+        if items.is_empty() {
+            None
+        } else {
+            Some(items.join("\n"))
+        }
+    })
+}
+
+/// JSON array stored for an edited genre or comment list. The editor sends
+/// either an array or the displayed text, split on `separator`.
+pub(crate) fn edited_list_json(value: &serde_json::Value, separator: char) -> String {
+    let items: Vec<String> = match value {
+        serde_json::Value::Array(values) => values
+            .iter()
+            .filter_map(|item| item.as_str())
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect(),
+        serde_json::Value::String(text) => text
+            .split(separator)
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect(),
+        _ => Vec::new(),
+    };
+    serde_json::to_string(&items).unwrap_or_else(|_| "[]".to_string())
+}
+
 pub fn ensure_schema(conn: &Connection) -> Result<(), String> {
     conn.execute_batch(
         "CREATE TABLE IF NOT EXISTS tracks (
@@ -1044,9 +2341,22 @@ pub fn ensure_schema(conn: &Connection) -> Result<(), String> {
             added_at INTEGER,
             updated_at INTEGER,
             last_write_error TEXT,
+            pending_write_json TEXT,
             is_missing INTEGER DEFAULT 0,
             cover_art_path TEXT,
-            cover_art_thumb_path TEXT
+            cover_art_thumb_path TEXT,
+            cover_art_source TEXT,
+            is_compilation INTEGER DEFAULT 0,
+            sample_rate INTEGER,
+            bit_depth INTEGER,
+            channels INTEGER,
+            codec TEXT,
+            container TEXT,
+            is_lossless INTEGER,
+            bitrate_mode TEXT,
+            file_size INTEGER,
+            file_mtime INTEGER,
+            audio_hash TEXT
         );",
     )
     .map_err(|error| error.to_string())?;
@@ -1063,6 +2373,231 @@ pub fn ensure_schema(conn: &Connection) -> Result<(), String> {
         "ALTER TABLE tracks ADD COLUMN play_count INTEGER DEFAULT 0",
         [],
     );
+    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN file_size INTEGER", []);
+    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN file_mtime INTEGER", []);
+    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN audio_hash TEXT", []);
+    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN cover_art_source TEXT", []);
+    let _ = conn.execute(
+        "ALTER TABLE tracks ADD COLUMN is_compilation INTEGER DEFAULT 0",
+        [],
+    );
+    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN sample_rate INTEGER", []);
+    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN bit_depth INTEGER", []);
+    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN channels INTEGER", []);
+    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN codec TEXT", []);
+    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN container TEXT", []);
+    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN is_lossless INTEGER", []);
+    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN bitrate_mode TEXT", []);
+    let _ = conn.execute("ALTER TABLE tracks ADD COLUMN pending_write_json TEXT", []);
+
+    artists::ensure_artist_schema(conn)?;
+    albums::ensure_album_schema(conn)?;
+    lyrics::ensure_lyrics_schema(conn)?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::wav_bytes;
+
+    fn tag_with(tag_type: TagType, key: ItemKey, values: &[&str]) -> Tag {
+        let mut tag = Tag::new(tag_type);
+        for value in values {
+            tag.push(TagItem::new(
+                key.clone(),
+                ItemValue::Text(value.to_string()),
+            ));
+        }
+        tag
+    }
+
+    #[test]
+    fn native_multi_values_keep_semicolons() {
+        let tag = tag_with(
+            TagType::VorbisComments,
+            ItemKey::AlbumArtist,
+            &["Crosby; Stills", "Nash"],
+        );
+        let split = multi_value_split(tag.tag_type());
+        assert_eq!(
+            collect_values(&tag, ItemKey::AlbumArtist, split),
+            ["Crosby; Stills", "Nash"]
+        );
+    }
+
+    fn job_count(db_path: &str) -> i64 {
+        let conn = Connection::open(db_path).unwrap();
+        ensure_import_job_schema(&conn).unwrap();
+        conn.query_row("SELECT COUNT(*) FROM import_jobs", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn only_unfinished_user_imports_keep_a_job() {
+        let dir = std::env::temp_dir().join(format!("muro-jobs-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("muro.db").to_string_lossy().to_string();
+        let cache_dir = dir.join("covers");
+        let paths: Vec<String> = ["01.wav", "02.wav", "03.wav"]
+            .iter()
+            .map(|name| {
+                let path = dir.join(name);
+                std::fs::write(&path, wav_bytes()).unwrap();
+                path.to_string_lossy().to_string()
+            })
+            .collect();
+
+        let report = import_files(vec![paths[0].clone()], &db_path, &cache_dir).unwrap();
+        assert_eq!(report.imported.len(), 1);
+        assert_eq!(job_count(&db_path), 0);
+
+        let cancel = AtomicBool::new(false);
+        let report = run_import_job(
+            "done",
+            vec![paths[1].clone()],
+            &db_path,
+            &cache_dir,
+            &cancel,
+            |_| {},
+        )
+        .unwrap();
+        assert_eq!(report.imported.len(), 1);
+        assert_eq!(job_count(&db_path), 0);
+
+        let cancel = AtomicBool::new(true);
+        let report = run_import_job(
+            "stopped",
+            vec![paths[2].clone()],
+            &db_path,
+            &cache_dir,
+            &cancel,
+            |_| {},
+        )
+        .unwrap();
+        assert!(report.cancelled);
+        let jobs = load_unfinished_import_jobs(&db_path).unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].id, "stopped");
+        assert_eq!(jobs[0].pending, 1);
+
+        let report = resume_import_job(
+            "stopped",
+            &db_path,
+            &cache_dir,
+            &AtomicBool::new(false),
+            |_| {},
+        )
+        .unwrap();
+        assert_eq!(report.imported.len(), 1);
+        assert_eq!(job_count(&db_path), 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn background_imports_keep_the_last_user_report() {
+        let dir = std::env::temp_dir().join(format!("muro-report-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("muro.db").to_string_lossy().to_string();
+        let cache_dir = dir.join("covers");
+        let first = dir.join("01.wav");
+        let second = dir.join("02.wav");
+        std::fs::write(&first, wav_bytes()).unwrap();
+        std::fs::write(&second, wav_bytes()).unwrap();
+
+        let cancel = AtomicBool::new(false);
+        let paths = vec![first.to_string_lossy().to_string()];
+        run_import_job("user", paths, &db_path, &cache_dir, &cancel, |_| {}).unwrap();
+        let paths = vec![second.to_string_lossy().to_string()];
+        run_background_import("watcher", paths, &db_path, &cache_dir, &cancel, |_| {}).unwrap();
+
+        let summary = load_last_import_report(&db_path).unwrap().unwrap();
+        assert_eq!(summary.job_id, "user");
+        assert_eq!(summary.imported, 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn folder_walk_skips_loops_hidden_and_excluded_entries() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = std::env::temp_dir().join(format!("muro-walk-{}", Uuid::new_v4()));
+        let album = dir.join("Artist").join("Album");
+        std::fs::create_dir_all(&album).unwrap();
+        std::fs::create_dir_all(dir.join(".hidden")).unwrap();
+        std::fs::create_dir_all(dir.join("Stems 2024")).unwrap();
+        let locked = dir.join("Locked");
+        std::fs::create_dir_all(&locked).unwrap();
+        for path in [
+            dir.join("01.wav"),
+            album.join("02.wav"),
+            dir.join(".hidden").join("03.wav"),
+            dir.join("Stems 2024").join("04.wav"),
+            locked.join("05.wav"),
+        ] {
+            std::fs::write(path, wav_bytes()).unwrap();
+        }
+        std::fs::write(album.join("cover.jpg"), b"not audio").unwrap();
+        // Points back at the root, so a naive walk would never end
+        symlink(&dir, album.join("loop")).unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+        // Root can read the folder regardless of its permissions
+        let locked_readable = std::fs::read_dir(&locked).is_ok();
+
+        let options = ScanOptions {
+            skip_hidden: true,
+            exclude: vec!["Stems*".to_string()],
+        };
+        let mut files = Vec::new();
+        let issues = collect_audio_paths(&dir, &options, &mut files);
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        files.sort();
+        let mut expected = vec![dir.join("01.wav"), album.join("02.wav")];
+        if locked_readable {
+            expected.push(locked.join("05.wav"));
+            assert!(issues.is_empty(), "{:?}", issues);
+        } else {
+            assert_eq!(issues.len(), 1);
+            assert_eq!(issues[0].path, locked.to_string_lossy());
+            assert!(issues[0].reason.starts_with("Could not read folder"));
+        }
+        expected.sort();
+        assert_eq!(files, expected);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn single_value_tags_split_on_semicolons() {
+        let tag = tag_with(TagType::Id3v1, ItemKey::TrackArtist, &["Alpha; Beta"]);
+        let split = multi_value_split(tag.tag_type());
+        assert_eq!(
+            collect_values(&tag, ItemKey::TrackArtist, split),
+            ["Alpha", "Beta"]
+        );
+    }
+
+    #[test]
+    fn edited_comments_keep_their_commas() {
+        let stored = serde_json::to_string(&["Live, remastered", "Promo copy"]).unwrap();
+        let shown = json_array_to_lines(&Some(stored.clone())).unwrap();
+        assert_eq!(shown, "Live, remastered\nPromo copy");
+        assert_eq!(
+            edited_list_json(&serde_json::Value::from(shown), '\n'),
+            stored
+        );
+        assert_eq!(
+            edited_list_json(&serde_json::json!(["Live, remastered", " "]), '\n'),
+            r#"["Live, remastered"]"#
+        );
+        assert_eq!(
+            edited_list_json(&serde_json::json!("House, Techno"), ','),
+            r#"["House","Techno"]"#
+        );
+    }
+}
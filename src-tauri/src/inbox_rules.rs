@@ -0,0 +1,328 @@
+use crate::import;
+use crate::organize::OrganizeReport;
+use crate::settings;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Setting holding the list of `InboxRule`s
+pub const INBOX_RULES_SETTING: &str = "inbox.rules";
+
+/// Fields a rule can require, matching what the inbox columns show
+pub const RULE_FIELDS: [&str; 10] = [
+    "title",
+    "artist",
+    "album",
+    "album_artist",
+    "year",
+    "genre",
+    "track_number",
+    "bpm",
+    "key",
+    "cover",
+];
+
+/// Accepts a staged track when every condition holds. A rule without
+/// conditions is rejected on save, so an empty rule cannot accept everything.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct InboxRule {
+    pub name: String,
+    pub enabled: bool,
+    /// Entries of `RULE_FIELDS` that must be filled in
+    pub require: Vec<String>,
+    /// Only tracks whose file is inside this folder
+    pub folder: Option<String>,
+}
+
+impl Default for InboxRule {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            enabled: true,
+            require: Vec::new(),
+            folder: None,
+        }
+    }
+}
+
+/// What the rules look at for one track
+struct TrackFacts {
+    source_path: String,
+    filled: Vec<&'static str>,
+}
+
+impl InboxRule {
+    fn matches(&self, facts: &TrackFacts) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if let Some(folder) = self.folder.as_deref().filter(|folder| !folder.is_empty()) {
+            if !Path::new(&facts.source_path).starts_with(folder) {
+                return false;
+            }
+        }
+        self.require
+            .iter()
+            .all(|field| facts.filled.contains(&field.as_str()))
+    }
+}
+
+pub fn load_rules(conn: &Connection) -> Result<Vec<InboxRule>, String> {
+    Ok(settings::get_setting(conn, INBOX_RULES_SETTING)?.unwrap_or_default())
+}
+
+pub fn save_rules(conn: &Connection, rules: &[InboxRule]) -> Result<(), String> {
+    for rule in rules {
+        if let Some(field) = rule
+            .require
+            .iter()
+            .find(|field| !RULE_FIELDS.contains(&field.as_str()))
+        {
+            return Err(format!(
+                "Unknown field \"{}\" in rule \"{}\"",
+                field, rule.name
+            ));
+        }
+        let has_folder = rule
+            .folder
+            .as_deref()
+            .is_some_and(|folder| !folder.trim().is_empty());
+        if rule.require.is_empty() && !has_folder {
+            return Err(format!("Rule \"{}\" has no conditions", rule.name));
+        }
+    }
+    settings::set_setting(conn, INBOX_RULES_SETTING, &rules)
+}
+
+/// Accept the staged track if one of `rules` matches it. Returns whether it
+/// was accepted.
+pub(crate) fn apply_to_track(
+    conn: &Connection,
+    rules: &[InboxRule],
+    track_id: &str,
+) -> Result<bool, String> {
+    if !rules.iter().any(|rule| rule.enabled) {
+        return Ok(false);
+    }
+    let Some(facts) = track_facts(conn, track_id)? else {
+        return Ok(false);
+    };
+    if !rules.iter().any(|rule| rule.matches(&facts)) {
+        return Ok(false);
+    }
+
+    conn.execute(
+        "UPDATE tracks SET import_status = ?1 WHERE id = ?2 AND import_status = ?3",
+        (import::STATUS_ACCEPTED, track_id, import::STATUS_STAGED),
+    )
+    .map_err(|error| error.to_string())?;
+    Ok(conn.changes() > 0)
+}
+
+/// Tracks the inbox rules accepted and how filing them into the library went
+#[derive(Debug, Serialize)]
+pub struct InboxRulesReport {
+    pub accepted: Vec<String>,
+    /// None when organising is off
+    pub organize: Option<OrganizeReport>,
+}
+
+/// Run the rules over everything in the inbox and return the ids of the
+/// tracks they accepted
+pub fn apply_inbox_rules(db_path: &str) -> Result<Vec<String>, String> {
+    if !Path::new(db_path).exists() {
+        return Ok(Vec::new());
+    }
+    let mut conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    import::ensure_schema(&conn)?;
+    let rules = load_rules(&conn)?;
+
+    let staged: Vec<String> = {
+        let mut stmt = conn
+            .prepare("SELECT id FROM tracks WHERE import_status = ?1 ORDER BY added_at")
+            .map_err(|error| error.to_string())?;
+        let rows = stmt
+            .query_map([import::STATUS_STAGED], |row| row.get(0))
+            .map_err(|error| error.to_string())?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|error| error.to_string())?
+    };
+
+    let tx = conn.transaction().map_err(|error| error.to_string())?;
+    let mut accepted = Vec::new();
+    for track_id in staged {
+        if apply_to_track(&tx, &rules, &track_id)? {
+            accepted.push(track_id);
+        }
+    }
+    tx.commit().map_err(|error| error.to_string())?;
+
+    Ok(accepted)
+}
+
+/// Fields filled in from tags. Title, artist and album are stored with
+/// placeholders when the tags lack them, so those count as empty, and so
+/// does a title that is just the file name.
+fn track_facts(conn: &Connection, track_id: &str) -> Result<Option<TrackFacts>, String> {
+    conn.query_row(
+        "SELECT source_path, title, artist, album, album_artist, year, genre_json,
+                track_number, bpm, key, cover_art_path
+         FROM tracks WHERE id = ?1",
+        [track_id],
+        |row| {
+            let source_path: String = row.get::<_, Option<String>>(0)?.unwrap_or_default();
+            let text = |index: usize| -> rusqlite::Result<bool> {
+                Ok(row
+                    .get::<_, Option<String>>(index)?
+                    .is_some_and(|value| !value.trim().is_empty()))
+            };
+            let title: Option<String> = row.get(1)?;
+            let artist: Option<String> = row.get(2)?;
+            let album: Option<String> = row.get(3)?;
+            let genre_json: Option<String> = row.get(6)?;
+            let fallback = import::fallback_title(Path::new(&source_path));
+
+            let checks = [
+                (
+                    "title",
+                    title.is_some_and(|title| !title.trim().is_empty() && title != fallback),
+                ),
+                (
+                    "artist",
+                    artist.is_some_and(|artist| {
+                        !artist.trim().is_empty() && artist != import::UNKNOWN_ARTIST
+                    }),
+                ),
+                (
+                    "album",
+                    album.is_some_and(|album| {
+                        !album.trim().is_empty() && album != import::UNKNOWN_ALBUM
+                    }),
+                ),
+                ("album_artist", text(4)?),
+                ("year", row.get::<_, Option<i32>>(5)?.is_some()),
+                (
+                    "genre",
+                    genre_json.is_some_and(|json| !json.is_empty() && json.trim() != "[]"),
+                ),
+                ("track_number", row.get::<_, Option<i32>>(7)?.is_some()),
+                (
+                    "bpm",
+                    row.get::<_, Option<f64>>(8)?.is_some_and(|bpm| bpm > 0.0),
+                ),
+                ("key", text(9)?),
+                ("cover", text(10)?),
+            ];
+
+            Ok(TrackFacts {
+                source_path,
+                filled: checks
+                    .into_iter()
+                    .filter(|(_, filled)| *filled)
+                    .map(|(field, _)| field)
+                    .collect(),
+            })
+        },
+    )
+    .optional()
+    .map_err(|error| error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(require: &[&str], folder: Option<&str>) -> InboxRule {
+        InboxRule {
+            name: "rule".to_string(),
+            require: require.iter().map(|field| field.to_string()).collect(),
+            folder: folder.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn facts(source_path: &str, filled: &[&'static str]) -> TrackFacts {
+        TrackFacts {
+            source_path: source_path.to_string(),
+            filled: filled.to_vec(),
+        }
+    }
+
+    #[test]
+    fn rules_need_every_field_and_the_folder() {
+        let track = facts("/music/incoming/a.flac", &["title", "artist", "cover"]);
+        assert!(rule(&["title", "artist"], None).matches(&track));
+        assert!(!rule(&["title", "album"], None).matches(&track));
+        assert!(rule(&["cover"], Some("/music/incoming")).matches(&track));
+        assert!(rule(&[], Some("/music")).matches(&track));
+        // Folders compare whole path components
+        assert!(!rule(&["cover"], Some("/music/inc")).matches(&track));
+        assert!(!rule(&["cover"], Some("/other")).matches(&track));
+        // An empty folder means any folder
+        assert!(rule(&["title"], Some("")).matches(&track));
+
+        let mut disabled = rule(&["title"], None);
+        disabled.enabled = false;
+        assert!(!disabled.matches(&track));
+    }
+
+    #[test]
+    fn placeholders_do_not_count_as_filled() {
+        let conn = Connection::open_in_memory().unwrap();
+        import::ensure_schema(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO tracks (id, source_path, title, artist, album, album_artist, year,
+                                 genre_json, track_number, bpm, key, cover_art_path)
+             VALUES ('placeholders', '/music/03 - Song.mp3', 'Song', ?1, ?2, ' ', NULL,
+                     '[]', NULL, 0, '', NULL)",
+            [import::UNKNOWN_ARTIST, import::UNKNOWN_ALBUM],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO tracks (id, source_path, title, artist, album, album_artist, year,
+                                 genre_json, track_number, bpm, key, cover_art_path)
+             VALUES ('tagged', '/music/04 - Other.mp3', 'Real Title', 'Band', 'Record',
+                     'Band', 2001, '[\"Rock\"]', 3, 120.5, 'Am', '/covers/a.jpg')",
+            [],
+        )
+        .unwrap();
+
+        let empty = track_facts(&conn, "placeholders").unwrap().unwrap();
+        assert!(empty.filled.is_empty(), "{:?}", empty.filled);
+        let full = track_facts(&conn, "tagged").unwrap().unwrap();
+        assert_eq!(full.filled, RULE_FIELDS);
+        assert!(track_facts(&conn, "missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn rules_accept_only_staged_tracks() {
+        let conn = Connection::open_in_memory().unwrap();
+        import::ensure_schema(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO tracks (id, source_path, title, import_status)
+             VALUES ('staged', '/a/one.mp3', 'One', 'staged'),
+                    ('accepted', '/a/two.mp3', 'Two', 'accepted'),
+                    ('untitled', '/a/three.mp3', NULL, 'staged');",
+        )
+        .unwrap();
+        let rules = [rule(&["title"], None)];
+        assert!(apply_to_track(&conn, &rules, "staged").unwrap());
+        assert!(!apply_to_track(&conn, &rules, "accepted").unwrap());
+        assert!(!apply_to_track(&conn, &rules, "untitled").unwrap());
+        assert!(!apply_to_track(&conn, &[], "untitled").unwrap());
+    }
+
+    #[test]
+    fn saving_rejects_rules_that_accept_everything() {
+        let conn = Connection::open_in_memory().unwrap();
+        import::ensure_schema(&conn).unwrap();
+        assert!(save_rules(&conn, &[rule(&[], None)]).is_err());
+        assert!(save_rules(&conn, &[rule(&[], Some("  "))]).is_err());
+        assert!(save_rules(&conn, &[rule(&["mood"], None)]).is_err());
+
+        let rules = vec![rule(&["title", "cover"], None), rule(&[], Some("/music"))];
+        save_rules(&conn, &rules).unwrap();
+        assert_eq!(load_rules(&conn).unwrap(), rules);
+    }
+}
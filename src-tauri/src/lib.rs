@@ -1,15 +1,38 @@
+pub mod albums;
+pub mod analysis;
+pub mod ape;
+pub mod artists;
 pub mod backfill;
 pub mod cover_art;
+pub mod dj_collection;
+pub mod dsd;
+pub mod duplicates;
+pub mod fingerprint;
+pub mod formats;
+pub mod glob;
 pub mod import;
+pub mod inbox_rules;
+pub mod lyrics;
+pub mod opus;
+pub mod organize;
 pub mod playback;
+pub mod player_history;
+pub mod playlist_io;
+pub mod rekordbox;
+pub mod relink;
+pub mod rescan;
+pub mod rhythmbox;
 pub mod search;
+pub mod settings;
+pub mod strawberry;
+pub mod tag_guess;
+pub mod tag_write;
+#[cfg(test)]
+mod test_support;
+pub mod traktor;
+pub mod watcher;
+pub mod wavpack;
 
-use lofty::config::WriteOptions;
-use lofty::file::FileType;
-use lofty::picture::{MimeType, Picture, PictureType};
-use lofty::prelude::*;
-use lofty::probe::Probe;
-use lofty::tag::{ItemKey, ItemValue, Tag, TagItem, TagType};
 use playback::{AudioPlayer, CurrentTrack, PlaybackState, SeekModePreference};
 use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
@@ -18,6 +41,7 @@ use std::path::Path;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tauri::{Emitter, Manager, State, WindowEvent};
+use watcher::FolderWatcher;
 
 // Constants for import status values
 const STATUS_STAGED: &str = "staged";
@@ -25,13 +49,13 @@ const STATUS_ACCEPTED: &str = "accepted";
 const COVERS_DIR: &str = "covers";
 
 #[tauri::command(rename_all = "camelCase")]
-fn import_files(
+async fn import_files(
     app: tauri::AppHandle,
     paths: Vec<String>,
     db_path: String,
-) -> Result<Vec<import::ImportedTrack>, String> {
+) -> Result<import::ImportReport, String> {
     if paths.is_empty() {
-        return Ok(Vec::new());
+        return Ok(import::ImportReport::default());
     }
 
     // Resolve cover art cache directory
@@ -41,9 +65,68 @@ fn import_files(
         .map_err(|e| e.to_string())?
         .join(COVERS_DIR);
 
-    import::import_files_with_progress(paths, &db_path, &cache_dir, |progress| {
-        let _ = app.emit("muro://import-progress", progress);
+    let job_id = uuid::Uuid::new_v4().to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        let jobs = app.state::<import::ImportJobs>();
+        let cancel = jobs.register(&job_id);
+        let result =
+            import::run_import_job(&job_id, paths, &db_path, &cache_dir, &cancel, |progress| {
+                let _ = app.emit("muro://import-progress", progress);
+            });
+        jobs.finish(&job_id);
+        result
     })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+#[tauri::command(rename_all = "camelCase")]
+async fn resume_import_job(
+    app: tauri::AppHandle,
+    job_id: String,
+    db_path: String,
+) -> Result<import::ImportReport, String> {
+    let cache_dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| e.to_string())?
+        .join(COVERS_DIR);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let jobs = app.state::<import::ImportJobs>();
+        if jobs.is_active(&job_id) {
+            return Err("Import is already running".to_string());
+        }
+        let cancel = jobs.register(&job_id);
+        let result =
+            import::resume_import_job(&job_id, &db_path, &cache_dir, &cancel, |progress| {
+                let _ = app.emit("muro://import-progress", progress);
+            });
+        jobs.finish(&job_id);
+        result
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn cancel_import(jobs: State<import::ImportJobs>, job_id: String) -> bool {
+    jobs.cancel(&job_id)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn list_import_jobs(db_path: String) -> Result<Vec<import::ImportJobRow>, String> {
+    import::load_unfinished_import_jobs(&db_path)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn discard_import_job(db_path: String, job_id: String) -> Result<(), String> {
+    import::discard_import_job(&db_path, &job_id)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn load_last_import_report(db_path: String) -> Result<Option<import::ImportReportSummary>, String> {
+    import::load_last_import_report(&db_path)
 }
 
 #[tauri::command(rename_all = "camelCase")]
@@ -81,6 +164,111 @@ fn backfill_cover_art(app: tauri::AppHandle, db_path: String) -> Result<usize, S
     backfill::run_cover_art_backfill(&db_path, &cache_dir)
 }
 
+#[tauri::command(rename_all = "camelCase")]
+fn backfill_audio_properties(db_path: String) -> Result<usize, String> {
+    backfill::run_audio_properties_backfill(&db_path)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn backfill_artist_credits(db_path: String) -> Result<usize, String> {
+    backfill::run_artist_credits_backfill(&db_path)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn backfill_albums(db_path: String) -> Result<usize, String> {
+    backfill::run_album_backfill(&db_path)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn rescan_library(
+    app: tauri::AppHandle,
+    db_path: String,
+    roots: Vec<String>,
+) -> Result<rescan::RescanSummary, String> {
+    let cache_dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| e.to_string())?
+        .join(COVERS_DIR);
+    rescan::rescan_library(&db_path, &roots, &cache_dir)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn list_watched_folders(db_path: String) -> Result<Vec<watcher::WatchedFolder>, String> {
+    watcher::list_watched_folders(&db_path)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn add_watched_folder(
+    app: tauri::AppHandle,
+    folder_watcher: State<'_, FolderWatcher>,
+    db_path: String,
+    path: String,
+) -> Result<watcher::WatchedFolder, String> {
+    let folder = watcher::add_watched_folder(&db_path, &path)?;
+    if folder_watcher.is_running(&db_path) {
+        folder_watcher.watch_folder(&db_path, &folder.path)?;
+    } else {
+        // Starting picks up every registered folder, including this one
+        let cache_dir = app
+            .path()
+            .app_cache_dir()
+            .map_err(|e| e.to_string())?
+            .join(COVERS_DIR);
+        folder_watcher.start(app.clone(), db_path, cache_dir)?;
+    }
+    Ok(folder)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn remove_watched_folder(
+    folder_watcher: State<'_, FolderWatcher>,
+    db_path: String,
+    folder_id: String,
+) -> Result<(), String> {
+    if let Some(path) = watcher::remove_watched_folder(&db_path, &folder_id)? {
+        folder_watcher.unwatch_folder(&db_path, &path);
+    }
+    Ok(())
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn start_folder_watcher(
+    app: tauri::AppHandle,
+    folder_watcher: State<'_, FolderWatcher>,
+    db_path: String,
+) -> Result<(), String> {
+    let cache_dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| e.to_string())?
+        .join(COVERS_DIR);
+    folder_watcher.start(app.clone(), db_path, cache_dir)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn find_duplicates(db_path: String) -> Result<Vec<duplicates::DuplicateCluster>, String> {
+    duplicates::find_duplicates(&db_path)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn resolve_duplicates(
+    db_path: String,
+    keep_id: String,
+    remove_ids: Vec<String>,
+) -> Result<duplicates::DuplicateResolution, String> {
+    duplicates::resolve_duplicates(&db_path, &keep_id, &remove_ids)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn relink_missing_tracks(
+    db_path: String,
+    roots: Vec<String>,
+    dry_run: bool,
+) -> Result<relink::RelinkReport, String> {
+    relink::relink_missing_tracks(&db_path, &roots, dry_run)
+}
+
 // Playback commands
 #[tauri::command]
 fn playback_play_file(
@@ -93,7 +281,13 @@ fn playback_play_file(
     duration_hint: f64,
     cover_art_path: Option<String>,
     cover_art_thumb_path: Option<String>,
+    db_path: Option<String>,
 ) -> Result<(), String> {
+    // Lyrics are optional; a failed lookup should not stop playback
+    let lines = db_path
+        .and_then(|db_path| lyrics::load_lyrics(&db_path, &id).ok().flatten())
+        .map(|lyrics| lyrics.lines)
+        .unwrap_or_default();
     let track = CurrentTrack {
         id,
         title,
@@ -103,7 +297,7 @@ fn playback_play_file(
         cover_art_path,
         cover_art_thumb_path,
     };
-    player.play_file(track, duration_hint)
+    player.play_file(track, duration_hint, lines)
 }
 
 #[tauri::command]
@@ -190,6 +384,21 @@ fn update_track_analysis(
     Ok(())
 }
 
+/// Mono PCM for the analyzer, as WAV bytes, for formats the webview cannot
+/// decode
+#[tauri::command(rename_all = "camelCase")]
+async fn decode_for_analysis(
+    path: String,
+    max_seconds: f64,
+) -> Result<tauri::ipc::Response, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        analysis::decode_mono_wav(Path::new(&path), max_seconds)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+    .map(tauri::ipc::Response::new)
+}
+
 #[tauri::command(rename_all = "camelCase")]
 fn record_track_play(db_path: String, track_id: String) -> Result<(), String> {
     if !Path::new(&db_path).exists() {
@@ -319,140 +528,189 @@ fn remove_last_tracks_from_playlist(
 #[tauri::command(rename_all = "camelCase")]
 fn delete_playlist(db_path: String, playlist_id: String) -> Result<(), String> {
     let conn = Connection::open(&db_path).map_err(|error| error.to_string())?;
+    // Enables foreign keys so a folder's playlists are deleted with it
+    import::ensure_playlist_schema(&conn)?;
     conn.execute("DELETE FROM playlists WHERE id = ?1", [&playlist_id])
         .map_err(|error| error.to_string())?;
 
     Ok(())
 }
 
-/// Write metadata tags back to an audio file on disk.
-/// Non-fatal: the DB is the source of truth; file write failures are logged.
-fn write_tags_to_file(
-    source_path: &str,
-    updates: &HashMap<String, serde_json::Value>,
-    cover_art_full_path: Option<&str>,
-) -> Result<(), String> {
-    let path = Path::new(source_path);
-    if !path.exists() {
-        return Err(format!("File not found: {}", source_path));
-    }
+#[tauri::command(rename_all = "camelCase")]
+async fn import_playlist_file(
+    app: tauri::AppHandle,
+    db_path: String,
+    path: String,
+) -> Result<playlist_io::PlaylistImportReport, String> {
+    let cache_dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| e.to_string())?
+        .join(COVERS_DIR);
 
-    let mut tagged = Probe::open(path)
+    tauri::async_runtime::spawn_blocking(move || {
+        playlist_io::import_playlist(&db_path, &cache_dir, Path::new(&path))
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn export_playlist(
+    db_path: String,
+    playlist_id: String,
+    dest_path: String,
+    format: playlist_io::PlaylistFormat,
+    relative_paths: bool,
+) -> Result<usize, String> {
+    playlist_io::export_playlist(
+        &db_path,
+        &playlist_id,
+        Path::new(&dest_path),
+        format,
+        relative_paths,
+    )
+}
+
+#[tauri::command(rename_all = "camelCase")]
+async fn import_dj_collection(
+    app: tauri::AppHandle,
+    db_path: String,
+    path: String,
+) -> Result<dj_collection::CollectionImportReport, String> {
+    let cache_dir = app
+        .path()
+        .app_cache_dir()
         .map_err(|e| e.to_string())?
-        .read()
-        .map_err(|e| e.to_string())?;
+        .join(COVERS_DIR);
 
-    // Get or create the primary tag
-    let file_type = tagged.file_type();
-    if tagged.primary_tag().is_none() {
-        let tag_type = match file_type {
-            FileType::Mpeg | FileType::Aiff | FileType::Wav => TagType::Id3v2,
-            FileType::Flac => TagType::VorbisComments,
-            FileType::Mp4 => TagType::Mp4Ilst,
-            _ => TagType::Id3v2,
-        };
-        tagged.insert_tag(Tag::new(tag_type));
-    }
-    let tag = tagged.primary_tag_mut().unwrap();
+    tauri::async_runtime::spawn_blocking(move || {
+        dj_collection::import_collection_file(&db_path, &cache_dir, Path::new(&path))
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
 
-    for (key, value) in updates {
-        let text_value = if value.is_null() {
-            continue;
-        } else if let Some(s) = value.as_str() {
-            s.to_string()
-        } else {
-            value.to_string()
-        };
+#[tauri::command(rename_all = "camelCase")]
+fn export_rekordbox_xml(
+    db_path: String,
+    dest_path: String,
+) -> Result<rekordbox::RekordboxExportSummary, String> {
+    rekordbox::export_collection(&db_path, Path::new(&dest_path))
+}
 
-        match key.as_str() {
-            "title" => {
-                tag.insert(TagItem::new(ItemKey::TrackTitle, ItemValue::Text(text_value)));
-            }
-            "artist" => {
-                tag.insert(TagItem::new(ItemKey::TrackArtist, ItemValue::Text(text_value)));
-            }
-            "artists" => {
-                tag.insert(TagItem::new(ItemKey::AlbumArtist, ItemValue::Text(text_value)));
-            }
-            "album" => {
-                tag.insert(TagItem::new(ItemKey::AlbumTitle, ItemValue::Text(text_value)));
-            }
-            "trackNumber" => {
-                tag.insert(TagItem::new(ItemKey::TrackNumber, ItemValue::Text(text_value)));
-            }
-            "trackTotal" => {
-                tag.insert(TagItem::new(ItemKey::TrackTotal, ItemValue::Text(text_value)));
-            }
-            "discNumber" => {
-                tag.insert(TagItem::new(ItemKey::DiscNumber, ItemValue::Text(text_value)));
-            }
-            "discTotal" => {
-                tag.insert(TagItem::new(ItemKey::DiscTotal, ItemValue::Text(text_value)));
-            }
-            "year" => {
-                tag.insert(TagItem::new(ItemKey::Year, ItemValue::Text(text_value)));
-            }
-            "genre" => {
-                tag.insert(TagItem::new(ItemKey::Genre, ItemValue::Text(text_value)));
-            }
-            "comment" => {
-                tag.insert(TagItem::new(ItemKey::Comment, ItemValue::Text(text_value)));
-            }
-            "label" => {
-                tag.insert(TagItem::new(ItemKey::Label, ItemValue::Text(text_value)));
-            }
-            "bpm" => {
-                tag.insert(TagItem::new(ItemKey::Bpm, ItemValue::Text(text_value)));
-            }
-            "key" => {
-                tag.insert(TagItem::new(
-                    ItemKey::InitialKey,
-                    ItemValue::Text(text_value),
-                ));
-            }
-            "rating" => {
-                if file_type == FileType::Mpeg {
-                    // Write POPM frame for MP3 files
-                    if let Ok(rating_f) = text_value.parse::<f32>() {
-                        let byte = (rating_f * 51.0).round() as u8;
-                        let mut data = Vec::new();
-                        data.push(0); // empty email, null-terminated
-                        data.push(byte);
-                        data.extend_from_slice(&[0, 0, 0, 0]); // play counter
-                        tag.insert(TagItem::new(
-                            ItemKey::Popularimeter,
-                            ItemValue::Binary(data),
-                        ));
-                    }
-                } else {
-                    tag.insert(TagItem::new(
-                        ItemKey::Unknown(String::from("RATING")),
-                        ItemValue::Text(text_value),
-                    ));
-                }
-            }
-            _ => {} // Skip coverArtPath, coverArtThumbPath — handled below
-        }
-    }
+#[tauri::command(rename_all = "camelCase")]
+fn load_cue_points(
+    db_path: String,
+    track_id: String,
+) -> Result<Vec<dj_collection::StoredCuePoint>, String> {
+    dj_collection::load_cue_points(&db_path, &track_id)
+}
 
-    // Handle cover art
-    if let Some(cover_path) = cover_art_full_path {
-        let cover_bytes = std::fs::read(cover_path)
-            .map_err(|e| format!("Failed to read cover art: {}", e))?;
-        tag.remove_picture_type(PictureType::CoverFront);
-        tag.push_picture(Picture::new_unchecked(
-            PictureType::CoverFront,
-            Some(MimeType::Jpeg),
-            None,
-            cover_bytes,
-        ));
-    }
+#[tauri::command(rename_all = "camelCase")]
+async fn import_player_history(
+    db_path: String,
+    path: String,
+    policy: player_history::StatsPolicy,
+) -> Result<player_history::PlayerImportReport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        player_history::import_player_library(&db_path, Path::new(&path), policy)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
 
-    tagged
-        .save_to_path(path, WriteOptions::default())
-        .map_err(|e| format!("Failed to save tags: {}", e))?;
+#[tauri::command(rename_all = "camelCase")]
+fn preview_filename_tags(
+    db_path: String,
+    track_ids: Vec<String>,
+    patterns: Vec<String>,
+) -> Result<Vec<tag_guess::FilenameTagPreview>, String> {
+    tag_guess::preview_filename_tags(&db_path, &track_ids, &patterns)
+}
 
-    Ok(())
+#[tauri::command(rename_all = "camelCase")]
+fn apply_filename_tags(
+    db_path: String,
+    track_ids: Vec<String>,
+    patterns: Vec<String>,
+) -> Result<usize, String> {
+    tag_guess::apply_filename_tags(&db_path, &track_ids, &patterns)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn get_cover_art_patterns(db_path: String) -> Result<Vec<String>, String> {
+    let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+    cover_art::sidecar_patterns(&conn)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn set_cover_art_patterns(db_path: String, patterns: Vec<String>) -> Result<(), String> {
+    let patterns: Vec<String> = patterns
+        .into_iter()
+        .map(|pattern| pattern.trim().to_string())
+        .filter(|pattern| !pattern.is_empty())
+        .collect();
+    let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+    settings::set_setting(&conn, cover_art::SIDECAR_PATTERNS_SETTING, &patterns)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn get_scan_options(db_path: String) -> Result<import::ScanOptions, String> {
+    import::ScanOptions::load_from(&db_path)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn set_scan_options(db_path: String, options: import::ScanOptions) -> Result<(), String> {
+    let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+    settings::set_setting(&conn, import::SCAN_OPTIONS_SETTING, &options)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn list_artists(db_path: String) -> Result<Vec<artists::ArtistSummary>, String> {
+    artists::list_artists(&db_path)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn get_lyrics(db_path: String, track_id: String) -> Result<Option<lyrics::TrackLyrics>, String> {
+    lyrics::load_lyrics(&db_path, &track_id)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn save_lyrics(
+    db_path: String,
+    track_id: String,
+    text: String,
+) -> Result<Option<lyrics::TrackLyrics>, String> {
+    lyrics::save_lyrics(&db_path, &track_id, &text)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn write_lyrics_to_file(db_path: String, track_id: String) -> Result<(), String> {
+    lyrics::write_lyrics_to_file(&db_path, &track_id)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn load_albums(db_path: String) -> Result<Vec<albums::AlbumSummary>, String> {
+    albums::load_albums(&db_path)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn get_various_artists_name(db_path: String) -> Result<String, String> {
+    let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+    albums::various_artists_name(&conn)
+}
+
+/// Compilations store no album artist of their own, so renaming only
+/// changes what `load_albums` shows
+#[tauri::command(rename_all = "camelCase")]
+fn set_various_artists_name(db_path: String, name: String) -> Result<(), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Name cannot be empty".to_string());
+    }
+    let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+    settings::set_setting(&conn, albums::VARIOUS_ARTISTS_SETTING, &name)
 }
 
 #[tauri::command(rename_all = "camelCase")]
@@ -502,24 +760,23 @@ fn update_track_metadata(
             None => continue, // Skip unknown keys
         };
 
-        // Special handling for genre and comment: comma-separated → JSON array
+        // Genres are edited comma-separated, comments one per line
         if column == "genre_json" || column == "comment_json" {
-            let json_value = if let Some(s) = value.as_str() {
-                let items: Vec<String> = s
-                    .split(',')
-                    .map(|item| item.trim().to_string())
-                    .filter(|item| !item.is_empty())
-                    .collect();
-                serde_json::to_string(&items).unwrap_or_else(|_| "[]".to_string())
-            } else {
-                "[]".to_string()
-            };
+            let separator = if column == "genre_json" { ',' } else { '\n' };
+            let json_value = import::edited_list_json(value, separator);
             set_clauses.push(format!("{} = ?{}", column, param_index));
             params.push(Box::new(json_value));
             param_index += 1;
             continue;
         }
 
+        // A cover picked in the app is embedded into the file below
+        if column == "cover_art_path" {
+            set_clauses.push(format!("cover_art_source = ?{}", param_index));
+            params.push(Box::new((!value.is_null()).then_some("tag")));
+            param_index += 1;
+        }
+
         set_clauses.push(format!("{} = ?{}", column, param_index));
 
         // Convert JSON value to appropriate SQL type
@@ -569,6 +826,8 @@ fn update_track_metadata(
     }
 
     let mut conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+    import::ensure_schema(&conn)?;
+    let various_artists = albums::various_artists_name(&conn)?;
     let tx = conn.transaction().map_err(|e| e.to_string())?;
 
     let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
@@ -627,39 +886,135 @@ fn update_track_metadata(
             )
             .map_err(|e| e.to_string())?;
         }
+
+        // Re-credit only the roles the edited fields feed
+        let mut roles = Vec::new();
+        if updates.contains_key("artist") {
+            roles.extend([artists::ROLE_MAIN, artists::ROLE_FEATURED]);
+        }
+        if updates.contains_key("artists") {
+            roles.push(artists::ROLE_ALBUM_ARTIST);
+        }
+        if updates.contains_key("title") {
+            roles.push(artists::ROLE_REMIXER);
+        }
+        if !roles.is_empty() {
+            if let Some(credits) = artists::ArtistCredits::from_row(&tx, track_id)? {
+                artists::set_track_credits(&tx, track_id, &credits, &roles)?;
+            }
+        }
+        if ["album", "artists", "artist", "year"]
+            .iter()
+            .any(|key| updates.contains_key(*key))
+        {
+            albums::assign_album(&tx, track_id, &various_artists)?;
+        }
     }
 
     tx.commit().map_err(|e| e.to_string())?;
 
     // Write tags back to audio files (non-fatal — DB is source of truth)
-    let cover_art_full_path = updates
-        .get("coverArtPath")
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
-
-    let conn_for_paths = Connection::open(&db_path).map_err(|e| e.to_string())?;
+    let keys: Vec<String> = updates.keys().cloned().collect();
     for track_id in &track_ids {
-        let source_path: Option<String> = conn_for_paths
-            .query_row(
-                "SELECT source_path FROM tracks WHERE id = ?1",
-                [track_id],
-                |row| row.get(0),
-            )
-            .ok();
-
-        if let Some(ref path) = source_path {
-            if let Err(e) = write_tags_to_file(path, &updates, cover_art_full_path.as_deref()) {
-                eprintln!(
-                    "Warning: failed to write tags to file '{}': {}",
-                    path, e
-                );
-            }
+        if let Err(e) = tag_write::sync_tags_to_file(&conn, track_id, &keys) {
+            eprintln!(
+                "Warning: failed to write tags for track '{}': {}",
+                track_id, e
+            );
         }
     }
 
     Ok(())
 }
 
+/// A track whose file no longer matches the database
+#[derive(Debug, Serialize)]
+struct UnsyncedTrack {
+    track_id: String,
+    title: Option<String>,
+    artist: Option<String>,
+    source_path: Option<String>,
+    error: String,
+    pending_fields: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Default)]
+struct TagWriteRetryReport {
+    written: Vec<String>,
+    failed: Vec<UnsyncedTrack>,
+}
+
+fn load_unsynced_tracks(conn: &Connection) -> Result<Vec<UnsyncedTrack>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, title, artist, source_path, last_write_error, pending_write_json
+             FROM tracks WHERE last_write_error IS NOT NULL
+             ORDER BY updated_at DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            let pending_json: Option<String> = row.get(5)?;
+            Ok(UnsyncedTrack {
+                track_id: row.get(0)?,
+                title: row.get(1)?,
+                artist: row.get(2)?,
+                source_path: row.get(3)?,
+                error: row.get(4)?,
+                pending_fields: pending_json
+                    .and_then(|json| serde_json::from_str(&json).ok())
+                    .unwrap_or_default(),
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn list_unsynced_tracks(db_path: String) -> Result<Vec<UnsyncedTrack>, String> {
+    if !Path::new(&db_path).exists() {
+        return Ok(Vec::new());
+    }
+    let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+    import::ensure_schema(&conn)?;
+    load_unsynced_tracks(&conn)
+}
+
+/// Re-attempt the pending tag writes of `track_ids`, or of every unsynced
+/// track when None, e.g. after a read-only drive was remounted
+#[tauri::command(rename_all = "camelCase")]
+async fn retry_tag_writes(
+    db_path: String,
+    track_ids: Option<Vec<String>>,
+) -> Result<TagWriteRetryReport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        if !Path::new(&db_path).exists() {
+            return Ok(TagWriteRetryReport::default());
+        }
+        let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+        import::ensure_schema(&conn)?;
+
+        let wanted = |track: &UnsyncedTrack| {
+            track_ids
+                .as_ref()
+                .is_none_or(|ids| ids.contains(&track.track_id))
+        };
+        let mut report = TagWriteRetryReport::default();
+        for track in load_unsynced_tracks(&conn)?.into_iter().filter(wanted) {
+            if tag_write::sync_tags_to_file(&conn, &track.track_id, &[]).is_ok() {
+                report.written.push(track.track_id);
+            }
+        }
+        report.failed = load_unsynced_tracks(&conn)?
+            .into_iter()
+            .filter(wanted)
+            .collect();
+        Ok(report)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct CachedCoverResult {
     full_path: String,
@@ -718,12 +1073,25 @@ fn execute_bulk_track_operation(
 }
 
 #[tauri::command(rename_all = "camelCase")]
-fn accept_tracks(db_path: String, track_ids: Vec<String>) -> Result<(), String> {
-    execute_bulk_track_operation(
-        &db_path,
-        &track_ids,
-        &format!("UPDATE tracks SET import_status = '{}' WHERE id IN ({{}})", STATUS_ACCEPTED),
-    )
+async fn accept_tracks(
+    db_path: String,
+    track_ids: Vec<String>,
+) -> Result<Option<organize::OrganizeReport>, String> {
+    // Organising copies or moves files, so it runs off the main thread
+    tauri::async_runtime::spawn_blocking(move || {
+        execute_bulk_track_operation(
+            &db_path,
+            &track_ids,
+            &format!("UPDATE tracks SET import_status = '{}' WHERE id IN ({{}})", STATUS_ACCEPTED),
+        )?;
+        if track_ids.is_empty() {
+            return Ok(None);
+        }
+        let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+        organize::organize_accepted(&conn, &track_ids)
+    })
+    .await
+    .map_err(|error| error.to_string())?
 }
 
 #[tauri::command(rename_all = "camelCase")]
@@ -735,9 +1103,84 @@ fn unaccept_tracks(db_path: String, track_ids: Vec<String>) -> Result<(), String
     )
 }
 
+#[tauri::command(rename_all = "camelCase")]
+fn get_inbox_rules(db_path: String) -> Result<Vec<inbox_rules::InboxRule>, String> {
+    if !Path::new(&db_path).exists() {
+        return Ok(Vec::new());
+    }
+    let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+    inbox_rules::load_rules(&conn)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn set_inbox_rules(db_path: String, rules: Vec<inbox_rules::InboxRule>) -> Result<(), String> {
+    let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+    inbox_rules::save_rules(&conn, &rules)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+async fn apply_inbox_rules(db_path: String) -> Result<inbox_rules::InboxRulesReport, String> {
+    // Organising copies or moves files, so it runs off the main thread
+    tauri::async_runtime::spawn_blocking(move || {
+        let accepted = inbox_rules::apply_inbox_rules(&db_path)?;
+        let organize = if accepted.is_empty() {
+            None
+        } else {
+            let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+            organize::organize_accepted(&conn, &accepted)?
+        };
+        Ok(inbox_rules::InboxRulesReport { accepted, organize })
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn get_organize_options(db_path: String) -> Result<organize::OrganizeOptions, String> {
+    if !Path::new(&db_path).exists() {
+        return Ok(organize::OrganizeOptions::default());
+    }
+    let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+    organize::load_options(&conn)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn set_organize_options(db_path: String, options: organize::OrganizeOptions) -> Result<(), String> {
+    let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+    organize::save_options(&conn, &options)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+async fn organize_tracks(
+    db_path: String,
+    track_ids: Vec<String>,
+    dry_run: bool,
+) -> Result<organize::OrganizeReport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        organize::organize_tracks(&db_path, &track_ids, dry_run)
+    })
+    .await
+    .map_err(|error| error.to_string())?
+}
+
+#[tauri::command(rename_all = "camelCase")]
+async fn undo_last_organize(db_path: String) -> Result<organize::OrganizeReport, String> {
+    tauri::async_runtime::spawn_blocking(move || organize::undo_last_organize(&db_path))
+        .await
+        .map_err(|error| error.to_string())?
+}
+
 #[tauri::command(rename_all = "camelCase")]
 fn reject_tracks(db_path: String, track_ids: Vec<String>) -> Result<(), String> {
-    execute_bulk_track_operation(&db_path, &track_ids, "DELETE FROM tracks WHERE id IN ({})")
+    execute_bulk_track_operation(&db_path, &track_ids, "DELETE FROM tracks WHERE id IN ({})")?;
+    if track_ids.is_empty() {
+        return Ok(());
+    }
+
+    let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+    import::ensure_schema(&conn)?;
+    artists::remove_orphaned_credits(&conn)?;
+    lyrics::remove_orphaned_lyrics(&conn)
 }
 
 #[derive(Clone, Serialize)]
@@ -761,6 +1204,8 @@ pub fn run() {
         .plugin(tauri_plugin_sql::Builder::default().build())
         .plugin(tauri_plugin_opener::init())
         .manage(audio_player.clone())
+        .manage(FolderWatcher::new())
+        .manage(import::ImportJobs::default())
         .setup(move |app| {
             // Initialize audio player with app handle
             audio_player.init(app.handle().clone());
@@ -796,12 +1241,47 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             import_files,
+            resume_import_job,
+            cancel_import,
+            list_import_jobs,
+            discard_import_job,
+            load_last_import_report,
             backfill_search_text,
             backfill_cover_art,
+            backfill_audio_properties,
+            backfill_artist_credits,
+            backfill_albums,
+            get_cover_art_patterns,
+            set_cover_art_patterns,
+            get_scan_options,
+            set_scan_options,
+            list_artists,
+            load_albums,
+            get_various_artists_name,
+            set_various_artists_name,
+            get_lyrics,
+            save_lyrics,
+            write_lyrics_to_file,
+            rescan_library,
+            list_watched_folders,
+            add_watched_folder,
+            remove_watched_folder,
+            start_folder_watcher,
+            relink_missing_tracks,
+            find_duplicates,
+            resolve_duplicates,
             create_playlist,
             delete_playlist,
             add_tracks_to_playlist,
             remove_last_tracks_from_playlist,
+            import_playlist_file,
+            export_playlist,
+            import_dj_collection,
+            export_rekordbox_xml,
+            load_cue_points,
+            import_player_history,
+            preview_filename_tags,
+            apply_filename_tags,
             load_tracks,
             load_playlists,
             load_recently_played,
@@ -809,6 +1289,13 @@ pub fn run() {
             accept_tracks,
             unaccept_tracks,
             reject_tracks,
+            get_inbox_rules,
+            set_inbox_rules,
+            apply_inbox_rules,
+            get_organize_options,
+            set_organize_options,
+            organize_tracks,
+            undo_last_organize,
             playback_play_file,
             playback_toggle,
             playback_play,
@@ -821,7 +1308,10 @@ pub fn run() {
             playback_is_finished,
             get_track_source_path,
             update_track_analysis,
+            decode_for_analysis,
             update_track_metadata,
+            list_unsynced_tracks,
+            retry_tag_writes,
             cache_cover_art_from_file,
             record_track_play
         ])
@@ -0,0 +1,527 @@
+use crate::import;
+use crate::tag_write::{self, LyricsUpdate};
+use lofty::id3::v2::{FrameValue, Id3v2Tag, SynchronizedText, TimestampFormat};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where a track's lyrics came from, stored in `lyrics.source`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LyricsSource {
+    /// USLT/SYLT frame or LYRICS field
+    Tag,
+    /// `.lrc` file next to the audio file
+    Sidecar,
+    /// Typed in the app and not read back from the file since
+    User,
+}
+
+impl LyricsSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Tag => "tag",
+            Self::Sidecar => "sidecar",
+            Self::User => "user",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LyricLine {
+    pub time_ms: u32,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackLyrics {
+    pub track_id: String,
+    /// Plain text; for synced-only lyrics, the lines joined
+    pub text: String,
+    /// Timed lines in order, empty for unsynchronised lyrics
+    pub lines: Vec<LyricLine>,
+    pub source: String,
+}
+
+/// Lyrics read from an audio file and its sidecar during import
+#[derive(Debug, Clone)]
+pub(crate) struct FileLyrics {
+    text: String,
+    lines: Vec<LyricLine>,
+    source: LyricsSource,
+}
+
+/// Payload of `muro://lyric-line`, sent when the active synced line changes
+#[derive(Debug, Clone, Serialize)]
+pub struct LyricLineEvent {
+    pub track_id: String,
+    /// None before the first line starts
+    pub index: Option<usize>,
+    pub text: Option<String>,
+}
+
+pub fn ensure_lyrics_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS lyrics (
+            track_id TEXT PRIMARY KEY,
+            text TEXT NOT NULL,
+            synced_json TEXT,
+            source TEXT NOT NULL,
+            updated_at INTEGER NOT NULL
+        )",
+        [],
+    )
+    .map_err(|error| error.to_string())?;
+
+    Ok(())
+}
+
+/// Collect lyrics for `path` from its tag text, the lines of its SYLT frame
+/// (see `sylt_lines`) and a `.lrc` sidecar. Timed lines are taken from SYLT
+/// first, then the sidecar, then tag text written in LRC format.
+pub(crate) fn read_file_lyrics(
+    tag_text: Option<&str>,
+    sylt: Option<Vec<LyricLine>>,
+    path: &Path,
+) -> Option<FileLyrics> {
+    let tag_text = tag_text.map(str::trim).filter(|text| !text.is_empty());
+    let tag_lines = tag_text.map(parse_lrc).unwrap_or_default();
+
+    let (lines, source) = if let Some(lines) = sylt {
+        (lines, LyricsSource::Tag)
+    } else if let Some(lines) = sidecar_path(path)
+        .and_then(|sidecar| fs::read_to_string(sidecar).ok())
+        .map(|text| parse_lrc(&text))
+        .filter(|lines| !lines.is_empty())
+    {
+        (lines, LyricsSource::Sidecar)
+    } else {
+        (tag_lines.clone(), LyricsSource::Tag)
+    };
+
+    // LRC-formatted tag text is shown without its timestamps
+    let text = match tag_text {
+        Some(text) if tag_lines.is_empty() => text.to_string(),
+        _ => join_lines(&lines),
+    };
+    if text.is_empty() && lines.is_empty() {
+        return None;
+    }
+
+    Some(FileLyrics {
+        text,
+        lines,
+        source,
+    })
+}
+
+/// Store lyrics read from the file, or remove them when the file has none.
+/// Lyrics edited in the app and not yet written back are kept.
+pub(crate) fn store_file_lyrics(
+    conn: &Connection,
+    track_id: &str,
+    lyrics: Option<&FileLyrics>,
+) -> Result<(), String> {
+    let current_source: Option<String> = conn
+        .query_row(
+            "SELECT source FROM lyrics WHERE track_id = ?1",
+            [track_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|error| error.to_string())?;
+    if current_source.as_deref() == Some(LyricsSource::User.as_str()) {
+        return Ok(());
+    }
+
+    match lyrics {
+        Some(lyrics) => save_row(conn, track_id, &lyrics.text, &lyrics.lines, lyrics.source),
+        None => conn
+            .execute("DELETE FROM lyrics WHERE track_id = ?1", [track_id])
+            .map(|_| ())
+            .map_err(|error| error.to_string()),
+    }
+}
+
+/// Drop lyrics of tracks that were removed
+pub fn remove_orphaned_lyrics(conn: &Connection) -> Result<(), String> {
+    ensure_lyrics_schema(conn)?;
+    conn.execute(
+        "DELETE FROM lyrics WHERE track_id NOT IN (SELECT id FROM tracks)",
+        [],
+    )
+    .map(|_| ())
+    .map_err(|error| error.to_string())
+}
+
+pub fn load_lyrics(db_path: &str, track_id: &str) -> Result<Option<TrackLyrics>, String> {
+    if !Path::new(db_path).exists() {
+        return Ok(None);
+    }
+    let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    ensure_lyrics_schema(&conn)?;
+    load_row(&conn, track_id)
+}
+
+/// Replace a track's lyrics with `text` typed in the app. Text in LRC format
+/// becomes synced lyrics; empty text removes them.
+pub fn save_lyrics(
+    db_path: &str,
+    track_id: &str,
+    text: &str,
+) -> Result<Option<TrackLyrics>, String> {
+    let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    ensure_lyrics_schema(&conn)?;
+
+    let text = text.trim();
+    if text.is_empty() {
+        conn.execute("DELETE FROM lyrics WHERE track_id = ?1", [track_id])
+            .map_err(|error| error.to_string())?;
+        return Ok(None);
+    }
+
+    let lines = parse_lrc(text);
+    let plain = if lines.is_empty() {
+        text.to_string()
+    } else {
+        join_lines(&lines)
+    };
+    save_row(&conn, track_id, &plain, &lines, LyricsSource::User)?;
+    load_row(&conn, track_id)
+}
+
+/// Write the stored lyrics into the file's tag and refresh a `.lrc` sidecar if
+/// the file has one, removing it when the lyrics have no timed lines. ID3v2
+/// tags get synced lines as a SYLT frame next to the plain text; other formats
+/// keep them in LRC form. Afterwards the file is the source again, so rescans
+/// pick up later outside edits.
+pub fn write_lyrics_to_file(db_path: &str, track_id: &str) -> Result<(), String> {
+    let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    ensure_lyrics_schema(&conn)?;
+    let source_path: String = conn
+        .query_row(
+            "SELECT source_path FROM tracks WHERE id = ?1",
+            [track_id],
+            |row| row.get(0),
+        )
+        .map_err(|error| error.to_string())?;
+    let lyrics = load_row(&conn, track_id)?;
+    let path = Path::new(&source_path);
+    if !path.exists() {
+        return Err(format!("File not found: {}", source_path));
+    }
+
+    let update = match &lyrics {
+        Some(lyrics) => LyricsUpdate::Set {
+            text: &lyrics.text,
+            lines: &lyrics.lines,
+        },
+        None => LyricsUpdate::Remove,
+    };
+    tag_write::write_lyrics_to_file(&source_path, update)?;
+
+    // A sidecar left behind would bring its lines back on the next rescan
+    if let Some(sidecar) = sidecar_path(path) {
+        match &lyrics {
+            Some(lyrics) if !lyrics.lines.is_empty() => {
+                fs::write(sidecar, format_lrc(&lyrics.lines))
+            }
+            _ => fs::remove_file(sidecar),
+        }
+        .map_err(|error| error.to_string())?;
+    }
+
+    conn.execute(
+        "UPDATE lyrics SET source = ?1 WHERE track_id = ?2",
+        (LyricsSource::Tag.as_str(), track_id),
+    )
+    .map_err(|error| error.to_string())?;
+
+    Ok(())
+}
+
+/// Index of the line showing at `position_secs`: the last one that has
+/// started
+pub fn active_line(lines: &[LyricLine], position_secs: f64) -> Option<usize> {
+    let position_ms = (position_secs.max(0.0) * 1000.0) as u32;
+    lines
+        .partition_point(|line| line.time_ms <= position_ms)
+        .checked_sub(1)
+}
+
+/// Parse LRC text: lines with one or more `[mm:ss.xx]` stamps. `[offset:]`
+/// is applied; other ID tags and unstamped lines are ignored.
+pub fn parse_lrc(text: &str) -> Vec<LyricLine> {
+    let mut lines = Vec::new();
+    let mut offset_ms: i64 = 0;
+
+    for raw in text.lines() {
+        let mut rest = raw.trim();
+        let mut times = Vec::new();
+        while let Some(tag) = rest.strip_prefix('[') {
+            let Some(end) = tag.find(']') else {
+                break;
+            };
+            let content = &tag[..end];
+            if let Some(time) = parse_lrc_time(content) {
+                times.push(time);
+            } else if let Some(value) = content.strip_prefix("offset:") {
+                offset_ms = value.trim().parse().unwrap_or(0);
+            }
+            rest = &tag[end + 1..];
+        }
+        let text = rest.trim();
+        for time in times {
+            // A positive offset makes lyrics appear sooner
+            let time_ms = (time - offset_ms).clamp(0, u32::MAX as i64) as u32;
+            lines.push(LyricLine {
+                time_ms,
+                text: text.to_string(),
+            });
+        }
+    }
+
+    lines.sort_by_key(|line| line.time_ms);
+    lines
+}
+
+pub fn format_lrc(lines: &[LyricLine]) -> String {
+    lines
+        .iter()
+        .map(|line| {
+            let centis = line.time_ms / 10;
+            format!(
+                "[{:02}:{:02}.{:02}]{}",
+                centis / 6000,
+                centis / 100 % 60,
+                centis % 100,
+                line.text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `mm:ss`, `mm:ss.xx` or `mm:ss.xxx` in milliseconds
+fn parse_lrc_time(value: &str) -> Option<i64> {
+    let (minutes, seconds) = value.split_once(':')?;
+    let minutes: i64 = minutes.trim().parse().ok()?;
+    let (whole, fraction) = seconds.split_once(['.', ':']).unwrap_or((seconds, ""));
+    let whole: i64 = whole.parse().ok()?;
+    let fraction_ms = match fraction.len() {
+        0 => 0,
+        1..=3 if fraction.bytes().all(|byte| byte.is_ascii_digit()) => {
+            fraction.parse::<i64>().ok()? * 10_i64.pow(3 - fraction.len() as u32)
+        }
+        _ => return None,
+    };
+    Some(minutes * 60_000 + whole * 1000 + fraction_ms)
+}
+
+fn join_lines(lines: &[LyricLine]) -> String {
+    lines
+        .iter()
+        .map(|line| line.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// `song.lrc` next to `song.mp3`, in either case
+pub(crate) fn sidecar_path(path: &Path) -> Option<PathBuf> {
+    ["lrc", "LRC"]
+        .iter()
+        .map(|extension| path.with_extension(extension))
+        .find(|candidate| candidate.is_file())
+}
+
+fn save_row(
+    conn: &Connection,
+    track_id: &str,
+    text: &str,
+    lines: &[LyricLine],
+    source: LyricsSource,
+) -> Result<(), String> {
+    let synced_json = if lines.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_string(lines).map_err(|error| error.to_string())?)
+    };
+    conn.execute(
+        "INSERT INTO lyrics (track_id, text, synced_json, source, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(track_id) DO UPDATE SET
+            text = excluded.text,
+            synced_json = excluded.synced_json,
+            source = excluded.source,
+            updated_at = excluded.updated_at",
+        (
+            track_id,
+            text,
+            synced_json,
+            source.as_str(),
+            import::current_timestamp(),
+        ),
+    )
+    .map_err(|error| error.to_string())?;
+
+    Ok(())
+}
+
+fn load_row(conn: &Connection, track_id: &str) -> Result<Option<TrackLyrics>, String> {
+    conn.query_row(
+        "SELECT text, synced_json, source FROM lyrics WHERE track_id = ?1",
+        [track_id],
+        |row| {
+            let synced_json: Option<String> = row.get(1)?;
+            Ok(TrackLyrics {
+                track_id: track_id.to_string(),
+                text: row.get(0)?,
+                lines: synced_json
+                    .and_then(|json| serde_json::from_str(&json).ok())
+                    .unwrap_or_default(),
+                source: row.get(2)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(|error| error.to_string())
+}
+
+/// Timed lines from the first millisecond-timed SYLT frame of an ID3v2 tag.
+/// lofty keeps SYLT frames as raw bytes, so they are parsed here.
+pub(crate) fn sylt_lines(tag: &Id3v2Tag) -> Option<Vec<LyricLine>> {
+    tag.into_iter()
+        .filter(|frame| frame.id_str() == "SYLT")
+        .filter_map(|frame| match frame.content() {
+            FrameValue::Binary(bytes) => SynchronizedText::parse(bytes).ok(),
+            _ => None,
+        })
+        .filter(|sylt| sylt.timestamp_format == TimestampFormat::MS)
+        .map(|sylt| {
+            let mut lines: Vec<LyricLine> = sylt
+                .content
+                .into_iter()
+                .map(|(time_ms, text)| LyricLine {
+                    time_ms,
+                    text: text.trim().to_string(),
+                })
+                .collect();
+            lines.sort_by_key(|line| line.time_ms);
+            lines
+        })
+        .find(|lines| !lines.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    #[test]
+    fn parses_lrc_with_repeated_stamps_and_offset() {
+        let lines =
+            parse_lrc("[ar:Someone]\n[offset:500]\n[00:12.00][01:02.5]Chorus\n[00:05.123]Verse\n");
+        assert_eq!(
+            lines,
+            [
+                LyricLine {
+                    time_ms: 4623,
+                    text: "Verse".to_string()
+                },
+                LyricLine {
+                    time_ms: 11500,
+                    text: "Chorus".to_string()
+                },
+                LyricLine {
+                    time_ms: 62000,
+                    text: "Chorus".to_string()
+                },
+            ]
+        );
+        assert_eq!(active_line(&lines, 3.0), None);
+        assert_eq!(active_line(&lines, 11.5), Some(1));
+        assert_eq!(format_lrc(&lines[..1]), "[00:04.62]Verse");
+    }
+
+    #[test]
+    fn sidecar_follows_written_lyrics_and_goes_when_they_have_no_timing() {
+        let dir = std::env::temp_dir().join(format!("muro-lyrics-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("track.wav");
+        fs::write(&path, test_support::wav_bytes()).unwrap();
+        let sidecar = dir.join("track.lrc");
+        let db_path = dir.join("library.db").to_string_lossy().to_string();
+        let conn = Connection::open(&db_path).unwrap();
+        import::ensure_schema(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO tracks (id, title, source_path, added_at, updated_at, import_status)
+             VALUES ('track', 'Track', ?1, 0, 0, 'accepted')",
+            [path.to_str().unwrap()],
+        )
+        .unwrap();
+
+        fs::write(&sidecar, "[00:01.00]Old line").unwrap();
+        save_lyrics(&db_path, "track", "[00:02.00]New line").unwrap();
+        write_lyrics_to_file(&db_path, "track").unwrap();
+        assert_eq!(fs::read_to_string(&sidecar).unwrap(), "[00:02.00]New line");
+
+        save_lyrics(&db_path, "track", "Plain words").unwrap();
+        write_lyrics_to_file(&db_path, "track").unwrap();
+        assert!(!sidecar.exists());
+        let lyrics = read_file_lyrics(Some("Plain words"), None, &path).unwrap();
+        assert_eq!(lyrics.text, "Plain words");
+        assert!(lyrics.lines.is_empty());
+
+        fs::write(&sidecar, "[00:01.00]Old line").unwrap();
+        save_lyrics(&db_path, "track", "").unwrap();
+        write_lyrics_to_file(&db_path, "track").unwrap();
+        assert!(!sidecar.exists());
+        assert!(read_file_lyrics(None, None, &path).is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn removing_tracks_drops_their_lyrics() {
+        let conn = Connection::open_in_memory().unwrap();
+        import::ensure_schema(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO tracks (id, title, added_at, updated_at, import_status)
+             VALUES ('kept', 'Kept', 0, 0, 'accepted')",
+            [],
+        )
+        .unwrap();
+        for track_id in ["kept", "rejected"] {
+            save_row(&conn, track_id, "Words", &[], LyricsSource::Tag).unwrap();
+        }
+
+        remove_orphaned_lyrics(&conn).unwrap();
+        assert!(load_row(&conn, "kept").unwrap().is_some());
+        assert!(load_row(&conn, "rejected").unwrap().is_none());
+    }
+
+    #[test]
+    fn parses_sylt_frame() {
+        use lofty::id3::v2::{Frame, FrameFlags};
+
+        let mut body = vec![3, b'e', b'n', b'g', TimestampFormat::MS as u8, 1, 0];
+        for (text, time) in [("Second", 2000u32), ("First", 1000)] {
+            body.extend_from_slice(text.as_bytes());
+            body.push(0);
+            body.extend_from_slice(&time.to_be_bytes());
+        }
+        let mut tag = Id3v2Tag::new();
+        tag.insert(Frame::new("SYLT", FrameValue::Binary(body), FrameFlags::default()).unwrap());
+        let lines = sylt_lines(&tag).unwrap();
+        assert_eq!(
+            lines[0],
+            LyricLine {
+                time_ms: 1000,
+                text: "First".to_string()
+            }
+        );
+        assert_eq!(lines[1].text, "Second");
+    }
+}
@@ -0,0 +1,215 @@
+use audiopus::coder::{Decoder as LibopusDecoder, GenericCtl};
+use audiopus::packet::Packet as OpusPacket;
+use audiopus::{Channels as OpusChannels, MutSignals, SampleRate};
+use parking_lot::Mutex;
+use symphonia::core::audio::{
+    AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec,
+};
+use symphonia::core::codecs::{
+    CodecDescriptor, CodecParameters, Decoder, DecoderOptions, FinalizeResult, CODEC_TYPE_OPUS,
+};
+use symphonia::core::errors::{decode_error, unsupported_error, Result};
+use symphonia::core::formats::Packet;
+use symphonia::core::support_codec;
+
+/// Opus always decodes at 48 kHz
+const OPUS_RATE: u32 = 48_000;
+/// Longest possible Opus packet: 120 ms at 48 kHz
+const MAX_FRAMES_PER_PACKET: usize = 5760;
+/// Decoding this much audio before a seek target lets the decoder converge
+/// (RFC 7845, section 4.6)
+pub const SEEK_PREROLL_MS: u64 = 80;
+
+/// Symphonia decoder for Ogg Opus backed by libopus. The ogg demuxer hands over
+/// the OpusHead packet as extra data, which carries the pre-skip and output gain.
+pub struct OpusDecoder {
+    params: CodecParameters,
+    // libopus decoders are not Sync; symphonia requires it
+    decoder: Mutex<LibopusDecoder>,
+    channels: usize,
+    pre_skip: u64,
+    scratch: Vec<f32>,
+    buf: AudioBuffer<f32>,
+}
+
+struct OpusHead {
+    channel_count: usize,
+    pre_skip: u16,
+    output_gain: i16,
+    mapping_family: u8,
+}
+
+fn parse_head(data: &[u8]) -> Option<OpusHead> {
+    if data.len() < 19 || &data[..8] != b"OpusHead" {
+        return None;
+    }
+    Some(OpusHead {
+        channel_count: data[9] as usize,
+        pre_skip: u16::from_le_bytes([data[10], data[11]]),
+        output_gain: i16::from_le_bytes([data[16], data[17]]),
+        mapping_family: data[18],
+    })
+}
+
+/// Output layout of a mono or stereo stream. The buffer and the interleaved
+/// scratch space are both sized from the OpusHead channel count, so a layout
+/// from the container that disagrees with it is rejected.
+fn channel_layout(head: &OpusHead, declared: Option<Channels>) -> Result<Channels> {
+    let layout = match head.channel_count {
+        1 => Channels::FRONT_LEFT,
+        2 => Channels::FRONT_LEFT | Channels::FRONT_RIGHT,
+        _ => return unsupported_error("opus: multichannel streams are not supported"),
+    };
+    match declared {
+        Some(declared) if declared.count() != head.channel_count => {
+            decode_error("opus: channel count does not match OpusHead")
+        }
+        _ => Ok(layout),
+    }
+}
+
+/// Frames at the start of a decoded packet that fall inside the pre-skip.
+/// Timestamps include the pre-skip, so the samples to drop follow from the
+/// packet position both at the start and after a seek back to it.
+fn pre_skip_frames(pre_skip: u64, ts: u64, frames: usize) -> usize {
+    (pre_skip.saturating_sub(ts) as usize).min(frames)
+}
+
+impl Decoder for OpusDecoder {
+    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self> {
+        let Some(head) = params.extra_data.as_deref().and_then(parse_head) else {
+            return decode_error("opus: missing or invalid OpusHead");
+        };
+
+        // Streams with more than two channels need the multistream decoder
+        let opus_channels = match (head.mapping_family, head.channel_count) {
+            (0 | 1, 1) => OpusChannels::Mono,
+            (0 | 1, 2) => OpusChannels::Stereo,
+            _ => return unsupported_error("opus: multichannel streams are not supported"),
+        };
+        let channels = channel_layout(&head, params.channels)?;
+
+        let decoder = LibopusDecoder::new(SampleRate::Hz48000, opus_channels)
+            .or_else(|_| decode_error("opus: failed to create decoder"))?;
+        // Output gain is Q7.8 dB, the same unit libopus expects
+        decoder
+            .set_gain(i32::from(head.output_gain))
+            .or_else(|_| decode_error("opus: invalid output gain"))?;
+
+        let spec = SignalSpec::new(OPUS_RATE, channels);
+        Ok(Self {
+            params: params.clone(),
+            decoder: Mutex::new(decoder),
+            channels: head.channel_count,
+            pre_skip: u64::from(head.pre_skip),
+            scratch: vec![0.0; MAX_FRAMES_PER_PACKET * head.channel_count],
+            buf: AudioBuffer::new(MAX_FRAMES_PER_PACKET as u64, spec),
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        &[support_codec!(CODEC_TYPE_OPUS, "opus", "Opus")]
+    }
+
+    fn reset(&mut self) {
+        // The output gain survives a reset
+        let _ = self.decoder.get_mut().reset_state();
+    }
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>> {
+        self.buf.clear();
+
+        let input =
+            OpusPacket::try_from(packet.buf()).or_else(|_| decode_error("opus: empty packet"))?;
+        let output = MutSignals::try_from(&mut self.scratch[..])
+            .or_else(|_| decode_error("opus: invalid output buffer"))?;
+        let frames = self
+            .decoder
+            .lock()
+            .decode_float(Some(input), output, false)
+            .or_else(|_| decode_error("opus: corrupt packet"))?;
+
+        let skip = pre_skip_frames(self.pre_skip, packet.ts(), frames);
+        let kept = frames - skip;
+
+        self.buf.render_reserved(Some(kept));
+        for channel in 0..self.channels {
+            let plane = self.buf.chan_mut(channel);
+            for (index, sample) in plane.iter_mut().enumerate() {
+                *sample = self.scratch[(skip + index) * self.channels + channel];
+            }
+        }
+
+        Ok(self.buf.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        FinalizeResult::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_> {
+        self.buf.as_audio_buffer_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn head_bytes(channel_count: u8, pre_skip: u16, output_gain: i16, family: u8) -> Vec<u8> {
+        let mut bytes = b"OpusHead".to_vec();
+        bytes.push(1);
+        bytes.push(channel_count);
+        bytes.extend_from_slice(&pre_skip.to_le_bytes());
+        bytes.extend_from_slice(&48_000u32.to_le_bytes());
+        bytes.extend_from_slice(&output_gain.to_le_bytes());
+        bytes.push(family);
+        bytes
+    }
+
+    #[test]
+    fn parses_opus_head_fields() {
+        let head = parse_head(&head_bytes(2, 312, -256, 0)).unwrap();
+        assert_eq!(head.channel_count, 2);
+        assert_eq!(head.pre_skip, 312);
+        assert_eq!(head.output_gain, -256);
+        assert_eq!(head.mapping_family, 0);
+    }
+
+    #[test]
+    fn rejects_short_or_foreign_heads() {
+        let bytes = head_bytes(2, 312, 0, 0);
+        assert!(parse_head(&bytes[..18]).is_none());
+
+        let mut tags = bytes.clone();
+        tags[..8].copy_from_slice(b"OpusTags");
+        assert!(parse_head(&tags).is_none());
+    }
+
+    #[test]
+    fn layout_follows_the_head_channel_count() {
+        let stereo = Channels::FRONT_LEFT | Channels::FRONT_RIGHT;
+        let head = parse_head(&head_bytes(2, 0, 0, 0)).unwrap();
+        assert_eq!(channel_layout(&head, None).unwrap(), stereo);
+        assert_eq!(channel_layout(&head, Some(stereo)).unwrap(), stereo);
+        assert!(channel_layout(&head, Some(Channels::FRONT_LEFT)).is_err());
+
+        let mono = parse_head(&head_bytes(1, 0, 0, 0)).unwrap();
+        assert_eq!(channel_layout(&mono, None).unwrap(), Channels::FRONT_LEFT);
+    }
+
+    #[test]
+    fn pre_skip_only_trims_the_first_packets() {
+        // 312 frames of pre-skip, 960-frame packets
+        assert_eq!(pre_skip_frames(312, 0, 960), 312);
+        assert_eq!(pre_skip_frames(312, 960, 960), 0);
+        // A pre-skip longer than one packet spills into the next
+        assert_eq!(pre_skip_frames(3840, 0, 960), 960);
+        assert_eq!(pre_skip_frames(3840, 2880, 960), 960);
+        assert_eq!(pre_skip_frames(3840, 3360, 960), 480);
+    }
+}
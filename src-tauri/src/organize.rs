@@ -0,0 +1,905 @@
+use crate::cover_art;
+use crate::formats;
+use crate::import;
+use crate::lyrics;
+use crate::settings;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Setting holding the `OrganizeOptions`
+pub const ORGANIZE_SETTING: &str = "organize.options";
+
+pub const DEFAULT_TEMPLATE: &str = "{album_artist}/{year} - {album}/{disc}{track:02} {title}.{ext}";
+
+/// Longest file or folder name written, in bytes. Most filesystems allow 255;
+/// the rest is left for a collision suffix.
+const MAX_COMPONENT_BYTES: usize = 200;
+
+/// `organize_log.kind` of a track's audio file and of a file moved with it
+const LOG_TRACK: &str = "track";
+const LOG_SIDECAR: &str = "sidecar";
+
+/// Names Windows refuses for files and folders, whatever the extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OrganizeMode {
+    Move,
+    Copy,
+}
+
+impl OrganizeMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            OrganizeMode::Move => "move",
+            OrganizeMode::Copy => "copy",
+        }
+    }
+}
+
+/// How accepted tracks are filed into the managed library folder
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct OrganizeOptions {
+    /// Organise tracks as they are accepted
+    pub enabled: bool,
+    pub library_root: String,
+    /// Path below `library_root`. Placeholders are `{title}`, `{artist}`,
+    /// `{album_artist}`, `{album}`, `{year}`, `{genre}`, `{label}`,
+    /// `{track}`, `{disc}` and `{ext}`; numbers take a width such as
+    /// `{track:02}`. `{disc}` renders as "2-" on multi-disc albums and
+    /// nothing otherwise.
+    pub template: String,
+    pub mode: OrganizeMode,
+}
+
+impl Default for OrganizeOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            library_root: String::new(),
+            template: DEFAULT_TEMPLATE.to_string(),
+            mode: OrganizeMode::Move,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct OrganizeMove {
+    pub track_id: String,
+    pub old_path: String,
+    pub new_path: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct OrganizeFailure {
+    pub track_id: String,
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct OrganizeReport {
+    pub dry_run: bool,
+    pub mode: OrganizeMode,
+    pub moved: Vec<OrganizeMove>,
+    /// Tracks already at their templated path
+    pub unchanged: usize,
+    pub failed: Vec<OrganizeFailure>,
+}
+
+impl OrganizeReport {
+    fn new(dry_run: bool, mode: OrganizeMode) -> Self {
+        Self {
+            dry_run,
+            mode,
+            moved: Vec::new(),
+            unchanged: 0,
+            failed: Vec::new(),
+        }
+    }
+}
+
+/// Tag values the template can use
+#[derive(Default)]
+struct TrackInfo {
+    id: String,
+    source_path: String,
+    title: Option<String>,
+    artist: Option<String>,
+    album_artist: Option<String>,
+    album: Option<String>,
+    year: Option<i32>,
+    genre: Option<String>,
+    label: Option<String>,
+    track_number: Option<i32>,
+    disc_number: Option<i32>,
+    disc_total: Option<i32>,
+}
+
+pub fn ensure_organize_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS organize_log (
+            position INTEGER NOT NULL,
+            track_id TEXT NOT NULL,
+            old_path TEXT NOT NULL,
+            new_path TEXT NOT NULL,
+            mode TEXT NOT NULL,
+            library_root TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            kind TEXT NOT NULL DEFAULT 'track'
+        )",
+        [],
+    )
+    .map_err(|error| error.to_string())?;
+    let _ = conn.execute(
+        "ALTER TABLE organize_log ADD COLUMN kind TEXT NOT NULL DEFAULT 'track'",
+        [],
+    );
+
+    Ok(())
+}
+
+pub fn load_options(conn: &Connection) -> Result<OrganizeOptions, String> {
+    Ok(settings::get_setting(conn, ORGANIZE_SETTING)?.unwrap_or_default())
+}
+
+pub fn save_options(conn: &Connection, options: &OrganizeOptions) -> Result<(), String> {
+    render_template(&options.template, &TrackInfo::default(), "flac")?;
+    if options.enabled && options.library_root.trim().is_empty() {
+        return Err("Choose a library folder before enabling organising".to_string());
+    }
+    settings::set_setting(conn, ORGANIZE_SETTING, options)
+}
+
+/// Move or copy the given tracks to their templated path below the library
+/// folder and point `source_path` at the new file. With `dry_run` nothing is
+/// touched and the report is the plan. A real run replaces the undo log.
+pub fn organize_tracks(
+    db_path: &str,
+    track_ids: &[String],
+    dry_run: bool,
+) -> Result<OrganizeReport, String> {
+    let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    import::ensure_schema(&conn)?;
+    let options = load_options(&conn)?;
+    if options.library_root.trim().is_empty() {
+        return Err("No library folder configured".to_string());
+    }
+    organize_with(&conn, &options, track_ids, dry_run)
+}
+
+/// Organise freshly accepted tracks when organising is enabled. Returns None
+/// when it is not.
+pub fn organize_accepted(
+    conn: &Connection,
+    track_ids: &[String],
+) -> Result<Option<OrganizeReport>, String> {
+    let options = load_options(conn)?;
+    if !options.enabled || options.library_root.trim().is_empty() || track_ids.is_empty() {
+        return Ok(None);
+    }
+    organize_with(conn, &options, track_ids, false).map(Some)
+}
+
+fn organize_with(
+    conn: &Connection,
+    options: &OrganizeOptions,
+    track_ids: &[String],
+    dry_run: bool,
+) -> Result<OrganizeReport, String> {
+    ensure_organize_schema(conn)?;
+    let root = PathBuf::from(options.library_root.trim());
+    let mut report = OrganizeReport::new(dry_run, options.mode);
+    let mut planned: HashSet<String> = HashSet::new();
+
+    for track_id in track_ids {
+        let Some(info) = load_track_info(conn, track_id)? else {
+            continue;
+        };
+        let source = PathBuf::from(&info.source_path);
+        if !source.exists() {
+            report.failed.push(failure(&info, "File not found"));
+            continue;
+        }
+        let ext = source
+            .extension()
+            .and_then(|value| value.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        let relative = render_template(&options.template, &info, &ext)?;
+        let target = root.join(relative);
+        let target = free_path(&source, &target, &planned);
+        planned.insert(path_key(&target));
+        if same_file(&source, &target) {
+            report.unchanged += 1;
+            continue;
+        }
+
+        report.moved.push(OrganizeMove {
+            track_id: info.id.clone(),
+            old_path: info.source_path.clone(),
+            new_path: target.to_string_lossy().to_string(),
+        });
+    }
+
+    if dry_run || report.moved.is_empty() {
+        return Ok(report);
+    }
+
+    conn.execute("DELETE FROM organize_log", [])
+        .map_err(|error| error.to_string())?;
+    let sidecar_patterns = cover_art::sidecar_patterns(conn)?;
+    let library_root = root.to_string_lossy().to_string();
+    let now = import::current_timestamp();
+    let mut position = 0;
+    let planned_moves = std::mem::take(&mut report.moved);
+    for planned_move in planned_moves {
+        let old_path = Path::new(&planned_move.old_path);
+        let new_path = Path::new(&planned_move.new_path);
+        if let Err(reason) = transfer(old_path, new_path, options.mode) {
+            report.failed.push(OrganizeFailure {
+                track_id: planned_move.track_id,
+                path: planned_move.old_path,
+                reason,
+            });
+            continue;
+        }
+        if let Err(reason) = update_source_path(conn, &planned_move.track_id, new_path) {
+            // Put the file back so the library does not point at nothing
+            let _ = undo_transfer(old_path, new_path, options.mode);
+            report.failed.push(OrganizeFailure {
+                track_id: planned_move.track_id,
+                path: planned_move.old_path,
+                reason,
+            });
+            continue;
+        }
+        let entry = LogEntry {
+            track_id: planned_move.track_id.clone(),
+            old_path: planned_move.old_path.clone(),
+            new_path: planned_move.new_path.clone(),
+            mode: options.mode,
+            library_root: library_root.clone(),
+            kind: LOG_TRACK.to_string(),
+        };
+        write_log(conn, &mut position, &entry, now)?;
+
+        for (from, to, mode) in
+            sidecar_transfers(old_path, new_path, options.mode, &sidecar_patterns)
+        {
+            if let Err(reason) = transfer(&from, &to, mode) {
+                report.failed.push(OrganizeFailure {
+                    track_id: planned_move.track_id.clone(),
+                    path: from.to_string_lossy().to_string(),
+                    reason,
+                });
+                continue;
+            }
+            let entry = LogEntry {
+                track_id: planned_move.track_id.clone(),
+                old_path: from.to_string_lossy().to_string(),
+                new_path: to.to_string_lossy().to_string(),
+                mode,
+                library_root: library_root.clone(),
+                kind: LOG_SIDECAR.to_string(),
+            };
+            write_log(conn, &mut position, &entry, now)?;
+        }
+        report.moved.push(planned_move);
+    }
+
+    Ok(report)
+}
+
+/// One file moved or copied by the last organise run
+struct LogEntry {
+    track_id: String,
+    old_path: String,
+    new_path: String,
+    mode: OrganizeMode,
+    library_root: String,
+    kind: String,
+}
+
+fn write_log(
+    conn: &Connection,
+    position: &mut i64,
+    entry: &LogEntry,
+    now: i64,
+) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO organize_log
+            (position, track_id, old_path, new_path, mode, library_root, created_at, kind)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            *position,
+            entry.track_id,
+            entry.old_path,
+            entry.new_path,
+            entry.mode.as_str(),
+            entry.library_root,
+            now,
+            entry.kind
+        ],
+    )
+    .map_err(|error| error.to_string())?;
+    *position += 1;
+    Ok(())
+}
+
+/// Files that belong with a track moved from `old_path` to `new_path`. An
+/// `.lrc` file takes the track's new name. A cover image is copied into the
+/// new folder while other audio is left in the old one, so the last track
+/// out takes it along.
+fn sidecar_transfers(
+    old_path: &Path,
+    new_path: &Path,
+    mode: OrganizeMode,
+    cover_patterns: &[String],
+) -> Vec<(PathBuf, PathBuf, OrganizeMode)> {
+    let mut transfers = Vec::new();
+    if let Some(lyrics) = lyrics::sidecar_path(old_path) {
+        let extension = lyrics.extension().unwrap_or_default().to_owned();
+        transfers.push((lyrics, new_path.with_extension(extension), mode));
+    }
+
+    let (Some(old_folder), Some(new_folder)) = (old_path.parent(), new_path.parent()) else {
+        return transfers;
+    };
+    if old_folder == new_folder {
+        return transfers;
+    }
+    if let Some(image) = cover_art::find_sidecar_image(old_path, cover_patterns) {
+        let target = new_folder.join(image.file_name().unwrap_or_default());
+        if !target.exists() {
+            let image_mode = if mode == OrganizeMode::Move && !has_audio_files(old_folder) {
+                OrganizeMode::Move
+            } else {
+                OrganizeMode::Copy
+            };
+            transfers.push((image, target, image_mode));
+        }
+    }
+    transfers
+}
+
+fn has_audio_files(folder: &Path) -> bool {
+    fs::read_dir(folder).is_ok_and(|entries| {
+        entries
+            .flatten()
+            .any(|entry| formats::is_audio_file(&entry.path()))
+    })
+}
+
+/// Put the files of the last organise run back where they came from. Copies
+/// are deleted, moved files are moved back and emptied folders below the
+/// library folder are removed.
+pub fn undo_last_organize(db_path: &str) -> Result<OrganizeReport, String> {
+    let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    ensure_organize_schema(&conn)?;
+
+    let entries: Vec<LogEntry> = {
+        let mut stmt = conn
+            .prepare(
+                "SELECT track_id, old_path, new_path, mode, library_root, kind
+                 FROM organize_log ORDER BY position DESC",
+            )
+            .map_err(|error| error.to_string())?;
+        let rows = stmt
+            .query_map([], |row| {
+                let mode: String = row.get(3)?;
+                Ok(LogEntry {
+                    track_id: row.get(0)?,
+                    old_path: row.get(1)?,
+                    new_path: row.get(2)?,
+                    mode: if mode == "copy" {
+                        OrganizeMode::Copy
+                    } else {
+                        OrganizeMode::Move
+                    },
+                    library_root: row.get(4)?,
+                    kind: row.get(5)?,
+                })
+            })
+            .map_err(|error| error.to_string())?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|error| error.to_string())?
+    };
+
+    let mode = entries
+        .iter()
+        .find(|entry| entry.kind == LOG_TRACK)
+        .map(|entry| entry.mode)
+        .unwrap_or(OrganizeMode::Move);
+    let mut report = OrganizeReport::new(false, mode);
+    for entry in entries {
+        let old_path = Path::new(&entry.old_path);
+        let new_path = Path::new(&entry.new_path);
+        if let Err(reason) = undo_transfer(old_path, new_path, entry.mode) {
+            report.failed.push(OrganizeFailure {
+                track_id: entry.track_id,
+                path: entry.new_path,
+                reason,
+            });
+            continue;
+        }
+        remove_empty_parents(new_path, Path::new(&entry.library_root));
+        if entry.kind != LOG_TRACK {
+            continue;
+        }
+        // The file is back; a failed update is reported and the rest undone
+        if let Err(reason) = update_source_path(&conn, &entry.track_id, old_path) {
+            report.failed.push(OrganizeFailure {
+                track_id: entry.track_id,
+                path: entry.old_path,
+                reason,
+            });
+            continue;
+        }
+        report.moved.push(OrganizeMove {
+            track_id: entry.track_id,
+            old_path: entry.new_path,
+            new_path: entry.old_path,
+        });
+    }
+
+    conn.execute("DELETE FROM organize_log", [])
+        .map_err(|error| error.to_string())?;
+    Ok(report)
+}
+
+fn failure(info: &TrackInfo, reason: &str) -> OrganizeFailure {
+    OrganizeFailure {
+        track_id: info.id.clone(),
+        path: info.source_path.clone(),
+        reason: reason.to_string(),
+    }
+}
+
+fn load_track_info(conn: &Connection, track_id: &str) -> Result<Option<TrackInfo>, String> {
+    conn.query_row(
+        "SELECT id, source_path, title, artist, album_artist, album, year, genre_json, label,
+                track_number, disc_number, disc_total
+         FROM tracks WHERE id = ?1",
+        [track_id],
+        |row| {
+            let genre_json: Option<String> = row.get(7)?;
+            Ok(TrackInfo {
+                id: row.get(0)?,
+                source_path: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                title: row.get(2)?,
+                artist: row.get(3)?,
+                album_artist: row.get(4)?,
+                album: row.get(5)?,
+                year: row.get(6)?,
+                genre: genre_json
+                    .and_then(|json| serde_json::from_str::<Vec<String>>(&json).ok())
+                    .and_then(|genres| genres.into_iter().next()),
+                label: row.get(8)?,
+                track_number: row.get(9)?,
+                disc_number: row.get(10)?,
+                disc_total: row.get(11)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(|error| error.to_string())
+}
+
+fn update_source_path(conn: &Connection, track_id: &str, path: &Path) -> Result<(), String> {
+    let filename = path
+        .file_name()
+        .and_then(|value| value.to_str())
+        .unwrap_or_default()
+        .to_string();
+    let signature = import::file_signature(path);
+    conn.execute(
+        "UPDATE tracks SET source_path = ?1, filename = ?2, is_missing = 0,
+            file_size = COALESCE(?3, file_size), file_mtime = COALESCE(?4, file_mtime)
+         WHERE id = ?5",
+        params![
+            path.to_string_lossy().to_string(),
+            filename,
+            signature.map(|(size, _)| size),
+            signature.map(|(_, modified)| modified),
+            track_id
+        ],
+    )
+    .map_err(|error| error.to_string())?;
+    Ok(())
+}
+
+/// Relative path for `info`, one sanitised component per `/` in the template.
+/// Folders that render empty are left out.
+fn render_template(template: &str, info: &TrackInfo, ext: &str) -> Result<PathBuf, String> {
+    let parts: Vec<&str> = template
+        .split(['/', '\\'])
+        .filter(|part| !part.trim().is_empty())
+        .collect();
+    let Some((file_part, folder_parts)) = parts.split_last() else {
+        return Err("The organise template is empty".to_string());
+    };
+
+    let mut path = PathBuf::new();
+    for part in folder_parts {
+        let rendered = render_part(part, info, ext)?;
+        let name = sanitize_component(&rendered);
+        if !name.is_empty() {
+            path.push(name);
+        }
+    }
+
+    let rendered = render_part(file_part, info, ext)?;
+    let (stem, extension) = match rendered.rsplit_once('.') {
+        Some((stem, extension)) if !extension.is_empty() && !extension.contains(' ') => {
+            (stem.to_string(), sanitize_component(extension))
+        }
+        _ => (rendered.clone(), String::new()),
+    };
+    let mut stem = sanitize_component(&stem);
+    if stem.is_empty() {
+        stem = sanitize_component(&import::fallback_title(Path::new(&info.source_path)));
+    }
+    let stem = truncate_bytes(
+        &stem,
+        MAX_COMPONENT_BYTES.saturating_sub(extension.len() + 1),
+    );
+    path.push(if extension.is_empty() {
+        stem.to_string()
+    } else {
+        format!("{}.{}", stem, extension)
+    });
+
+    Ok(path)
+}
+
+fn render_part(part: &str, info: &TrackInfo, ext: &str) -> Result<String, String> {
+    let mut output = String::new();
+    let mut rest = part;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let Some(length) = rest[start..].find('}') else {
+            return Err(format!("Unclosed placeholder in \"{}\"", part));
+        };
+        let placeholder = &rest[start + 1..start + length];
+        let (name, width) = match placeholder.split_once(':') {
+            Some((name, width)) => (
+                name.trim(),
+                Some(
+                    width
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|_| format!("Invalid width in {{{}}}", placeholder))?,
+                ),
+            ),
+            None => (placeholder.trim(), None),
+        };
+        let value = placeholder_value(name, info, ext, width)
+            .ok_or_else(|| format!("Unknown placeholder {{{}}}", name))?;
+        // A slash inside a tag must not create a folder
+        output.push_str(&value.replace(['/', '\\'], "_"));
+        rest = &rest[start + length + 1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn placeholder_value(
+    name: &str,
+    info: &TrackInfo,
+    ext: &str,
+    width: Option<usize>,
+) -> Option<String> {
+    let number = |value: Option<i32>| {
+        value
+            .filter(|value| *value > 0)
+            .map(|value| format!("{:0width$}", value, width = width.unwrap_or(0)))
+            .unwrap_or_default()
+    };
+    let text = |value: &Option<String>| {
+        value
+            .as_deref()
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+
+    let value = match name {
+        "title" => text(&info.title)
+            .unwrap_or_else(|| import::fallback_title(Path::new(&info.source_path))),
+        "artist" => text(&info.artist).unwrap_or_else(|| import::UNKNOWN_ARTIST.to_string()),
+        "album_artist" => text(&info.album_artist)
+            .or_else(|| text(&info.artist))
+            .unwrap_or_else(|| import::UNKNOWN_ARTIST.to_string()),
+        "album" => text(&info.album).unwrap_or_else(|| import::UNKNOWN_ALBUM.to_string()),
+        "genre" => text(&info.genre).unwrap_or_default(),
+        "label" => text(&info.label).unwrap_or_default(),
+        "year" => number(info.year),
+        "track" => number(info.track_number),
+        "disc" => {
+            let multi_disc = info.disc_total.is_some_and(|total| total > 1)
+                || info.disc_number.is_some_and(|disc| disc > 1);
+            if multi_disc && info.disc_number.is_some() {
+                format!("{}-", number(info.disc_number))
+            } else {
+                String::new()
+            }
+        }
+        "ext" => ext.to_string(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Replace characters that are illegal on common filesystems, drop the
+/// separators an empty placeholder leaves behind, and avoid reserved names
+fn sanitize_component(value: &str) -> String {
+    let replaced: String = value
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let collapsed = replaced.split_whitespace().collect::<Vec<_>>().join(" ");
+    let trimmed = collapsed
+        .trim_matches(|c: char| c == ' ' || c == '-')
+        .trim_end_matches('.')
+        .trim_end();
+    let trimmed = truncate_bytes(trimmed, MAX_COMPONENT_BYTES).trim_end_matches(['.', ' ']);
+
+    let stem = trimmed.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+    {
+        return format!("_{}", trimmed);
+    }
+    trimmed.to_string()
+}
+
+fn truncate_bytes(value: &str, max: usize) -> &str {
+    if value.len() <= max {
+        return value;
+    }
+    let mut end = max;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
+
+/// `target`, or the first of "name (2).ext", "name (3).ext", ... that is
+/// neither another file on disk nor already planned. A track already sitting
+/// at one of those names keeps it.
+fn free_path(source: &Path, target: &Path, planned: &HashSet<String>) -> PathBuf {
+    let taken = |path: &Path| {
+        (path.exists() && !same_file(source, path)) || planned.contains(&path_key(path))
+    };
+    if !taken(target) {
+        return target.to_path_buf();
+    }
+    let stem = target
+        .file_stem()
+        .and_then(|value| value.to_str())
+        .unwrap_or_default();
+    let extension = target.extension().and_then(|value| value.to_str());
+    let mut counter = 2;
+    loop {
+        let name = match extension {
+            Some(extension) => format!("{} ({}).{}", stem, counter, extension),
+            None => format!("{} ({})", stem, counter),
+        };
+        let candidate = target.with_file_name(name);
+        if !taken(&candidate) {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+/// Planned paths are compared without case so two tracks cannot collide on a
+/// case-insensitive filesystem
+fn path_key(path: &Path) -> String {
+    path.to_string_lossy().to_lowercase()
+}
+
+fn same_file(source: &Path, target: &Path) -> bool {
+    match (fs::canonicalize(source), fs::canonicalize(target)) {
+        (Ok(source), Ok(target)) => source == target,
+        _ => false,
+    }
+}
+
+fn transfer(from: &Path, to: &Path, mode: OrganizeMode) -> Result<(), String> {
+    if to.exists() {
+        return Err(format!("{} already exists", to.display()));
+    }
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+    }
+    match mode {
+        OrganizeMode::Copy => fs::copy(from, to)
+            .map(|_| ())
+            .map_err(|error| error.to_string()),
+        OrganizeMode::Move => move_file(from, to),
+    }
+}
+
+fn undo_transfer(original: &Path, organized: &Path, mode: OrganizeMode) -> Result<(), String> {
+    match mode {
+        OrganizeMode::Copy => {
+            // Never delete the only copy left
+            if !original.exists() {
+                return Err(format!("{} no longer exists", original.display()));
+            }
+            fs::remove_file(organized).map_err(|error| error.to_string())
+        }
+        OrganizeMode::Move => {
+            if original.exists() {
+                return Err(format!("{} already exists", original.display()));
+            }
+            if let Some(parent) = original.parent() {
+                fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+            }
+            move_file(organized, original)
+        }
+    }
+}
+
+/// Rename, falling back to copy and delete across filesystems
+fn move_file(from: &Path, to: &Path) -> Result<(), String> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to).map_err(|error| error.to_string())?;
+    if let Err(error) = fs::remove_file(from) {
+        let _ = fs::remove_file(to);
+        return Err(error.to_string());
+    }
+    Ok(())
+}
+
+fn remove_empty_parents(path: &Path, root: &Path) {
+    let mut current = path.parent();
+    while let Some(dir) = current {
+        if dir == root || !dir.starts_with(root) || fs::remove_dir(dir).is_err() {
+            break;
+        }
+        current = dir.parent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::wav_bytes;
+
+    fn track() -> TrackInfo {
+        TrackInfo {
+            source_path: "/inbox/01 song.flac".to_string(),
+            title: Some("Song: Part 1/2".to_string()),
+            artist: Some("Artist".to_string()),
+            album: Some("Album?".to_string()),
+            year: Some(1999),
+            track_number: Some(3),
+            disc_number: Some(2),
+            disc_total: Some(2),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn templates_render_sanitised_paths() {
+        let path = render_template(DEFAULT_TEMPLATE, &track(), "flac").unwrap();
+        assert_eq!(
+            path,
+            PathBuf::from("Artist/1999 - Album_/2-03 Song_ Part 1_2.flac")
+        );
+
+        // Empty placeholders do not leave separators or folders behind
+        let mut info = track();
+        info.year = None;
+        info.disc_total = Some(1);
+        info.disc_number = Some(1);
+        info.genre = None;
+        let path = render_template(
+            "{genre}/{year} - {album}/{disc}{track:02} {title}.{ext}",
+            &info,
+            "mp3",
+        )
+        .unwrap();
+        assert_eq!(path, PathBuf::from("Album_/03 Song_ Part 1_2.mp3"));
+
+        assert!(render_template("{artist}/{nope}.{ext}", &info, "mp3").is_err());
+        assert!(render_template("{track:xx}.{ext}", &info, "mp3").is_err());
+    }
+
+    #[test]
+    fn components_are_kept_short_and_legal() {
+        assert_eq!(sanitize_component("CON"), "_CON");
+        assert_eq!(sanitize_component("aux.txt"), "_aux.txt");
+        assert_eq!(sanitize_component("Name. "), "Name");
+
+        let mut info = track();
+        info.title = Some("é".repeat(300));
+        let path = render_template("{title}.{ext}", &info, "flac").unwrap();
+        let name = path.to_str().unwrap();
+        assert!(name.len() <= MAX_COMPONENT_BYTES);
+        assert!(name.ends_with("é.flac"));
+    }
+
+    #[test]
+    fn sidecars_move_with_their_tracks_and_undo_restores_them() {
+        let dir = std::env::temp_dir().join(format!("muro-organize-{}", uuid::Uuid::new_v4()));
+        let inbox = dir.join("inbox");
+        let library = dir.join("library");
+        fs::create_dir_all(&inbox).unwrap();
+        for name in ["one.wav", "two.wav"] {
+            fs::write(inbox.join(name), wav_bytes()).unwrap();
+        }
+        fs::write(inbox.join("one.lrc"), "[00:01.00]Hello").unwrap();
+        fs::write(inbox.join("cover.jpg"), "image").unwrap();
+
+        let db_path = dir.join("library.db");
+        let db = db_path.to_str().unwrap();
+        let conn = Connection::open(db).unwrap();
+        import::ensure_schema(&conn).unwrap();
+        for (id, album) in [("one", "First"), ("two", "Second")] {
+            conn.execute(
+                "INSERT INTO tracks (id, title, artist, album, source_path)
+                 VALUES (?1, ?1, 'Artist', ?2, ?3)",
+                params![id, album, inbox.join(format!("{}.wav", id)).to_str()],
+            )
+            .unwrap();
+        }
+        let options = OrganizeOptions {
+            enabled: false,
+            library_root: library.to_string_lossy().to_string(),
+            template: "{album}/{title}.{ext}".to_string(),
+            mode: OrganizeMode::Move,
+        };
+        save_options(&conn, &options).unwrap();
+
+        let ids = ["one".to_string(), "two".to_string()];
+        let report = organize_tracks(db, &ids, false).unwrap();
+        assert_eq!(report.moved.len(), 2);
+        assert!(report.failed.is_empty());
+        assert!(library.join("First/one.wav").is_file());
+        assert!(library.join("First/one.lrc").is_file());
+        // Copied while two.wav was still in the inbox, moved with it
+        assert!(library.join("First/cover.jpg").is_file());
+        assert!(library.join("Second/cover.jpg").is_file());
+        assert!(!inbox.join("one.lrc").exists());
+        assert!(!inbox.join("cover.jpg").exists());
+
+        let report = undo_last_organize(db).unwrap();
+        assert_eq!(report.moved.len(), 2);
+        assert!(report.failed.is_empty());
+        for name in ["one.wav", "two.wav", "one.lrc", "cover.jpg"] {
+            assert!(inbox.join(name).is_file(), "{} not restored", name);
+        }
+        assert!(!library.join("First").exists());
+        assert!(!library.join("Second").exists());
+        let source: String = conn
+            .query_row(
+                "SELECT source_path FROM tracks WHERE id = 'one'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(Path::new(&source), inbox.join("one.wav"));
+
+        drop(conn);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,3 +1,5 @@
+use crate::formats;
+use crate::lyrics::{self, LyricLine, LyricLineEvent};
 use parking_lot::Mutex;
 use rodio::{source::SeekError, OutputStream, OutputStreamHandle, Sink, Source};
 use serde::Serialize;
@@ -17,8 +19,7 @@ use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
 use symphonia::core::io::MediaSourceStream;
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
-use symphonia::core::units::Time;
-use symphonia::default::{get_codecs, get_probe};
+use symphonia::core::units::{Time, TimeBase};
 use tauri::{AppHandle, Emitter};
 
 /// Global media controls - stored globally since souvlaki requires it to stay alive
@@ -61,6 +62,7 @@ pub enum PlaybackCommand {
     PlayFile {
         track: CurrentTrack,
         duration_hint: f64,
+        lyrics: Vec<LyricLine>,
     },
     Toggle,
     Play,
@@ -110,6 +112,10 @@ struct SymphoniaSource {
     prebuffer_samples: usize,
     is_exhausted: bool,
     seek_mode: Arc<AtomicU8>,
+    time_base: Option<TimeBase>,
+    seek_preroll: Duration,
+    /// Decoded frames still to drop after a seek that landed early
+    discard_frames: u64,
 }
 
 impl SymphoniaSource {
@@ -125,7 +131,7 @@ impl SymphoniaSource {
             hint.with_extension(ext);
         }
 
-        let probed = get_probe()
+        let probed = formats::probe()
             .format(
                 &hint,
                 mss,
@@ -147,7 +153,7 @@ impl SymphoniaSource {
             )
         };
 
-        let mut decoder: Box<dyn Decoder + Send> = get_codecs()
+        let mut decoder: Box<dyn Decoder + Send> = formats::codecs()
             .make(&codec_params, &DecoderOptions::default())
             .map_err(|e| format!("Failed to create decoder: {}", e))?;
 
@@ -203,6 +209,9 @@ impl SymphoniaSource {
             prebuffer_samples,
             is_exhausted: false,
             seek_mode,
+            time_base: duration_time_base,
+            seek_preroll: formats::seek_preroll(codec_params.codec),
+            discard_frames: 0,
         };
 
         source.fill_prebuffer()?;
@@ -262,7 +271,16 @@ impl SymphoniaSource {
             let mut sample_buf =
                 SampleBuffer::<i16>::new(decoded.capacity() as u64, self.signal_spec);
             sample_buf.copy_interleaved_ref(decoded);
-            self.buffer.extend(sample_buf.samples());
+            let samples = sample_buf.samples();
+            let channels = usize::from(self.channels.max(1));
+            let skip = (self.discard_frames as usize)
+                .saturating_mul(channels)
+                .min(samples.len());
+            self.discard_frames -= (skip / channels) as u64;
+            if skip == samples.len() {
+                continue;
+            }
+            self.buffer.extend(&samples[skip..]);
             return Ok(true);
         }
     }
@@ -306,9 +324,14 @@ impl Source for SymphoniaSource {
     }
 
     fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
-        let time = Time::from(pos);
+        // Codecs that need pre-roll seek a little early, and readers may land
+        // on the frame holding the target (a Monkey's Audio frame runs to
+        // several seconds); either way the frames before the target are
+        // dropped once decoded
+        let time = Time::from(pos.saturating_sub(self.seek_preroll));
         let mode = self.current_seek_mode();
-        self.format
+        let seeked = self
+            .format
             .seek(
                 mode,
                 SeekTo::Time {
@@ -322,6 +345,12 @@ impl Source for SymphoniaSource {
                     format!("Seek failed: {}", err),
                 )))
             })?;
+        self.discard_frames = match self.time_base {
+            Some(time_base) => time_base
+                .calc_timestamp(Time::from(pos))
+                .saturating_sub(seeked.actual_ts),
+            None => 0,
+        };
         self.decoder.reset();
         self.buffer.clear();
         self.is_exhausted = false;
@@ -346,6 +375,10 @@ struct AudioThreadState {
     position_base: f64,
     /// Instant when playback started from position_base
     playback_started_at: Option<Instant>,
+    /// Synced lyrics of the current track
+    lyrics: Vec<LyricLine>,
+    /// Last emitted lyric line; None until the first emit for this track
+    lyric_index: Option<Option<usize>>,
 }
 
 impl Default for AudioThreadState {
@@ -358,6 +391,8 @@ impl Default for AudioThreadState {
             seek_mode: Arc::new(AtomicU8::new(DEFAULT_SEEK_MODE as u8)),
             position_base: 0.0,
             playback_started_at: None,
+            lyrics: Vec::new(),
+            lyric_index: None,
         }
     }
 }
@@ -376,6 +411,30 @@ impl AudioThreadState {
             self.position_base
         }
     }
+
+    /// Emit `muro://lyric-line` when the synced line at `position` differs
+    /// from the last one sent
+    fn emit_lyric_line(&mut self, app_handle: &AppHandle, position: f64) {
+        if self.lyrics.is_empty() {
+            return;
+        }
+        let Some(track) = self.state.current_track.as_ref() else {
+            return;
+        };
+        let index = lyrics::active_line(&self.lyrics, position);
+        if self.lyric_index == Some(index) {
+            return;
+        }
+        self.lyric_index = Some(index);
+        let _ = app_handle.emit(
+            "muro://lyric-line",
+            LyricLineEvent {
+                track_id: track.id.clone(),
+                index,
+                text: index.map(|index| self.lyrics[index].text.clone()),
+            },
+        );
+    }
 }
 
 /// The audio player that can be stored in Tauri state
@@ -455,10 +514,16 @@ impl AudioPlayer {
         }
     }
 
-    pub fn play_file(&self, track: CurrentTrack, duration_hint: f64) -> Result<(), String> {
+    pub fn play_file(
+        &self,
+        track: CurrentTrack,
+        duration_hint: f64,
+        lyrics: Vec<LyricLine>,
+    ) -> Result<(), String> {
         self.send_command(PlaybackCommand::PlayFile {
             track,
             duration_hint,
+            lyrics,
         });
         Ok(())
     }
@@ -555,6 +620,7 @@ fn run_audio_thread(
                     audio_state.state.current_position = pos;
                     update_shared_state(&shared_state, &audio_state.state);
                     let _ = app_handle.emit("muro://playback-position", pos);
+                    audio_state.emit_lyric_line(&app_handle, pos);
                     last_position_emit = Instant::now();
                 }
             }
@@ -583,6 +649,7 @@ fn process_command(
         PlaybackCommand::PlayFile {
             track,
             duration_hint,
+            lyrics,
         } => {
             let path = Path::new(&track.source_path);
             if !path.exists() {
@@ -628,6 +695,8 @@ fn process_command(
             audio_state.state.duration = duration_secs;
             audio_state.state.current_position = 0.0;
             audio_state.state.current_track = Some(track.clone());
+            audio_state.lyrics = lyrics;
+            audio_state.lyric_index = None;
 
             // Update media controls
             update_media_controls_metadata(&track, duration_secs);
@@ -697,6 +766,8 @@ fn process_command(
             audio_state.state.current_track = None;
             audio_state.position_base = 0.0;
             audio_state.playback_started_at = None;
+            audio_state.lyrics.clear();
+            audio_state.lyric_index = None;
 
             clear_media_controls();
             update_shared_state(shared_state, &audio_state.state);
@@ -725,6 +796,7 @@ fn process_command(
                     audio_state.state.current_position = target_position;
                     update_shared_state(shared_state, &audio_state.state);
                     let _ = app_handle.emit("muro://playback-state", audio_state.state.clone());
+                    audio_state.emit_lyric_line(app_handle, target_position);
                 } else {
                     eprintln!("Seek failed at {:.2}s", target_position);
                 }
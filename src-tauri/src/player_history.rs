@@ -0,0 +1,493 @@
+use crate::backfill;
+use crate::import;
+use crate::playlist_io::{self, UnresolvedEntry};
+use crate::rhythmbox;
+use crate::search;
+use crate::strawberry;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use uuid::Uuid;
+
+/// Seconds two durations may differ by and still describe the same recording
+const DURATION_TOLERANCE: f64 = 2.0;
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+/// A library entry as remembered by another music player
+#[derive(Debug, Default)]
+pub struct HistoryTrack {
+    /// `file://` URI or plain path; playlists refer to tracks by it
+    pub location: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub duration_seconds: Option<f64>,
+    /// 0-5 stars, like `tracks.rating`
+    pub rating: Option<f32>,
+    pub play_count: Option<i32>,
+    /// Unix seconds
+    pub last_played: Option<i64>,
+    /// Unix seconds
+    pub added_at: Option<i64>,
+}
+
+#[derive(Debug, Default)]
+pub struct PlayerLibrary {
+    pub tracks: Vec<HistoryTrack>,
+    /// Playlist names with the locations of their entries, in order
+    pub playlists: Vec<(String, Vec<String>)>,
+}
+
+/// What to do with tracks that already have a rating, play count or last
+/// played time in our library
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsPolicy {
+    /// Leave their stats alone; only tracks without any are filled in
+    Keep,
+    /// Replace them with the imported values
+    Overwrite,
+    /// Add play counts together and keep the latest play and the existing rating
+    Merge,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct PlayerImportReport {
+    pub source: String,
+    pub total: usize,
+    pub matched_by_path: usize,
+    /// Matched on normalised artist and title with a close duration
+    pub matched_by_tags: usize,
+    /// Tracks whose stats were changed
+    pub updated: usize,
+    /// Tracks that already had stats and were skipped under the "keep" policy.
+    /// Their date added may still move earlier; they are not counted as updated.
+    pub kept: usize,
+    pub playlists: usize,
+    pub unresolved: Vec<UnresolvedEntry>,
+}
+
+#[derive(Debug, Default)]
+struct TrackStats {
+    rating: Option<f32>,
+    play_count: i32,
+    last_played_at: Option<String>,
+    added_at: Option<i64>,
+}
+
+impl TrackStats {
+    fn is_empty(&self) -> bool {
+        self.rating.unwrap_or(0.0) <= 0.0 && self.play_count <= 0 && self.last_played_at.is_none()
+    }
+}
+
+struct LibraryEntry {
+    id: String,
+    artist: String,
+    duration_seconds: Option<f64>,
+}
+
+/// Import a Rhythmbox `rhythmdb.xml` (with the `playlists.xml` next to it) or
+/// a Strawberry/Clementine database
+pub fn import_player_library(
+    db_path: &str,
+    path: &Path,
+    policy: StatsPolicy,
+) -> Result<PlayerImportReport, String> {
+    let mut header = [0u8; 16];
+    let is_sqlite = std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok()
+        && header == SQLITE_HEADER;
+
+    if is_sqlite {
+        let (source, library) = strawberry::read_library(path)?;
+        return apply_player_library(db_path, source, library, policy);
+    }
+
+    let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+    if !text.contains("<rhythmdb") {
+        return Err("Not a Rhythmbox, Strawberry or Clementine library".to_string());
+    }
+    let mut library = rhythmbox::parse_database(&text)?;
+    let playlists_path = path.with_file_name("playlists.xml");
+    if let Ok(playlists) = std::fs::read_to_string(playlists_path) {
+        library.playlists = rhythmbox::parse_playlists(&playlists)?;
+    }
+    apply_player_library(db_path, "rhythmbox", library, policy)
+}
+
+/// Carry ratings, play counts, last played and date added over to the tracks
+/// they match. Unlike DJ collections nothing is imported: entries without a
+/// library track are reported as unresolved. Date added always takes the
+/// earlier of the two dates, since the other player saw the file first.
+/// Playlists are created anew with the entries that matched.
+fn apply_player_library(
+    db_path: &str,
+    source: &str,
+    library: PlayerLibrary,
+    policy: StatsPolicy,
+) -> Result<PlayerImportReport, String> {
+    let mut report = PlayerImportReport {
+        source: source.to_string(),
+        total: library.tracks.len(),
+        ..Default::default()
+    };
+
+    if !Path::new(db_path).exists() {
+        return Err("Database not found".to_string());
+    }
+    let mut conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    import::ensure_schema(&conn)?;
+    import::ensure_playlist_schema(&conn)?;
+
+    let by_title = load_library_index(&conn)?;
+    let mut track_ids: HashMap<&str, String> = HashMap::new();
+    let mut matched = Vec::new();
+    for track in &library.tracks {
+        let path_match = match playlist_io::resolve_location(&track.location, Path::new(""), true) {
+            Ok(path) => playlist_io::find_track_id(&conn, &path)?.ok_or("Not in the library"),
+            Err(reason) => Err(reason),
+        };
+        let track_id = match path_match {
+            Ok(track_id) => {
+                report.matched_by_path += 1;
+                track_id
+            }
+            Err(reason) => match match_by_tags(track, &by_title) {
+                Ok(track_id) => {
+                    report.matched_by_tags += 1;
+                    track_id
+                }
+                Err(tag_reason) => {
+                    let reason = tag_reason.unwrap_or(reason);
+                    report.unresolved.push(unresolved_entry(track, reason));
+                    continue;
+                }
+            },
+        };
+        track_ids.insert(track.location.as_str(), track_id.clone());
+        matched.push((track, track_id));
+    }
+
+    let now = import::current_timestamp();
+    let tx = conn.transaction().map_err(|error| error.to_string())?;
+    for (track, track_id) in &matched {
+        let existing = tx
+            .query_row(
+                "SELECT rating, play_count, last_played_at, added_at FROM tracks WHERE id = ?1",
+                [track_id],
+                |row| {
+                    Ok(TrackStats {
+                        rating: row.get(0)?,
+                        play_count: row.get::<_, Option<i32>>(1)?.unwrap_or(0),
+                        last_played_at: row.get(2)?,
+                        added_at: row.get(3)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|error| error.to_string())?
+            .unwrap_or_default();
+
+        let kept = policy == StatsPolicy::Keep && !existing.is_empty();
+        if kept {
+            report.kept += 1;
+        }
+        let stats = resolve_stats(policy, &existing, track);
+        if stats.rating == existing.rating
+            && stats.play_count == existing.play_count
+            && stats.last_played_at == existing.last_played_at
+            && stats.added_at == existing.added_at
+        {
+            continue;
+        }
+        tx.execute(
+            "UPDATE tracks SET rating = ?1, play_count = ?2, last_played_at = ?3, added_at = ?4,
+                updated_at = ?5
+             WHERE id = ?6",
+            rusqlite::params![
+                stats.rating,
+                stats.play_count,
+                stats.last_played_at,
+                stats.added_at,
+                now,
+                track_id
+            ],
+        )
+        .map_err(|error| error.to_string())?;
+        if !kept {
+            report.updated += 1;
+        }
+    }
+
+    for (name, locations) in &library.playlists {
+        let playlist_id = Uuid::new_v4().to_string();
+        tx.execute(
+            "INSERT INTO playlists (id, name, created_at) VALUES (?1, ?2, ?3)",
+            (&playlist_id, name, now),
+        )
+        .map_err(|error| error.to_string())?;
+        let mut position = 0i64;
+        for location in locations {
+            // Entries for tracks that could not be matched are dropped
+            let Some(track_id) = track_ids.get(location.as_str()) else {
+                continue;
+            };
+            tx.execute(
+                "INSERT INTO playlist_tracks (playlist_id, track_id, position) VALUES (?1, ?2, ?3)",
+                (&playlist_id, track_id, position),
+            )
+            .map_err(|error| error.to_string())?;
+            position += 1;
+        }
+        report.playlists += 1;
+    }
+    tx.commit().map_err(|error| error.to_string())?;
+
+    backfill::run_backfill(db_path)?;
+    Ok(report)
+}
+
+fn resolve_stats(policy: StatsPolicy, existing: &TrackStats, track: &HistoryTrack) -> TrackStats {
+    let rating = track.rating.filter(|rating| *rating > 0.0);
+    let play_count = track.play_count.filter(|count| *count > 0);
+    let last_played_at = track
+        .last_played
+        .filter(|value| *value > 0)
+        .map(import::format_timestamp);
+    let added_at = match (existing.added_at, track.added_at.filter(|value| *value > 0)) {
+        (Some(current), Some(imported)) => Some(current.min(imported)),
+        (current, imported) => current.or(imported),
+    };
+
+    match policy {
+        StatsPolicy::Keep if !existing.is_empty() => TrackStats {
+            rating: existing.rating,
+            play_count: existing.play_count,
+            last_played_at: existing.last_played_at.clone(),
+            added_at,
+        },
+        StatsPolicy::Keep | StatsPolicy::Overwrite => TrackStats {
+            rating: rating.or(existing.rating),
+            play_count: play_count.unwrap_or(existing.play_count),
+            last_played_at: last_played_at.or_else(|| existing.last_played_at.clone()),
+            added_at,
+        },
+        StatsPolicy::Merge => TrackStats {
+            rating: existing.rating.filter(|rating| *rating > 0.0).or(rating),
+            play_count: existing.play_count + play_count.unwrap_or(0),
+            // ISO 8601 timestamps in UTC sort as text
+            last_played_at: existing.last_played_at.clone().max(last_played_at),
+            added_at,
+        },
+    }
+}
+
+/// Library tracks keyed by normalised title
+fn load_library_index(conn: &Connection) -> Result<HashMap<String, Vec<LibraryEntry>>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id, title, artist, duration_seconds FROM tracks")
+        .map_err(|error| error.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<f64>>(3)?,
+            ))
+        })
+        .map_err(|error| error.to_string())?;
+
+    let mut by_title: HashMap<String, Vec<LibraryEntry>> = HashMap::new();
+    for row in rows {
+        let (id, title, artist, duration_seconds) = row.map_err(|error| error.to_string())?;
+        let title = search::normalize_fragment(title.as_deref().unwrap_or(""));
+        if title.is_empty() {
+            continue;
+        }
+        by_title.entry(title).or_default().push(LibraryEntry {
+            id,
+            artist: search::normalize_fragment(artist.as_deref().unwrap_or("")),
+            duration_seconds,
+        });
+    }
+    Ok(by_title)
+}
+
+/// Find the one library track with the same title and artist and a duration
+/// within `DURATION_TOLERANCE`. Err carries a reason when the tags were
+/// usable but did not lead to a single track.
+fn match_by_tags(
+    track: &HistoryTrack,
+    by_title: &HashMap<String, Vec<LibraryEntry>>,
+) -> Result<String, Option<&'static str>> {
+    let title = search::normalize_fragment(track.title.as_deref().unwrap_or(""));
+    let artist = search::normalize_fragment(track.artist.as_deref().unwrap_or(""));
+    if title.is_empty() || artist.is_empty() {
+        return Err(None);
+    }
+
+    let candidates: Vec<&LibraryEntry> = by_title
+        .get(&title)
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry.artist == artist)
+        .filter(
+            |entry| match (entry.duration_seconds, track.duration_seconds) {
+                (Some(ours), Some(theirs)) if ours > 0.0 && theirs > 0.0 => {
+                    (ours - theirs).abs() <= DURATION_TOLERANCE
+                }
+                _ => true,
+            },
+        )
+        .collect();
+
+    match candidates.as_slice() {
+        [entry] => Ok(entry.id.clone()),
+        [] => Err(None),
+        _ => Err(Some("Several library tracks share this artist and title")),
+    }
+}
+
+fn unresolved_entry(track: &HistoryTrack, reason: &str) -> UnresolvedEntry {
+    let title = match (&track.artist, &track.title) {
+        (Some(artist), Some(title)) => Some(format!("{} - {}", artist, title)),
+        (None, Some(title)) => Some(title.clone()),
+        _ => None,
+    };
+    UnresolvedEntry {
+        location: track.location.clone(),
+        title,
+        duration_seconds: track.duration_seconds,
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history_track(location: &str) -> HistoryTrack {
+        HistoryTrack {
+            location: location.to_string(),
+            rating: Some(4.0),
+            play_count: Some(3),
+            last_played: Some(1_700_000_000),
+            added_at: Some(1_600_000_000),
+            ..Default::default()
+        }
+    }
+
+    fn existing_stats() -> TrackStats {
+        TrackStats {
+            rating: Some(2.0),
+            play_count: 5,
+            last_played_at: Some("2024-01-01T00:00:00Z".to_string()),
+            added_at: Some(1_650_000_000),
+        }
+    }
+
+    #[test]
+    fn keep_only_fills_tracks_without_stats() {
+        let track = history_track("file:///music/a.flac");
+
+        let kept = resolve_stats(StatsPolicy::Keep, &existing_stats(), &track);
+        assert_eq!(kept.rating, Some(2.0));
+        assert_eq!(kept.play_count, 5);
+        assert_eq!(kept.last_played_at.as_deref(), Some("2024-01-01T00:00:00Z"));
+        assert_eq!(kept.added_at, Some(1_600_000_000));
+
+        let filled = resolve_stats(StatsPolicy::Keep, &TrackStats::default(), &track);
+        assert_eq!(filled.rating, Some(4.0));
+        assert_eq!(filled.play_count, 3);
+        assert_eq!(
+            filled.last_played_at,
+            Some(import::format_timestamp(1_700_000_000))
+        );
+    }
+
+    #[test]
+    fn overwrite_replaces_stats_the_import_has() {
+        let mut track = history_track("file:///music/a.flac");
+        track.rating = None;
+
+        let stats = resolve_stats(StatsPolicy::Overwrite, &existing_stats(), &track);
+        assert_eq!(stats.rating, Some(2.0));
+        assert_eq!(stats.play_count, 3);
+        assert_eq!(
+            stats.last_played_at,
+            Some(import::format_timestamp(1_700_000_000))
+        );
+        assert_eq!(stats.added_at, Some(1_600_000_000));
+    }
+
+    #[test]
+    fn merge_adds_play_counts_and_keeps_the_latest_play() {
+        let track = history_track("file:///music/a.flac");
+
+        let stats = resolve_stats(StatsPolicy::Merge, &existing_stats(), &track);
+        assert_eq!(stats.rating, Some(2.0));
+        assert_eq!(stats.play_count, 8);
+        // 2023-11-14 is older than the play we already have
+        assert_eq!(
+            stats.last_played_at.as_deref(),
+            Some("2024-01-01T00:00:00Z")
+        );
+        assert_eq!(stats.added_at, Some(1_600_000_000));
+    }
+
+    #[test]
+    fn kept_tracks_are_not_counted_as_updated() {
+        let dir = std::env::temp_dir().join(format!("muro-history-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("muro.db").to_string_lossy().to_string();
+        // Locations only resolve to files that exist
+        let paths: Vec<String> = ["a.flac", "b.flac"]
+            .iter()
+            .map(|name| {
+                let path = dir.join(name);
+                std::fs::write(&path, b"").unwrap();
+                path.to_string_lossy().to_string()
+            })
+            .collect();
+        {
+            let conn = Connection::open(&db_path).unwrap();
+            import::ensure_schema(&conn).unwrap();
+            for (id, path, rating) in [("a", &paths[0], Some(2.0)), ("b", &paths[1], None)] {
+                conn.execute(
+                    "INSERT INTO tracks (id, title, artist, added_at, updated_at, import_status,
+                        source_path, rating)
+                     VALUES (?1, ?1, 'Artist', 1650000000, 0, 'accepted', ?2, ?3)",
+                    rusqlite::params![id, path, rating],
+                )
+                .unwrap();
+            }
+        }
+
+        let library = PlayerLibrary {
+            tracks: paths
+                .iter()
+                .map(|path| history_track(&format!("file://{}", path)))
+                .collect(),
+            playlists: Vec::new(),
+        };
+        let report =
+            apply_player_library(&db_path, "rhythmbox", library, StatsPolicy::Keep).unwrap();
+        assert_eq!(report.matched_by_path, 2);
+        assert_eq!(report.kept, 1);
+        assert_eq!(report.updated, 1);
+
+        let conn = Connection::open(&db_path).unwrap();
+        let added_at: i64 = conn
+            .query_row("SELECT added_at FROM tracks WHERE id = 'a'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(added_at, 1_600_000_000);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,872 @@
+use crate::import;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use quick_xml::escape::{escape, resolve_predefined_entity};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Characters escaped in the path part of a `file://` URI
+const URI_PATH: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b'\\')
+    .add(b']')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistFormat {
+    M3u8,
+    Pls,
+    Xspf,
+}
+
+impl PlaylistFormat {
+    fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "m3u" | "m3u8" => Some(Self::M3u8),
+            "pls" => Some(Self::Pls),
+            "xspf" => Some(Self::Xspf),
+            _ => None,
+        }
+    }
+
+    fn sniff(text: &str) -> Self {
+        let start = text.trim_start();
+        if start.to_ascii_lowercase().starts_with("[playlist]") {
+            Self::Pls
+        } else if start.starts_with("<?xml") || start.starts_with("<playlist") {
+            Self::Xspf
+        } else {
+            Self::M3u8
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct UnresolvedEntry {
+    pub location: String,
+    /// Title and duration as given by the playlist, to help locate the file
+    pub title: Option<String>,
+    pub duration_seconds: Option<f64>,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PlaylistImportReport {
+    pub playlist_id: String,
+    pub name: String,
+    pub total: usize,
+    /// Entries that were already in the library
+    pub matched: usize,
+    /// Entries imported into the library while reading the playlist
+    pub imported: usize,
+    pub unresolved: Vec<UnresolvedEntry>,
+}
+
+#[derive(Debug, Default)]
+struct PlaylistEntry {
+    location: String,
+    title: Option<String>,
+    duration_seconds: Option<f64>,
+}
+
+#[derive(Debug, Default)]
+struct ParsedPlaylist {
+    name: Option<String>,
+    entries: Vec<PlaylistEntry>,
+}
+
+struct ExportTrack {
+    title: String,
+    artist: String,
+    duration_seconds: Option<f64>,
+    source_path: String,
+}
+
+/// Read an M3U/M3U8, PLS or XSPF file into a new playlist. Entries are resolved
+/// relative to the playlist file; files that exist but are not in the library
+/// yet are imported first (they land in the inbox like any other import).
+/// Entries that cannot be resolved are skipped and listed in the report.
+pub fn import_playlist(
+    db_path: &str,
+    cache_dir: &Path,
+    playlist_path: &Path,
+) -> Result<PlaylistImportReport, String> {
+    let bytes = std::fs::read(playlist_path).map_err(|error| error.to_string())?;
+    let text = decode_text(&bytes);
+    let format =
+        PlaylistFormat::from_path(playlist_path).unwrap_or_else(|| PlaylistFormat::sniff(&text));
+    let parsed = match format {
+        PlaylistFormat::M3u8 => parse_m3u(&text),
+        PlaylistFormat::Pls => parse_pls(&text),
+        PlaylistFormat::Xspf => parse_xspf(&text)?,
+    };
+
+    let name = parsed
+        .name
+        .clone()
+        .filter(|name| !name.trim().is_empty())
+        .or_else(|| {
+            playlist_path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| "Imported Playlist".to_string());
+
+    if let Some(parent) = Path::new(db_path).parent() {
+        std::fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+    }
+    let mut conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    import::ensure_schema(&conn)?;
+    import::ensure_playlist_schema(&conn)?;
+
+    let base_dir = playlist_path.parent().unwrap_or(Path::new(""));
+    let mut unresolved = Vec::new();
+    // Each entry resolves to a known track id or to a file still to import
+    let mut resolved: Vec<(&PlaylistEntry, Result<String, PathBuf>)> = Vec::new();
+    for entry in &parsed.entries {
+        let path = match resolve_location(&entry.location, base_dir, format == PlaylistFormat::Xspf)
+        {
+            Ok(path) => path,
+            Err(reason) => {
+                unresolved.push(UnresolvedEntry {
+                    location: entry.location.clone(),
+                    title: entry.title.clone(),
+                    duration_seconds: entry.duration_seconds,
+                    reason: reason.to_string(),
+                });
+                continue;
+            }
+        };
+        match find_track_id(&conn, &path)? {
+            Some(track_id) => resolved.push((entry, Ok(track_id))),
+            None => resolved.push((entry, Err(path))),
+        }
+    }
+
+    let matched = resolved.iter().filter(|(_, item)| item.is_ok()).count();
+    let mut to_import: Vec<String> = resolved
+        .iter()
+        .filter_map(|(_, item)| item.as_ref().err())
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    to_import.sort();
+    to_import.dedup();
+
+    let mut imported = 0;
+    let mut import_issues: HashMap<String, String> = HashMap::new();
+    if !to_import.is_empty() {
+        let report = import::import_files(to_import, db_path, cache_dir)?;
+        imported = report.imported.len();
+        for issue in report
+            .skipped_duplicates
+            .into_iter()
+            .chain(report.unsupported)
+            .chain(report.failed)
+        {
+            import_issues.insert(issue.path, issue.reason);
+        }
+    }
+
+    let mut track_ids = Vec::new();
+    for (entry, item) in resolved {
+        match item {
+            Ok(track_id) => track_ids.push(track_id),
+            Err(path) => match find_track_id(&conn, &path)? {
+                Some(track_id) => track_ids.push(track_id),
+                None => {
+                    let reason = import_issues
+                        .get(path.to_string_lossy().as_ref())
+                        .cloned()
+                        .unwrap_or_else(|| "Could not be imported".to_string());
+                    unresolved.push(UnresolvedEntry {
+                        location: entry.location.clone(),
+                        title: entry.title.clone(),
+                        duration_seconds: entry.duration_seconds,
+                        reason,
+                    });
+                }
+            },
+        }
+    }
+
+    let playlist_id = Uuid::new_v4().to_string();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|error| error.to_string())?
+        .as_secs() as i64;
+
+    let tx = conn.transaction().map_err(|error| error.to_string())?;
+    tx.execute(
+        "INSERT INTO playlists (id, name, created_at) VALUES (?1, ?2, ?3)",
+        (&playlist_id, &name, now),
+    )
+    .map_err(|error| error.to_string())?;
+    for (position, track_id) in track_ids.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO playlist_tracks (playlist_id, track_id, position) VALUES (?1, ?2, ?3)",
+            (&playlist_id, track_id, position as i64),
+        )
+        .map_err(|error| error.to_string())?;
+    }
+    tx.commit().map_err(|error| error.to_string())?;
+
+    Ok(PlaylistImportReport {
+        playlist_id,
+        name,
+        total: parsed.entries.len(),
+        matched,
+        imported,
+        unresolved,
+    })
+}
+
+/// Write a playlist to `dest_path`. With `relative_paths`, locations are
+/// relative to the playlist file where possible (files on another drive stay
+/// absolute). Returns the number of tracks written.
+pub fn export_playlist(
+    db_path: &str,
+    playlist_id: &str,
+    dest_path: &Path,
+    format: PlaylistFormat,
+    relative_paths: bool,
+) -> Result<usize, String> {
+    let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    import::ensure_playlist_schema(&conn)?;
+
+    let name: String = conn
+        .query_row(
+            "SELECT name FROM playlists WHERE id = ?1",
+            [playlist_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|error| error.to_string())?
+        .ok_or_else(|| "Playlist not found".to_string())?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT t.title, t.artist, t.duration_seconds, t.source_path
+             FROM playlist_tracks pt
+             JOIN tracks t ON t.id = pt.track_id
+             WHERE pt.playlist_id = ?1
+             ORDER BY pt.position ASC",
+        )
+        .map_err(|error| error.to_string())?;
+    let tracks = stmt
+        .query_map([playlist_id], |row| {
+            Ok(ExportTrack {
+                title: row.get::<_, Option<String>>(0)?.unwrap_or_default(),
+                artist: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                duration_seconds: row.get(2)?,
+                source_path: row.get(3)?,
+            })
+        })
+        .map_err(|error| error.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| error.to_string())?;
+
+    let base_dir = dest_path.parent().unwrap_or(Path::new(""));
+    let locate = |track: &ExportTrack| -> (PathBuf, bool) {
+        let source = PathBuf::from(&track.source_path);
+        if relative_paths {
+            if let Some(relative) = relative_to(&source, base_dir) {
+                return (relative, true);
+            }
+        }
+        (source, false)
+    };
+
+    let contents = match format {
+        PlaylistFormat::M3u8 => {
+            let mut out = format!("#EXTM3U\n#PLAYLIST:{}\n", single_line(&name));
+            for track in &tracks {
+                let (location, _) = locate(track);
+                let _ = writeln!(
+                    out,
+                    "#EXTINF:{},{}",
+                    track
+                        .duration_seconds
+                        .map(|seconds| seconds.round() as i64)
+                        .unwrap_or(-1),
+                    single_line(&display_title(track))
+                );
+                let _ = writeln!(out, "{}", location.to_string_lossy());
+            }
+            out
+        }
+        PlaylistFormat::Pls => {
+            let mut out = String::from("[playlist]\n");
+            for (index, track) in tracks.iter().enumerate() {
+                let number = index + 1;
+                let (location, _) = locate(track);
+                let _ = writeln!(out, "File{}={}", number, location.to_string_lossy());
+                let _ = writeln!(
+                    out,
+                    "Title{}={}",
+                    number,
+                    single_line(&display_title(track))
+                );
+                let _ = writeln!(
+                    out,
+                    "Length{}={}",
+                    number,
+                    track
+                        .duration_seconds
+                        .map(|seconds| seconds.round() as i64)
+                        .unwrap_or(-1)
+                );
+            }
+            let _ = writeln!(out, "NumberOfEntries={}\nVersion=2", tracks.len());
+            out
+        }
+        PlaylistFormat::Xspf => {
+            let mut out = String::from(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                 <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n",
+            );
+            let _ = writeln!(out, "  <title>{}</title>", escape(name.as_str()));
+            out.push_str("  <trackList>\n");
+            for track in &tracks {
+                let (location, relative) = locate(track);
+                out.push_str("    <track>\n");
+                let _ = writeln!(
+                    out,
+                    "      <location>{}</location>",
+                    escape(path_to_uri(&location, relative).as_str())
+                );
+                if !track.title.is_empty() {
+                    let _ = writeln!(out, "      <title>{}</title>", escape(track.title.as_str()));
+                }
+                if !track.artist.is_empty() {
+                    let _ = writeln!(
+                        out,
+                        "      <creator>{}</creator>",
+                        escape(track.artist.as_str())
+                    );
+                }
+                if let Some(seconds) = track.duration_seconds {
+                    let _ = writeln!(
+                        out,
+                        "      <duration>{}</duration>",
+                        (seconds * 1000.0).round() as i64
+                    );
+                }
+                out.push_str("    </track>\n");
+            }
+            out.push_str("  </trackList>\n</playlist>\n");
+            out
+        }
+    };
+
+    std::fs::write(dest_path, contents).map_err(|error| error.to_string())?;
+    Ok(tracks.len())
+}
+
+/// Playlist files without a BOM are UTF-8 (M3U8, PLS, XSPF) or Latin-1 (old
+/// M3U); fall back to the latter when the bytes are not valid UTF-8
+fn decode_text(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&byte| byte as char).collect(),
+    }
+}
+
+fn parse_m3u(text: &str) -> ParsedPlaylist {
+    let mut playlist = ParsedPlaylist::default();
+    let mut pending: Option<PlaylistEntry> = None;
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            let (duration, title) = info.split_once(',').unwrap_or((info, ""));
+            // Attributes like tvg-id="..." may follow the duration
+            let duration = duration.split_whitespace().next().unwrap_or("");
+            pending = Some(PlaylistEntry {
+                location: String::new(),
+                title: Some(title.trim().to_string()).filter(|title| !title.is_empty()),
+                duration_seconds: duration.parse::<f64>().ok().filter(|value| *value >= 0.0),
+            });
+            continue;
+        }
+        if let Some(name) = line.strip_prefix("#PLAYLIST:") {
+            playlist.name = Some(name.trim().to_string());
+            continue;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+
+        let mut entry = pending.take().unwrap_or_default();
+        entry.location = line.to_string();
+        playlist.entries.push(entry);
+    }
+
+    playlist
+}
+
+fn parse_pls(text: &str) -> ParsedPlaylist {
+    let mut entries: BTreeMap<usize, PlaylistEntry> = BTreeMap::new();
+
+    for line in text.lines() {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim();
+        let split = key
+            .find(|ch: char| ch.is_ascii_digit())
+            .unwrap_or(key.len());
+        let Ok(number) = key[split..].parse::<usize>() else {
+            continue;
+        };
+        let entry = entries.entry(number).or_default();
+        match &key[..split] {
+            "file" => entry.location = value.to_string(),
+            "title" => entry.title = Some(value.to_string()).filter(|title| !title.is_empty()),
+            "length" => {
+                entry.duration_seconds = value.parse::<f64>().ok().filter(|value| *value >= 0.0)
+            }
+            _ => {}
+        }
+    }
+
+    ParsedPlaylist {
+        name: None,
+        entries: entries
+            .into_values()
+            .filter(|entry| !entry.location.is_empty())
+            .collect(),
+    }
+}
+
+fn parse_xspf(text: &str) -> Result<ParsedPlaylist, String> {
+    let mut reader = Reader::from_str(text);
+    let mut playlist = ParsedPlaylist::default();
+    let mut stack: Vec<String> = Vec::new();
+    let mut current: Option<PlaylistEntry> = None;
+    let mut value = String::new();
+
+    loop {
+        match reader.read_event().map_err(|error| error.to_string())? {
+            Event::Start(start) => {
+                let name = String::from_utf8_lossy(start.local_name().as_ref()).to_string();
+                if name == "track" {
+                    current = Some(PlaylistEntry::default());
+                }
+                stack.push(name);
+                value.clear();
+            }
+            Event::Text(text) => {
+                value.push_str(&text.xml_content().map_err(|error| error.to_string())?)
+            }
+            Event::CData(data) => {
+                value.push_str(&data.decode().map_err(|error| error.to_string())?)
+            }
+            Event::GeneralRef(reference) => {
+                if let Ok(Some(ch)) = reference.resolve_char_ref() {
+                    value.push(ch);
+                } else if let Ok(name) = reference.decode() {
+                    value.push_str(resolve_predefined_entity(&name).unwrap_or_default());
+                }
+            }
+            Event::End(_) => {
+                let name = stack.pop().unwrap_or_default();
+                let parent = stack.last().map(String::as_str);
+                let text = value.trim().to_string();
+                match (name.as_str(), parent, current.as_mut()) {
+                    ("track", _, _) => {
+                        if let Some(entry) = current.take() {
+                            if !entry.location.is_empty() {
+                                playlist.entries.push(entry);
+                            }
+                        }
+                    }
+                    ("location", Some("track"), Some(entry)) if entry.location.is_empty() => {
+                        entry.location = text
+                    }
+                    ("title", Some("track"), Some(entry)) => {
+                        entry.title = Some(text).filter(|title| !title.is_empty())
+                    }
+                    ("duration", Some("track"), Some(entry)) => {
+                        entry.duration_seconds = text.parse::<f64>().ok().map(|ms| ms / 1000.0)
+                    }
+                    ("title", Some("playlist"), _) => playlist.name = Some(text),
+                    _ => {}
+                }
+                value.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(playlist)
+}
+
+/// Turn a playlist location into a path on disk. XSPF locations are URIs, so
+/// relative ones are percent-encoded too.
+pub(crate) fn resolve_location(
+    location: &str,
+    base_dir: &Path,
+    is_uri: bool,
+) -> Result<PathBuf, &'static str> {
+    let location = location.trim();
+    let path = if let Some(rest) = strip_prefix_ignore_case(location, "file://") {
+        let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+        let decoded = percent_decode_str(rest).decode_utf8_lossy().to_string();
+        // file:///C:/Music/... on Windows
+        let bytes = decoded.as_bytes();
+        if cfg!(windows) && bytes.len() > 2 && bytes[0] == b'/' && bytes[2] == b':' {
+            PathBuf::from(&decoded[1..])
+        } else {
+            PathBuf::from(decoded)
+        }
+    } else if location.contains("://") {
+        return Err("Remote locations are not supported");
+    } else if is_uri {
+        PathBuf::from(percent_decode_str(location).decode_utf8_lossy().to_string())
+    } else if cfg!(windows) {
+        PathBuf::from(location)
+    } else {
+        // Playlists written on Windows use backslashes
+        PathBuf::from(location.replace('\\', "/"))
+    };
+
+    let path = if path.is_absolute() {
+        path
+    } else {
+        base_dir.join(path)
+    };
+    let path = normalize(&path);
+    if !path.is_file() {
+        return Err("File not found");
+    }
+    Ok(path)
+}
+
+fn strip_prefix_ignore_case<'a>(value: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = value.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &value[prefix.len()..])
+}
+
+/// Resolve `.` and `..` without touching the filesystem, so the result matches
+/// the paths stored at import time
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+pub(crate) fn find_track_id(conn: &Connection, path: &Path) -> Result<Option<String>, String> {
+    let mut stmt = conn
+        .prepare_cached("SELECT id FROM tracks WHERE source_path = ?1")
+        .map_err(|error| error.to_string())?;
+    let id = stmt
+        .query_row([path.to_string_lossy().as_ref()], |row| row.get(0))
+        .optional()
+        .map_err(|error| error.to_string())?;
+    if id.is_some() {
+        return Ok(id);
+    }
+
+    // The playlist may reach the file through a symlink or another spelling
+    match path.canonicalize() {
+        Ok(canonical) if canonical != path => stmt
+            .query_row([canonical.to_string_lossy().as_ref()], |row| row.get(0))
+            .optional()
+            .map_err(|error| error.to_string()),
+        _ => Ok(None),
+    }
+}
+
+/// `path` relative to `base`, or None when they share no root (e.g. another
+/// drive on Windows)
+fn relative_to(path: &Path, base: &Path) -> Option<PathBuf> {
+    let path = normalize(path);
+    let base = normalize(base);
+    let mut path_components = path.components().peekable();
+    let mut base_components = base.components().peekable();
+
+    match (path_components.peek(), base_components.peek()) {
+        (Some(Component::Prefix(a)), Some(Component::Prefix(b))) if a != b => return None,
+        (Some(Component::RootDir | Component::Prefix(_)), _) if !base.is_absolute() => return None,
+        _ => {}
+    }
+
+    while let (Some(a), Some(b)) = (path_components.peek(), base_components.peek()) {
+        if a != b {
+            break;
+        }
+        path_components.next();
+        base_components.next();
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in base_components {
+        relative.push("..");
+    }
+    for component in path_components {
+        relative.push(component.as_os_str());
+    }
+    Some(relative)
+}
+
+/// Percent-encode a path for use in a URI, with forward slashes
+pub(crate) fn encode_uri_path(path: &Path) -> String {
+    let text = path.to_string_lossy().replace('\\', "/");
+    utf8_percent_encode(&text, URI_PATH).to_string()
+}
+
+fn path_to_uri(path: &Path, relative: bool) -> String {
+    let encoded = encode_uri_path(path);
+    if relative {
+        encoded
+    } else if encoded.starts_with('/') {
+        format!("file://{}", encoded)
+    } else {
+        format!("file:///{}", encoded)
+    }
+}
+
+fn display_title(track: &ExportTrack) -> String {
+    match (track.artist.is_empty(), track.title.is_empty()) {
+        (false, false) => format!("{} - {}", track.artist, track.title),
+        (true, false) => track.title.clone(),
+        _ => Path::new(&track.source_path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::wav_bytes;
+    use std::fs;
+
+    fn locations(playlist: &ParsedPlaylist) -> Vec<&str> {
+        playlist
+            .entries
+            .iter()
+            .map(|entry| entry.location.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn parses_extended_m3u() {
+        let playlist = parse_m3u(
+            "#EXTM3U\n#PLAYLIST:Warm up\n\
+             #EXTINF:215 tvg-id=\"x\",Artist - Opener\nmusic/opener.mp3\n\
+             \n# a comment\nC:\\Music\\plain.flac\n#EXTINF:-1,Stream\nhttp://radio/live\n",
+        );
+        assert_eq!(playlist.name.as_deref(), Some("Warm up"));
+        assert_eq!(
+            locations(&playlist),
+            [
+                "music/opener.mp3",
+                "C:\\Music\\plain.flac",
+                "http://radio/live"
+            ]
+        );
+        let opener = &playlist.entries[0];
+        assert_eq!(opener.title.as_deref(), Some("Artist - Opener"));
+        assert_eq!(opener.duration_seconds, Some(215.0));
+        assert_eq!(playlist.entries[1].title, None);
+        // Unknown length
+        assert_eq!(playlist.entries[2].duration_seconds, None);
+    }
+
+    #[test]
+    fn parses_pls_in_entry_order() {
+        let playlist = parse_pls(
+            "[playlist]\nFile2=b.mp3\nTitle2=Second\nFile1=a.mp3\nLength1=61\n\
+             Title3=No file\nNumberOfEntries=3\nVersion=2\n",
+        );
+        assert_eq!(locations(&playlist), ["a.mp3", "b.mp3"]);
+        assert_eq!(playlist.entries[0].duration_seconds, Some(61.0));
+        assert_eq!(playlist.entries[1].title.as_deref(), Some("Second"));
+    }
+
+    #[test]
+    fn parses_xspf_tracks_and_escapes() {
+        let playlist = parse_xspf(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<playlist version="1" xmlns="http://xspf.org/ns/0/">
+  <title>Rock &amp; Roll</title>
+  <trackList>
+    <track>
+      <location>file:///music/a%20b.flac</location>
+      <location>file:///mirror/a%20b.flac</location>
+      <title><![CDATA[A & B]]></title>
+      <duration>90500</duration>
+    </track>
+    <track><title>No location</title></track>
+    <track><location>rel/caf&#233;.mp3</location></track>
+  </trackList>
+</playlist>"#,
+        )
+        .unwrap();
+        assert_eq!(playlist.name.as_deref(), Some("Rock & Roll"));
+        assert_eq!(
+            locations(&playlist),
+            ["file:///music/a%20b.flac", "rel/café.mp3"]
+        );
+        assert_eq!(playlist.entries[0].title.as_deref(), Some("A & B"));
+        assert_eq!(playlist.entries[0].duration_seconds, Some(90.5));
+    }
+
+    #[test]
+    fn sniffs_the_format_when_the_extension_does_not_say() {
+        assert_eq!(
+            PlaylistFormat::sniff("  [Playlist]\nFile1=a"),
+            PlaylistFormat::Pls
+        );
+        assert_eq!(
+            PlaylistFormat::sniff("<?xml version=\"1.0\"?>"),
+            PlaylistFormat::Xspf
+        );
+        assert_eq!(PlaylistFormat::sniff("#EXTM3U"), PlaylistFormat::M3u8);
+    }
+
+    #[test]
+    fn decodes_latin1_and_strips_the_bom() {
+        assert_eq!(decode_text(b"Caf\xe9 d\xe9j\xe0"), "Café déjà");
+        assert_eq!(decode_text("\u{feff}Café".as_bytes()), "Café");
+    }
+
+    #[test]
+    fn resolves_locations_against_the_playlist_folder() {
+        let dir = std::env::temp_dir().join(format!("muro-playlist-{}", uuid::Uuid::new_v4()));
+        let music = dir.join("music");
+        fs::create_dir_all(music.join("sub dir")).unwrap();
+        let track = music.join("sub dir").join("a#1.mp3");
+        fs::write(&track, b"").unwrap();
+        let lists = dir.join("lists");
+        fs::create_dir_all(&lists).unwrap();
+
+        let relative = "../music/sub dir/a#1.mp3";
+        assert_eq!(resolve_location(relative, &lists, false), Ok(track.clone()));
+        assert_eq!(
+            resolve_location("..\\music\\sub dir\\a#1.mp3", &lists, false),
+            Ok(track.clone())
+        );
+        // XSPF locations are URIs, so relative ones are percent-encoded too
+        let encoded = "../music/sub%20dir/a%231.mp3";
+        assert_eq!(resolve_location(encoded, &lists, true), Ok(track.clone()));
+        let uri = path_to_uri(&track, false);
+        assert_eq!(resolve_location(&uri, &lists, true), Ok(track.clone()));
+        let localhost = uri.replacen("file://", "FILE://localhost", 1);
+        assert_eq!(resolve_location(&localhost, &lists, false), Ok(track));
+
+        assert_eq!(
+            resolve_location("http://radio/live", &lists, false),
+            Err("Remote locations are not supported")
+        );
+        assert_eq!(
+            resolve_location("missing.mp3", &lists, false),
+            Err("File not found")
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn imported_playlists_join_library_tracks_and_report_the_rest() {
+        let dir = std::env::temp_dir().join(format!("muro-playlist-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["known.wav", "new.wav"] {
+            fs::write(dir.join(name), wav_bytes()).unwrap();
+        }
+        let db_path = dir.join("library.db").to_string_lossy().to_string();
+        let cache_dir = dir.join("covers");
+        let known = dir.join("known.wav").to_string_lossy().to_string();
+        import::import_files(vec![known], &db_path, &cache_dir).unwrap();
+
+        let playlist = dir.join("set.m3u");
+        fs::write(&playlist, "known.wav\nnew.wav\nmissing.wav\n").unwrap();
+        let report = import_playlist(&db_path, &cache_dir, &playlist).unwrap();
+        assert_eq!(report.name, "set");
+        assert_eq!((report.total, report.matched, report.imported), (3, 1, 1));
+        assert_eq!(report.unresolved.len(), 1);
+        assert_eq!(report.unresolved[0].location, "missing.wav");
+        assert_eq!(report.unresolved[0].reason, "File not found");
+
+        let conn = Connection::open(&db_path).unwrap();
+        let entries: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM playlist_tracks WHERE playlist_id = ?1",
+                [&report.playlist_id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(entries, 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn relative_paths_climb_out_of_the_playlist_folder() {
+        assert_eq!(
+            relative_to(Path::new("/music/a/b.mp3"), Path::new("/music/lists")),
+            Some(PathBuf::from("../a/b.mp3"))
+        );
+        assert_eq!(
+            relative_to(Path::new("/music/./lists/b.mp3"), Path::new("/music/lists")),
+            Some(PathBuf::from("b.mp3"))
+        );
+        assert_eq!(relative_to(Path::new("/b.mp3"), Path::new("lists")), None);
+    }
+
+    #[test]
+    fn uris_escape_reserved_characters() {
+        let path = Path::new("/music/AC DC/50% #1 [live].mp3");
+        assert_eq!(
+            path_to_uri(path, false),
+            "file:///music/AC%20DC/50%25%20%231%20%5Blive%5D.mp3"
+        );
+        assert_eq!(path_to_uri(Path::new("sub/é.mp3"), true), "sub/%C3%A9.mp3");
+        assert_eq!(
+            path_to_uri(Path::new("C:\\Music\\a.mp3"), false),
+            "file:///C:/Music/a.mp3"
+        );
+    }
+}
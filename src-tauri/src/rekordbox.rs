@@ -0,0 +1,848 @@
+use crate::dj_collection::{
+    self, attributes, key_index, non_empty, rating_from_byte, Collection, CollectionNode,
+    CollectionTrack, CuePoint, KEY_NAMES,
+};
+use crate::import::{self, PlaylistRow};
+use crate::playlist_io;
+use chrono::{DateTime, Utc};
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// Playlist node types in `<PLAYLISTS>`
+const NODE_FOLDER: &str = "0";
+const NODE_PLAYLIST: &str = "1";
+/// Cue points imported from Rekordbox take precedence on export
+const SOURCE: &str = "rekordbox";
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RekordboxExportSummary {
+    pub tracks: usize,
+    pub playlists: usize,
+    pub cue_points: usize,
+}
+
+struct ExportTrack {
+    id: String,
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    genres: Vec<String>,
+    comments: Vec<String>,
+    label: Option<String>,
+    year: Option<i32>,
+    track_number: Option<i32>,
+    disc_number: Option<i32>,
+    key: Option<String>,
+    bpm: Option<f64>,
+    rating: Option<f64>,
+    play_count: Option<i32>,
+    duration_seconds: Option<f64>,
+    bitrate_kbps: Option<i64>,
+    added_at: Option<i64>,
+    source_path: String,
+    file_size: Option<i64>,
+}
+
+/// Parse a Rekordbox `collection.xml` (File > Export Collection in xml format)
+pub fn parse_collection(text: &str) -> Result<Collection, String> {
+    let mut reader = Reader::from_str(text);
+    let mut collection = Collection::default();
+    let mut current: Option<CollectionTrack> = None;
+    let mut in_playlists = false;
+    // Open folders, innermost last; the ROOT node is the first one
+    let mut folders: Vec<(String, Vec<CollectionNode>)> = Vec::new();
+    let mut playlist: Option<(String, Vec<String>)> = None;
+
+    loop {
+        let event = reader.read_event().map_err(|error| error.to_string())?;
+        let (element, is_empty) = match &event {
+            Event::Start(element) => (element, false),
+            Event::Empty(element) => (element, true),
+            Event::End(end) => {
+                match end.local_name().as_ref() {
+                    b"TRACK" if !in_playlists => {
+                        if let Some(track) = current.take() {
+                            collection.tracks.push(track);
+                        }
+                    }
+                    b"PLAYLISTS" => in_playlists = false,
+                    b"NODE" => {
+                        if let Some((name, track_keys)) = playlist.take() {
+                            push_node(
+                                &mut folders,
+                                &mut collection.nodes,
+                                CollectionNode::Playlist { name, track_keys },
+                            );
+                        } else if let Some((name, children)) = folders.pop() {
+                            close_folder(&mut folders, &mut collection.nodes, name, children);
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+
+        let attrs = attributes(element);
+        match element.local_name().as_ref() {
+            b"PLAYLISTS" => in_playlists = !is_empty,
+            b"TRACK" if in_playlists => {
+                if let (Some((_, track_keys)), Some(key)) = (playlist.as_mut(), attrs.get("Key")) {
+                    track_keys.push(key.clone());
+                }
+            }
+            b"TRACK" => {
+                let track = parse_track(&attrs);
+                if is_empty {
+                    collection.tracks.push(track);
+                } else {
+                    current = Some(track);
+                }
+            }
+            b"POSITION_MARK" => {
+                if let Some(track) = current.as_mut() {
+                    if let Some(cue) = parse_position_mark(&attrs) {
+                        track.cues.push(cue);
+                    }
+                }
+            }
+            b"NODE" if in_playlists => {
+                let name = attrs.get("Name").cloned().unwrap_or_default();
+                let node_type = attrs.get("Type").map(String::as_str).unwrap_or(NODE_FOLDER);
+                if node_type == NODE_PLAYLIST {
+                    if is_empty {
+                        push_node(
+                            &mut folders,
+                            &mut collection.nodes,
+                            CollectionNode::Playlist {
+                                name,
+                                track_keys: Vec::new(),
+                            },
+                        );
+                    } else {
+                        playlist = Some((name, Vec::new()));
+                    }
+                } else if is_empty {
+                    close_folder(&mut folders, &mut collection.nodes, name, Vec::new());
+                } else {
+                    folders.push((name, Vec::new()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Playlists with KeyType="1" refer to tracks by location instead of TrackID
+    let by_location: HashMap<String, String> = collection
+        .tracks
+        .iter()
+        .map(|track| (track.location.clone(), track.key.clone()))
+        .collect();
+    resolve_location_keys(&mut collection.nodes, &by_location);
+
+    Ok(collection)
+}
+
+fn parse_track(attrs: &HashMap<String, String>) -> CollectionTrack {
+    CollectionTrack {
+        key: attrs.get("TrackID").cloned().unwrap_or_default(),
+        location: attrs.get("Location").cloned().unwrap_or_default(),
+        location_is_uri: true,
+        title: non_empty(attrs.get("Name")),
+        artist: non_empty(attrs.get("Artist")),
+        duration_seconds: attrs
+            .get("TotalTime")
+            .and_then(|value| value.parse::<f64>().ok()),
+        bpm: attrs
+            .get("AverageBpm")
+            .and_then(|value| value.parse::<f64>().ok())
+            .filter(|bpm| *bpm > 0.0),
+        musical_key: non_empty(attrs.get("Tonality")),
+        rating: attrs
+            .get("Rating")
+            .and_then(|value| rating_from_byte(value)),
+        comment: non_empty(attrs.get("Comments")),
+        play_count: attrs
+            .get("PlayCount")
+            .and_then(|value| value.parse::<i32>().ok()),
+        cues: Vec::new(),
+    }
+}
+
+fn parse_position_mark(attrs: &HashMap<String, String>) -> Option<CuePoint> {
+    let start_seconds = attrs.get("Start")?.parse::<f64>().ok()?;
+    let slot = attrs
+        .get("Num")
+        .and_then(|value| value.parse::<i32>().ok())
+        .filter(|num| *num >= 0);
+    let kind = match attrs.get("Type").map(String::as_str) {
+        Some("1") => "fade_in",
+        Some("2") => "fade_out",
+        Some("3") => "load",
+        Some("4") => "loop",
+        _ if slot.is_some() => "hotcue",
+        _ => "cue",
+    };
+    let channel = |name: &str| attrs.get(name).and_then(|value| value.parse::<u8>().ok());
+    let color = match (channel("Red"), channel("Green"), channel("Blue")) {
+        (Some(red), Some(green), Some(blue)) => {
+            Some(format!("#{:02x}{:02x}{:02x}", red, green, blue))
+        }
+        _ => None,
+    };
+
+    Some(CuePoint {
+        kind: kind.to_string(),
+        slot,
+        start_seconds,
+        end_seconds: attrs.get("End").and_then(|value| value.parse::<f64>().ok()),
+        name: non_empty(attrs.get("Name")),
+        color,
+    })
+}
+
+fn push_node(
+    folders: &mut [(String, Vec<CollectionNode>)],
+    roots: &mut Vec<CollectionNode>,
+    node: CollectionNode,
+) {
+    match folders.last_mut() {
+        Some((_, children)) => children.push(node),
+        None => roots.push(node),
+    }
+}
+
+/// The outermost folder is Rekordbox's ROOT node; its children become the
+/// top-level playlists instead of a folder of their own
+fn close_folder(
+    folders: &mut [(String, Vec<CollectionNode>)],
+    roots: &mut Vec<CollectionNode>,
+    name: String,
+    children: Vec<CollectionNode>,
+) {
+    if folders.is_empty() && name == "ROOT" {
+        roots.extend(children);
+    } else {
+        push_node(folders, roots, CollectionNode::Folder { name, children });
+    }
+}
+
+fn resolve_location_keys(nodes: &mut [CollectionNode], by_location: &HashMap<String, String>) {
+    for node in nodes {
+        match node {
+            CollectionNode::Folder { children, .. } => resolve_location_keys(children, by_location),
+            CollectionNode::Playlist { track_keys, .. } => {
+                for key in track_keys.iter_mut() {
+                    if let Some(track_id) = by_location.get(key.as_str()) {
+                        *key = track_id.clone();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Write the library and its playlists as a Rekordbox collection XML, to be
+/// loaded in Rekordbox through the "rekordbox xml" library view. Tracks in the
+/// inbox are only included when a playlist refers to them.
+pub fn export_collection(
+    db_path: &str,
+    dest_path: &Path,
+) -> Result<RekordboxExportSummary, String> {
+    let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    import::ensure_schema(&conn)?;
+    import::ensure_playlist_schema(&conn)?;
+    dj_collection::ensure_cue_schema(&conn)?;
+
+    let tracks = load_export_tracks(&conn)?;
+    let cues = load_export_cues(&conn)?;
+    let playlists = import::load_playlists(db_path)?.playlists;
+
+    // Rekordbox wants numeric track ids
+    let track_numbers: HashMap<&str, usize> = tracks
+        .iter()
+        .enumerate()
+        .map(|(index, track)| (track.id.as_str(), index + 1))
+        .collect();
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<DJ_PLAYLISTS Version=\"1.0.0\">\n");
+    let _ = writeln!(
+        out,
+        "  <PRODUCT Name=\"muro\" Version=\"{}\" Company=\"\"/>",
+        env!("CARGO_PKG_VERSION")
+    );
+    let _ = writeln!(out, "  <COLLECTION Entries=\"{}\">", tracks.len());
+    let mut cue_points = 0;
+    for (index, track) in tracks.iter().enumerate() {
+        let track_cues = cues
+            .get(track.id.as_str())
+            .map(Vec::as_slice)
+            .unwrap_or(&[]);
+        cue_points += track_cues.len();
+        write_track(&mut out, index + 1, track, track_cues);
+    }
+    out.push_str("  </COLLECTION>\n");
+
+    let children = children_by_parent(&playlists);
+    let roots = children.get(&None).map(Vec::len).unwrap_or(0);
+    out.push_str("  <PLAYLISTS>\n");
+    let _ = writeln!(
+        out,
+        "    <NODE Type=\"{}\" Name=\"ROOT\" Count=\"{}\">",
+        NODE_FOLDER, roots
+    );
+    let mut playlist_count = 0;
+    write_nodes(
+        &mut out,
+        &children,
+        None,
+        &track_numbers,
+        3,
+        &mut playlist_count,
+    );
+    out.push_str("    </NODE>\n  </PLAYLISTS>\n</DJ_PLAYLISTS>\n");
+
+    std::fs::write(dest_path, out).map_err(|error| error.to_string())?;
+    Ok(RekordboxExportSummary {
+        tracks: tracks.len(),
+        playlists: playlist_count,
+        cue_points,
+    })
+}
+
+fn load_export_tracks(conn: &Connection) -> Result<Vec<ExportTrack>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, title, artist, album, genre_json, comment_json, label, year, track_number,
+                disc_number, key, bpm, rating, play_count, duration_seconds, bitrate_kbps,
+                added_at, source_path, file_size
+             FROM tracks
+             WHERE source_path IS NOT NULL
+               AND (COALESCE(import_status, 'accepted') != 'staged'
+                    OR id IN (SELECT track_id FROM playlist_tracks))
+             ORDER BY added_at ASC, rowid ASC",
+        )
+        .map_err(|error| error.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(ExportTrack {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                artist: row.get(2)?,
+                album: row.get(3)?,
+                genres: parse_json_list(row.get(4)?),
+                comments: parse_json_list(row.get(5)?),
+                label: row.get(6)?,
+                year: row.get(7)?,
+                track_number: row.get(8)?,
+                disc_number: row.get(9)?,
+                key: row.get(10)?,
+                bpm: row.get(11)?,
+                rating: row.get(12)?,
+                play_count: row.get(13)?,
+                duration_seconds: row.get(14)?,
+                bitrate_kbps: row.get(15)?,
+                added_at: row.get(16)?,
+                source_path: row.get(17)?,
+                file_size: row.get(18)?,
+            })
+        })
+        .map_err(|error| error.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|error| error.to_string())
+}
+
+/// Cue points per track, without beat grid anchors which Rekordbox has no
+/// counterpart for. When a track has cues that came from Rekordbox only those
+/// are exported, so a round trip does not duplicate them.
+fn load_export_cues(conn: &Connection) -> Result<HashMap<String, Vec<CuePoint>>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT track_id, source, kind, slot, start_seconds, end_seconds, name, color
+             FROM cue_points WHERE kind != 'grid' ORDER BY track_id, start_seconds ASC",
+        )
+        .map_err(|error| error.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            let track_id: String = row.get(0)?;
+            let source: String = row.get(1)?;
+            Ok((
+                track_id,
+                source,
+                CuePoint {
+                    kind: row.get(2)?,
+                    slot: row.get(3)?,
+                    start_seconds: row.get(4)?,
+                    end_seconds: row.get(5)?,
+                    name: row.get(6)?,
+                    color: row.get(7)?,
+                },
+            ))
+        })
+        .map_err(|error| error.to_string())?;
+
+    let mut by_track: HashMap<String, Vec<(String, CuePoint)>> = HashMap::new();
+    for row in rows {
+        let (track_id, source, cue) = row.map_err(|error| error.to_string())?;
+        by_track.entry(track_id).or_default().push((source, cue));
+    }
+
+    Ok(by_track
+        .into_iter()
+        .map(|(track_id, cues)| {
+            let from_rekordbox = cues.iter().any(|(source, _)| source == SOURCE);
+            let cues = cues
+                .into_iter()
+                .filter(|(source, _)| !from_rekordbox || source == SOURCE)
+                .map(|(_, cue)| cue)
+                .collect();
+            (track_id, cues)
+        })
+        .collect())
+}
+
+fn write_track(out: &mut String, number: usize, track: &ExportTrack, cues: &[CuePoint]) {
+    let path = Path::new(&track.source_path);
+    let kind = path
+        .extension()
+        .map(|extension| format!("{} File", extension.to_string_lossy().to_uppercase()))
+        .unwrap_or_default();
+    let mut location = playlist_io::encode_uri_path(path);
+    if !location.starts_with('/') {
+        location.insert(0, '/');
+    }
+    let date_added = track
+        .added_at
+        .and_then(|timestamp| DateTime::<Utc>::from_timestamp(timestamp, 0))
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_default();
+    let tonality = track
+        .key
+        .as_deref()
+        .and_then(key_index)
+        .map(|index| KEY_NAMES[index])
+        .unwrap_or("");
+    // Rekordbox only knows whole stars
+    let rating = track
+        .rating
+        .map(|stars| (stars.round().clamp(0.0, 5.0) * 51.0) as i64)
+        .unwrap_or(0);
+    let optional = |value: Option<i64>| value.map(|value| value.to_string()).unwrap_or_default();
+
+    let attributes = [
+        ("TrackID", number.to_string()),
+        ("Name", track.title.clone().unwrap_or_default()),
+        ("Artist", track.artist.clone().unwrap_or_default()),
+        ("Album", track.album.clone().unwrap_or_default()),
+        ("Genre", track.genres.join(", ")),
+        ("Kind", kind),
+        ("Size", optional(track.file_size)),
+        (
+            "TotalTime",
+            optional(track.duration_seconds.map(|seconds| seconds.round() as i64)),
+        ),
+        ("DiscNumber", optional(track.disc_number.map(i64::from))),
+        ("TrackNumber", optional(track.track_number.map(i64::from))),
+        ("Year", optional(track.year.map(i64::from))),
+        (
+            "AverageBpm",
+            track
+                .bpm
+                .map(|bpm| format!("{:.2}", bpm))
+                .unwrap_or_else(|| "0.00".to_string()),
+        ),
+        ("DateAdded", date_added),
+        ("BitRate", optional(track.bitrate_kbps)),
+        ("Comments", track.comments.join(", ")),
+        ("PlayCount", track.play_count.unwrap_or(0).to_string()),
+        ("Rating", rating.to_string()),
+        ("Location", format!("file://localhost{}", location)),
+        ("Tonality", tonality.to_string()),
+        ("Label", track.label.clone().unwrap_or_default()),
+    ];
+
+    out.push_str("    <TRACK");
+    for (name, value) in attributes.iter().filter(|(_, value)| !value.is_empty()) {
+        let _ = write!(out, " {}=\"{}\"", name, escape(value.as_str()));
+    }
+    if cues.is_empty() {
+        out.push_str("/>\n");
+        return;
+    }
+    out.push_str(">\n");
+    for cue in cues {
+        write_position_mark(out, cue);
+    }
+    out.push_str("    </TRACK>\n");
+}
+
+fn write_position_mark(out: &mut String, cue: &CuePoint) {
+    let cue_type = match cue.kind.as_str() {
+        "fade_in" => 1,
+        "fade_out" => 2,
+        "load" => 3,
+        "loop" => 4,
+        _ => 0,
+    };
+    let _ = write!(
+        out,
+        "      <POSITION_MARK Name=\"{}\" Type=\"{}\" Start=\"{:.3}\"",
+        escape(cue.name.as_deref().unwrap_or("")),
+        cue_type,
+        cue.start_seconds
+    );
+    if let Some(end) = cue.end_seconds {
+        let _ = write!(out, " End=\"{:.3}\"", end);
+    }
+    let _ = write!(out, " Num=\"{}\"", cue.slot.unwrap_or(-1));
+    if let Some((red, green, blue)) = cue.color.as_deref().and_then(parse_color) {
+        let _ = write!(
+            out,
+            " Red=\"{}\" Green=\"{}\" Blue=\"{}\"",
+            red, green, blue
+        );
+    }
+    out.push_str("/>\n");
+}
+
+fn parse_color(color: &str) -> Option<(u8, u8, u8)> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |range: std::ops::Range<usize>| u8::from_str_radix(hex.get(range)?, 16).ok();
+    Some((channel(0..2)?, channel(2..4)?, channel(4..6)?))
+}
+
+fn children_by_parent(playlists: &[PlaylistRow]) -> HashMap<Option<&str>, Vec<&PlaylistRow>> {
+    let ids: std::collections::HashSet<&str> = playlists
+        .iter()
+        .map(|playlist| playlist.id.as_str())
+        .collect();
+    let mut children: HashMap<Option<&str>, Vec<&PlaylistRow>> = HashMap::new();
+    for playlist in playlists {
+        // Orphans of a deleted folder are shown at the top level
+        let parent = playlist
+            .parent_id
+            .as_deref()
+            .filter(|parent| ids.contains(parent));
+        children.entry(parent).or_default().push(playlist);
+    }
+    children
+}
+
+fn write_nodes(
+    out: &mut String,
+    children: &HashMap<Option<&str>, Vec<&PlaylistRow>>,
+    parent: Option<&str>,
+    track_numbers: &HashMap<&str, usize>,
+    depth: usize,
+    playlist_count: &mut usize,
+) {
+    let indent = "  ".repeat(depth);
+    for playlist in children.get(&parent).map(Vec::as_slice).unwrap_or(&[]) {
+        let name = escape(playlist.name.as_str());
+        if playlist.is_folder {
+            let count = children
+                .get(&Some(playlist.id.as_str()))
+                .map(Vec::len)
+                .unwrap_or(0);
+            let _ = writeln!(
+                out,
+                "{}<NODE Type=\"{}\" Name=\"{}\" Count=\"{}\">",
+                indent, NODE_FOLDER, name, count
+            );
+            write_nodes(
+                out,
+                children,
+                Some(playlist.id.as_str()),
+                track_numbers,
+                depth + 1,
+                playlist_count,
+            );
+            let _ = writeln!(out, "{}</NODE>", indent);
+            continue;
+        }
+
+        *playlist_count += 1;
+        let numbers: Vec<usize> = playlist
+            .track_ids
+            .iter()
+            .filter_map(|track_id| track_numbers.get(track_id.as_str()).copied())
+            .collect();
+        let _ = writeln!(
+            out,
+            "{}<NODE Name=\"{}\" Type=\"{}\" KeyType=\"0\" Entries=\"{}\">",
+            indent,
+            name,
+            NODE_PLAYLIST,
+            numbers.len()
+        );
+        for number in numbers {
+            let _ = writeln!(out, "{}  <TRACK Key=\"{}\"/>", indent, number);
+        }
+        let _ = writeln!(out, "{}</NODE>", indent);
+    }
+}
+
+fn parse_json_list(value: Option<String>) -> Vec<String> {
+    value
+        .and_then(|json| serde_json::from_str::<Vec<String>>(&json).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLLECTION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<DJ_PLAYLISTS Version="1.0.0">
+  <PRODUCT Name="rekordbox" Version="6.8.5" Company="AlphaTheta"/>
+  <COLLECTION Entries="2">
+    <TRACK TrackID="11" Name="Opener" Artist="DJ A" TotalTime="301" AverageBpm="124.00"
+           Tonality="Am" Rating="204" Comments="Warm up" PlayCount="7"
+           Location="file://localhost/music/opener.mp3">
+      <TEMPO Inizio="0.025" Bpm="124.00" Metro="4/4" Battito="1"/>
+      <POSITION_MARK Name="" Type="0" Start="0.025" Num="-1"/>
+      <POSITION_MARK Name="Drop" Type="0" Start="64.5" Num="2" Red="255" Green="0" Blue="16"/>
+      <POSITION_MARK Name="Roll" Type="4" Start="96.0" End="98.0" Num="-1"/>
+    </TRACK>
+    <TRACK TrackID="12" Name="Closer" Artist="DJ B" AverageBpm="0.00" Rating="0"
+           Location="file://localhost/music/closer%20(edit).flac"/>
+  </COLLECTION>
+  <PLAYLISTS>
+    <NODE Type="0" Name="ROOT" Count="2">
+      <NODE Type="0" Name="Gigs" Count="1">
+        <NODE Type="0" Name="2024" Count="1">
+          <NODE Name="Club" Type="1" KeyType="0" Entries="2">
+            <TRACK Key="12"/>
+            <TRACK Key="11"/>
+          </NODE>
+        </NODE>
+      </NODE>
+      <NODE Name="By path" Type="1" KeyType="1" Entries="1">
+        <TRACK Key="file://localhost/music/opener.mp3"/>
+      </NODE>
+      <NODE Name="Empty" Type="1" KeyType="0" Entries="0"/>
+    </NODE>
+  </PLAYLISTS>
+</DJ_PLAYLISTS>"#;
+
+    #[test]
+    fn parses_tracks_and_position_marks() {
+        let collection = parse_collection(COLLECTION).unwrap();
+        assert_eq!(collection.tracks.len(), 2);
+
+        let opener = &collection.tracks[0];
+        assert_eq!(opener.key, "11");
+        assert_eq!(opener.location, "file://localhost/music/opener.mp3");
+        assert!(opener.location_is_uri);
+        assert_eq!(opener.title.as_deref(), Some("Opener"));
+        assert_eq!(opener.duration_seconds, Some(301.0));
+        assert_eq!(opener.bpm, Some(124.0));
+        assert_eq!(opener.musical_key.as_deref(), Some("Am"));
+        assert_eq!(opener.rating, Some(4.0));
+        assert_eq!(opener.comment.as_deref(), Some("Warm up"));
+        assert_eq!(opener.play_count, Some(7));
+
+        let cues: Vec<(&str, Option<i32>, f64)> = opener
+            .cues
+            .iter()
+            .map(|cue| (cue.kind.as_str(), cue.slot, cue.start_seconds))
+            .collect();
+        assert_eq!(
+            cues,
+            [
+                ("cue", None, 0.025),
+                ("hotcue", Some(2), 64.5),
+                ("loop", None, 96.0)
+            ]
+        );
+        assert_eq!(opener.cues[1].name.as_deref(), Some("Drop"));
+        assert_eq!(opener.cues[1].color.as_deref(), Some("#ff0010"));
+        assert_eq!(opener.cues[2].end_seconds, Some(98.0));
+
+        // Unset values stay empty rather than zero
+        let closer = &collection.tracks[1];
+        assert_eq!(closer.bpm, None);
+        assert_eq!(closer.rating, Some(0.0));
+        assert!(closer.cues.is_empty());
+    }
+
+    #[test]
+    fn parses_nested_folders_under_root() {
+        let collection = parse_collection(COLLECTION).unwrap();
+        let names: Vec<&str> = collection
+            .nodes
+            .iter()
+            .map(|node| match node {
+                CollectionNode::Folder { name, .. } | CollectionNode::Playlist { name, .. } => {
+                    name.as_str()
+                }
+            })
+            .collect();
+        assert_eq!(names, ["Gigs", "By path", "Empty"]);
+
+        let CollectionNode::Folder { children, .. } = &collection.nodes[0] else {
+            panic!("Gigs should be a folder");
+        };
+        let [CollectionNode::Folder { name, children }] = children.as_slice() else {
+            panic!("Gigs should hold one folder");
+        };
+        assert_eq!(name, "2024");
+        let [CollectionNode::Playlist { name, track_keys }] = children.as_slice() else {
+            panic!("2024 should hold one playlist");
+        };
+        assert_eq!(name, "Club");
+        assert_eq!(track_keys, &["12", "11"]);
+
+        // Playlists keyed by location point at the track ids
+        let CollectionNode::Playlist { track_keys, .. } = &collection.nodes[1] else {
+            panic!("By path should be a playlist");
+        };
+        assert_eq!(track_keys, &["11"]);
+    }
+
+    #[test]
+    fn exported_library_parses_back() {
+        let dir = std::env::temp_dir().join(format!("muro-rekordbox-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("library.db").to_string_lossy().to_string();
+        let conn = Connection::open(&db_path).unwrap();
+        import::ensure_schema(&conn).unwrap();
+        import::ensure_playlist_schema(&conn).unwrap();
+        dj_collection::ensure_cue_schema(&conn).unwrap();
+        for (id, title, path, rating, status, added_at) in [
+            (
+                "opener",
+                "Tom & \"Jerry\" <Live>",
+                "/music/AC DC/50% #1.mp3",
+                4.2,
+                "accepted",
+                1,
+            ),
+            ("closer", "Closer", "/music/closer.flac", 2.0, "accepted", 2),
+            ("inbox", "Inbox", "/music/inbox.mp3", 5.0, "staged", 3),
+        ] {
+            conn.execute(
+                "INSERT INTO tracks (id, title, source_path, rating, key, bpm, comment_json,
+                    import_status, added_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, '8A', 124.0, ?5, ?6, ?7, 0)",
+                rusqlite::params![
+                    id,
+                    title,
+                    path,
+                    rating,
+                    r#"["Peak <time>", "Fade & out"]"#,
+                    status,
+                    added_at
+                ],
+            )
+            .unwrap();
+        }
+        conn.execute_batch(
+            "INSERT INTO cue_points (id, track_id, source, kind, slot, start_seconds, end_seconds, name, color)
+             VALUES ('c1', 'opener', 'muro', 'hotcue', 2, 64.5, NULL, 'Drop & roll', '#ff0010'),
+                    ('c2', 'opener', 'muro', 'loop', NULL, 96.0, 98.0, NULL, NULL);
+             INSERT INTO playlists (id, name, created_at, parent_id, is_folder)
+             VALUES ('gigs', 'Gigs', 3, NULL, 1),
+                    ('year', '2024', 2, 'gigs', 1),
+                    ('club', 'Club & Bar', 2, 'year', 0),
+                    ('solo', 'Solo', 1, NULL, 0);
+             INSERT INTO playlist_tracks (playlist_id, track_id, position)
+             VALUES ('club', 'closer', 0), ('club', 'opener', 1), ('solo', 'opener', 0);",
+        )
+        .unwrap();
+
+        let dest = dir.join("rekordbox.xml");
+        let summary = export_collection(&db_path, &dest).unwrap();
+        assert_eq!(
+            (summary.tracks, summary.playlists, summary.cue_points),
+            (2, 2, 2)
+        );
+        let collection = parse_collection(&std::fs::read_to_string(&dest).unwrap()).unwrap();
+
+        // Staged tracks no playlist refers to stay out
+        let titles: Vec<&str> = collection
+            .tracks
+            .iter()
+            .filter_map(|track| track.title.as_deref())
+            .collect();
+        assert_eq!(titles, ["Tom & \"Jerry\" <Live>", "Closer"]);
+        let opener = &collection.tracks[0];
+        assert_eq!(
+            opener.location,
+            "file://localhost/music/AC%20DC/50%25%20%231.mp3"
+        );
+        assert_eq!(opener.rating, Some(4.0));
+        assert_eq!(collection.tracks[1].rating, Some(2.0));
+        assert_eq!(opener.musical_key.as_deref(), Some("Am"));
+        assert_eq!(opener.bpm, Some(124.0));
+        assert_eq!(opener.comment.as_deref(), Some("Peak <time>, Fade & out"));
+        let cues: Vec<(&str, Option<i32>, f64, Option<f64>)> = opener
+            .cues
+            .iter()
+            .map(|cue| {
+                (
+                    cue.kind.as_str(),
+                    cue.slot,
+                    cue.start_seconds,
+                    cue.end_seconds,
+                )
+            })
+            .collect();
+        assert_eq!(
+            cues,
+            [
+                ("hotcue", Some(2), 64.5, None),
+                ("loop", None, 96.0, Some(98.0))
+            ]
+        );
+        assert_eq!(opener.cues[0].name.as_deref(), Some("Drop & roll"));
+        assert_eq!(opener.cues[0].color.as_deref(), Some("#ff0010"));
+
+        let [CollectionNode::Folder { name, children }, CollectionNode::Playlist {
+            name: solo,
+            track_keys: solo_keys,
+        }] = collection.nodes.as_slice()
+        else {
+            panic!("expected the Gigs folder and the Solo playlist");
+        };
+        assert_eq!((name.as_str(), solo.as_str()), ("Gigs", "Solo"));
+        assert_eq!(solo_keys, &[opener.key.as_str()]);
+        let [CollectionNode::Folder { name, children }] = children.as_slice() else {
+            panic!("Gigs should hold one folder");
+        };
+        assert_eq!(name, "2024");
+        let [CollectionNode::Playlist { name, track_keys }] = children.as_slice() else {
+            panic!("2024 should hold one playlist");
+        };
+        assert_eq!(name, "Club & Bar");
+        assert_eq!(
+            track_keys,
+            &[collection.tracks[1].key.as_str(), opener.key.as_str()]
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn exported_tonality_uses_key_names() {
+        for key in ["8A", "A minor", "Am"] {
+            let name = key_index(key).map(|index| KEY_NAMES[index]);
+            assert_eq!(name, Some("Am"));
+        }
+        assert_eq!(parse_color("#ff0010"), Some((255, 0, 16)));
+        assert_eq!(parse_color("red"), None);
+    }
+}
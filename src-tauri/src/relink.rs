@@ -0,0 +1,479 @@
+use crate::fingerprint;
+use crate::import;
+use crate::search;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Maximum difference in seconds for two files to count as the same recording
+const DURATION_TOLERANCE: f64 = 2.0;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RelinkMatch {
+    pub track_id: String,
+    pub old_path: String,
+    pub new_path: String,
+    /// "hash" when the stored audio hash matched, "size" when file size and
+    /// duration did
+    pub matched_by: &'static str,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RelinkAmbiguous {
+    pub track_id: String,
+    pub old_path: String,
+    /// "tags" when only title/artist/duration matched, which is never enough
+    /// to relink on its own
+    pub matched_by: &'static str,
+    pub candidates: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct RelinkReport {
+    pub dry_run: bool,
+    pub missing: usize,
+    pub relinked: Vec<RelinkMatch>,
+    pub ambiguous: Vec<RelinkAmbiguous>,
+    pub unresolved: Vec<String>,
+    /// Roots and folders under them that could not be scanned
+    pub errors: Vec<import::ImportIssue>,
+}
+
+struct MissingTrack {
+    id: String,
+    source_path: String,
+    title: Option<String>,
+    artist: Option<String>,
+    duration_seconds: Option<f64>,
+    file_size: Option<i64>,
+    audio_hash: Option<String>,
+}
+
+struct Candidate {
+    path: PathBuf,
+    size: Option<i64>,
+    fingerprint: Option<Option<import::TrackFingerprint>>,
+    audio_hash: Option<Option<String>>,
+}
+
+impl Candidate {
+    fn fingerprint(&mut self) -> Option<&import::TrackFingerprint> {
+        if self.fingerprint.is_none() {
+            self.fingerprint = Some(import::read_fingerprint(&self.path).ok());
+        }
+        self.fingerprint.as_ref().and_then(|value| value.as_ref())
+    }
+
+    fn audio_hash(&mut self) -> Option<&str> {
+        if self.audio_hash.is_none() {
+            self.audio_hash = Some(fingerprint::audio_hash(&self.path));
+        }
+        self.audio_hash.as_ref().and_then(|value| value.as_deref())
+    }
+}
+
+/// Find new locations for tracks whose file no longer exists by searching the
+/// given roots. Candidates are narrowed by file size and filename, then matched
+/// on the stored audio hash or on size and duration. A track is only relinked
+/// when exactly one file matches; several hits (including byte-identical
+/// copies) and matches on tags alone are reported as ambiguous. Unless
+/// `dry_run` is set, matched tracks get their `source_path` updated in place
+/// so play counts, ratings and playlist memberships are kept.
+pub fn relink_missing_tracks(
+    db_path: &str,
+    roots: &[String],
+    dry_run: bool,
+) -> Result<RelinkReport, String> {
+    let mut report = RelinkReport {
+        dry_run,
+        ..Default::default()
+    };
+    if !Path::new(db_path).exists() {
+        return Ok(report);
+    }
+
+    let mut conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    import::ensure_schema(&conn)?;
+
+    let (missing, known_paths) = load_missing_tracks(&conn)?;
+    report.missing = missing.len();
+    if missing.is_empty() {
+        return Ok(report);
+    }
+
+    let options = import::ScanOptions::load(&conn)?;
+    let mut candidates = Vec::new();
+    for root in roots {
+        let mut files = Vec::new();
+        report.errors.extend(import::collect_audio_paths(
+            Path::new(root),
+            &options,
+            &mut files,
+        ));
+        for path in files {
+            if known_paths.contains(path.to_string_lossy().as_ref()) {
+                continue;
+            }
+            let size = std::fs::metadata(&path).ok().map(|meta| meta.len() as i64);
+            candidates.push(Candidate {
+                path,
+                size,
+                fingerprint: None,
+                audio_hash: None,
+            });
+        }
+    }
+
+    let mut by_size: HashMap<i64, Vec<usize>> = HashMap::new();
+    let mut by_name: HashMap<String, Vec<usize>> = HashMap::new();
+    for (index, candidate) in candidates.iter().enumerate() {
+        if let Some(size) = candidate.size {
+            by_size.entry(size).or_default().push(index);
+        }
+        if let Some(name) = file_name_key(&candidate.path) {
+            by_name.entry(name).or_default().push(index);
+        }
+    }
+
+    let mut claimed: HashSet<usize> = HashSet::new();
+
+    for track in missing {
+        let mut pool: Vec<usize> = Vec::new();
+        if let Some(indexes) = track.file_size.and_then(|size| by_size.get(&size)) {
+            pool.extend(indexes);
+        }
+        if let Some(indexes) =
+            file_name_key(Path::new(&track.source_path)).and_then(|name| by_name.get(&name))
+        {
+            pool.extend(indexes);
+        }
+        if pool.is_empty() {
+            // Renamed and resized (e.g. retagged): fall back to comparing tags
+            pool.extend(0..candidates.len());
+        }
+        pool.sort_unstable();
+        pool.dedup();
+        pool.retain(|index| !claimed.contains(index));
+
+        let (matches, matched_by) = find_matches(&track, &pool, &mut candidates);
+        match resolve(&matches, matched_by) {
+            Resolution::Unresolved => report.unresolved.push(track.id),
+            Resolution::Relink(index) => {
+                claimed.insert(index);
+                report.relinked.push(RelinkMatch {
+                    track_id: track.id,
+                    old_path: track.source_path,
+                    new_path: candidates[index].path.to_string_lossy().to_string(),
+                    matched_by,
+                });
+            }
+            Resolution::Ambiguous => report.ambiguous.push(RelinkAmbiguous {
+                track_id: track.id,
+                old_path: track.source_path,
+                matched_by,
+                candidates: matches
+                    .iter()
+                    .map(|index| candidates[*index].path.to_string_lossy().to_string())
+                    .collect(),
+            }),
+        }
+    }
+
+    if dry_run || report.relinked.is_empty() {
+        return Ok(report);
+    }
+
+    let tx = conn.transaction().map_err(|error| error.to_string())?;
+    for relinked in &report.relinked {
+        let new_path = Path::new(&relinked.new_path);
+        let filename = new_path
+            .file_name()
+            .and_then(|value| value.to_str())
+            .unwrap_or_default()
+            .to_string();
+        let signature = import::file_signature(new_path);
+        let audio_hash = fingerprint::audio_hash(new_path);
+
+        tx.execute(
+            "UPDATE tracks SET source_path = ?1, filename = ?2, is_missing = 0,
+                file_size = ?3, file_mtime = ?4, audio_hash = COALESCE(audio_hash, ?5)
+             WHERE id = ?6",
+            rusqlite::params![
+                relinked.new_path,
+                filename,
+                signature.map(|(size, _)| size),
+                signature.map(|(_, modified)| modified),
+                audio_hash,
+                relinked.track_id
+            ],
+        )
+        .map_err(|error| error.to_string())?;
+    }
+    tx.commit().map_err(|error| error.to_string())?;
+
+    Ok(report)
+}
+
+fn load_missing_tracks(conn: &Connection) -> Result<(Vec<MissingTrack>, HashSet<String>), String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, source_path, title, artist, duration_seconds, file_size, audio_hash
+             FROM tracks WHERE source_path IS NOT NULL AND source_path != ''",
+        )
+        .map_err(|error| error.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(MissingTrack {
+                id: row.get(0)?,
+                source_path: row.get(1)?,
+                title: row.get(2)?,
+                artist: row.get(3)?,
+                duration_seconds: row.get(4)?,
+                file_size: row.get(5)?,
+                audio_hash: row.get(6)?,
+            })
+        })
+        .map_err(|error| error.to_string())?;
+
+    let mut missing = Vec::new();
+    let mut known_paths = HashSet::new();
+    for row in rows {
+        let track = row.map_err(|error| error.to_string())?;
+        known_paths.insert(track.source_path.clone());
+        if !Path::new(&track.source_path).exists() {
+            missing.push(track);
+        }
+    }
+
+    Ok((missing, known_paths))
+}
+
+#[derive(Debug, PartialEq)]
+enum Resolution {
+    Unresolved,
+    Relink(usize),
+    Ambiguous,
+}
+
+fn resolve(matches: &[usize], matched_by: &str) -> Resolution {
+    match matches {
+        [] => Resolution::Unresolved,
+        [index] if matched_by != "tags" => Resolution::Relink(*index),
+        _ => Resolution::Ambiguous,
+    }
+}
+
+/// Candidates matching `track`, tried from most to least reliable: the stored
+/// audio hash, then file size with duration, then title/artist/duration
+fn find_matches(
+    track: &MissingTrack,
+    pool: &[usize],
+    candidates: &mut [Candidate],
+) -> (Vec<usize>, &'static str) {
+    // A stored audio hash is conclusive on its own
+    if let Some(expected) = track.audio_hash.as_deref() {
+        let hashed: Vec<usize> = pool
+            .iter()
+            .copied()
+            .filter(|index| candidates[*index].audio_hash() == Some(expected))
+            .collect();
+        if !hashed.is_empty() {
+            return (hashed, "hash");
+        }
+    }
+
+    let sized: Vec<usize> = pool
+        .iter()
+        .copied()
+        .filter(|index| {
+            track.file_size.is_some()
+                && candidates[*index].size == track.file_size
+                && candidates[*index]
+                    .fingerprint()
+                    .is_some_and(|candidate| duration_matches(track, candidate))
+        })
+        .collect();
+    if !sized.is_empty() {
+        return (sized, "size");
+    }
+
+    let tagged = pool
+        .iter()
+        .copied()
+        .filter(|index| {
+            candidates[*index]
+                .fingerprint()
+                .is_some_and(|candidate| tags_match(track, candidate))
+        })
+        .collect();
+    (tagged, "tags")
+}
+
+fn duration_matches(track: &MissingTrack, candidate: &import::TrackFingerprint) -> bool {
+    match track.duration_seconds {
+        Some(duration) if duration > 0.0 => {
+            (duration - candidate.duration_seconds).abs() <= DURATION_TOLERANCE
+        }
+        _ => true,
+    }
+}
+
+fn tags_match(track: &MissingTrack, candidate: &import::TrackFingerprint) -> bool {
+    let Some(title) = track.title.as_deref().map(search::normalize_fragment) else {
+        return false;
+    };
+    let candidate_title = candidate
+        .title
+        .as_deref()
+        .map(search::normalize_fragment)
+        .unwrap_or_default();
+    if title.is_empty() || title != candidate_title {
+        return false;
+    }
+
+    let artist = track.artist.as_deref().map(search::normalize_fragment);
+    let candidate_artist = candidate.artist.as_deref().map(search::normalize_fragment);
+    if artist.is_some() && candidate_artist.is_some() && artist != candidate_artist {
+        return false;
+    }
+
+    duration_matches(track, candidate)
+}
+
+fn file_name_key(path: &Path) -> Option<String> {
+    path.file_name()
+        .and_then(|value| value.to_str())
+        .map(|value| value.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    fn missing(file_size: Option<i64>, audio_hash: Option<&str>) -> MissingTrack {
+        MissingTrack {
+            id: "t".to_string(),
+            source_path: "/old/Song.flac".to_string(),
+            title: Some("Song".to_string()),
+            artist: Some("Band".to_string()),
+            duration_seconds: Some(200.0),
+            file_size,
+            audio_hash: audio_hash.map(str::to_string),
+        }
+    }
+
+    fn candidate(size: i64, title: &str, duration: f64, hash: &str) -> Candidate {
+        Candidate {
+            path: PathBuf::from(format!("/new/{}-{}.flac", title, size)),
+            size: Some(size),
+            fingerprint: Some(Some(import::TrackFingerprint {
+                title: Some(title.to_string()),
+                artist: Some("Band".to_string()),
+                duration_seconds: duration,
+            })),
+            audio_hash: Some(Some(hash.to_string())),
+        }
+    }
+
+    fn outcome(track: &MissingTrack, candidates: &mut [Candidate]) -> (Resolution, &'static str) {
+        let pool: Vec<usize> = (0..candidates.len()).collect();
+        let (matches, matched_by) = find_matches(track, &pool, candidates);
+        (resolve(&matches, matched_by), matched_by)
+    }
+
+    #[test]
+    fn a_single_hash_or_size_match_relinks() {
+        let mut candidates = vec![
+            candidate(1000, "Other", 200.0, "aaa"),
+            candidate(2000, "Song", 200.5, "bbb"),
+        ];
+        assert_eq!(
+            outcome(&missing(None, Some("bbb")), &mut candidates),
+            (Resolution::Relink(1), "hash")
+        );
+        assert_eq!(
+            outcome(&missing(Some(1000), None), &mut candidates),
+            (Resolution::Relink(0), "size")
+        );
+        // Same size but a different length is another recording
+        assert_eq!(
+            outcome(
+                &missing(Some(1000), None),
+                &mut [candidate(1000, "Other", 180.0, "aaa")]
+            ),
+            (Resolution::Unresolved, "tags")
+        );
+    }
+
+    #[test]
+    fn duplicate_hash_hits_are_ambiguous() {
+        let mut candidates = vec![
+            candidate(2000, "Song", 200.0, "bbb"),
+            candidate(2000, "Song", 200.0, "bbb"),
+        ];
+        assert_eq!(
+            outcome(&missing(None, Some("bbb")), &mut candidates),
+            (Resolution::Ambiguous, "hash")
+        );
+        assert_eq!(
+            outcome(&missing(Some(2000), None), &mut candidates),
+            (Resolution::Ambiguous, "size")
+        );
+    }
+
+    #[test]
+    fn moved_files_relink_and_unreadable_roots_are_reported() {
+        let dir = std::env::temp_dir().join(format!("muro-relink-{}", uuid::Uuid::new_v4()));
+        let root = dir.join("music");
+        std::fs::create_dir_all(&root).unwrap();
+        let moved = root.join("moved.wav");
+        std::fs::write(&moved, test_support::wav_bytes()).unwrap();
+        let db_path = dir.join("library.db").to_string_lossy().to_string();
+        let conn = Connection::open(&db_path).unwrap();
+        import::ensure_schema(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO tracks (id, title, source_path, file_size) VALUES ('t', 'Song', ?1, ?2)",
+            rusqlite::params![
+                dir.join("old.wav").to_str().unwrap(),
+                test_support::wav_bytes().len() as i64
+            ],
+        )
+        .unwrap();
+
+        let roots = [
+            root.to_string_lossy().to_string(),
+            dir.join("unplugged").to_string_lossy().to_string(),
+        ];
+        let report = relink_missing_tracks(&db_path, &roots, true).unwrap();
+        assert_eq!(report.missing, 1);
+        let [relinked] = report.relinked.as_slice() else {
+            panic!("expected one relink, got {:?}", report.relinked);
+        };
+        assert_eq!(relinked.new_path, moved.to_string_lossy());
+        let [error] = report.errors.as_slice() else {
+            panic!("expected one error, got {:?}", report.errors);
+        };
+        assert!(error.path.ends_with("unplugged"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn tag_only_matches_are_ambiguous() {
+        // Retagged and re-encoded: only title, artist and duration agree
+        let mut candidates = vec![candidate(3000, "Song", 201.0, "ccc")];
+        assert_eq!(
+            outcome(&missing(Some(2000), Some("bbb")), &mut candidates),
+            (Resolution::Ambiguous, "tags")
+        );
+
+        let mut candidates = vec![candidate(3000, "Other", 201.0, "ccc")];
+        assert_eq!(
+            outcome(&missing(Some(2000), None), &mut candidates),
+            (Resolution::Unresolved, "tags")
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use crate::formats;
+use crate::import;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct RescanSummary {
+    /// New audio files found under the roots or next to existing tracks and
+    /// imported into the inbox
+    pub added: usize,
+    /// Tracks whose file changed on disk and had their tags re-read
+    pub updated: usize,
+    /// Tracks whose file could not be found
+    pub missing: usize,
+    /// Previously missing tracks whose file is back
+    pub restored: usize,
+    /// Tracks whose file is unchanged, including ones that only had their
+    /// size and modification time recorded for the first time
+    pub unchanged: usize,
+    pub missing_track_ids: Vec<String>,
+    /// Files and folders that could not be read, re-read or imported
+    pub errors: Vec<import::ImportIssue>,
+}
+
+struct TrackFileRow {
+    id: String,
+    source_path: String,
+    file_size: Option<i64>,
+    file_mtime: Option<i64>,
+    is_missing: bool,
+}
+
+/// Check every track's `source_path`, flag missing files, re-read tags for files
+/// whose size or modification time changed, and import new audio files found
+/// under `roots` or in folders that already contain library tracks. Tracks
+/// imported before sizes and modification times were stored only get them
+/// recorded; their tags are not re-read.
+pub fn rescan_library(
+    db_path: &str,
+    roots: &[String],
+    cache_dir: &Path,
+) -> Result<RescanSummary, String> {
+    let mut summary = RescanSummary::default();
+    if !Path::new(db_path).exists() {
+        return Ok(summary);
+    }
+
+    std::fs::create_dir_all(cache_dir).map_err(|error| error.to_string())?;
+
+    let mut conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    import::ensure_schema(&conn)?;
+
+    let mut rows = Vec::new();
+    {
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, source_path, file_size, file_mtime, is_missing FROM tracks
+                 WHERE source_path IS NOT NULL AND source_path != ''",
+            )
+            .map_err(|error| error.to_string())?;
+
+        let mapped = stmt
+            .query_map([], |row| {
+                Ok(TrackFileRow {
+                    id: row.get(0)?,
+                    source_path: row.get(1)?,
+                    file_size: row.get(2)?,
+                    file_mtime: row.get(3)?,
+                    is_missing: row.get::<_, Option<i32>>(4)?.unwrap_or(0) != 0,
+                })
+            })
+            .map_err(|error| error.to_string())?;
+
+        for row in mapped {
+            rows.push(row.map_err(|error| error.to_string())?);
+        }
+    }
+
+    let options = import::ScanOptions::load(&conn)?;
+    let settings = import::ImportSettings::load(&conn)?;
+    let known_paths: HashSet<String> = rows.iter().map(|row| row.source_path.clone()).collect();
+    let mut folders: BTreeSet<PathBuf> = BTreeSet::new();
+    let now = import::current_timestamp();
+
+    let tx = conn.transaction().map_err(|error| error.to_string())?;
+
+    for row in rows {
+        let path = Path::new(&row.source_path);
+        let Some((size, modified)) = import::file_signature(path) else {
+            if !row.is_missing {
+                tx.execute("UPDATE tracks SET is_missing = 1 WHERE id = ?1", [&row.id])
+                    .map_err(|error| error.to_string())?;
+            }
+            summary.missing += 1;
+            summary.missing_track_ids.push(row.id);
+            continue;
+        };
+
+        if let Some(parent) = path.parent() {
+            folders.insert(parent.to_path_buf());
+        }
+
+        if row.is_missing {
+            tx.execute("UPDATE tracks SET is_missing = 0 WHERE id = ?1", [&row.id])
+                .map_err(|error| error.to_string())?;
+            summary.restored += 1;
+        }
+
+        if row.file_size.is_none() || row.file_mtime.is_none() {
+            tx.execute(
+                "UPDATE tracks SET file_size = ?1, file_mtime = ?2 WHERE id = ?3",
+                rusqlite::params![size, modified, row.id],
+            )
+            .map_err(|error| error.to_string())?;
+            summary.unchanged += 1;
+            continue;
+        }
+        if row.file_size == Some(size) && row.file_mtime == Some(modified) {
+            summary.unchanged += 1;
+            continue;
+        }
+
+        match import::refresh_track_from_file(&tx, &row.id, path, now, cache_dir, &settings) {
+            Ok(()) => summary.updated += 1,
+            Err(error) => summary.errors.push(import::ImportIssue::new(path, &error)),
+        }
+    }
+
+    let mut new_files: Vec<PathBuf> = Vec::new();
+    for root in roots {
+        summary.errors.extend(import::collect_audio_paths(
+            Path::new(root),
+            &options,
+            &mut new_files,
+        ));
+    }
+    for folder in folders {
+        let entries = match std::fs::read_dir(&folder) {
+            Ok(entries) => entries,
+            Err(error) => {
+                summary.errors.push(import::ImportIssue::new(
+                    &folder,
+                    &format!("Could not read folder: {}", error),
+                ));
+                continue;
+            }
+        };
+        new_files.extend(entries.flatten().map(|entry| entry.path()).filter(|path| {
+            path.is_file() && formats::is_audio_file(path) && !options.is_excluded(path)
+        }));
+    }
+
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    for path in new_files {
+        if known_paths.contains(path.to_string_lossy().as_ref()) || !seen.insert(path.clone()) {
+            continue;
+        }
+        let result = formats::ProbedFile::open(&path).and_then(|mut probed| {
+            import::import_single(&tx, &mut probed, &path, now, cache_dir, &settings)
+        });
+        match result {
+            Ok(Some(_)) => summary.added += 1,
+            Ok(None) => {}
+            Err(error) => summary.errors.push(import::ImportIssue::new(&path, &error)),
+        }
+    }
+
+    tx.commit().map_err(|error| error.to_string())?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::wav_bytes;
+    use std::fs;
+
+    #[test]
+    fn rescan_backfills_signatures_and_imports_new_files_under_roots() {
+        let dir = std::env::temp_dir().join(format!("muro-rescan-{}", uuid::Uuid::new_v4()));
+        let root = dir.join("music");
+        fs::create_dir_all(root.join("Old Album")).unwrap();
+        fs::create_dir_all(root.join("New Artist").join("New Album")).unwrap();
+        let known = root.join("Old Album").join("01.wav");
+        fs::write(&known, wav_bytes()).unwrap();
+        fs::write(root.join("Old Album").join("02.wav"), wav_bytes()).unwrap();
+        fs::write(
+            root.join("New Artist").join("New Album").join("01.wav"),
+            wav_bytes(),
+        )
+        .unwrap();
+        // Recognised as WAV but without a format chunk
+        fs::write(root.join("broken.wav"), b"RIFF\x04\x00\x00\x00WAVE").unwrap();
+
+        let db_path = dir.join("library.db");
+        let db = db_path.to_str().unwrap();
+        let cache_dir = dir.join("covers");
+        {
+            let conn = Connection::open(db).unwrap();
+            import::ensure_schema(&conn).unwrap();
+            // Imported before sizes and modification times were stored
+            conn.execute(
+                "INSERT INTO tracks (id, title, source_path) VALUES ('known', 'Kept', ?1)",
+                [known.to_str().unwrap()],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO tracks (id, source_path) VALUES ('gone', ?1)",
+                [root.join("gone.wav").to_str().unwrap()],
+            )
+            .unwrap();
+        }
+
+        let roots = [root.to_string_lossy().to_string()];
+        let summary = rescan_library(db, &roots, &cache_dir).unwrap();
+        assert_eq!(summary.unchanged, 1);
+        assert_eq!(summary.updated, 0);
+        assert_eq!(summary.added, 2);
+        assert_eq!(summary.missing_track_ids, ["gone"]);
+        let [error] = summary.errors.as_slice() else {
+            panic!("expected one error, got {:?}", summary.errors);
+        };
+        assert!(error.path.ends_with("broken.wav"));
+
+        let conn = Connection::open(db).unwrap();
+        let (title, size, mtime): (Option<String>, Option<i64>, Option<i64>) = conn
+            .query_row(
+                "SELECT title, file_size, file_mtime FROM tracks WHERE id = 'known'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        // Tags are not re-read just because the signature was missing
+        assert_eq!(title.as_deref(), Some("Kept"));
+        assert_eq!(size, Some(wav_bytes().len() as i64));
+        assert!(mtime.is_some());
+
+        // A second pass finds nothing new
+        let summary = rescan_library(db, &roots, &cache_dir).unwrap();
+        assert_eq!((summary.added, summary.updated), (0, 0));
+        assert_eq!(summary.unchanged, 3);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
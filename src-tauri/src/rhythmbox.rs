@@ -0,0 +1,198 @@
+use crate::player_history::{HistoryTrack, PlayerLibrary};
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+/// Parse Rhythmbox's `rhythmdb.xml`. Only song entries are read; radio
+/// stations and podcast episodes are skipped.
+pub fn parse_database(text: &str) -> Result<PlayerLibrary, String> {
+    let mut library = PlayerLibrary::default();
+    let mut current: Option<HistoryTrack> = None;
+
+    read_elements(text, |event| match event {
+        Element::Start(name, attribute)
+            if name == "entry" && attribute.as_deref() == Some("song") =>
+        {
+            current = Some(HistoryTrack::default());
+        }
+        Element::End(name, value) => match (name.as_str(), current.as_mut()) {
+            ("entry", _) => {
+                if let Some(track) = current.take() {
+                    if !track.location.is_empty() {
+                        library.tracks.push(track);
+                    }
+                }
+            }
+            ("location", Some(track)) => track.location = value,
+            ("title", Some(track)) => track.title = Some(value).filter(|v| !v.is_empty()),
+            ("artist", Some(track)) => track.artist = Some(value).filter(|v| !v.is_empty()),
+            ("duration", Some(track)) => track.duration_seconds = value.parse().ok(),
+            // Whole stars from 0 to 5
+            ("rating", Some(track)) => track.rating = value.parse().ok(),
+            ("play-count", Some(track)) => track.play_count = value.parse().ok(),
+            ("last-played", Some(track)) => track.last_played = value.parse().ok(),
+            ("first-seen", Some(track)) => track.added_at = value.parse().ok(),
+            _ => {}
+        },
+        _ => {}
+    })?;
+
+    Ok(library)
+}
+
+/// Parse Rhythmbox's `playlists.xml`. Automatic (smart) playlists and the play
+/// queue have no fixed entries and are skipped.
+pub fn parse_playlists(text: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let mut playlists = Vec::new();
+    let mut current: Option<(String, Vec<String>)> = None;
+
+    read_elements(text, |event| match event {
+        Element::Start(name, attribute) if name == "playlist" => {
+            current = attribute.map(|name| (name, Vec::new()));
+        }
+        Element::End(name, value) => match (name.as_str(), current.as_mut()) {
+            ("playlist", _) => playlists.extend(current.take()),
+            ("location", Some((_, locations))) if !value.is_empty() => locations.push(value),
+            _ => {}
+        },
+        _ => {}
+    })?;
+
+    Ok(playlists)
+}
+
+enum Element {
+    /// Element name with the attribute relevant to it: `type` for entries and
+    /// the name of static playlists
+    Start(String, Option<String>),
+    /// Element name with its trimmed text content
+    End(String, String),
+}
+
+fn read_elements(text: &str, mut handle: impl FnMut(Element)) -> Result<(), String> {
+    let mut reader = Reader::from_str(text);
+    let mut stack: Vec<String> = Vec::new();
+    let mut value = String::new();
+
+    loop {
+        match reader.read_event().map_err(|error| error.to_string())? {
+            Event::Start(start) => {
+                let name = String::from_utf8_lossy(start.local_name().as_ref()).to_string();
+                let mut attribute = None;
+                let mut is_static = false;
+                for attr in start.attributes().flatten() {
+                    let value = attr
+                        .unescape_value()
+                        .map(|value| value.to_string())
+                        .unwrap_or_default();
+                    match (name.as_str(), attr.key.local_name().as_ref()) {
+                        ("entry", b"type") => attribute = Some(value),
+                        ("playlist", b"name") => attribute = Some(value),
+                        ("playlist", b"type") => is_static = value == "static",
+                        _ => {}
+                    }
+                }
+                if name == "playlist" && !is_static {
+                    attribute = None;
+                }
+                handle(Element::Start(name.clone(), attribute));
+                stack.push(name);
+                value.clear();
+            }
+            Event::Text(text) => {
+                value.push_str(&text.xml_content().map_err(|error| error.to_string())?)
+            }
+            Event::GeneralRef(reference) => {
+                if let Ok(Some(ch)) = reference.resolve_char_ref() {
+                    value.push(ch);
+                } else if let Ok(name) = reference.decode() {
+                    value.push_str(resolve_predefined_entity(&name).unwrap_or_default());
+                }
+            }
+            Event::End(_) => {
+                let name = stack.pop().unwrap_or_default();
+                handle(Element::End(name, value.trim().to_string()));
+                value.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_song_entries_and_skips_other_types() {
+        let text = r#"<?xml version="1.0" standalone="yes"?>
+<rhythmdb version="2.0">
+  <entry type="song">
+    <title>Rock &amp; Roll</title>
+    <artist>Led Zeppelin</artist>
+    <duration>220</duration>
+    <location>file:///music/Led%20Zeppelin/IV/03.flac</location>
+    <rating>4</rating>
+    <play-count>12</play-count>
+    <last-played>1700000000</last-played>
+    <first-seen>1600000000</first-seen>
+  </entry>
+  <entry type="iradio">
+    <title>Radio</title>
+    <location>http://radio.example/stream</location>
+  </entry>
+  <entry type="song">
+    <title></title>
+    <location>file:///music/untitled.mp3</location>
+  </entry>
+</rhythmdb>"#;
+
+        let library = parse_database(text).unwrap();
+        assert_eq!(library.tracks.len(), 2);
+
+        let track = &library.tracks[0];
+        assert_eq!(track.location, "file:///music/Led%20Zeppelin/IV/03.flac");
+        assert_eq!(track.title.as_deref(), Some("Rock & Roll"));
+        assert_eq!(track.artist.as_deref(), Some("Led Zeppelin"));
+        assert_eq!(track.duration_seconds, Some(220.0));
+        assert_eq!(track.rating, Some(4.0));
+        assert_eq!(track.play_count, Some(12));
+        assert_eq!(track.last_played, Some(1_700_000_000));
+        assert_eq!(track.added_at, Some(1_600_000_000));
+
+        let untitled = &library.tracks[1];
+        assert_eq!(untitled.title, None);
+        assert_eq!(untitled.play_count, None);
+    }
+
+    #[test]
+    fn reads_static_playlists_only() {
+        let text = r#"<?xml version="1.0"?>
+<rhythmdb-playlists>
+  <playlist name="Road Trip" show-browser="false" type="static">
+    <location>file:///music/a.flac</location>
+    <location>file:///music/b.flac</location>
+  </playlist>
+  <playlist name="Top Rated" type="automatic" limit-count="0">
+    <conjunction>
+      <equals prop="type">song</equals>
+    </conjunction>
+  </playlist>
+  <playlist name="Play Queue" type="queue"/>
+</rhythmdb-playlists>"#;
+
+        let playlists = parse_playlists(text).unwrap();
+        assert_eq!(
+            playlists,
+            vec![(
+                "Road Trip".to_string(),
+                vec![
+                    "file:///music/a.flac".to_string(),
+                    "file:///music/b.flac".to_string()
+                ]
+            )]
+        );
+    }
+}
@@ -93,7 +93,7 @@ fn push_normalized(values: &mut Vec<String>, value: &str) {
     }
 }
 
-fn split_artist_tokens(value: &str) -> Vec<&str> {
+pub(crate) fn split_artist_tokens(value: &str) -> Vec<&str> {
     let mut tokens = Vec::new();
     let mut start = 0;
     let mut index = 0;
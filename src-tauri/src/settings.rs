@@ -0,0 +1,42 @@
+use rusqlite::{Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Settings the backend needs on its own, e.g. for imports started by the
+/// folder watcher. Values are stored as JSON under a dotted key.
+pub fn ensure_settings_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        )",
+        [],
+    )
+    .map_err(|error| error.to_string())?;
+
+    Ok(())
+}
+
+/// Stored value for `key`, or None when unset or no longer parseable
+pub fn get_setting<T: DeserializeOwned>(conn: &Connection, key: &str) -> Result<Option<T>, String> {
+    ensure_settings_schema(conn)?;
+    let value: Option<String> = conn
+        .prepare_cached("SELECT value FROM settings WHERE key = ?1")
+        .and_then(|mut stmt| stmt.query_row([key], |row| row.get(0)).optional())
+        .map_err(|error| error.to_string())?;
+
+    Ok(value.and_then(|value| serde_json::from_str(&value).ok()))
+}
+
+pub fn set_setting<T: Serialize>(conn: &Connection, key: &str, value: &T) -> Result<(), String> {
+    ensure_settings_schema(conn)?;
+    let value = serde_json::to_string(value).map_err(|error| error.to_string())?;
+    conn.execute(
+        "INSERT INTO settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        (key, value),
+    )
+    .map_err(|error| error.to_string())?;
+
+    Ok(())
+}
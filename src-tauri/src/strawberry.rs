@@ -0,0 +1,281 @@
+use crate::player_history::{HistoryTrack, PlayerLibrary};
+use rusqlite::types::Value;
+use rusqlite::{Connection, OpenFlags};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+const NANOS_PER_SECOND: f64 = 1_000_000_000.0;
+
+/// Read a Strawberry `strawberry.db` or Clementine `clementine.db`. They share
+/// a schema apart from a few renamed columns; the first value is the source
+/// name to report.
+pub fn read_library(path: &Path) -> Result<(&'static str, PlayerLibrary), String> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|error| error.to_string())?;
+
+    let song_columns = table_columns(&conn, "songs")?;
+    if song_columns.is_empty() {
+        return Err("Not a Strawberry or Clementine database".to_string());
+    }
+    // Clementine calls the location "filename" and the collection the "library"
+    let (source, url_column, ref_column) = if song_columns.contains("url") {
+        ("strawberry", "url", "collection_id")
+    } else {
+        ("clementine", "filename", "library_id")
+    };
+
+    let mut library = PlayerLibrary::default();
+    let mut locations: HashMap<i64, String> = HashMap::new();
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT ROWID, {}, title, artist, length, rating, playcount, lastplayed, ctime
+             FROM songs",
+            url_column
+        ))
+        .map_err(|error| error.to_string())?;
+    let mut rows = stmt.query([]).map_err(|error| error.to_string())?;
+    while let Some(row) = rows.next().map_err(|error| error.to_string())? {
+        let location = text_value(row.get(1).map_err(|error| error.to_string())?);
+        if location.is_empty() {
+            continue;
+        }
+        let get_i64 = |index: usize| row.get::<_, Option<i64>>(index).ok().flatten();
+        locations.insert(get_i64(0).unwrap_or_default(), location.clone());
+        library.tracks.push(HistoryTrack {
+            location,
+            title: row.get::<_, Option<String>>(2).ok().flatten(),
+            artist: row.get::<_, Option<String>>(3).ok().flatten(),
+            duration_seconds: length_seconds(get_i64(4)),
+            rating: row
+                .get::<_, Option<f64>>(5)
+                .ok()
+                .flatten()
+                .and_then(stars_from_fraction),
+            play_count: get_i64(6).and_then(|count| i32::try_from(count).ok()),
+            last_played: get_i64(7).filter(|value| *value > 0),
+            // Neither player records when a song was added; the file's creation
+            // time is the closest thing they keep
+            added_at: get_i64(8).filter(|value| *value > 0),
+        });
+    }
+    drop(rows);
+
+    let item_columns = table_columns(&conn, "playlist_items")?;
+    if item_columns.contains(ref_column) && item_columns.contains(url_column) {
+        library.playlists = read_playlists(
+            &conn,
+            ref_column,
+            url_column,
+            &locations,
+            &mut library.tracks,
+        )?;
+    }
+
+    Ok((source, library))
+}
+
+/// Playlist entries either point at a collection song or carry their own
+/// location for files outside the collection. The latter are added to
+/// `tracks` without stats so they can still be matched to the library.
+fn read_playlists(
+    conn: &Connection,
+    ref_column: &str,
+    url_column: &str,
+    locations: &HashMap<i64, String>,
+    tracks: &mut Vec<HistoryTrack>,
+) -> Result<Vec<(String, Vec<String>)>, String> {
+    let mut known: HashSet<String> = locations.values().cloned().collect();
+    let mut playlists: Vec<(i64, String, Vec<String>)> = Vec::new();
+    let mut stmt = conn
+        .prepare("SELECT ROWID, name FROM playlists ORDER BY ROWID")
+        .map_err(|error| error.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?))
+        })
+        .map_err(|error| error.to_string())?;
+    for row in rows {
+        let (id, name) = row.map_err(|error| error.to_string())?;
+        playlists.push((id, name.unwrap_or_default(), Vec::new()));
+    }
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT playlist, {}, {}, title, artist, length FROM playlist_items ORDER BY ROWID",
+            ref_column, url_column
+        ))
+        .map_err(|error| error.to_string())?;
+    let mut rows = stmt.query([]).map_err(|error| error.to_string())?;
+    while let Some(row) = rows.next().map_err(|error| error.to_string())? {
+        let playlist_id: i64 = row.get(0).map_err(|error| error.to_string())?;
+        let Some((_, _, entries)) = playlists.iter_mut().find(|(id, ..)| *id == playlist_id) else {
+            continue;
+        };
+        let song = row.get::<_, Option<i64>>(1).ok().flatten();
+        if let Some(location) = song.and_then(|id| locations.get(&id)) {
+            entries.push(location.clone());
+            continue;
+        }
+
+        let location = text_value(row.get(2).map_err(|error| error.to_string())?);
+        if location.is_empty() {
+            continue;
+        }
+        if known.insert(location.clone()) {
+            tracks.push(HistoryTrack {
+                location: location.clone(),
+                title: row.get::<_, Option<String>>(3).ok().flatten(),
+                artist: row.get::<_, Option<String>>(4).ok().flatten(),
+                duration_seconds: length_seconds(row.get::<_, Option<i64>>(5).ok().flatten()),
+                ..Default::default()
+            });
+        }
+        entries.push(location);
+    }
+
+    Ok(playlists
+        .into_iter()
+        .filter(|(_, name, _)| !name.is_empty())
+        .map(|(_, name, entries)| (name, entries))
+        .collect())
+}
+
+fn table_columns(conn: &Connection, table: &str) -> Result<HashSet<String>, String> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({})", table))
+        .map_err(|error| error.to_string())?;
+    let rows = stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .map_err(|error| error.to_string())?;
+    rows.collect::<Result<HashSet<_>, _>>()
+        .map_err(|error| error.to_string())
+}
+
+/// Newer Strawberry versions store URLs as blobs
+fn text_value(value: Value) -> String {
+    match value {
+        Value::Text(text) => text,
+        Value::Blob(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+        _ => String::new(),
+    }
+}
+
+fn length_seconds(nanos: Option<i64>) -> Option<f64> {
+    nanos
+        .filter(|value| *value > 0)
+        .map(|value| value as f64 / NANOS_PER_SECOND)
+}
+
+/// Ratings are stored as 0.0-1.0, with -1 for unrated songs
+fn stars_from_fraction(value: f64) -> Option<f32> {
+    (value > 0.0).then(|| ((value.min(1.0) * 10.0).round() / 2.0) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library_db(location_column: &str, ref_column: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("muro-{}.db", uuid::Uuid::new_v4()));
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(&format!(
+            "CREATE TABLE songs ({loc}, title TEXT, artist TEXT, length INTEGER,
+                rating REAL, playcount INTEGER, lastplayed INTEGER, ctime INTEGER);
+             CREATE TABLE playlists (name TEXT);
+             CREATE TABLE playlist_items (playlist INTEGER, {rf} INTEGER, {loc},
+                title TEXT, artist TEXT, length INTEGER);",
+            loc = location_column,
+            rf = ref_column,
+        ))
+        .unwrap();
+        path
+    }
+
+    #[test]
+    fn reads_strawberry_songs_and_playlists() {
+        let path = library_db("url BLOB", "collection_id");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute(
+                "INSERT INTO songs VALUES (?1, 'Song', 'Artist', 215000000000, 0.8, 7, 1700000000, 1600000000)",
+                [b"file:///music/song.flac".to_vec()],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO songs VALUES ('file:///music/unrated.flac', 'Other', 'Artist', 0, -1, 0, -1, 0)",
+                [],
+            )
+            .unwrap();
+            conn.execute_batch(
+                "INSERT INTO playlists VALUES ('Mix');
+                 INSERT INTO playlist_items VALUES (1, 1, NULL, NULL, NULL, NULL);
+                 INSERT INTO playlist_items VALUES (1, NULL, 'file:///elsewhere/x.mp3', 'X', 'Y', 180000000000);",
+            )
+            .unwrap();
+        }
+
+        let (source, library) = read_library(&path).unwrap();
+        assert_eq!(source, "strawberry");
+        assert_eq!(library.tracks.len(), 3);
+
+        let song = &library.tracks[0];
+        assert_eq!(song.location, "file:///music/song.flac");
+        assert_eq!(song.duration_seconds, Some(215.0));
+        assert_eq!(song.rating, Some(4.0));
+        assert_eq!(song.play_count, Some(7));
+        assert_eq!(song.last_played, Some(1_700_000_000));
+        assert_eq!(song.added_at, Some(1_600_000_000));
+
+        let unrated = &library.tracks[1];
+        assert_eq!(unrated.rating, None);
+        assert_eq!(unrated.duration_seconds, None);
+        assert_eq!(unrated.last_played, None);
+
+        // Entries outside the collection are added without stats
+        let outside = &library.tracks[2];
+        assert_eq!(outside.location, "file:///elsewhere/x.mp3");
+        assert_eq!(outside.title.as_deref(), Some("X"));
+        assert_eq!(outside.play_count, None);
+
+        assert_eq!(
+            library.playlists,
+            vec![(
+                "Mix".to_string(),
+                vec![
+                    "file:///music/song.flac".to_string(),
+                    "file:///elsewhere/x.mp3".to_string()
+                ]
+            )]
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reads_clementine_column_names() {
+        let path = library_db("filename TEXT", "library_id");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "INSERT INTO songs VALUES ('file:///music/old.ogg', 'Old', 'Band', 100000000000, 0.5, 2, 1500000000, 1400000000);
+                 INSERT INTO playlists VALUES ('Old Mix');
+                 INSERT INTO playlist_items VALUES (1, 1, NULL, NULL, NULL, NULL);",
+            )
+            .unwrap();
+        }
+
+        let (source, library) = read_library(&path).unwrap();
+        assert_eq!(source, "clementine");
+        assert_eq!(library.tracks.len(), 1);
+        assert_eq!(library.tracks[0].rating, Some(2.5));
+        assert_eq!(
+            library.playlists,
+            vec![(
+                "Old Mix".to_string(),
+                vec!["file:///music/old.ogg".to_string()]
+            )]
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,526 @@
+use crate::albums;
+use crate::artists::{self, ArtistCredits};
+use crate::backfill;
+use crate::import;
+use crate::tag_write;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Title,
+    Artist,
+    AlbumArtist,
+    Album,
+    Track,
+    Disc,
+    Year,
+    Genre,
+    /// Matches anything and is thrown away
+    Ignore,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "title" => Some(Self::Title),
+            "artist" => Some(Self::Artist),
+            "albumartist" => Some(Self::AlbumArtist),
+            "album" => Some(Self::Album),
+            "track" => Some(Self::Track),
+            "disc" => Some(Self::Disc),
+            "year" => Some(Self::Year),
+            "genre" => Some(Self::Genre),
+            "ignore" => Some(Self::Ignore),
+            _ => None,
+        }
+    }
+
+    fn is_number(self) -> bool {
+        matches!(self, Self::Track | Self::Disc | Self::Year)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(String),
+    Field(Field),
+}
+
+/// A pattern such as `%artist%/%album%/%track% - %title%`. Each `/`-separated
+/// part matches one path component, counted back from the file name (without
+/// its extension), so the pattern only needs to describe the tail of the path.
+#[derive(Debug)]
+pub struct PathPattern {
+    source: String,
+    components: Vec<Vec<Token>>,
+}
+
+/// Metadata read from a path
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct GuessedTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album_artist: Option<String>,
+    pub album: Option<String>,
+    pub track_number: Option<i32>,
+    pub disc_number: Option<i32>,
+    pub year: Option<i32>,
+    pub genre: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct FieldChange {
+    /// Field name as used by `update_track_metadata`
+    pub field: &'static str,
+    pub current: Option<String>,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct FilenameTagPreview {
+    pub track_id: String,
+    pub source_path: String,
+    /// First pattern that matched the path, if any
+    pub pattern: Option<String>,
+    pub tags: GuessedTags,
+    /// Fields that applying would fill in
+    pub changes: Vec<FieldChange>,
+}
+
+struct CurrentTags {
+    id: String,
+    source_path: String,
+    title: Option<String>,
+    artist: Option<String>,
+    album_artist: Option<String>,
+    album: Option<String>,
+    track_number: Option<i32>,
+    disc_number: Option<i32>,
+    year: Option<i32>,
+    genre_json: Option<String>,
+}
+
+impl PathPattern {
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        let source = pattern.trim().replace('\\', "/");
+        let mut components = Vec::new();
+        for part in source.split('/').filter(|part| !part.is_empty()) {
+            components.push(tokenize(part).map_err(|error| format!("{}: {}", pattern, error))?);
+        }
+        if !components
+            .iter()
+            .flatten()
+            .any(|token| matches!(token, Token::Field(field) if *field != Field::Ignore))
+        {
+            return Err(format!("{}: pattern has no fields", pattern));
+        }
+        Ok(Self { source, components })
+    }
+
+    pub fn extract(&self, path: &Path) -> Option<GuessedTags> {
+        let stem = path.file_stem()?.to_str()?;
+        let mut parts = vec![stem];
+        let mut parent = path.parent();
+        while parts.len() < self.components.len() {
+            let dir = parent?;
+            parts.push(dir.file_name()?.to_str()?);
+            parent = dir.parent();
+        }
+        parts.reverse();
+
+        let mut tags = GuessedTags::default();
+        for (tokens, part) in self.components.iter().zip(parts) {
+            let mut captures = Vec::new();
+            if !match_tokens(tokens, part, &mut captures) {
+                return None;
+            }
+            for (field, value) in captures {
+                set_field(&mut tags, field, value);
+            }
+        }
+        Some(tags)
+    }
+}
+
+fn tokenize(part: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = part;
+    while let Some(start) = rest.find('%') {
+        if start > 0 {
+            tokens.push(Token::Literal(rest[..start].to_string()));
+        }
+        let after = &rest[start + 1..];
+        let end = after.find('%').ok_or("unclosed %")?;
+        let name = after[..end].to_lowercase();
+        let field = Field::from_name(&name).ok_or_else(|| format!("unknown field %{}%", name))?;
+        // Two fields in a row cannot be told apart
+        if matches!(tokens.last(), Some(Token::Field(_))) {
+            return Err("fields must be separated by text".to_string());
+        }
+        tokens.push(Token::Field(field));
+        rest = &after[end + 1..];
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Literal(rest.to_string()));
+    }
+    Ok(tokens)
+}
+
+/// Match `text` against the tokens, capturing as little as possible for each
+/// field so that e.g. `%artist% - %title%` splits at the first " - "
+fn match_tokens<'a>(tokens: &[Token], text: &'a str, captures: &mut Vec<(Field, &'a str)>) -> bool {
+    let Some((token, rest_tokens)) = tokens.split_first() else {
+        return text.is_empty();
+    };
+
+    match token {
+        Token::Literal(literal) => {
+            text.get(..literal.len())
+                .is_some_and(|head| head.eq_ignore_ascii_case(literal))
+                && match_tokens(rest_tokens, &text[literal.len()..], captures)
+        }
+        Token::Field(field) => {
+            let ends = text
+                .char_indices()
+                .map(|(index, _)| index)
+                .skip(1)
+                .chain(std::iter::once(text.len()));
+            for end in ends {
+                let value = &text[..end];
+                if field.is_number() && !value.trim().chars().all(|ch| ch.is_ascii_digit()) {
+                    return false;
+                }
+                captures.push((*field, value));
+                if match_tokens(rest_tokens, &text[end..], captures) {
+                    return true;
+                }
+                captures.pop();
+            }
+            false
+        }
+    }
+}
+
+fn set_field(tags: &mut GuessedTags, field: Field, value: &str) {
+    // Underscores commonly stand in for spaces in file names
+    let text = value.replace('_', " ").trim().to_string();
+    if text.is_empty() {
+        return;
+    }
+    let number = || text.parse::<i32>().ok();
+    match field {
+        Field::Title => tags.title = Some(text),
+        Field::Artist => tags.artist = Some(text),
+        Field::AlbumArtist => tags.album_artist = Some(text),
+        Field::Album => tags.album = Some(text),
+        Field::Track => tags.track_number = number(),
+        Field::Disc => tags.disc_number = number(),
+        Field::Year => tags.year = number(),
+        Field::Genre => tags.genre = Some(text),
+        Field::Ignore => {}
+    }
+}
+
+/// Show what the first matching pattern would extract for each track, and
+/// which fields applying it would fill in. Only fields that are empty or
+/// still hold the import placeholder ("Unknown Artist", or the file name as
+/// title) are changed; tagged values are never overwritten.
+pub fn preview_filename_tags(
+    db_path: &str,
+    track_ids: &[String],
+    patterns: &[String],
+) -> Result<Vec<FilenameTagPreview>, String> {
+    let patterns = patterns
+        .iter()
+        .filter(|pattern| !pattern.trim().is_empty())
+        .map(|pattern| PathPattern::parse(pattern))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if !Path::new(db_path).exists() {
+        return Err("Database not found".to_string());
+    }
+    let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    let mut previews = Vec::new();
+    for current in load_current_tags(&conn, track_ids)? {
+        let path = Path::new(&current.source_path);
+        let matched = patterns
+            .iter()
+            .find_map(|pattern| pattern.extract(path).map(|tags| (pattern, tags)));
+        let (pattern, tags) = match matched {
+            Some((pattern, tags)) => (Some(pattern.source.clone()), tags),
+            None => (None, GuessedTags::default()),
+        };
+        let changes = field_changes(&current, &tags);
+        previews.push(FilenameTagPreview {
+            track_id: current.id,
+            source_path: current.source_path,
+            pattern,
+            tags,
+            changes,
+        });
+    }
+    Ok(previews)
+}
+
+/// Write the fields `preview_filename_tags` reports to the database and the
+/// files' tags. Returns the number of tracks that changed.
+pub fn apply_filename_tags(
+    db_path: &str,
+    track_ids: &[String],
+    patterns: &[String],
+) -> Result<usize, String> {
+    let previews = preview_filename_tags(db_path, track_ids, patterns)?;
+    let now = import::current_timestamp();
+    let mut conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    import::ensure_schema(&conn)?;
+    let various_artists = albums::various_artists_name(&conn)?;
+    let tx = conn.transaction().map_err(|error| error.to_string())?;
+    let mut written: Vec<(&str, Vec<String>)> = Vec::new();
+    for preview in previews
+        .iter()
+        .filter(|preview| !preview.changes.is_empty())
+    {
+        let tags = &preview.tags;
+        let changed = |field: &str| preview.changes.iter().any(|change| change.field == field);
+        let genre_json = tags
+            .genre
+            .as_ref()
+            .filter(|_| changed("genre"))
+            .map(|genre| serde_json::to_string(&[genre]).unwrap_or_else(|_| "[]".to_string()));
+        // Clearing search_text lets the backfill below index the new values
+        tx.execute(
+            "UPDATE tracks SET
+                title = COALESCE(?1, title),
+                artist = COALESCE(?2, artist),
+                album_artist = COALESCE(?3, album_artist),
+                album = COALESCE(?4, album),
+                track_number = COALESCE(?5, track_number),
+                disc_number = COALESCE(?6, disc_number),
+                year = COALESCE(?7, year),
+                genre_json = COALESCE(?8, genre_json),
+                search_text = NULL,
+                updated_at = ?9
+             WHERE id = ?10",
+            rusqlite::params![
+                tags.title.as_ref().filter(|_| changed("title")),
+                tags.artist.as_ref().filter(|_| changed("artist")),
+                tags.album_artist.as_ref().filter(|_| changed("artists")),
+                tags.album.as_ref().filter(|_| changed("album")),
+                tags.track_number.filter(|_| changed("trackNumber")),
+                tags.disc_number.filter(|_| changed("discNumber")),
+                tags.year.filter(|_| changed("year")),
+                genre_json,
+                now,
+                preview.track_id
+            ],
+        )
+        .map_err(|error| error.to_string())?;
+        // Guesses only fill fields the file left empty, so there are no
+        // multi-value tags to preserve
+        if ["title", "artist", "artists"]
+            .iter()
+            .any(|field| changed(field))
+        {
+            if let Some(credits) = ArtistCredits::from_row(&tx, &preview.track_id)? {
+                artists::set_track_credits(&tx, &preview.track_id, &credits, &artists::ALL_ROLES)?;
+            }
+        }
+        if ["album", "artist", "artists", "year"]
+            .iter()
+            .any(|field| changed(field))
+        {
+            albums::assign_album(&tx, &preview.track_id, &various_artists)?;
+        }
+        let fields = preview
+            .changes
+            .iter()
+            .map(|change| change.field.to_string())
+            .collect();
+        written.push((preview.track_id.as_str(), fields));
+    }
+    tx.commit().map_err(|error| error.to_string())?;
+
+    // The guessed values go to the files as well. Failures are recorded on
+    // the track so retry_tag_writes can pick them up later.
+    for (track_id, fields) in &written {
+        let _ = tag_write::sync_tags_to_file(&conn, track_id, fields);
+    }
+
+    let updated = written.len();
+    if updated > 0 {
+        backfill::run_backfill(db_path)?;
+    }
+    Ok(updated)
+}
+
+fn load_current_tags(conn: &Connection, track_ids: &[String]) -> Result<Vec<CurrentTags>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, source_path, title, artist, album_artist, album, track_number,
+                    disc_number, year, genre_json
+             FROM tracks WHERE id = ?1 AND source_path IS NOT NULL",
+        )
+        .map_err(|error| error.to_string())?;
+
+    let mut tracks = Vec::new();
+    for track_id in track_ids {
+        let rows = stmt
+            .query_map([track_id], |row| {
+                Ok(CurrentTags {
+                    id: row.get(0)?,
+                    source_path: row.get(1)?,
+                    title: row.get(2)?,
+                    artist: row.get(3)?,
+                    album_artist: row.get(4)?,
+                    album: row.get(5)?,
+                    track_number: row.get(6)?,
+                    disc_number: row.get(7)?,
+                    year: row.get(8)?,
+                    genre_json: row.get(9)?,
+                })
+            })
+            .map_err(|error| error.to_string())?;
+        for row in rows {
+            tracks.push(row.map_err(|error| error.to_string())?);
+        }
+    }
+    Ok(tracks)
+}
+
+fn field_changes(current: &CurrentTags, tags: &GuessedTags) -> Vec<FieldChange> {
+    let path = Path::new(&current.source_path);
+    let fallback_title = import::fallback_title(path);
+    let is_placeholder = |value: &Option<String>, placeholder: &str| {
+        value
+            .as_deref()
+            .map(str::trim)
+            .is_none_or(|value| value.is_empty() || value == placeholder)
+    };
+    let genres: Vec<String> = current
+        .genre_json
+        .as_deref()
+        .and_then(|value| serde_json::from_str(value).ok())
+        .unwrap_or_default();
+
+    let mut changes = Vec::new();
+    let mut push = |field: &'static str, current: Option<String>, value: Option<String>| {
+        if let Some(value) = value.filter(|value| current.as_ref() != Some(value)) {
+            changes.push(FieldChange {
+                field,
+                current,
+                value,
+            });
+        }
+    };
+    if is_placeholder(&current.title, &fallback_title) {
+        push("title", current.title.clone(), tags.title.clone());
+    }
+    if is_placeholder(&current.artist, import::UNKNOWN_ARTIST) {
+        push("artist", current.artist.clone(), tags.artist.clone());
+    }
+    if is_placeholder(&current.album_artist, "") {
+        push("artists", None, tags.album_artist.clone());
+    }
+    if is_placeholder(&current.album, import::UNKNOWN_ALBUM) {
+        push("album", current.album.clone(), tags.album.clone());
+    }
+    let numbers = [
+        ("trackNumber", current.track_number, tags.track_number),
+        ("discNumber", current.disc_number, tags.disc_number),
+        ("year", current.year, tags.year),
+    ];
+    for (field, current, guessed) in numbers {
+        if current.unwrap_or(0) <= 0 {
+            push(field, None, guessed.map(|value| value.to_string()));
+        }
+    }
+    if genres.is_empty() {
+        push("genre", None, tags.genre.clone());
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_read_the_tail_of_the_path() {
+        let pattern = PathPattern::parse("%artist%/%album%/%track% - %title%").unwrap();
+        let tags = pattern
+            .extract(Path::new(
+                "/music/Some Artist/An Album/03 - Song - Remix.flac",
+            ))
+            .unwrap();
+        assert_eq!(tags.artist.as_deref(), Some("Some Artist"));
+        assert_eq!(tags.album.as_deref(), Some("An Album"));
+        assert_eq!(tags.track_number, Some(3));
+        assert_eq!(tags.title.as_deref(), Some("Song - Remix"));
+
+        let pattern = PathPattern::parse("%track%_%artist%_-_%title%").unwrap();
+        let tags = pattern
+            .extract(Path::new("/x/07_The_Band_-_A_Song.mp3"))
+            .unwrap();
+        assert_eq!(tags.artist.as_deref(), Some("The Band"));
+        assert_eq!(tags.title.as_deref(), Some("A Song"));
+
+        // Track numbers must be digits
+        let pattern = PathPattern::parse("%track% - %title%").unwrap();
+        assert_eq!(pattern.extract(Path::new("/x/Artist - Title.mp3")), None);
+
+        assert!(PathPattern::parse("%artist%%title%").is_err());
+        assert!(PathPattern::parse("%nope% - %title%").is_err());
+    }
+
+    #[test]
+    fn applied_guesses_are_written_to_the_file() {
+        use lofty::prelude::{Accessor, TaggedFileExt};
+        let dir = std::env::temp_dir().join(format!("muro-tag-guess-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("Some Artist")).unwrap();
+        let path = dir.join("Some Artist").join("03 - Song.wav");
+        let mut wav = b"RIFF".to_vec();
+        wav.extend_from_slice(&(36 + 1600u32).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        for value in [16u32, 0x0001_0001, 8000, 16000, 0x0010_0002] {
+            wav.extend_from_slice(&value.to_le_bytes());
+        }
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&1600u32.to_le_bytes());
+        wav.extend(std::iter::repeat_n(0, 1600));
+        std::fs::write(&path, wav).unwrap();
+
+        let db_path = dir.join("library.db");
+        let db = db_path.to_str().unwrap();
+        let conn = Connection::open(db).unwrap();
+        import::ensure_schema(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO tracks (id, source_path) VALUES ('t', ?1)",
+            [path.to_str().unwrap()],
+        )
+        .unwrap();
+
+        let patterns = ["%artist%/%track% - %title%".to_string()];
+        assert_eq!(
+            apply_filename_tags(db, &["t".to_string()], &patterns).unwrap(),
+            1
+        );
+
+        let (error, pending): (Option<String>, Option<String>) = conn
+            .query_row(
+                "SELECT last_write_error, pending_write_json FROM tracks WHERE id = 't'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((error, pending), (None, None));
+        let tagged = lofty::read_from_path(&path).unwrap();
+        let tag = tagged.primary_tag().unwrap();
+        assert_eq!(tag.title().as_deref(), Some("Song"));
+        assert_eq!(tag.artist().as_deref(), Some("Some Artist"));
+        assert_eq!(tag.track(), Some(3));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
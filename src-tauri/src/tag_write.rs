@@ -0,0 +1,808 @@
+use crate::formats;
+use crate::import;
+use crate::lyrics::{self, LyricLine};
+use lofty::config::WriteOptions;
+use lofty::file::{FileType, TaggedFile};
+use lofty::id3::v2::{
+    CommentFrame, Frame, FrameFlags, FrameValue, Id3v2Tag, SyncTextContentType, SynchronizedText,
+    TimestampFormat, UnsynchronizedTextFrame,
+};
+use lofty::picture::{MimeType, Picture, PictureType};
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::{ItemKey, ItemValue, Tag, TagItem, TagType};
+use lofty::TextEncoding;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Keys of `update_track_metadata` that end up in the file's tags
+const TAG_WRITE_KEYS: [&str; 16] = [
+    "title",
+    "artist",
+    "artists",
+    "album",
+    "trackNumber",
+    "trackTotal",
+    "discNumber",
+    "discTotal",
+    "year",
+    "genre",
+    "comment",
+    "label",
+    "bpm",
+    "key",
+    "rating",
+    "coverArtPath",
+];
+
+/// What to do with the lyrics in a file whose tags are being saved
+pub(crate) enum LyricsUpdate<'a> {
+    /// Leave the lyrics, including SYLT frames, as they are
+    Keep,
+    /// Plain text, with the timed lines when the lyrics are synced
+    Set {
+        text: &'a str,
+        lines: &'a [LyricLine],
+    },
+    Remove,
+}
+
+/// Write `keys` plus any writes still pending from earlier failures to the
+/// track's file, taking the values from the database. Any failure, including
+/// a database value that cannot be read back, is recorded in
+/// `last_write_error` with the keys kept in `pending_write_json` so
+/// `retry_tag_writes` can finish the job later.
+pub(crate) fn sync_tags_to_file(
+    conn: &Connection,
+    track_id: &str,
+    keys: &[String],
+) -> Result<(), String> {
+    let pending_json: Option<String> = conn
+        .query_row(
+            "SELECT pending_write_json FROM tracks WHERE id = ?1",
+            [track_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let pending = merge_pending(pending_json.as_deref(), keys);
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let result = write_pending(conn, track_id, &pending);
+    match &result {
+        Ok(()) => conn.execute(
+            "UPDATE tracks SET last_write_error = NULL, pending_write_json = NULL WHERE id = ?1",
+            [track_id],
+        ),
+        Err(error) => conn.execute(
+            "UPDATE tracks SET last_write_error = ?1, pending_write_json = ?2 WHERE id = ?3",
+            rusqlite::params![
+                error,
+                serde_json::to_string(&pending).map_err(|e| e.to_string())?,
+                track_id
+            ],
+        ),
+    }
+    .map_err(|e| e.to_string())?;
+
+    result
+}
+
+/// Keys still pending from earlier failures followed by the new tag keys
+fn merge_pending(pending_json: Option<&str>, keys: &[String]) -> Vec<String> {
+    let mut pending: Vec<String> = pending_json
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default();
+    for key in keys {
+        if TAG_WRITE_KEYS.contains(&key.as_str()) && !pending.contains(key) {
+            pending.push(key.clone());
+        }
+    }
+    pending
+}
+
+/// Write the database values of `pending` to the track's file
+fn write_pending(conn: &Connection, track_id: &str, pending: &[String]) -> Result<(), String> {
+    let (source_path, values, cover_art_path) = conn
+        .query_row(
+            "SELECT source_path, title, artist, album_artist, album,
+                    track_number, track_total, disc_number, disc_total, year, genre_json,
+                    comment_json, label, bpm, key, rating, cover_art_path
+             FROM tracks WHERE id = ?1",
+            [track_id],
+            |row| {
+                let text = |index: usize| -> rusqlite::Result<serde_json::Value> {
+                    Ok(row
+                        .get::<_, Option<String>>(index)?
+                        .map(serde_json::Value::from)
+                        .unwrap_or_default())
+                };
+                let number = |index: usize| -> rusqlite::Result<serde_json::Value> {
+                    Ok(row
+                        .get::<_, Option<i64>>(index)?
+                        .map(serde_json::Value::from)
+                        .unwrap_or_default())
+                };
+                let list = |index: usize| -> rusqlite::Result<serde_json::Value> {
+                    Ok(row
+                        .get::<_, Option<String>>(index)?
+                        .and_then(|json| serde_json::from_str::<Vec<String>>(&json).ok())
+                        .map(serde_json::Value::from)
+                        .unwrap_or_default())
+                };
+                let values: HashMap<String, serde_json::Value> = [
+                    ("title", text(1)?),
+                    ("artist", text(2)?),
+                    // Several album artists are shown joined with "; "
+                    (
+                        "artists",
+                        row.get::<_, Option<String>>(3)?
+                            .map(|value| serde_json::Value::from(import::split_multi_value(&value)))
+                            .unwrap_or_default(),
+                    ),
+                    ("album", text(4)?),
+                    ("trackNumber", number(5)?),
+                    ("trackTotal", number(6)?),
+                    ("discNumber", number(7)?),
+                    ("discTotal", number(8)?),
+                    ("year", number(9)?),
+                    ("genre", list(10)?),
+                    ("comment", list(11)?),
+                    ("label", text(12)?),
+                    (
+                        "bpm",
+                        row.get::<_, Option<f64>>(13)?
+                            .map(serde_json::Value::from)
+                            .unwrap_or_default(),
+                    ),
+                    ("key", text(14)?),
+                    (
+                        "rating",
+                        row.get::<_, Option<f64>>(15)?
+                            .map(serde_json::Value::from)
+                            .unwrap_or_default(),
+                    ),
+                ]
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect();
+                Ok((
+                    row.get::<_, Option<String>>(0)?,
+                    values,
+                    row.get::<_, Option<String>>(16)?,
+                ))
+            },
+        )
+        .map_err(|e| e.to_string())?;
+
+    let updates: HashMap<String, serde_json::Value> = values
+        .into_iter()
+        .filter(|(key, _)| pending.contains(key))
+        .collect();
+    let cover = cover_art_path.filter(|_| pending.iter().any(|key| key == "coverArtPath"));
+    match source_path {
+        Some(path) => write_tags_to_file(&path, &updates, cover.as_deref()),
+        None => Err("Track has no file".to_string()),
+    }
+}
+
+/// Write metadata tags back to an audio file on disk.
+/// Non-fatal: the DB is the source of truth; `sync_tags_to_file` records
+/// failures so they can be retried.
+pub(crate) fn write_tags_to_file(
+    source_path: &str,
+    updates: &HashMap<String, serde_json::Value>,
+    cover_art_full_path: Option<&str>,
+) -> Result<(), String> {
+    let path = Path::new(source_path);
+    let (
+        file_type,
+        formats::FileTags {
+            mut tagged,
+            id3v2: read_id3v2,
+        },
+    ) = read_for_write(path)?;
+    let tag = tagged.primary_tag_mut().unwrap();
+
+    for (key, value) in updates {
+        if value.is_null() {
+            continue;
+        }
+        if let Some(item_key) = multi_value_key(key) {
+            set_multi_value(tag, item_key, tag_values(value));
+            continue;
+        }
+
+        let text_value = if let Some(s) = value.as_str() {
+            s.to_string()
+        } else {
+            value.to_string()
+        };
+
+        match key.as_str() {
+            "title" => {
+                tag.insert(TagItem::new(
+                    ItemKey::TrackTitle,
+                    ItemValue::Text(text_value),
+                ));
+            }
+            "artist" => {
+                tag.insert(TagItem::new(
+                    ItemKey::TrackArtist,
+                    ItemValue::Text(text_value),
+                ));
+            }
+            "album" => {
+                tag.insert(TagItem::new(
+                    ItemKey::AlbumTitle,
+                    ItemValue::Text(text_value),
+                ));
+            }
+            "trackNumber" => {
+                tag.insert(TagItem::new(
+                    ItemKey::TrackNumber,
+                    ItemValue::Text(text_value),
+                ));
+            }
+            "trackTotal" => {
+                tag.insert(TagItem::new(
+                    ItemKey::TrackTotal,
+                    ItemValue::Text(text_value),
+                ));
+            }
+            "discNumber" => {
+                tag.insert(TagItem::new(
+                    ItemKey::DiscNumber,
+                    ItemValue::Text(text_value),
+                ));
+            }
+            "discTotal" => {
+                tag.insert(TagItem::new(
+                    ItemKey::DiscTotal,
+                    ItemValue::Text(text_value),
+                ));
+            }
+            "year" => {
+                tag.insert(TagItem::new(ItemKey::Year, ItemValue::Text(text_value)));
+            }
+            "label" => {
+                tag.insert(TagItem::new(ItemKey::Label, ItemValue::Text(text_value)));
+            }
+            "bpm" => {
+                tag.insert(TagItem::new(ItemKey::Bpm, ItemValue::Text(text_value)));
+            }
+            "key" => {
+                tag.insert(TagItem::new(
+                    ItemKey::InitialKey,
+                    ItemValue::Text(text_value),
+                ));
+            }
+            "rating" => {
+                if file_type == FileType::Mpeg {
+                    // Write POPM frame for MP3 files
+                    if let Ok(rating_f) = text_value.parse::<f32>() {
+                        let byte = (rating_f * 51.0).round() as u8;
+                        let mut data = Vec::new();
+                        data.push(0); // empty email, null-terminated
+                        data.push(byte);
+                        data.extend_from_slice(&[0, 0, 0, 0]); // play counter
+                        tag.insert(TagItem::new(
+                            ItemKey::Popularimeter,
+                            ItemValue::Binary(data),
+                        ));
+                    }
+                } else {
+                    tag.insert(TagItem::new(
+                        ItemKey::Unknown(String::from("RATING")),
+                        ItemValue::Text(text_value),
+                    ));
+                }
+            }
+            _ => {} // Skip coverArtPath, coverArtThumbPath — handled below
+        }
+    }
+
+    // Handle cover art
+    if let Some(cover_path) = cover_art_full_path {
+        let cover_bytes =
+            std::fs::read(cover_path).map_err(|e| format!("Failed to read cover art: {}", e))?;
+        tag.remove_picture_type(PictureType::CoverFront);
+        tag.push_picture(Picture::new_unchecked(
+            PictureType::CoverFront,
+            Some(MimeType::Jpeg),
+            None,
+            cover_bytes,
+        ));
+    }
+
+    let comments_edited = updates.get("comment").is_some_and(|value| !value.is_null());
+    save_tags(
+        path,
+        file_type,
+        tagged,
+        read_id3v2,
+        comments_edited,
+        &LyricsUpdate::Keep,
+    )
+}
+
+/// Write lyrics to an audio file. ID3v2 tags get the plain text as USLT and
+/// synced lines as a SYLT frame; other formats have a single lyrics field,
+/// which holds synced lines in LRC form.
+pub(crate) fn write_lyrics_to_file(
+    source_path: &str,
+    update: LyricsUpdate<'_>,
+) -> Result<(), String> {
+    let path = Path::new(source_path);
+    let (
+        file_type,
+        formats::FileTags {
+            mut tagged,
+            id3v2: read_id3v2,
+        },
+    ) = read_for_write(path)?;
+    let tag = tagged.primary_tag_mut().unwrap();
+
+    match &update {
+        LyricsUpdate::Set { text, lines } => {
+            let value = if lines.is_empty() || tag.tag_type() == TagType::Id3v2 {
+                text.to_string()
+            } else {
+                lyrics::format_lrc(lines)
+            };
+            tag.insert_text(ItemKey::Lyrics, value);
+        }
+        LyricsUpdate::Remove => {
+            tag.remove_key(&ItemKey::Lyrics);
+        }
+        LyricsUpdate::Keep => {}
+    }
+
+    save_tags(path, file_type, tagged, read_id3v2, false, &update)
+}
+
+/// Read a file's tags for editing, adding an empty primary tag if it has none
+fn read_for_write(path: &Path) -> Result<(FileType, formats::FileTags), String> {
+    if !path.exists() {
+        return Err(format!("File not found: {}", path.display()));
+    }
+
+    let probe = Probe::open(path)
+        .map_err(|e| e.to_string())?
+        .guess_file_type()
+        .map_err(|e| e.to_string())?;
+    let file_type = probe
+        .file_type()
+        .ok_or_else(|| format!("Unknown file type: {}", path.display()))?;
+    let mut tags =
+        formats::read_tags(&mut probe.into_inner(), file_type).map_err(|e| e.to_string())?;
+
+    // Get or create the primary tag
+    if tags.tagged.primary_tag().is_none() {
+        let tag_type = match file_type {
+            FileType::Mpeg | FileType::Aiff | FileType::Wav => TagType::Id3v2,
+            FileType::Flac => TagType::VorbisComments,
+            FileType::Mp4 => TagType::Mp4Ilst,
+            _ => TagType::Id3v2,
+        };
+        tags.tagged.insert_tag(Tag::new(tag_type));
+    }
+    Ok((file_type, tags))
+}
+
+fn save_tags(
+    path: &Path,
+    file_type: FileType,
+    mut tagged: TaggedFile,
+    read_id3v2: Option<Id3v2Tag>,
+    comments_edited: bool,
+    lyrics: &LyricsUpdate<'_>,
+) -> Result<(), String> {
+    // lofty saves a generic tag's comments as one NUL-joined COMM frame and
+    // drops the frames it could not read into it, so ID3v2 is saved apart
+    let id3v2 = match file_type {
+        FileType::Mpeg | FileType::Wav | FileType::Aiff => tagged.remove(TagType::Id3v2),
+        _ => None,
+    }
+    .map(|tag| id3v2_tag(tag, read_id3v2, comments_edited, lyrics));
+    tagged
+        .save_to_path(path, WriteOptions::default())
+        .map_err(|e| format!("Failed to save tags: {}", e))?;
+    if let Some(id3v2) = id3v2 {
+        id3v2
+            .save_to_path(path, WriteOptions::default())
+            .map_err(|e| format!("Failed to save tags: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Editor fields that hold several values
+fn multi_value_key(key: &str) -> Option<ItemKey> {
+    match key {
+        "artists" => Some(ItemKey::AlbumArtist),
+        "genre" => Some(ItemKey::Genre),
+        "comment" => Some(ItemKey::Comment),
+        _ => None,
+    }
+}
+
+fn tag_values(value: &serde_json::Value) -> Vec<String> {
+    let values = match value {
+        serde_json::Value::Array(items) => items
+            .iter()
+            .filter_map(|item| item.as_str())
+            .map(str::to_string)
+            .collect(),
+        serde_json::Value::String(text) => vec![text.clone()],
+        _ => Vec::new(),
+    };
+    values
+        .into_iter()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+/// Replace every value of `key` the way the tag format stores lists, so
+/// reading the file back gives the same values
+pub(crate) fn set_multi_value(tag: &mut Tag, key: ItemKey, values: Vec<String>) {
+    tag.remove_key(&key);
+    if values.is_empty() {
+        return;
+    }
+    match tag.tag_type() {
+        // One field or atom per value
+        TagType::VorbisComments | TagType::Mp4Ilst => {
+            for value in values {
+                tag.push(TagItem::new(key.clone(), ItemValue::Text(value)));
+            }
+        }
+        // Comments are told apart by their descriptions, see `id3v2_tag`
+        TagType::Id3v2 if key == ItemKey::Comment => {
+            for value in values {
+                tag.push(TagItem::new(key.clone(), ItemValue::Text(value)));
+            }
+        }
+        // ID3v2.4 and APE keep the values NUL-separated in one frame or item
+        TagType::Id3v2 | TagType::Ape => {
+            tag.insert(TagItem::new(key, ItemValue::Text(values.join("\0"))));
+        }
+        // No list support, so the values share one "; "-joined field
+        _ => {
+            tag.insert(TagItem::new(key, ItemValue::Text(values.join("; "))));
+        }
+    }
+}
+
+/// Convert the edited generic tag, giving each comment its own COMM frame.
+/// Unless the comments were edited, the frames are kept as they were read;
+/// the same goes for SYLT frames and the lyrics.
+fn id3v2_tag(
+    mut tag: Tag,
+    read: Option<Id3v2Tag>,
+    comments_edited: bool,
+    lyrics: &LyricsUpdate<'_>,
+) -> Id3v2Tag {
+    let comments: Vec<String> = tag.take_strings(&ItemKey::Comment).collect();
+    // Converted as is, the lyrics item becomes a USLT frame lofty cannot save
+    let lyrics_text = tag.take_strings(&ItemKey::Lyrics).next();
+    let mut id3v2 = Id3v2Tag::from(tag);
+    if let Some(text) = lyrics_text {
+        id3v2.insert(lyrics_frame(text));
+    }
+    // SYLT survives the generic tag as raw bytes; it is put back from the
+    // frames as read so it is neither duplicated nor left stale
+    id3v2.retain(|frame| frame.id_str() != "SYLT");
+    let keep_sylt = matches!(lyrics, LyricsUpdate::Keep);
+    for frame in read.into_iter().flatten() {
+        let keep = match frame.content() {
+            FrameValue::Comment(comment) => !comments_edited || import::is_machine_comment(comment),
+            FrameValue::Binary(_) => keep_sylt && frame.id_str() == "SYLT",
+            _ => false,
+        };
+        if keep {
+            id3v2.insert(frame);
+        }
+    }
+    if comments_edited {
+        for (index, comment) in comments.into_iter().enumerate() {
+            id3v2.insert(comment_frame(index, comment));
+        }
+    }
+    if let LyricsUpdate::Set { lines, .. } = lyrics {
+        if let Some(frame) = sylt_frame(lines) {
+            id3v2.insert(frame);
+        }
+    }
+    id3v2
+}
+
+fn lyrics_frame(content: String) -> Frame<'static> {
+    let lyrics = UnsynchronizedTextFrame {
+        encoding: TextEncoding::UTF8,
+        language: *b"XXX",
+        description: String::new(),
+        content,
+    };
+    Frame::new("USLT", lyrics, FrameFlags::default()).expect("USLT is a valid frame id")
+}
+
+/// Millisecond-timed SYLT frame holding `lines`, None when there are none
+fn sylt_frame(lines: &[LyricLine]) -> Option<Frame<'static>> {
+    if lines.is_empty() {
+        return None;
+    }
+    let sylt = SynchronizedText {
+        encoding: TextEncoding::UTF8,
+        language: *b"XXX",
+        timestamp_format: TimestampFormat::MS,
+        content_type: SyncTextContentType::Lyrics,
+        description: None,
+        content: lines
+            .iter()
+            .map(|line| (line.time_ms, line.text.clone()))
+            .collect(),
+    };
+    let bytes = sylt.as_bytes().ok()?;
+    Frame::new("SYLT", FrameValue::Binary(bytes), FrameFlags::default()).ok()
+}
+
+/// The first comment has the empty description players show, the others
+/// are numbered so they stay separate frames
+fn comment_frame(index: usize, content: String) -> Frame<'static> {
+    let description = if index == 0 {
+        String::new()
+    } else {
+        format!("Comment {}", index + 1)
+    };
+    let comment = CommentFrame {
+        encoding: TextEncoding::UTF8,
+        language: *b"XXX",
+        description,
+        content,
+    };
+    Frame::new("COMM", comment, FrameFlags::default()).expect("COMM is a valid frame id")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::wav_bytes;
+    use lofty::mp4::Ilst;
+    use lofty::ogg::VorbisComments;
+    use rusqlite::params;
+    use std::fs;
+
+    const COMMENTS: [&str; 2] = ["Live, remastered; twice", "Promo copy"];
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn tag_with_values(tag_type: TagType) -> Tag {
+        let mut tag = Tag::new(tag_type);
+        set_multi_value(&mut tag, ItemKey::Comment, strings(&COMMENTS));
+        set_multi_value(&mut tag, ItemKey::Genre, strings(&["House", "Techno"]));
+        tag
+    }
+
+    fn assert_values(tag: &Tag) {
+        assert_eq!(
+            import::collect_values(tag, ItemKey::Comment, import::split_comments),
+            COMMENTS
+        );
+        assert_eq!(
+            import::collect_values(tag, ItemKey::Genre, import::split_genres),
+            ["House", "Techno"]
+        );
+    }
+
+    #[test]
+    fn vorbis_comments_keep_a_field_per_value() {
+        let tag = tag_with_values(TagType::VorbisComments);
+        let stored = VorbisComments::from(tag);
+        assert_eq!(stored.get_all("COMMENT").count(), 2);
+        assert_values(&Tag::from(stored));
+    }
+
+    #[test]
+    fn mp4_keeps_a_value_per_item() {
+        let tag = tag_with_values(TagType::Mp4Ilst);
+        assert_values(&Tag::from(Ilst::from(tag)));
+    }
+
+    fn read_back(path: &Path) -> formats::FileTags {
+        let mut probed = formats::ProbedFile::open(path).unwrap();
+        probed.read_tags().unwrap()
+    }
+
+    #[test]
+    fn synced_lyrics_are_written_as_sylt() {
+        let dir = std::env::temp_dir().join(format!("muro-tag-write-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("track.wav");
+        fs::write(&path, wav_bytes()).unwrap();
+        let source = path.to_str().unwrap();
+
+        let updates = HashMap::from([("comment".to_string(), serde_json::json!(COMMENTS))]);
+        write_tags_to_file(source, &updates, None).unwrap();
+
+        let lines = vec![
+            LyricLine {
+                time_ms: 1000,
+                text: "First".to_string(),
+            },
+            LyricLine {
+                time_ms: 2500,
+                text: "Second".to_string(),
+            },
+        ];
+        let update = LyricsUpdate::Set {
+            text: "First\nSecond",
+            lines: &lines,
+        };
+        write_lyrics_to_file(source, update).unwrap();
+
+        let tags = read_back(&path);
+        let id3v2 = tags.id3v2.unwrap();
+        assert_eq!(lyrics::sylt_lines(&id3v2), Some(lines.clone()));
+        // USLT holds the plain text, not LRC
+        assert_eq!(
+            tags.tagged
+                .primary_tag()
+                .unwrap()
+                .get_string(&ItemKey::Lyrics),
+            Some("First\nSecond")
+        );
+        assert_eq!(import::id3v2_comments(&id3v2), COMMENTS);
+
+        // Other edits leave a single SYLT frame in place
+        let updates = HashMap::from([("title".to_string(), serde_json::json!("Renamed"))]);
+        write_tags_to_file(source, &updates, None).unwrap();
+        let id3v2 = read_back(&path).id3v2.unwrap();
+        assert_eq!(lyrics::sylt_lines(&id3v2), Some(lines));
+        let sylt_frames = (&id3v2)
+            .into_iter()
+            .filter(|frame| frame.id_str() == "SYLT")
+            .count();
+        assert_eq!(sylt_frames, 1);
+
+        write_lyrics_to_file(source, LyricsUpdate::Remove).unwrap();
+        let tags = read_back(&path);
+        assert_eq!(lyrics::sylt_lines(&tags.id3v2.unwrap()), None);
+        assert_eq!(
+            tags.tagged
+                .primary_tag()
+                .unwrap()
+                .get_string(&ItemKey::Lyrics),
+            None
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn id3v2_writes_a_comment_frame_per_value() {
+        let dir = std::env::temp_dir().join(format!("muro-tag-write-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("track.wav");
+        fs::write(&path, wav_bytes()).unwrap();
+        let source = path.to_str().unwrap();
+
+        let updates = HashMap::from([
+            ("comment".to_string(), serde_json::json!(COMMENTS)),
+            ("genre".to_string(), serde_json::json!(["House", "Techno"])),
+        ]);
+        write_tags_to_file(source, &updates, None).unwrap();
+
+        let tags = read_back(&path);
+        let id3v2 = tags.id3v2.unwrap();
+        let descriptions: Vec<&str> = (&id3v2)
+            .into_iter()
+            .filter_map(|frame| match frame.content() {
+                FrameValue::Comment(comment) => Some(comment.description.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(descriptions, ["", "Comment 2"]);
+        assert_eq!(import::id3v2_comments(&id3v2), COMMENTS);
+        let tag = tags.tagged.primary_tag().unwrap();
+        assert_eq!(
+            import::collect_values(tag, ItemKey::Genre, import::split_genres),
+            ["House", "Techno"]
+        );
+
+        // Other edits keep the comments, including the described ones
+        let updates = HashMap::from([("title".to_string(), serde_json::json!("Renamed"))]);
+        write_tags_to_file(source, &updates, None).unwrap();
+        let tags = read_back(&path);
+        assert_eq!(import::id3v2_comments(&tags.id3v2.unwrap()), COMMENTS);
+        assert_eq!(
+            tags.tagged.primary_tag().unwrap().title().as_deref(),
+            Some("Renamed")
+        );
+
+        let updates = HashMap::from([("comment".to_string(), serde_json::json!(["Only one"]))]);
+        write_tags_to_file(source, &updates, None).unwrap();
+        assert_eq!(
+            import::id3v2_comments(&read_back(&path).id3v2.unwrap()),
+            ["Only one"]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn write_state(conn: &Connection) -> (Option<String>, Option<String>) {
+        conn.query_row(
+            "SELECT last_write_error, pending_write_json FROM tracks WHERE id = 't'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap()
+    }
+
+    fn keys(values: &[&str]) -> Vec<String> {
+        strings(values)
+    }
+
+    #[test]
+    fn pending_keys_merge_until_a_write_succeeds() {
+        let dir = std::env::temp_dir().join(format!("muro-tag-sync-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("track.wav");
+        let conn = Connection::open_in_memory().unwrap();
+        import::ensure_schema(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO tracks (id, title, genre_json, source_path)
+             VALUES ('t', 'Night Drive', '[\"Disco\"]', ?1)",
+            params![path.to_str()],
+        )
+        .unwrap();
+
+        // Nothing to write for fields that are not tags
+        sync_tags_to_file(&conn, "t", &keys(&["playCount"])).unwrap();
+        assert_eq!(write_state(&conn), (None, None));
+
+        // The file is missing, so the keys wait
+        assert!(sync_tags_to_file(&conn, "t", &keys(&["title"])).is_err());
+        assert!(sync_tags_to_file(&conn, "t", &keys(&["genre", "title", "playCount"])).is_err());
+        let (error, pending) = write_state(&conn);
+        assert!(error.unwrap().starts_with("File not found"));
+        assert_eq!(pending.as_deref(), Some(r#"["title","genre"]"#));
+
+        // A retry writes everything still pending and clears the record
+        fs::write(&path, wav_bytes()).unwrap();
+        sync_tags_to_file(&conn, "t", &[]).unwrap();
+        assert_eq!(write_state(&conn), (None, None));
+        let tags = read_back(&path);
+        let tag = tags.tagged.primary_tag().unwrap();
+        assert_eq!(tag.title().as_deref(), Some("Night Drive"));
+        assert_eq!(
+            import::collect_values(tag, ItemKey::Genre, import::split_genres),
+            ["Disco"]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unreadable_values_are_recorded_as_failures() {
+        let conn = Connection::open_in_memory().unwrap();
+        import::ensure_schema(&conn).unwrap();
+        // Older imports could store "3/12" in the numeric column
+        conn.execute(
+            "INSERT INTO tracks (id, title, track_number, source_path)
+             VALUES ('t', 'Intro', '3/12', '/missing/track.mp3')",
+            [],
+        )
+        .unwrap();
+
+        assert!(sync_tags_to_file(&conn, "t", &keys(&["trackNumber"])).is_err());
+        let (error, pending) = write_state(&conn);
+        assert!(error.unwrap().contains("Invalid column type"));
+        assert_eq!(pending.as_deref(), Some(r#"["trackNumber"]"#));
+    }
+}
@@ -0,0 +1,24 @@
+//! Fixtures shared by the unit tests
+
+/// One second of 8 kHz mono 16-bit PCM, enough to be recognised and tagged
+/// as audio
+pub(crate) fn wav_bytes() -> Vec<u8> {
+    let samples: Vec<u8> = (0..8000u32)
+        .flat_map(|index| ((index % 64) as i16 * 256).to_le_bytes())
+        .collect();
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + samples.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&8000u32.to_le_bytes());
+    bytes.extend_from_slice(&16000u32.to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&(samples.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&samples);
+    bytes
+}
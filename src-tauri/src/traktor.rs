@@ -0,0 +1,339 @@
+use crate::dj_collection::{
+    attributes, non_empty, rating_from_byte, Collection, CollectionNode, CollectionTrack, CuePoint,
+    KEY_NAMES,
+};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Traktor separates directories in `DIR` with "/:"
+const DIR_SEPARATOR: &str = "/:";
+/// Playlists Traktor maintains itself for recordings and sampler loops
+const SYSTEM_PLAYLISTS: [&str; 2] = ["_RECORDINGS", "_LOOPS"];
+
+enum OpenNode {
+    Folder(String, Vec<CollectionNode>),
+    Playlist(String, Vec<String>),
+    /// Smartlists and other node types are skipped
+    Other,
+}
+
+/// Parse a Traktor `collection.nml`
+pub fn parse_collection(text: &str) -> Result<Collection, String> {
+    let mut reader = Reader::from_str(text);
+    let mut collection = Collection::default();
+    let mut current: Option<CollectionTrack> = None;
+    let mut in_collection = false;
+    let mut nodes: Vec<OpenNode> = Vec::new();
+
+    loop {
+        let event = reader.read_event().map_err(|error| error.to_string())?;
+        let (element, is_empty) = match &event {
+            Event::Start(element) => (element, false),
+            Event::Empty(element) => (element, true),
+            Event::End(end) => {
+                match end.local_name().as_ref() {
+                    b"COLLECTION" => in_collection = false,
+                    b"ENTRY" if in_collection => {
+                        if let Some(track) = current.take() {
+                            collection.tracks.push(track);
+                        }
+                    }
+                    b"NODE" => {
+                        if let Some(node) = nodes.pop() {
+                            close_node(&mut nodes, &mut collection.nodes, node);
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+
+        let attrs = attributes(element);
+        match element.local_name().as_ref() {
+            b"COLLECTION" => in_collection = !is_empty,
+            b"ENTRY" if in_collection => {
+                let track = CollectionTrack {
+                    title: non_empty(attrs.get("TITLE")),
+                    artist: non_empty(attrs.get("ARTIST")),
+                    ..Default::default()
+                };
+                if is_empty {
+                    collection.tracks.push(track);
+                } else {
+                    current = Some(track);
+                }
+            }
+            b"LOCATION" => {
+                if let Some(track) = current.as_mut() {
+                    let volume = attrs.get("VOLUME").map(String::as_str).unwrap_or("");
+                    let dir = attrs.get("DIR").map(String::as_str).unwrap_or("");
+                    let file = attrs.get("FILE").map(String::as_str).unwrap_or("");
+                    track.key = format!("{}{}{}", volume, dir, file);
+                    track.location = file_path(volume, dir, file);
+                }
+            }
+            b"INFO" => {
+                if let Some(track) = current.as_mut() {
+                    track.comment = non_empty(attrs.get("COMMENT"));
+                    track.rating = attrs
+                        .get("RANKING")
+                        .and_then(|value| rating_from_byte(value));
+                    track.play_count = attrs
+                        .get("PLAYCOUNT")
+                        .and_then(|value| value.parse::<i32>().ok());
+                    track.duration_seconds = attrs
+                        .get("PLAYTIME_FLOAT")
+                        .or_else(|| attrs.get("PLAYTIME"))
+                        .and_then(|value| value.parse::<f64>().ok());
+                    // Shown in the user's key notation; MUSICAL_KEY wins if present
+                    if track.musical_key.is_none() {
+                        track.musical_key = non_empty(attrs.get("KEY"));
+                    }
+                }
+            }
+            b"TEMPO" => {
+                if let Some(track) = current.as_mut() {
+                    track.bpm = attrs
+                        .get("BPM")
+                        .and_then(|value| value.parse::<f64>().ok())
+                        .filter(|bpm| *bpm > 0.0)
+                        .map(|bpm| (bpm * 100.0).round() / 100.0);
+                }
+            }
+            b"MUSICAL_KEY" => {
+                if let Some(track) = current.as_mut() {
+                    if let Some(name) = attrs
+                        .get("VALUE")
+                        .and_then(|value| value.parse::<usize>().ok())
+                        .and_then(|value| KEY_NAMES.get(value))
+                    {
+                        track.musical_key = Some(name.to_string());
+                    }
+                }
+            }
+            b"CUE_V2" => {
+                if let Some(track) = current.as_mut() {
+                    if let Some(cue) = parse_cue(&attrs) {
+                        track.cues.push(cue);
+                    }
+                }
+            }
+            b"NODE" => {
+                let name = attrs.get("NAME").cloned().unwrap_or_default();
+                let node = match attrs.get("TYPE").map(String::as_str) {
+                    Some("FOLDER") => OpenNode::Folder(name, Vec::new()),
+                    Some("PLAYLIST") if !SYSTEM_PLAYLISTS.contains(&name.as_str()) => {
+                        OpenNode::Playlist(name, Vec::new())
+                    }
+                    _ => OpenNode::Other,
+                };
+                if is_empty {
+                    close_node(&mut nodes, &mut collection.nodes, node);
+                } else {
+                    nodes.push(node);
+                }
+            }
+            b"PRIMARYKEY" => {
+                if let (Some(OpenNode::Playlist(_, track_keys)), Some(key)) =
+                    (nodes.last_mut(), attrs.get("KEY"))
+                {
+                    track_keys.push(key.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(collection)
+}
+
+/// Rebuild a path from Traktor's volume/directory/file triple. On Windows the
+/// volume is the drive letter; on macOS it is the volume name, which is the
+/// root for the boot volume and lives under /Volumes otherwise.
+fn file_path(volume: &str, dir: &str, file: &str) -> String {
+    let dir = dir.replace(DIR_SEPARATOR, "/");
+    if volume.len() == 2 && volume.ends_with(':') {
+        return format!("{}{}{}", volume, dir, file);
+    }
+
+    let on_root = format!("{}{}", dir, file);
+    let on_volume = format!("/Volumes/{}{}{}", volume, dir, file);
+    if !Path::new(&on_root).exists() && Path::new(&on_volume).exists() {
+        on_volume
+    } else {
+        on_root
+    }
+}
+
+fn parse_cue(attrs: &HashMap<String, String>) -> Option<CuePoint> {
+    let start_ms = attrs.get("START")?.parse::<f64>().ok()?;
+    let length_ms = attrs
+        .get("LEN")
+        .and_then(|value| value.parse::<f64>().ok())
+        .unwrap_or(0.0);
+    let slot = attrs
+        .get("HOTCUE")
+        .and_then(|value| value.parse::<i32>().ok())
+        .filter(|slot| *slot >= 0);
+    let kind = match attrs.get("TYPE").map(String::as_str) {
+        Some("1") => "fade_in",
+        Some("2") => "fade_out",
+        Some("3") => "load",
+        Some("4") => "grid",
+        Some("5") => "loop",
+        _ if slot.is_some() => "hotcue",
+        _ => "cue",
+    };
+    // Traktor names unnamed cues "n.n."
+    let name = non_empty(attrs.get("NAME")).filter(|name| name != "n.n.");
+
+    Some(CuePoint {
+        kind: kind.to_string(),
+        slot,
+        start_seconds: start_ms / 1000.0,
+        end_seconds: (length_ms > 0.0).then(|| (start_ms + length_ms) / 1000.0),
+        name,
+        color: None,
+    })
+}
+
+/// Attach a finished node to its parent. Traktor's "$ROOT" folder is dropped
+/// and its children become the top-level playlists.
+fn close_node(open: &mut [OpenNode], roots: &mut Vec<CollectionNode>, node: OpenNode) {
+    let node = match node {
+        OpenNode::Folder(name, children) if open.is_empty() && name == "$ROOT" => {
+            roots.extend(children);
+            return;
+        }
+        OpenNode::Folder(name, children) => CollectionNode::Folder { name, children },
+        OpenNode::Playlist(name, track_keys) => CollectionNode::Playlist { name, track_keys },
+        OpenNode::Other => return,
+    };
+
+    match open
+        .iter_mut()
+        .rev()
+        .find(|parent| matches!(parent, OpenNode::Folder(..)))
+    {
+        Some(OpenNode::Folder(_, children)) => children.push(node),
+        _ => roots.push(node),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLLECTION: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="no" ?>
+<NML VERSION="19">
+  <HEAD COMPANY="www.native-instruments.com" PROGRAM="Traktor"/>
+  <COLLECTION ENTRIES="2">
+    <ENTRY TITLE="Opener" ARTIST="DJ A">
+      <LOCATION DIR="/:Users/:dj/:Music/:" FILE="opener.mp3" VOLUME="C:"/>
+      <INFO COMMENT="Warm up" RANKING="153" PLAYCOUNT="3" PLAYTIME="301"
+            PLAYTIME_FLOAT="300.75" KEY="8m"/>
+      <TEMPO BPM="123.996" BPM_QUALITY="100"/>
+      <MUSICAL_KEY VALUE="21"/>
+      <CUE_V2 NAME="AutoGrid" TYPE="4" START="25.5" LEN="0" HOTCUE="0"/>
+      <CUE_V2 NAME="n.n." TYPE="0" START="64500" LEN="0" HOTCUE="1"/>
+      <CUE_V2 NAME="Roll" TYPE="5" START="96000" LEN="2000" HOTCUE="-1"/>
+    </ENTRY>
+    <ENTRY TITLE="Closer">
+      <LOCATION DIR="/:Music/:" FILE="closer.flac" VOLUME="D:"/>
+      <INFO KEY="11d"/>
+    </ENTRY>
+  </COLLECTION>
+  <PLAYLISTS>
+    <NODE TYPE="FOLDER" NAME="$ROOT">
+      <SUBNODES COUNT="3">
+        <NODE TYPE="FOLDER" NAME="Gigs">
+          <SUBNODES COUNT="1">
+            <NODE TYPE="PLAYLIST" NAME="Club">
+              <PLAYLIST ENTRIES="2" TYPE="LIST">
+                <ENTRY><PRIMARYKEY TYPE="TRACK" KEY="D:/:Music/:closer.flac"/></ENTRY>
+                <ENTRY><PRIMARYKEY TYPE="TRACK" KEY="C:/:Users/:dj/:Music/:opener.mp3"/></ENTRY>
+              </PLAYLIST>
+            </NODE>
+          </SUBNODES>
+        </NODE>
+        <NODE TYPE="PLAYLIST" NAME="_RECORDINGS">
+          <PLAYLIST ENTRIES="0" TYPE="LIST"></PLAYLIST>
+        </NODE>
+        <NODE TYPE="SMARTLIST" NAME="Recent">
+          <SMARTLIST><SEARCH_EXPRESSION VERSION="1" QUERY="$IMPORTDATE &gt; 30"/></SMARTLIST>
+        </NODE>
+      </SUBNODES>
+    </NODE>
+  </PLAYLISTS>
+</NML>"#;
+
+    #[test]
+    fn parses_entries_and_cues() {
+        let collection = parse_collection(COLLECTION).unwrap();
+        assert_eq!(collection.tracks.len(), 2);
+
+        let opener = &collection.tracks[0];
+        assert_eq!(opener.key, "C:/:Users/:dj/:Music/:opener.mp3");
+        assert_eq!(opener.location, "C:/Users/dj/Music/opener.mp3");
+        assert!(!opener.location_is_uri);
+        assert_eq!(opener.artist.as_deref(), Some("DJ A"));
+        assert_eq!(opener.comment.as_deref(), Some("Warm up"));
+        assert_eq!(opener.rating, Some(3.0));
+        assert_eq!(opener.play_count, Some(3));
+        assert_eq!(opener.duration_seconds, Some(300.75));
+        assert_eq!(opener.bpm, Some(124.0));
+        // MUSICAL_KEY indexes KEY_NAMES and wins over the display notation
+        assert_eq!(opener.musical_key.as_deref(), Some(KEY_NAMES[21]));
+        assert_eq!(opener.musical_key.as_deref(), Some("Am"));
+
+        let cues: Vec<(&str, Option<i32>, f64, Option<f64>)> = opener
+            .cues
+            .iter()
+            .map(|cue| {
+                (
+                    cue.kind.as_str(),
+                    cue.slot,
+                    cue.start_seconds,
+                    cue.end_seconds,
+                )
+            })
+            .collect();
+        assert_eq!(
+            cues,
+            [
+                ("grid", Some(0), 0.0255, None),
+                ("hotcue", Some(1), 64.5, None),
+                ("loop", None, 96.0, Some(98.0)),
+            ]
+        );
+        assert_eq!(opener.cues[0].name.as_deref(), Some("AutoGrid"));
+        // Traktor's placeholder name is dropped
+        assert_eq!(opener.cues[1].name, None);
+
+        let closer = &collection.tracks[1];
+        assert_eq!(closer.musical_key.as_deref(), Some("11d"));
+        assert_eq!(closer.location, "D:/Music/closer.flac");
+    }
+
+    #[test]
+    fn parses_nested_folders_and_skips_system_lists() {
+        let collection = parse_collection(COLLECTION).unwrap();
+        let [CollectionNode::Folder { name, children }] = collection.nodes.as_slice() else {
+            panic!("expected only the Gigs folder at the top level");
+        };
+        assert_eq!(name, "Gigs");
+        let [CollectionNode::Playlist { name, track_keys }] = children.as_slice() else {
+            panic!("Gigs should hold one playlist");
+        };
+        assert_eq!(name, "Club");
+        assert_eq!(
+            track_keys,
+            &["D:/:Music/:closer.flac", "C:/:Users/:dj/:Music/:opener.mp3"]
+        );
+    }
+}
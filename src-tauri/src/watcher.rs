@@ -0,0 +1,506 @@
+use crate::formats;
+use crate::import;
+use notify::event::{AccessKind, AccessMode, ModifyKind};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use parking_lot::Mutex;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use uuid::Uuid;
+
+/// Quiet period after the last filesystem event before pending files are checked,
+/// so a folder being copied in is picked up as one batch. Files that were not
+/// closed by their writer are only imported once their size and mtime hold still
+/// across two checks.
+const DEBOUNCE: Duration = Duration::from_millis(1500);
+const IDLE_WAIT: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Serialize, Clone)]
+pub struct WatchedFolder {
+    pub id: String,
+    pub path: String,
+    pub added_at: i64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct LibraryChanged {
+    pub source: &'static str,
+    pub tracks: Vec<import::ImportedTrack>,
+}
+
+pub fn ensure_watched_folder_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS watched_folders (
+            id TEXT PRIMARY KEY,
+            path TEXT NOT NULL UNIQUE,
+            added_at INTEGER NOT NULL
+        )",
+        [],
+    )
+    .map_err(|error| error.to_string())?;
+
+    Ok(())
+}
+
+pub fn list_watched_folders(db_path: &str) -> Result<Vec<WatchedFolder>, String> {
+    if !Path::new(db_path).exists() {
+        return Ok(Vec::new());
+    }
+
+    let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    ensure_watched_folder_schema(&conn)?;
+
+    let mut stmt = conn
+        .prepare("SELECT id, path, added_at FROM watched_folders ORDER BY path ASC")
+        .map_err(|error| error.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(WatchedFolder {
+                id: row.get(0)?,
+                path: row.get(1)?,
+                added_at: row.get(2)?,
+            })
+        })
+        .map_err(|error| error.to_string())?;
+
+    let mut folders = Vec::new();
+    for row in rows {
+        folders.push(row.map_err(|error| error.to_string())?);
+    }
+
+    Ok(folders)
+}
+
+pub fn add_watched_folder(db_path: &str, path: &str) -> Result<WatchedFolder, String> {
+    if !Path::new(path).is_dir() {
+        return Err(format!("Not a folder: {}", path));
+    }
+
+    if let Some(parent) = Path::new(db_path).parent() {
+        std::fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+    }
+
+    let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    ensure_watched_folder_schema(&conn)?;
+
+    let folder = WatchedFolder {
+        id: Uuid::new_v4().to_string(),
+        path: path.to_string(),
+        added_at: import::current_timestamp(),
+    };
+
+    conn.execute(
+        "INSERT INTO watched_folders (id, path, added_at) VALUES (?1, ?2, ?3)",
+        (&folder.id, &folder.path, folder.added_at),
+    )
+    .map_err(|error| error.to_string())?;
+
+    Ok(folder)
+}
+
+/// Remove a watched folder, returning its path so the caller can stop watching it
+pub fn remove_watched_folder(db_path: &str, folder_id: &str) -> Result<Option<String>, String> {
+    let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    ensure_watched_folder_schema(&conn)?;
+
+    let path: Option<String> = conn
+        .query_row(
+            "SELECT path FROM watched_folders WHERE id = ?1",
+            [folder_id],
+            |row| row.get(0),
+        )
+        .ok();
+
+    conn.execute("DELETE FROM watched_folders WHERE id = ?1", [folder_id])
+        .map_err(|error| error.to_string())?;
+
+    Ok(path)
+}
+
+/// A path reported by the watcher. `settled` is set when the event says the
+/// writer is done with the file (closed after writing, or moved in whole).
+struct WatchEvent {
+    path: PathBuf,
+    settled: bool,
+}
+
+#[derive(Debug, Default)]
+struct PendingPath {
+    settled: bool,
+    signature: Option<(i64, i64)>,
+}
+
+struct ActiveWatcher {
+    watcher: RecommendedWatcher,
+    tx: Sender<WatchEvent>,
+    db_path: String,
+}
+
+/// Background watcher that imports new audio files dropped into watched folders.
+/// Dropping the `ActiveWatcher` closes the event channel, which ends the import
+/// thread.
+pub struct FolderWatcher {
+    active: Mutex<Option<ActiveWatcher>>,
+}
+
+impl FolderWatcher {
+    pub fn new() -> Self {
+        Self {
+            active: Mutex::new(None),
+        }
+    }
+
+    /// Start (or restart) watching every folder registered in the database
+    pub fn start(&self, app: AppHandle, db_path: String, cache_dir: PathBuf) -> Result<(), String> {
+        self.stop();
+
+        let folders = list_watched_folders(&db_path)?;
+        let (tx, rx) = mpsc::channel::<WatchEvent>();
+
+        let event_tx = tx.clone();
+        let mut watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+            let Ok(event) = result else {
+                return;
+            };
+            let Some(settled) = import_candidate_event(&event.kind) else {
+                return;
+            };
+            for path in event.paths {
+                let _ = event_tx.send(WatchEvent { path, settled });
+            }
+        })
+        .map_err(|error| error.to_string())?;
+
+        for folder in &folders {
+            if let Err(error) = watcher.watch(Path::new(&folder.path), RecursiveMode::Recursive) {
+                eprintln!("Failed to watch {}: {}", folder.path, error);
+            }
+        }
+
+        // Pick up files that were added while the app was not running
+        let roots: Vec<String> = folders.into_iter().map(|folder| folder.path).collect();
+        let queue_tx = tx.clone();
+        let thread_db_path = db_path.clone();
+        thread::spawn(move || {
+            queue_unknown_files(&thread_db_path, &roots, &queue_tx);
+            drop(queue_tx);
+            run_import_loop(rx, app, thread_db_path, cache_dir);
+        });
+
+        *self.active.lock() = Some(ActiveWatcher {
+            watcher,
+            tx,
+            db_path,
+        });
+        Ok(())
+    }
+
+    pub fn stop(&self) {
+        self.active.lock().take();
+    }
+
+    pub fn is_running(&self, db_path: &str) -> bool {
+        self.active
+            .lock()
+            .as_ref()
+            .is_some_and(|active| active.db_path == db_path)
+    }
+
+    /// Watch one more folder and queue the audio files already in it.
+    /// Fails when the watcher is not running for this library.
+    pub fn watch_folder(&self, db_path: &str, path: &str) -> Result<(), String> {
+        let mut guard = self.active.lock();
+        let Some(active) = guard.as_mut().filter(|active| active.db_path == db_path) else {
+            return Err("Folder watcher is not running".to_string());
+        };
+        active
+            .watcher
+            .watch(Path::new(path), RecursiveMode::Recursive)
+            .map_err(|error| error.to_string())?;
+
+        let tx = active.tx.clone();
+        let db_path = db_path.to_string();
+        let roots = vec![path.to_string()];
+        thread::spawn(move || queue_unknown_files(&db_path, &roots, &tx));
+        Ok(())
+    }
+
+    pub fn unwatch_folder(&self, db_path: &str, path: &str) {
+        let mut guard = self.active.lock();
+        if let Some(active) = guard.as_mut().filter(|active| active.db_path == db_path) {
+            let _ = active.watcher.unwatch(Path::new(path));
+        }
+    }
+}
+
+impl Default for FolderWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether an event should queue its paths, and if so whether the file is
+/// already complete. Creates and writes only mark a file as pending; it is
+/// imported once closed or once its size and mtime stop changing.
+fn import_candidate_event(kind: &EventKind) -> Option<bool> {
+    match kind {
+        EventKind::Access(AccessKind::Close(AccessMode::Write))
+        | EventKind::Modify(ModifyKind::Name(_)) => Some(true),
+        EventKind::Create(_) | EventKind::Modify(ModifyKind::Data(_)) => Some(false),
+        _ => None,
+    }
+}
+
+fn queue_unknown_files(db_path: &str, roots: &[String], tx: &Sender<WatchEvent>) {
+    if roots.is_empty() {
+        return;
+    }
+
+    let known = match load_known_paths(db_path) {
+        Ok(known) => known,
+        Err(error) => {
+            eprintln!("Failed to load library paths: {}", error);
+            return;
+        }
+    };
+
+    let options = match import::ScanOptions::load_from(db_path) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("Failed to load scan options: {}", error);
+            return;
+        }
+    };
+
+    for root in roots {
+        let mut files = Vec::new();
+        for issue in import::collect_audio_paths(Path::new(root), &options, &mut files) {
+            eprintln!("Failed to scan {}: {}", issue.path, issue.reason);
+        }
+        for file in files {
+            if !known.contains(file.to_string_lossy().as_ref()) {
+                let _ = tx.send(WatchEvent {
+                    path: file,
+                    settled: false,
+                });
+            }
+        }
+    }
+}
+
+fn load_known_paths(db_path: &str) -> Result<HashSet<String>, String> {
+    let conn = Connection::open(db_path).map_err(|error| error.to_string())?;
+    import::ensure_schema(&conn)?;
+
+    let mut stmt = conn
+        .prepare("SELECT source_path FROM tracks WHERE source_path IS NOT NULL")
+        .map_err(|error| error.to_string())?;
+    let rows = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|error| error.to_string())?;
+
+    let mut known = HashSet::new();
+    for row in rows {
+        known.insert(row.map_err(|error| error.to_string())?);
+    }
+    Ok(known)
+}
+
+fn run_import_loop(rx: Receiver<WatchEvent>, app: AppHandle, db_path: String, cache_dir: PathBuf) {
+    let mut pending: BTreeMap<PathBuf, PendingPath> = BTreeMap::new();
+
+    loop {
+        let wait = if pending.is_empty() {
+            IDLE_WAIT
+        } else {
+            DEBOUNCE
+        };
+        match rx.recv_timeout(wait) {
+            Ok(event) => {
+                // The latest event wins: a file written to again after closing
+                // has to settle again
+                pending.entry(event.path).or_default().settled = event.settled;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                if !pending.is_empty() {
+                    let options = import::ScanOptions::load_from(&db_path).unwrap_or_default();
+                    let batch = take_settled(&mut pending, &options);
+                    if !batch.is_empty() {
+                        import_batch(&app, &db_path, &cache_dir, batch);
+                    }
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                break;
+            }
+        }
+    }
+}
+
+/// Remove and return the pending files that are ready to import. Folders are
+/// expanded into their audio files, which then have to settle like any other
+/// file; paths that no longer exist are dropped.
+fn take_settled(
+    pending: &mut BTreeMap<PathBuf, PendingPath>,
+    options: &import::ScanOptions,
+) -> Vec<PathBuf> {
+    let mut ready = Vec::new();
+    let mut folders = Vec::new();
+
+    pending.retain(|path, entry| {
+        if path.is_dir() {
+            folders.push(path.clone());
+            return false;
+        }
+        let signature = import::file_signature(path);
+        if signature.is_none() {
+            return false;
+        }
+        if entry.settled || signature == entry.signature {
+            ready.push(path.clone());
+            return false;
+        }
+        entry.signature = signature;
+        true
+    });
+
+    for folder in folders {
+        let mut files = Vec::new();
+        for issue in import::collect_audio_paths(&folder, options, &mut files) {
+            eprintln!("Failed to scan {}: {}", issue.path, issue.reason);
+        }
+        for file in files {
+            pending.entry(file).or_default();
+        }
+    }
+
+    ready
+}
+
+fn import_batch(app: &AppHandle, db_path: &str, cache_dir: &Path, batch: Vec<PathBuf>) {
+    // Events inside skipped folders (NAS thumbnails, trash) are dropped here
+    let options = import::ScanOptions::load_from(db_path).unwrap_or_default();
+    let roots = list_watched_folders(db_path).unwrap_or_default();
+    let paths: Vec<String> = batch
+        .into_iter()
+        .filter(|path| formats::is_audio_file(path))
+        .filter(|path| {
+            !roots
+                .iter()
+                .any(|root| options.is_excluded_below(Path::new(&root.path), path))
+        })
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+
+    if paths.is_empty() {
+        return;
+    }
+
+    // Registered like a manual import so the progress bar can cancel it, but
+    // not persisted as a resumable job
+    let jobs = app.state::<import::ImportJobs>();
+    let job_id = Uuid::new_v4().to_string();
+    let cancel = jobs.register(&job_id);
+    let result =
+        import::run_background_import(&job_id, paths, db_path, cache_dir, &cancel, |progress| {
+            let _ = app.emit("muro://import-progress", progress);
+        });
+    jobs.finish(&job_id);
+
+    match result {
+        Ok(report) if !report.imported.is_empty() => {
+            let _ = app.emit(
+                "muro://library-changed",
+                LibraryChanged {
+                    source: "watched-folder",
+                    tracks: report.imported,
+                },
+            );
+        }
+        Ok(_) => {}
+        Err(error) => {
+            eprintln!("Watched folder import failed: {}", error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_wait_until_closed_or_unchanged() {
+        let dir = std::env::temp_dir().join(format!("muro-watch-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("album")).unwrap();
+        let closed = dir.join("closed.wav");
+        let growing = dir.join("growing.wav");
+        let nested = dir.join("album").join("01.wav");
+        for path in [&closed, &growing, &nested] {
+            std::fs::write(path, b"RIFF\x04\x00\x00\x00WAVE").unwrap();
+        }
+
+        let mut pending = BTreeMap::new();
+        pending.insert(
+            closed.clone(),
+            PendingPath {
+                settled: true,
+                signature: None,
+            },
+        );
+        pending.insert(growing.clone(), PendingPath::default());
+        pending.insert(dir.join("album"), PendingPath::default());
+        pending.insert(dir.join("gone.wav"), PendingPath::default());
+        let options = import::ScanOptions::default();
+
+        // Closed files go straight through; everything else is only measured
+        assert_eq!(take_settled(&mut pending, &options), vec![closed.clone()]);
+        assert!(pending.contains_key(&growing));
+        assert!(pending.contains_key(&nested));
+        assert!(!pending.contains_key(&dir.join("album")));
+        assert!(!pending.contains_key(&dir.join("gone.wav")));
+
+        // A file that grew since the last check keeps waiting
+        std::fs::write(&growing, b"RIFF\x04\x00\x00\x00WAVEfmt ").unwrap();
+        assert!(take_settled(&mut pending, &options).is_empty());
+        assert!(pending.contains_key(&nested));
+
+        let mut ready = take_settled(&mut pending, &options);
+        ready.sort();
+        assert_eq!(ready, vec![nested, growing]);
+        assert!(pending.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn writes_queue_files_without_settling_them() {
+        use notify::event::{CreateKind, DataChange, RenameMode};
+
+        assert_eq!(
+            import_candidate_event(&EventKind::Modify(ModifyKind::Data(DataChange::Any))),
+            Some(false)
+        );
+        assert_eq!(
+            import_candidate_event(&EventKind::Create(CreateKind::File)),
+            Some(false)
+        );
+        assert_eq!(
+            import_candidate_event(&EventKind::Access(AccessKind::Close(AccessMode::Write))),
+            Some(true)
+        );
+        assert_eq!(
+            import_candidate_event(&EventKind::Modify(ModifyKind::Name(RenameMode::To))),
+            Some(true)
+        );
+        assert_eq!(
+            import_candidate_event(&EventKind::Access(AccessKind::Read)),
+            None
+        );
+    }
+}
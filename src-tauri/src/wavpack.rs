@@ -0,0 +1,1505 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::OnceLock;
+use symphonia::core::audio::{
+    AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec,
+};
+use symphonia::core::codecs::{
+    CodecDescriptor, CodecParameters, Decoder, DecoderOptions, FinalizeResult, CODEC_TYPE_WAVPACK,
+};
+use symphonia::core::errors::{decode_error, seek_error, unsupported_error, Result, SeekErrorKind};
+use symphonia::core::formats::{
+    Cue, FormatOptions, FormatReader, Packet, SeekMode, SeekTo, SeekedTo, Track,
+};
+use symphonia::core::io::{MediaSourceStream, ReadBytes};
+use symphonia::core::meta::{Metadata, MetadataLog};
+use symphonia::core::probe::{Descriptor, Instantiate, QueryDescriptor};
+use symphonia::core::sample::SampleFormat;
+use symphonia::core::units::TimeBase;
+use symphonia::core::{support_codec, support_format};
+
+/// Every block starts with a 32-byte header
+const HEADER_BYTES: usize = 32;
+const MIN_VERSION: u16 = 0x402;
+const MAX_VERSION: u16 = 0x410;
+
+const BYTES_STORED: u32 = 0x3;
+const MONO_FLAG: u32 = 0x4;
+const HYBRID_FLAG: u32 = 0x8;
+const JOINT_STEREO: u32 = 0x10;
+const FLOAT_DATA: u32 = 0x80;
+const HYBRID_BITRATE: u32 = 0x200;
+const INITIAL_BLOCK: u32 = 0x800;
+const FINAL_BLOCK: u32 = 0x1000;
+const SHIFT_LSB: u32 = 13;
+const SRATE_LSB: u32 = 23;
+const FALSE_STEREO: u32 = 0x4000_0000;
+const DSD_FLAG: u32 = 0x8000_0000;
+
+const SAMPLE_RATES: [u32; 15] = [
+    6000, 8000, 9600, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000, 64000, 88200, 96000,
+    192000,
+];
+
+const ID_DECORR_TERMS: u8 = 0x2;
+const ID_DECORR_WEIGHTS: u8 = 0x3;
+const ID_DECORR_SAMPLES: u8 = 0x4;
+const ID_ENTROPY_VARS: u8 = 0x5;
+const ID_HYBRID_PROFILE: u8 = 0x6;
+const ID_FLOAT_INFO: u8 = 0x8;
+const ID_INT32_INFO: u8 = 0x9;
+const ID_WV_BITSTREAM: u8 = 0xa;
+const ID_WVX_BITSTREAM: u8 = 0xc;
+const ID_CHANNEL_INFO: u8 = 0xd;
+const ID_SAMPLE_RATE: u8 = 0x27;
+
+const FLOAT_SHIFT_ONES: u8 = 0x1;
+const FLOAT_SHIFT_SAME: u8 = 0x2;
+const FLOAT_SHIFT_SENT: u8 = 0x4;
+const FLOAT_ZERO_SENT: u8 = 0x8;
+const FLOAT_ZERO_SIGN: u8 = 0x10;
+
+/// Most decorrelation passes a block may declare
+const MAX_TERMS: usize = 16;
+
+fn channel_layout(count: usize) -> Option<Channels> {
+    match count {
+        1 => Some(Channels::FRONT_LEFT),
+        2 => Some(Channels::FRONT_LEFT | Channels::FRONT_RIGHT),
+        3..=8 => Some(Channels::from_bits_truncate((1 << count) - 1)),
+        _ => None,
+    }
+}
+
+fn le16(bytes: &[u8], at: usize) -> Option<i16> {
+    Some(i16::from_le_bytes([*bytes.get(at)?, *bytes.get(at + 1)?]))
+}
+
+fn le32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BlockHeader {
+    /// Whole block, header included
+    size: usize,
+    block_index: u32,
+    block_samples: u32,
+    flags: u32,
+}
+
+impl BlockHeader {
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < HEADER_BYTES || &bytes[..4] != b"wvpk" {
+            return None;
+        }
+        let size = le32(bytes, 4) as usize + 8;
+        let version = u16::from_le_bytes([bytes[8], bytes[9]]);
+        if !(MIN_VERSION..=MAX_VERSION).contains(&version) || size < HEADER_BYTES {
+            return None;
+        }
+        Some(Self {
+            size,
+            block_index: le32(bytes, 16),
+            block_samples: le32(bytes, 20),
+            flags: le32(bytes, 24),
+        })
+    }
+
+    fn channels(&self) -> usize {
+        if self.flags & MONO_FLAG != 0 {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+/// Sub-blocks of a block's body as `(id, payload)` pairs
+struct SubBlocks<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for SubBlocks<'a> {
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = *self.data.first()?;
+        // Sizes are in 16-bit words, in three bytes for large sub-blocks
+        let (header, words) = if id & 0x80 != 0 {
+            let size = self.data.get(1..4)?;
+            (
+                4,
+                u32::from_le_bytes([size[0], size[1], size[2], 0]) as usize,
+            )
+        } else {
+            (2, *self.data.get(1)? as usize)
+        };
+        let payload = self.data.get(header..header + words * 2)?;
+        self.data = &self.data[header + words * 2..];
+        let used = if id & 0x40 != 0 {
+            payload.len().checked_sub(1)?
+        } else {
+            payload.len()
+        };
+        Some((id & 0x3f, &payload[..used]))
+    }
+}
+
+fn sub_blocks(block: &[u8]) -> SubBlocks<'_> {
+    SubBlocks {
+        data: block.get(HEADER_BYTES..).unwrap_or_default(),
+    }
+}
+
+/// Stream properties taken from the blocks of the first frame
+struct StreamInfo {
+    channels: Channels,
+    sample_rate: u32,
+    bits_per_sample: u32,
+    float: bool,
+}
+
+fn stream_info(frame: &[u8]) -> Result<StreamInfo> {
+    let mut offset = 0;
+    let mut channels = 0;
+    let mut mask = 0;
+    let mut first = None;
+    let mut custom_rate = None;
+    while let Some(header) = frame.get(offset..).and_then(BlockHeader::parse) {
+        let Some(block) = frame.get(offset..offset + header.size) else {
+            break;
+        };
+        channels += header.channels();
+        for (id, data) in sub_blocks(block) {
+            match id {
+                ID_CHANNEL_INFO if data.len() > 1 => {
+                    let mut bytes = [0u8; 4];
+                    for (to, from) in bytes.iter_mut().zip(&data[1..]) {
+                        *to = *from;
+                    }
+                    mask = u32::from_le_bytes(bytes);
+                }
+                ID_SAMPLE_RATE if data.len() >= 3 => {
+                    custom_rate = Some(u32::from_le_bytes([data[0], data[1], data[2], 0]));
+                }
+                _ => {}
+            }
+        }
+        first.get_or_insert(header);
+        offset += header.size;
+        if header.flags & FINAL_BLOCK != 0 {
+            break;
+        }
+    }
+
+    let Some(header) = first else {
+        return decode_error("wavpack: no audio blocks");
+    };
+    if header.flags & DSD_FLAG != 0 {
+        return unsupported_error("wavpack: DSD audio is not supported");
+    }
+    let rate_index = ((header.flags >> SRATE_LSB) & 0xf) as usize;
+    let Some(sample_rate) = SAMPLE_RATES.get(rate_index).copied().or(custom_rate) else {
+        return decode_error("wavpack: unknown sample rate");
+    };
+    let layout = match Channels::from_bits(mask) {
+        Some(layout) if mask != 0 && layout.count() == channels => Some(layout),
+        _ => channel_layout(channels),
+    };
+    let Some(channels) = layout else {
+        return unsupported_error("wavpack: unsupported channel count");
+    };
+    let float = header.flags & FLOAT_DATA != 0;
+    Ok(StreamInfo {
+        channels,
+        sample_rate,
+        bits_per_sample: if float {
+            32
+        } else {
+            ((header.flags & BYTES_STORED) + 1) * 8
+        },
+        float,
+    })
+}
+
+/// A run of blocks from an initial to a final block, decoded as one packet
+#[derive(Clone, Copy)]
+struct Frame {
+    offset: u64,
+    size: u64,
+    ts: u64,
+    samples: u64,
+}
+
+/// Demuxer for WavPack (.wv) files. Correction (.wvc) files are not read, so
+/// hybrid-mode files play back as their lossy part.
+pub struct WavPackReader {
+    reader: MediaSourceStream,
+    tracks: Vec<Track>,
+    cues: Vec<Cue>,
+    metadata: MetadataLog,
+    frames: Vec<Frame>,
+    /// Index of the next frame to return
+    next: usize,
+}
+
+impl QueryDescriptor for WavPackReader {
+    fn query() -> &'static [Descriptor] {
+        &[support_format!(
+            "wavpack",
+            "WavPack",
+            &["wv"],
+            &["audio/x-wavpack", "audio/wavpack"],
+            &[b"wvpk"]
+        )]
+    }
+
+    fn score(_context: &[u8]) -> u8 {
+        255
+    }
+}
+
+/// Walks the block headers from the current position and groups the blocks
+/// into frames. Stops at the first thing that isn't a block, such as a trailing
+/// APEv2 tag.
+fn index_frames(source: &mut MediaSourceStream) -> Result<Vec<Frame>> {
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut open = false;
+    let mut header = [0u8; HEADER_BYTES];
+    loop {
+        let offset = source.pos();
+        if source.read_exact(&mut header).is_err() {
+            break;
+        }
+        let Some(block) = BlockHeader::parse(&header) else {
+            break;
+        };
+        source.seek(SeekFrom::Start(offset + block.size as u64))?;
+        if block.block_samples == 0 {
+            continue;
+        }
+
+        if block.flags & INITIAL_BLOCK != 0 {
+            frames.push(Frame {
+                offset,
+                size: block.size as u64,
+                ts: u64::from(block.block_index),
+                samples: u64::from(block.block_samples),
+            });
+            open = block.flags & FINAL_BLOCK == 0;
+        } else if let (true, Some(frame)) = (open, frames.last_mut()) {
+            frame.size = offset + block.size as u64 - frame.offset;
+            open = block.flags & FINAL_BLOCK == 0;
+        }
+    }
+    Ok(frames)
+}
+
+impl FormatReader for WavPackReader {
+    fn try_new(mut source: MediaSourceStream, _options: &FormatOptions) -> Result<Self> {
+        let frames = index_frames(&mut source)?;
+        let Some(first) = frames.first().copied() else {
+            return decode_error("wavpack: no audio blocks");
+        };
+        source.seek(SeekFrom::Start(first.offset))?;
+        let info = stream_info(&source.read_boxed_slice_exact(first.size as usize)?)?;
+
+        // Block indexes count from the start of the file, which may not be zero
+        // for a file cut from a longer one
+        let base = first.ts;
+        let frames: Vec<Frame> = frames
+            .into_iter()
+            .map(|frame| Frame {
+                ts: frame.ts.saturating_sub(base),
+                ..frame
+            })
+            .collect();
+        let last = frames[frames.len() - 1];
+        let max_frames = frames.iter().map(|frame| frame.samples).max().unwrap_or(0);
+
+        let mut codec_params = CodecParameters::new();
+        codec_params
+            .for_codec(CODEC_TYPE_WAVPACK)
+            .with_sample_rate(info.sample_rate)
+            .with_time_base(TimeBase::new(1, info.sample_rate))
+            .with_n_frames(last.ts + last.samples)
+            .with_channels(info.channels)
+            .with_bits_per_sample(info.bits_per_sample)
+            .with_max_frames_per_packet(max_frames);
+        if info.float {
+            codec_params.with_sample_format(SampleFormat::F32);
+        }
+
+        Ok(Self {
+            reader: source,
+            tracks: vec![Track::new(0, codec_params)],
+            cues: Vec::new(),
+            metadata: MetadataLog::default(),
+            frames,
+            next: 0,
+        })
+    }
+
+    fn cues(&self) -> &[Cue] {
+        &self.cues
+    }
+
+    fn metadata(&mut self) -> Metadata<'_> {
+        self.metadata.metadata()
+    }
+
+    fn seek(&mut self, _mode: SeekMode, to: SeekTo) -> Result<SeekedTo> {
+        let required_ts = match to {
+            SeekTo::TimeStamp { ts, .. } => ts,
+            SeekTo::Time { time, .. } => {
+                let Some(rate) = self.tracks[0].codec_params.sample_rate else {
+                    return seek_error(SeekErrorKind::Unseekable);
+                };
+                TimeBase::new(1, rate).calc_timestamp(time)
+            }
+        };
+        if required_ts > self.tracks[0].codec_params.n_frames.unwrap_or(0) {
+            return seek_error(SeekErrorKind::OutOfRange);
+        }
+
+        // Blocks decode independently, so any frame is a valid start
+        self.next = self
+            .frames
+            .partition_point(|frame| frame.ts <= required_ts)
+            .saturating_sub(1);
+        let frame = self.frames[self.next];
+        self.reader.seek(SeekFrom::Start(frame.offset))?;
+
+        Ok(SeekedTo {
+            track_id: 0,
+            required_ts,
+            actual_ts: frame.ts,
+        })
+    }
+
+    fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    fn next_packet(&mut self) -> Result<Packet> {
+        let Some(frame) = self.frames.get(self.next).copied() else {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        };
+        if self.reader.pos() != frame.offset {
+            self.reader.seek(SeekFrom::Start(frame.offset))?;
+        }
+        let data = self.reader.read_boxed_slice_exact(frame.size as usize)?;
+        self.next += 1;
+        Ok(Packet::new_from_boxed_slice(
+            0,
+            frame.ts,
+            frame.samples,
+            data,
+        ))
+    }
+
+    fn into_inner(self: Box<Self>) -> MediaSourceStream {
+        self.reader
+    }
+}
+
+/// `(2^(i/256) - 1) * 256`, the fraction of WavPack's 8.8 fixed-point exponent
+fn exp2_table() -> &'static [u8; 256] {
+    static TABLE: OnceLock<[u8; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [0u8; 256];
+        for (index, entry) in table.iter_mut().enumerate() {
+            let value = ((index as f64 / 256.0).exp2() - 1.0) * 256.0;
+            *entry = value.round().min(255.0) as u8;
+        }
+        table
+    })
+}
+
+/// `log2(1 + i/256) * 256`, the inverse of `exp2_table`
+fn log2_table() -> &'static [u8; 256] {
+    static TABLE: OnceLock<[u8; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [0u8; 256];
+        for (index, entry) in table.iter_mut().enumerate() {
+            let value = (1.0 + index as f64 / 256.0).log2() * 256.0;
+            *entry = value.round().min(255.0) as u8;
+        }
+        table
+    })
+}
+
+/// Expands an 8.8 fixed-point base-2 logarithm
+fn wp_exp2(value: i16) -> i32 {
+    let magnitude = i32::from(value).abs();
+    let mantissa = i32::from(exp2_table()[(magnitude & 0xff) as usize]) | 0x100;
+    let exponent = magnitude >> 8;
+    if exponent > 31 {
+        return i32::MIN;
+    }
+    let result = if exponent > 9 {
+        mantissa << (exponent - 9)
+    } else {
+        mantissa >> (9 - exponent)
+    };
+    if value < 0 {
+        -result
+    } else {
+        result
+    }
+}
+
+/// 8.8 fixed-point base-2 logarithm, as used for the hybrid-mode slow level
+fn wp_log2(value: u32) -> i32 {
+    if value == 0 {
+        return 0;
+    }
+    let value = value.wrapping_add(value >> 9);
+    let bits = 32 - value.leading_zeros();
+    let index = if bits < 9 {
+        value << (9 - bits)
+    } else {
+        value >> (bits - 9)
+    } & 0xff;
+    ((bits << 8) + u32::from(log2_table()[index as usize])) as i32
+}
+
+/// Reads bits least significant first, as WavPack writes them
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn left(&self) -> usize {
+        (self.data.len() * 8).saturating_sub(self.position)
+    }
+
+    fn bit(&mut self) -> Option<u32> {
+        let byte = self.data.get(self.position / 8)?;
+        let bit = (byte >> (self.position % 8)) & 1;
+        self.position += 1;
+        Some(u32::from(bit))
+    }
+
+    fn bits(&mut self, count: u32) -> Option<u32> {
+        let mut value = 0;
+        for shift in 0..count {
+            value |= self.bit()? << shift;
+        }
+        Some(value)
+    }
+
+    /// Counts set bits up to the next clear one, at most 33
+    fn unary(&mut self) -> Option<u32> {
+        let mut count = 0;
+        while count < 33 && self.bit()? == 1 {
+            count += 1;
+        }
+        Some(count)
+    }
+
+    /// Values sent as a bit count followed by the bits below the leading one
+    fn escaped(&mut self, count: u32) -> Option<u32> {
+        if count < 2 {
+            return Some(count);
+        }
+        if count >= 32 {
+            return None;
+        }
+        Some(self.bits(count - 1)? | (1 << (count - 1)))
+    }
+
+    /// A value in `0..=range`, using the fewest bits that can cover it
+    fn tail(&mut self, range: u32) -> Option<u32> {
+        if range < 1 {
+            return Some(0);
+        }
+        let bits = 31 - range.leading_zeros();
+        let escape = (1 << (bits + 1)) - range - 1;
+        let value = self.bits(bits)?;
+        if value >= escape {
+            Some((value << 1) - escape + self.bit()?)
+        } else {
+            Some(value)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct EntropyChannel {
+    median: [i32; 3],
+    slow_level: i32,
+    error_limit: i32,
+    bitrate_acc: u32,
+    bitrate_delta: u32,
+}
+
+fn level_decay(level: i32) -> i32 {
+    (level + 0x80) >> 8
+}
+
+/// Adaptive Golomb decoder shared by the channels of a block
+#[derive(Default)]
+struct Entropy {
+    channels: [EntropyChannel; 2],
+    stereo: bool,
+    hybrid: bool,
+    hybrid_bitrate: bool,
+    zero: bool,
+    one: bool,
+    /// Zero samples still to come from the current run
+    zeros: u32,
+}
+
+impl Entropy {
+    fn median(&self, channel: usize, index: usize) -> i32 {
+        (self.channels[channel].median[index] >> 4) + 1
+    }
+
+    fn increase_median(&mut self, channel: usize, index: usize) {
+        let step = 128 >> index;
+        let median = &mut self.channels[channel].median[index];
+        *median = median.wrapping_add(((median.wrapping_add(step)) / step).wrapping_mul(5));
+    }
+
+    fn decrease_median(&mut self, channel: usize, index: usize) {
+        let step = 128 >> index;
+        let median = &mut self.channels[channel].median[index];
+        *median = median.wrapping_sub(((median.wrapping_add(step - 2)) / step).wrapping_mul(2));
+    }
+
+    /// Hybrid mode: how far the decoded magnitude may stray from the original
+    fn update_error_limit(&mut self) {
+        let count = if self.stereo { 2 } else { 1 };
+        let mut bitrate = [0i32; 2];
+        let mut slow = [0i32; 2];
+        for index in 0..count {
+            let channel = &mut self.channels[index];
+            channel.bitrate_acc = channel.bitrate_acc.wrapping_add(channel.bitrate_delta);
+            bitrate[index] = (channel.bitrate_acc >> 16) as i32;
+            slow[index] = level_decay(channel.slow_level);
+        }
+
+        if self.stereo && self.hybrid_bitrate {
+            let balance = (slow[1] - slow[0] + bitrate[1] + 1) >> 1;
+            if balance > bitrate[0] {
+                bitrate[1] = bitrate[0] * 2;
+                bitrate[0] = 0;
+            } else if -balance > bitrate[0] {
+                bitrate[0] *= 2;
+                bitrate[1] = 0;
+            } else {
+                bitrate[1] = bitrate[0] + balance;
+                bitrate[0] -= balance;
+            }
+        }
+
+        for index in 0..count {
+            self.channels[index].error_limit = if self.hybrid_bitrate {
+                if slow[index] - bitrate[index] > -0x100 {
+                    wp_exp2((slow[index] - bitrate[index] + 0x100) as i16)
+                } else {
+                    0
+                }
+            } else {
+                wp_exp2(bitrate[index] as i16)
+            };
+        }
+    }
+
+    /// The next residual for `channel`, or `None` once the bitstream runs out
+    fn value(&mut self, bits: &mut BitReader, channel: usize) -> Option<i32> {
+        let quiet =
+            self.channels[0].median[0] < 2 && (!self.stereo || self.channels[1].median[0] < 2);
+        if quiet && !self.zero && !self.one {
+            let run = if self.zeros > 0 {
+                self.zeros -= 1;
+                self.zeros
+            } else {
+                let count = bits.unary()?;
+                self.zeros = bits.escaped(count)?;
+                if self.zeros > 0 {
+                    for channel in &mut self.channels {
+                        channel.median = [0; 3];
+                    }
+                }
+                self.zeros
+            };
+            if run > 0 {
+                let level = &mut self.channels[channel].slow_level;
+                *level -= level_decay(*level);
+                return Some(0);
+            }
+        }
+
+        let ones = if self.zero {
+            self.zero = false;
+            0
+        } else {
+            let mut count = bits.unary()?;
+            if count == 16 {
+                let extra = bits.unary()?;
+                count += bits.escaped(extra)?;
+            }
+            let ones = if self.one {
+                (count >> 1) + 1
+            } else {
+                count >> 1
+            };
+            self.one = count & 1 == 1;
+            self.zero = !self.one;
+            ones
+        };
+
+        if self.hybrid && channel == 0 {
+            self.update_error_limit();
+        }
+
+        let (base, add) = match ones {
+            0 => {
+                let add = self.median(channel, 0) - 1;
+                self.decrease_median(channel, 0);
+                (0, add)
+            }
+            1 => {
+                let base = self.median(channel, 0);
+                let add = self.median(channel, 1) - 1;
+                self.increase_median(channel, 0);
+                self.decrease_median(channel, 1);
+                (base, add)
+            }
+            _ => {
+                let base = self
+                    .median(channel, 0)
+                    .wrapping_add(self.median(channel, 1));
+                self.increase_median(channel, 0);
+                self.increase_median(channel, 1);
+                let add = self.median(channel, 2) - 1;
+                if ones == 2 {
+                    self.decrease_median(channel, 2);
+                    (base, add)
+                } else {
+                    let base =
+                        base.wrapping_add(self.median(channel, 2).wrapping_mul(ones as i32 - 2));
+                    self.increase_median(channel, 2);
+                    (base, add)
+                }
+            }
+        };
+
+        let error_limit = self.channels[channel].error_limit;
+        let magnitude = if error_limit == 0 {
+            if add as u32 >= 0x200_0000 {
+                return None;
+            }
+            base.wrapping_add(bits.tail(add as u32)? as i32)
+        } else {
+            let (mut base, mut add) = (base, add);
+            let mut mid = ((base as u32).wrapping_mul(2).wrapping_add(add as u32 + 1) >> 1) as i32;
+            while add > error_limit {
+                if bits.bit()? == 1 {
+                    add = add.wrapping_sub(mid.wrapping_sub(base));
+                    base = mid;
+                } else {
+                    add = mid.wrapping_sub(base) - 1;
+                }
+                mid = ((base as u32).wrapping_mul(2).wrapping_add(add as u32 + 1) >> 1) as i32;
+            }
+            mid
+        };
+
+        let negative = bits.bit()? == 1;
+        if self.hybrid_bitrate {
+            let level = &mut self.channels[channel].slow_level;
+            *level += wp_log2(magnitude as u32) - level_decay(*level);
+        }
+        Some(if negative { !magnitude } else { magnitude })
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct DecorrPass {
+    term: i32,
+    delta: i32,
+    weight_a: i32,
+    weight_b: i32,
+    samples_a: [i32; 8],
+    samples_b: [i32; 8],
+}
+
+fn apply_weight(weight: i32, sample: i32) -> i32 {
+    ((i64::from(weight) * i64::from(sample) + 512) >> 10) as i32
+}
+
+fn update_weight(weight: &mut i32, delta: i32, sample: i32, input: i32) {
+    if sample != 0 && input != 0 {
+        *weight -= ((((sample ^ input) >> 30) & 2) - 1) * delta;
+    }
+}
+
+fn update_weight_clip(weight: &mut i32, delta: i32, sample: i32, input: i32) {
+    if sample != 0 && input != 0 {
+        if (sample ^ input) < 0 {
+            *weight = (*weight - delta).max(-1024);
+        } else {
+            *weight = (*weight + delta).min(1024);
+        }
+    }
+}
+
+fn restore_weight(weight: i8) -> i32 {
+    let weight = i32::from(weight) * 8;
+    if weight > 0 {
+        weight + ((weight + 64) >> 7)
+    } else {
+        weight
+    }
+}
+
+/// Terms 17 and 18 predict from the last two samples rather than a delay
+fn extrapolate(term: i32, samples: &mut [i32; 8]) -> i32 {
+    let sample = if term & 1 == 1 {
+        samples[0].wrapping_mul(2).wrapping_sub(samples[1])
+    } else {
+        samples[0].wrapping_mul(3).wrapping_sub(samples[1]) >> 1
+    };
+    samples[1] = samples[0];
+    sample
+}
+
+fn unpack_mono(
+    passes: &mut [DecorrPass],
+    entropy: &mut Entropy,
+    bits: &mut BitReader,
+    output: &mut [i32],
+) {
+    let mut position = 0;
+    for out in output.iter_mut() {
+        let Some(mut sample) = entropy.value(bits, 0) else {
+            break;
+        };
+        for pass in passes.iter_mut().filter(|pass| pass.term > 0) {
+            let (history, slot) = if pass.term > 8 {
+                (extrapolate(pass.term, &mut pass.samples_a), 0)
+            } else {
+                (
+                    pass.samples_a[position],
+                    (position + pass.term as usize) & 7,
+                )
+            };
+            let decoded = sample.wrapping_add(apply_weight(pass.weight_a, history));
+            update_weight(&mut pass.weight_a, pass.delta, history, sample);
+            sample = decoded;
+            pass.samples_a[slot] = sample;
+        }
+        position = (position + 1) & 7;
+        *out = sample;
+    }
+}
+
+fn unpack_stereo(
+    passes: &mut [DecorrPass],
+    entropy: &mut Entropy,
+    bits: &mut BitReader,
+    joint: bool,
+    left: &mut [i32],
+    right: &mut [i32],
+) {
+    let mut position = 0;
+    for (out_left, out_right) in left.iter_mut().zip(right.iter_mut()) {
+        let Some(mut l) = entropy.value(bits, 0) else {
+            break;
+        };
+        let Some(mut r) = entropy.value(bits, 1) else {
+            break;
+        };
+        for pass in passes.iter_mut() {
+            match pass.term {
+                term if term > 0 => {
+                    let (a, b, slot) = if term > 8 {
+                        let a = extrapolate(term, &mut pass.samples_a);
+                        let b = extrapolate(term, &mut pass.samples_b);
+                        (a, b, 0)
+                    } else {
+                        (
+                            pass.samples_a[position],
+                            pass.samples_b[position],
+                            (position + term as usize) & 7,
+                        )
+                    };
+                    let decoded_l = l.wrapping_add(apply_weight(pass.weight_a, a));
+                    let decoded_r = r.wrapping_add(apply_weight(pass.weight_b, b));
+                    update_weight(&mut pass.weight_a, pass.delta, a, l);
+                    update_weight(&mut pass.weight_b, pass.delta, b, r);
+                    l = decoded_l;
+                    r = decoded_r;
+                    pass.samples_a[slot] = l;
+                    pass.samples_b[slot] = r;
+                }
+                -1 => {
+                    let decoded_l = l.wrapping_add(apply_weight(pass.weight_a, pass.samples_a[0]));
+                    update_weight_clip(&mut pass.weight_a, pass.delta, pass.samples_a[0], l);
+                    l = decoded_l;
+                    let decoded_r = r.wrapping_add(apply_weight(pass.weight_b, l));
+                    update_weight_clip(&mut pass.weight_b, pass.delta, l, r);
+                    r = decoded_r;
+                    pass.samples_a[0] = r;
+                }
+                term => {
+                    let decoded_r = r.wrapping_add(apply_weight(pass.weight_b, pass.samples_b[0]));
+                    update_weight_clip(&mut pass.weight_b, pass.delta, pass.samples_b[0], r);
+                    r = decoded_r;
+                    let mut source = r;
+                    if term == -3 {
+                        source = pass.samples_a[0];
+                        pass.samples_a[0] = r;
+                    }
+                    let decoded_l = l.wrapping_add(apply_weight(pass.weight_a, source));
+                    update_weight_clip(&mut pass.weight_a, pass.delta, source, l);
+                    l = decoded_l;
+                    pass.samples_b[0] = l;
+                }
+            }
+        }
+        position = (position + 1) & 7;
+        if joint {
+            r = r.wrapping_sub(l >> 1);
+            l = l.wrapping_add(r);
+        }
+        *out_left = l;
+        *out_right = r;
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct FloatInfo {
+    flags: u8,
+    shift: u8,
+    max_exp: u8,
+}
+
+/// Turns decorrelated values into output samples, pulling any bits the
+/// encoder moved into the extra (WVX) bitstream back in
+struct PostProcess<'a> {
+    extra: Option<BitReader<'a>>,
+    extra_bits: u32,
+    and: u32,
+    or: u32,
+    shift: u32,
+    post_shift: u32,
+    hybrid: bool,
+    min_clip: i32,
+    max_clip: i32,
+    float: FloatInfo,
+}
+
+impl PostProcess<'_> {
+    fn integer(&mut self, value: i32) -> i32 {
+        let mut value = value as u32;
+        if self.extra_bits > 0 {
+            value = value.wrapping_shl(self.extra_bits);
+            if let Some(extra) = &mut self.extra {
+                if extra.left() >= self.extra_bits as usize {
+                    value |= extra.bits(self.extra_bits).unwrap_or(0);
+                }
+            }
+        }
+        let bit = (value & self.and) | self.or;
+        let mut sample =
+            (value.wrapping_add(bit).wrapping_shl(self.shift)).wrapping_sub(bit) as i32;
+        if self.hybrid {
+            sample = sample.clamp(self.min_clip, self.max_clip);
+        }
+        sample.wrapping_shl(self.post_shift)
+    }
+
+    fn extra_bit(&mut self) -> bool {
+        self.extra
+            .as_mut()
+            .and_then(|extra| extra.bit())
+            .is_some_and(|bit| bit == 1)
+    }
+
+    fn extra_value(&mut self, count: u32) -> u32 {
+        self.extra
+            .as_mut()
+            .and_then(|extra| extra.bits(count))
+            .unwrap_or(0)
+    }
+
+    fn float(&mut self, value: i32) -> f32 {
+        let info = self.float;
+        let has_extra = self.extra.is_some();
+        let mut exponent = u32::from(info.max_exp);
+        let mut negative = false;
+        let mut mantissa;
+        if value != 0 {
+            let value = value.wrapping_shl(u32::from(info.shift));
+            negative = value < 0;
+            mantissa = value.unsigned_abs();
+            if mantissa >= 0x100_0000 {
+                mantissa = if has_extra && self.extra_bit() {
+                    self.extra_value(23)
+                } else {
+                    0
+                };
+                exponent = 255;
+            } else if exponent != 0 {
+                let mut shift = 23 - (31 - mantissa.leading_zeros());
+                if exponent <= shift {
+                    exponent -= 1;
+                    shift = exponent;
+                }
+                exponent -= shift;
+                if shift > 0 {
+                    mantissa <<= shift;
+                    if info.flags & FLOAT_SHIFT_ONES != 0
+                        || (has_extra && info.flags & FLOAT_SHIFT_SAME != 0 && self.extra_bit())
+                    {
+                        mantissa |= (1 << shift) - 1;
+                    } else if has_extra && info.flags & FLOAT_SHIFT_SENT != 0 {
+                        mantissa |= self.extra_value(shift);
+                    }
+                }
+            }
+            mantissa &= 0x7f_ffff;
+        } else {
+            exponent = 0;
+            mantissa = 0;
+            if has_extra && info.flags & FLOAT_ZERO_SENT != 0 {
+                if self.extra_bit() {
+                    mantissa = self.extra_value(23);
+                    if info.max_exp >= 25 {
+                        exponent = self.extra_value(8);
+                    }
+                    negative = self.extra_bit();
+                } else if info.flags & FLOAT_ZERO_SIGN != 0 {
+                    negative = self.extra_bit();
+                }
+            }
+        }
+        f32::from_bits(u32::from(negative) << 31 | (exponent & 0xff) << 23 | mantissa)
+    }
+}
+
+enum BlockSamples {
+    Int(Vec<Vec<i32>>),
+    Float(Vec<Vec<f32>>),
+}
+
+/// Decodes one block into its one or two channels. Blocks carry all of their
+/// own state, so nothing survives from one block to the next.
+fn decode_block(block: &[u8], header: &BlockHeader) -> Result<BlockSamples> {
+    let flags = header.flags;
+    if flags & DSD_FLAG != 0 {
+        return unsupported_error("wavpack: DSD audio is not supported");
+    }
+    let stereo = flags & (MONO_FLAG | FALSE_STEREO) == 0;
+    let mut entropy = Entropy {
+        stereo,
+        hybrid: flags & HYBRID_FLAG != 0,
+        hybrid_bitrate: flags & HYBRID_BITRATE != 0,
+        ..Default::default()
+    };
+    let mut passes: Vec<DecorrPass> = Vec::new();
+    let mut got_entropy = false;
+    let mut bitstream = None;
+    let mut extra = None;
+    let mut float = FloatInfo::default();
+    let (mut extra_bits, mut and, mut or, mut shift) = (0, 0, 0, 0);
+    let channel_count = if stereo { 2 } else { 1 };
+
+    for (id, data) in sub_blocks(block) {
+        match id {
+            ID_DECORR_TERMS => {
+                if data.len() > MAX_TERMS {
+                    return decode_error("wavpack: too many decorrelation terms");
+                }
+                // Stored in the reverse of the order they are applied in
+                passes = data
+                    .iter()
+                    .rev()
+                    .map(|byte| DecorrPass {
+                        term: i32::from(byte & 0x1f) - 5,
+                        delta: i32::from(byte >> 5),
+                        ..Default::default()
+                    })
+                    .collect();
+                for pass in &passes {
+                    let valid = matches!(pass.term, 1..=8 | 17 | 18)
+                        || (stereo && matches!(pass.term, -3..=-1));
+                    if !valid {
+                        return decode_error("wavpack: invalid decorrelation term");
+                    }
+                }
+            }
+            ID_DECORR_WEIGHTS => {
+                let count = data.len() / channel_count;
+                if count > passes.len() {
+                    return decode_error("wavpack: too many decorrelation weights");
+                }
+                let total = passes.len();
+                for (index, weights) in data.chunks_exact(channel_count).enumerate() {
+                    let pass = &mut passes[total - index - 1];
+                    pass.weight_a = restore_weight(weights[0] as i8);
+                    if stereo {
+                        pass.weight_b = restore_weight(weights[1] as i8);
+                    }
+                }
+            }
+            ID_DECORR_SAMPLES => {
+                let mut values = data
+                    .chunks_exact(2)
+                    .map(|pair| wp_exp2(i16::from_le_bytes([pair[0], pair[1]])));
+                for pass in passes.iter_mut().rev() {
+                    if values.len() == 0 {
+                        break;
+                    }
+                    let invalid = || decode_error("wavpack: short decorrelation samples");
+                    if pass.term > 8 {
+                        for samples in [&mut pass.samples_a, &mut pass.samples_b]
+                            .into_iter()
+                            .take(channel_count)
+                        {
+                            let (Some(first), Some(second)) = (values.next(), values.next()) else {
+                                return invalid();
+                            };
+                            samples[0] = first;
+                            samples[1] = second;
+                        }
+                    } else if pass.term < 0 {
+                        let (Some(a), Some(b)) = (values.next(), values.next()) else {
+                            return invalid();
+                        };
+                        pass.samples_a[0] = a;
+                        pass.samples_b[0] = b;
+                    } else {
+                        for index in 0..pass.term as usize {
+                            let Some(a) = values.next() else {
+                                return invalid();
+                            };
+                            pass.samples_a[index] = a;
+                            if stereo {
+                                let Some(b) = values.next() else {
+                                    return invalid();
+                                };
+                                pass.samples_b[index] = b;
+                            }
+                        }
+                    }
+                }
+            }
+            ID_ENTROPY_VARS => {
+                if data.len() != 6 * channel_count {
+                    return decode_error("wavpack: malformed entropy variables");
+                }
+                for (channel, values) in entropy.channels.iter_mut().zip(data.chunks_exact(6)) {
+                    for (index, median) in channel.median.iter_mut().enumerate() {
+                        *median = wp_exp2(le16(values, index * 2).unwrap_or(0));
+                    }
+                }
+                got_entropy = true;
+            }
+            ID_HYBRID_PROFILE => {
+                let mut at = 0;
+                let mut read = || {
+                    let value = le16(data, at);
+                    at += 2;
+                    value
+                };
+                for channel in entropy.channels.iter_mut().take(channel_count) {
+                    if flags & HYBRID_BITRATE != 0 {
+                        channel.slow_level = read().map(wp_exp2).unwrap_or(0);
+                    }
+                }
+                for channel in entropy.channels.iter_mut().take(channel_count) {
+                    channel.bitrate_acc = u32::from(read().unwrap_or(0) as u16) << 16;
+                }
+                for channel in entropy.channels.iter_mut().take(channel_count) {
+                    channel.bitrate_delta = read().map(wp_exp2).unwrap_or(0) as u32;
+                }
+            }
+            ID_INT32_INFO if data.len() == 4 => {
+                extra_bits = u32::from(data[0]);
+                if data[1] != 0 {
+                    shift = u32::from(data[1]);
+                } else if data[2] != 0 {
+                    and = 1;
+                    or = 1;
+                    shift = u32::from(data[2]);
+                } else if data[3] != 0 {
+                    and = 1;
+                    shift = u32::from(data[3]);
+                }
+            }
+            ID_FLOAT_INFO if data.len() == 4 => {
+                float = FloatInfo {
+                    flags: data[0],
+                    shift: data[1],
+                    max_exp: data[2],
+                };
+            }
+            ID_WV_BITSTREAM => bitstream = Some(data),
+            // The first four bytes are a CRC of the extra bits
+            ID_WVX_BITSTREAM if data.len() > 4 => extra = Some(BitReader::new(&data[4..])),
+            _ => {}
+        }
+    }
+
+    let Some(bitstream) = bitstream else {
+        return decode_error("wavpack: block has no bitstream");
+    };
+    if !got_entropy {
+        return decode_error("wavpack: block has no entropy variables");
+    }
+    if extra_bits > 30 || shift > 31 {
+        return decode_error("wavpack: invalid integer layout");
+    }
+
+    let count = header.block_samples as usize;
+    let mut bits = BitReader::new(bitstream);
+    let mut decoded = vec![vec![0i32; count]; channel_count];
+    if let [left, right] = decoded.as_mut_slice() {
+        let joint = flags & JOINT_STEREO != 0;
+        unpack_stereo(&mut passes, &mut entropy, &mut bits, joint, left, right);
+    } else {
+        unpack_mono(&mut passes, &mut entropy, &mut bits, &mut decoded[0]);
+    }
+
+    let bytes = (flags & BYTES_STORED) + 1;
+    let original_bits = bytes * 8;
+    let mut post = PostProcess {
+        extra,
+        extra_bits,
+        and,
+        or,
+        shift,
+        post_shift: 32 - original_bits + ((flags >> SHIFT_LSB) & 0x1f),
+        hybrid: entropy.hybrid,
+        min_clip: (-(1i64 << (original_bits - 1))) as i32,
+        max_clip: ((1i64 << (original_bits - 1)) - 1) as i32,
+        float,
+    };
+    // 32-bit lossy audio is clipped as 24-bit, like the reference decoder
+    if post.hybrid && bytes == 4 && post.post_shift < 8 && post.shift > 8 {
+        post.post_shift += 8;
+        post.shift -= 8;
+        post.min_clip >>= 8;
+        post.max_clip >>= 8;
+    }
+    let output_channels = header.channels();
+
+    // Channels are post-processed sample by sample, in the order the encoder
+    // wrote their extra bits
+    if flags & FLOAT_DATA != 0 {
+        let mut output = vec![vec![0f32; count]; channel_count];
+        for index in 0..count {
+            for channel in 0..channel_count {
+                output[channel][index] = post.float(decoded[channel][index]);
+            }
+        }
+        if output_channels > channel_count {
+            output.push(output[0].clone());
+        }
+        Ok(BlockSamples::Float(output))
+    } else {
+        for index in 0..count {
+            for channel in decoded.iter_mut() {
+                channel[index] = post.integer(channel[index]);
+            }
+        }
+        if output_channels > channel_count {
+            decoded.push(decoded[0].clone());
+        }
+        Ok(BlockSamples::Int(decoded))
+    }
+}
+
+enum Output {
+    Int(AudioBuffer<i32>),
+    Float(AudioBuffer<f32>),
+}
+
+/// Decodes lossless and hybrid (lossy) WavPack, integer and floating point
+pub struct WavPackDecoder {
+    params: CodecParameters,
+    buf: Output,
+}
+
+impl Decoder for WavPackDecoder {
+    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self> {
+        let Some(channels) = params.channels else {
+            return decode_error("wavpack: missing channel layout");
+        };
+        let Some(sample_rate) = params.sample_rate else {
+            return decode_error("wavpack: missing sample rate");
+        };
+        let Some(max_frames) = params.max_frames_per_packet else {
+            return decode_error("wavpack: missing block size");
+        };
+        let spec = SignalSpec::new(sample_rate, channels);
+        let buf = if matches!(params.sample_format, Some(SampleFormat::F32)) {
+            Output::Float(AudioBuffer::new(max_frames, spec))
+        } else {
+            Output::Int(AudioBuffer::new(max_frames, spec))
+        };
+        Ok(Self {
+            params: params.clone(),
+            buf,
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        &[support_codec!(CODEC_TYPE_WAVPACK, "wavpack", "WavPack")]
+    }
+
+    fn reset(&mut self) {}
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>> {
+        let data = packet.buf();
+        let mut offset = 0;
+        let mut channel = 0;
+        let mut frames = None;
+        match &mut self.buf {
+            Output::Int(buf) => buf.clear(),
+            Output::Float(buf) => buf.clear(),
+        }
+
+        while let Some(header) = data.get(offset..).and_then(BlockHeader::parse) {
+            let Some(block) = data.get(offset..offset + header.size) else {
+                return decode_error("wavpack: truncated block");
+            };
+            offset += header.size;
+            if header.block_samples == 0 {
+                continue;
+            }
+
+            let count = header.block_samples as usize;
+            if frames.is_none() {
+                let capacity = match &self.buf {
+                    Output::Int(buf) => buf.capacity(),
+                    Output::Float(buf) => buf.capacity(),
+                };
+                if count > capacity {
+                    return decode_error("wavpack: block larger than the packet limit");
+                }
+                match &mut self.buf {
+                    Output::Int(buf) => buf.render_reserved(Some(count)),
+                    Output::Float(buf) => buf.render_reserved(Some(count)),
+                }
+                frames = Some(count);
+            }
+            if frames != Some(count) {
+                return decode_error("wavpack: blocks of one frame differ in length");
+            }
+
+            let channels = self.buf_channels();
+            let samples = decode_block(block, &header)?;
+            match (&mut self.buf, samples) {
+                (Output::Int(buf), BlockSamples::Int(samples)) => {
+                    for values in samples {
+                        if channel >= channels {
+                            return decode_error("wavpack: more channels than declared");
+                        }
+                        buf.chan_mut(channel).copy_from_slice(&values);
+                        channel += 1;
+                    }
+                }
+                (Output::Float(buf), BlockSamples::Float(samples)) => {
+                    for values in samples {
+                        if channel >= channels {
+                            return decode_error("wavpack: more channels than declared");
+                        }
+                        buf.chan_mut(channel).copy_from_slice(&values);
+                        channel += 1;
+                    }
+                }
+                _ => return decode_error("wavpack: sample format changed mid-stream"),
+            }
+        }
+
+        Ok(self.last_decoded())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        FinalizeResult::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_> {
+        match &self.buf {
+            Output::Int(buf) => buf.as_audio_buffer_ref(),
+            Output::Float(buf) => buf.as_audio_buffer_ref(),
+        }
+    }
+}
+
+impl WavPackDecoder {
+    fn buf_channels(&self) -> usize {
+        match &self.buf {
+            Output::Int(buf) => buf.spec().channels.count(),
+            Output::Float(buf) => buf.spec().channels.count(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn open(bytes: Vec<u8>) -> WavPackReader {
+        let source = MediaSourceStream::new(Box::new(Cursor::new(bytes)), Default::default());
+        WavPackReader::try_new(source, &FormatOptions::default()).unwrap()
+    }
+
+    fn sub_block(id: u8, data: &[u8]) -> Vec<u8> {
+        let odd = data.len() % 2 == 1;
+        let mut bytes = vec![
+            if odd { id | 0x40 } else { id },
+            data.len().div_ceil(2) as u8,
+        ];
+        bytes.extend_from_slice(data);
+        if odd {
+            bytes.push(0);
+        }
+        bytes
+    }
+
+    /// A 16-bit 44.1 kHz mono block whose bitstream holds the residuals 2 and
+    /// -2, run through one first-order decorrelation pass
+    fn mono_block(block_index: u32) -> Vec<u8> {
+        let mut body = Vec::new();
+        // Term 1, delta 2
+        body.extend(sub_block(ID_DECORR_TERMS, &[6 | (2 << 5)]));
+        body.extend(sub_block(ID_DECORR_WEIGHTS, &[64]));
+        body.extend(sub_block(ID_DECORR_SAMPLES, &[0, 0]));
+        // Every median at wp_exp2(0x700) = 64
+        body.extend(sub_block(ID_ENTROPY_VARS, &[0, 7, 0, 7, 0, 7]));
+        // Residual 2: no ones, tail 2 in two bits, positive. Residual -2: the
+        // zero flag skips the unary count, tail 1 in two bits, negative.
+        body.extend(sub_block(ID_WV_BITSTREAM, &[0b0110_0100, 0]));
+
+        let flags = 1 | MONO_FLAG | INITIAL_BLOCK | FINAL_BLOCK | (9 << SRATE_LSB);
+        let mut bytes = b"wvpk".to_vec();
+        bytes.extend_from_slice(&((HEADER_BYTES + body.len() - 8) as u32).to_le_bytes());
+        bytes.extend_from_slice(&0x410u16.to_le_bytes());
+        bytes.extend_from_slice(&[0, 0]);
+        for value in [u32::MAX, block_index, 2, flags, 0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend(body);
+        bytes
+    }
+
+    #[test]
+    fn exponent_tables_invert_each_other() {
+        assert_eq!(wp_exp2(0), 0);
+        assert_eq!(wp_exp2(0x700), 64);
+        assert_eq!(wp_exp2(-0x700), -64);
+        assert_eq!(wp_exp2(0x1000), 128 << 8);
+        for value in [1u32, 7, 100, 4096, 65_535, 1_000_000] {
+            let round_trip = wp_exp2(wp_log2(value) as i16);
+            let error = (i64::from(round_trip) - i64::from(value)).abs();
+            assert!(
+                error <= i64::from(value / 64 + 1),
+                "{} -> {}",
+                value,
+                round_trip
+            );
+        }
+    }
+
+    #[test]
+    fn reads_sub_blocks_with_odd_and_large_sizes() {
+        let mut body = vec![0u8; HEADER_BYTES];
+        body.extend(sub_block(ID_DECORR_TERMS, &[1, 2, 3]));
+        body.extend_from_slice(&[ID_WV_BITSTREAM | 0x80, 2, 0, 0, 9, 9, 9, 9]);
+        let blocks: Vec<(u8, &[u8])> = sub_blocks(&body).collect();
+        assert_eq!(
+            blocks,
+            vec![
+                (ID_DECORR_TERMS, &[1u8, 2, 3][..]),
+                (ID_WV_BITSTREAM, &[9u8, 9, 9, 9][..]),
+            ]
+        );
+    }
+
+    #[test]
+    fn shared_probe_and_registry_handle_the_format() {
+        let source =
+            MediaSourceStream::new(Box::new(Cursor::new(mono_block(0))), Default::default());
+        let probed = crate::formats::probe()
+            .format(
+                &Default::default(),
+                source,
+                &FormatOptions::default(),
+                &Default::default(),
+            )
+            .unwrap();
+        let params = &probed.format.tracks()[0].codec_params;
+        let codec = crate::formats::codecs().get_codec(params.codec).unwrap();
+        assert_eq!(codec.short_name, "wavpack");
+        assert!(crate::formats::codecs()
+            .make(params, &DecoderOptions::default())
+            .is_ok());
+    }
+
+    #[test]
+    fn indexes_blocks_and_seeks_to_the_frame_holding_a_timestamp() {
+        let mut bytes = mono_block(0);
+        bytes.extend(mono_block(2));
+        bytes.extend_from_slice(b"APETAGEX");
+        let mut reader = open(bytes);
+        let params = &reader.tracks()[0].codec_params;
+        assert_eq!(params.codec, CODEC_TYPE_WAVPACK);
+        assert_eq!(params.sample_rate, Some(44_100));
+        assert_eq!(params.n_frames, Some(4));
+        assert_eq!(params.bits_per_sample, Some(16));
+        assert_eq!(params.channels.map(|channels| channels.count()), Some(1));
+
+        let first = reader.next_packet().unwrap();
+        assert_eq!((first.ts(), first.dur()), (0, 2));
+        assert_eq!(reader.next_packet().unwrap().ts(), 2);
+        assert!(reader.next_packet().is_err());
+
+        let seeked = reader
+            .seek(SeekMode::Accurate, SeekTo::TimeStamp { ts: 3, track_id: 0 })
+            .unwrap();
+        assert_eq!(seeked.actual_ts, 2);
+        assert_eq!(reader.next_packet().unwrap().ts(), 2);
+    }
+
+    #[test]
+    fn decodes_residuals_through_the_decorrelation_pass() {
+        let mut reader = open(mono_block(0));
+        let params = reader.tracks()[0].codec_params.clone();
+        let mut decoder = WavPackDecoder::try_new(&params, &DecoderOptions::default()).unwrap();
+        let packet = reader.next_packet().unwrap();
+        let AudioBufferRef::S32(buffer) = decoder.decode(&packet).unwrap() else {
+            panic!("expected integer samples");
+        };
+        // The second sample adds the first back at a weight of 516/1024
+        assert_eq!(buffer.chan(0), &[2 << 16, -1 << 16]);
+    }
+
+    #[test]
+    fn zero_runs_decode_as_silence() {
+        let mut entropy = Entropy::default();
+        // Three ones then a zero: a run length of three bits whose leading one
+        // is implied, here 0b100 = 4 zeros
+        let data = [0b0000_0111];
+        let mut bits = BitReader::new(&data);
+        for _ in 0..4 {
+            assert_eq!(entropy.value(&mut bits, 0), Some(0));
+        }
+    }
+}
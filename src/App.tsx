@@ -37,6 +37,8 @@ import {
   useTrackRatings,
   usePlaylistOperations,
   useInboxOperations,
+  useInboxRules,
+  useWatchedFolders,
   useTrackAnalysis,
   useTrackEdit,
   useLibraryInit,
@@ -76,11 +78,13 @@ function App() {
   const theme = useSettingsStore((s) => s.theme);
   const locale = useSettingsStore((s) => s.locale);
   const seekMode = useSettingsStore((s) => s.seekMode);
+  const filenamePatterns = useSettingsStore((s) => s.filenamePatterns);
   const dbPath = useSettingsStore((s) => s.dbPath);
   const dbFileName = useSettingsStore((s) => s.dbFileName);
   const setTheme = useSettingsStore((s) => s.setTheme);
   const setLocale = useSettingsStore((s) => s.setLocale);
   const setSeekMode = useSettingsStore((s) => s.setSeekMode);
+  const setFilenamePatterns = useSettingsStore((s) => s.setFilenamePatterns);
   const setDbPath = useSettingsStore((s) => s.setDbPath);
   const setDbFileName = useSettingsStore((s) => s.setDbFileName);
   const setUseAutoDbPath = useSettingsStore((s) => s.setUseAutoDbPath);
@@ -361,6 +365,7 @@ function App() {
   // File import and playlist drop
   const {
     handleImportPaths,
+    handleCancelImport,
     handlePlaylistDrop,
     handleCreatePlaylist,
     confirmPlaylistDropOperation,
@@ -410,7 +415,21 @@ function App() {
   });
 
   // Inbox operations
-  const { handleAcceptTracks, handleRejectTracks } = useInboxOperations();
+  const { handleAcceptTracks, handleRejectTracks, handleApplyInboxRules } =
+    useInboxOperations();
+  const {
+    inboxRules,
+    inboxRulesSavePending,
+    inboxRulesStatus,
+    handleInboxRulesChange,
+    handleSaveInboxRules,
+  } = useInboxRules();
+  const {
+    watchedFolders,
+    watchedFoldersStatus,
+    handleAddWatchedFolder,
+    handleRemoveWatchedFolder,
+  } = useWatchedFolders();
 
   // Track analysis
   const {
@@ -444,9 +463,21 @@ function App() {
     backfillStatus,
     coverArtBackfillPending,
     coverArtBackfillStatus,
+    audioPropertiesBackfillPending,
+    audioPropertiesBackfillStatus,
+    artistCreditsBackfillPending,
+    artistCreditsBackfillStatus,
+    albumsBackfillPending,
+    albumsBackfillStatus,
+    tagWriteRetryPending,
+    tagWriteRetryStatus,
     clearSongsPending,
     handleBackfillSearchText,
     handleBackfillCoverArt,
+    handleBackfillAudioProperties,
+    handleBackfillArtistCredits,
+    handleBackfillAlbums,
+    handleRetryTagWrites,
     handleClearSongs,
   } = useLibraryInit();
 
@@ -539,7 +570,16 @@ function App() {
               "aac",
               "ogg",
               "aiff",
+              "aif",
               "alac",
+              "m4b",
+              "opus",
+              "wv",
+              "ape",
+              "mka",
+              "caf",
+              "dsf",
+              "dff",
             ],
           },
         ],
@@ -713,11 +753,26 @@ function App() {
                 />
                 {viewConfig.trackTable && importProgress && (
                   <div className="border-b border-[var(--color-border-light)] bg-[var(--color-bg-primary)] px-[var(--spacing-lg)] py-[var(--spacing-md)]">
-                    <div className="mb-[var(--spacing-xs)] text-[length:var(--font-size-xs)] font-semibold text-[color:var(--color-text-secondary)]">
+                    <div className="mb-[var(--spacing-xs)] flex items-center text-[length:var(--font-size-xs)] font-semibold text-[color:var(--color-text-secondary)]">
                       {importProgress.phase === "scanning" ||
                       importProgress.total === 0
                         ? "Scanning files..."
                         : `${importProgress.imported} of ${importProgress.total} songs imported`}
+                      {importProgress.currentFile && (
+                        <span className="ml-[var(--spacing-sm)] font-normal text-[color:var(--color-text-muted)]">
+                          {importProgress.currentFile}
+                        </span>
+                      )}
+                      {importProgress.jobId &&
+                        importProgress.imported < importProgress.total && (
+                          <button
+                            type="button"
+                            className="ml-auto rounded-[var(--radius-md)] px-[var(--spacing-sm)] font-medium text-[color:var(--color-text-secondary)] transition-all duration-[var(--transition-fast)] hover:bg-[var(--color-bg-hover)] hover:text-[var(--color-text-primary)]"
+                            onClick={() => void handleCancelImport()}
+                          >
+                            Cancel
+                          </button>
+                        )}
                     </div>
                     <div className="h-2 w-full overflow-hidden rounded-[var(--radius-full)] bg-[var(--color-bg-tertiary)]">
                       <div
@@ -740,15 +795,38 @@ function App() {
                       backfillStatus={backfillStatus}
                       coverArtBackfillPending={coverArtBackfillPending}
                       coverArtBackfillStatus={coverArtBackfillStatus}
+                      audioPropertiesBackfillPending={audioPropertiesBackfillPending}
+                      audioPropertiesBackfillStatus={audioPropertiesBackfillStatus}
+                      artistCreditsBackfillPending={artistCreditsBackfillPending}
+                      artistCreditsBackfillStatus={artistCreditsBackfillStatus}
+                      albumsBackfillPending={albumsBackfillPending}
+                      albumsBackfillStatus={albumsBackfillStatus}
+                      tagWriteRetryPending={tagWriteRetryPending}
+                      tagWriteRetryStatus={tagWriteRetryStatus}
                       clearSongsPending={clearSongsPending}
                       seekMode={seekMode}
+                      filenamePatterns={filenamePatterns}
+                      inboxRules={inboxRules}
+                      inboxRulesSavePending={inboxRulesSavePending}
+                      inboxRulesStatus={inboxRulesStatus}
+                      watchedFolders={watchedFolders}
+                      watchedFoldersStatus={watchedFoldersStatus}
                       onThemeChange={setTheme}
                       onLocaleChange={setLocale}
                       onSeekModeChange={setSeekMode}
+                      onFilenamePatternsChange={setFilenamePatterns}
+                      onInboxRulesChange={handleInboxRulesChange}
+                      onSaveInboxRules={handleSaveInboxRules}
+                      onAddWatchedFolder={handleAddWatchedFolder}
+                      onRemoveWatchedFolder={handleRemoveWatchedFolder}
                       onDbPathChange={setDbPath}
                       onDbFileNameChange={setDbFileName}
                       onBackfillSearchText={handleBackfillSearchText}
                       onBackfillCoverArt={handleBackfillCoverArt}
+                      onBackfillAudioProperties={handleBackfillAudioProperties}
+                      onBackfillArtistCredits={handleBackfillArtistCredits}
+                      onBackfillAlbums={handleBackfillAlbums}
+                      onRetryTagWrites={handleRetryTagWrites}
                       onClearSongs={handleClearSongs}
                       onUseDefaultLocation={() => setUseAutoDbPath(true)}
                     />
@@ -810,6 +888,7 @@ function App() {
                             selectedCount={selectedIds.size}
                             onAccept={handleAcceptTracks}
                             onReject={handleRejectTracks}
+                            onApplyRules={handleApplyInboxRules}
                           />
                         )}
                       </>
@@ -0,0 +1,158 @@
+import { open } from "@tauri-apps/plugin-dialog";
+import { Plus, Trash2 } from "lucide-react";
+import type { InboxRule, InboxRuleField } from "../../utils";
+
+type InboxRulesEditorProps = {
+  rules: InboxRule[];
+  savePending: boolean;
+  status: string | null;
+  onRulesChange: (rules: InboxRule[]) => void;
+  onSave: () => void;
+};
+
+const ruleFields: ReadonlyArray<{ id: InboxRuleField; label: string }> = [
+  { id: "title", label: "Title" },
+  { id: "artist", label: "Artist" },
+  { id: "album", label: "Album" },
+  { id: "album_artist", label: "Album artist" },
+  { id: "year", label: "Year" },
+  { id: "genre", label: "Genre" },
+  { id: "track_number", label: "Track number" },
+  { id: "bpm", label: "BPM" },
+  { id: "key", label: "Key" },
+  { id: "cover", label: "Cover art" },
+];
+
+const inputClass =
+  "h-[var(--input-height)] rounded-[var(--radius-md)] border border-[var(--color-border)] bg-[var(--color-bg-tertiary)] px-[var(--spacing-md)] text-[var(--font-size-sm)] text-[var(--color-text-primary)] transition-all duration-[var(--transition-fast)] placeholder:text-[var(--color-text-muted)] focus:border-[var(--color-accent)] focus:outline-none focus:ring-4 focus:ring-[var(--color-accent-light)]";
+const secondaryButtonClass =
+  "flex h-[var(--button-height)] items-center gap-[var(--spacing-sm)] rounded-[var(--radius-md)] border border-[var(--color-border)] bg-[var(--color-bg-tertiary)] px-[var(--spacing-md)] text-[var(--font-size-sm)] font-medium text-[var(--color-text-primary)] transition-all duration-[var(--transition-fast)] hover:bg-[var(--color-bg-hover)] disabled:cursor-not-allowed disabled:opacity-60";
+
+/** Edits the rules that accept imported tracks straight into the library */
+export const InboxRulesEditor = ({
+  rules,
+  savePending,
+  status,
+  onRulesChange,
+  onSave,
+}: InboxRulesEditorProps) => {
+  const updateRule = (index: number, changes: Partial<InboxRule>) => {
+    onRulesChange(
+      rules.map((rule, position) => (position === index ? { ...rule, ...changes } : rule))
+    );
+  };
+
+  const toggleField = (index: number, field: InboxRuleField) => {
+    const rule = rules[index];
+    const require = rule.require.includes(field)
+      ? rule.require.filter((value) => value !== field)
+      : [...rule.require, field];
+    updateRule(index, { require });
+  };
+
+  const handlePickFolder = async (index: number) => {
+    const result = await open({ directory: true });
+    if (typeof result === "string") {
+      updateRule(index, { folder: result });
+    }
+  };
+
+  return (
+    <div className="space-y-3">
+      {rules.map((rule, index) => (
+        <div
+          key={index}
+          className="max-w-xl space-y-3 rounded-[var(--radius-md)] border border-[var(--color-border)] p-[var(--spacing-md)]"
+        >
+          <div className="flex items-center gap-3">
+            <input
+              type="checkbox"
+              checked={rule.enabled}
+              onChange={(event) => updateRule(index, { enabled: event.target.checked })}
+              aria-label="Enabled"
+            />
+            <input
+              className={`${inputClass} flex-1`}
+              placeholder="Rule name"
+              value={rule.name}
+              onChange={(event) => updateRule(index, { name: event.target.value })}
+            />
+            <button
+              className={secondaryButtonClass}
+              onClick={() => onRulesChange(rules.filter((_, position) => position !== index))}
+              type="button"
+              aria-label="Remove rule"
+            >
+              <Trash2 className="h-4 w-4" />
+            </button>
+          </div>
+          <div className="flex flex-wrap gap-x-4 gap-y-2">
+            {ruleFields.map((field) => (
+              <label
+                key={field.id}
+                className="flex items-center gap-2 text-[var(--font-size-sm)] text-[var(--color-text-primary)]"
+              >
+                <input
+                  type="checkbox"
+                  checked={rule.require.includes(field.id)}
+                  onChange={() => toggleField(index, field.id)}
+                />
+                {field.label}
+              </label>
+            ))}
+          </div>
+          <div className="flex items-center gap-3">
+            <input
+              className={`${inputClass} flex-1`}
+              placeholder="Any folder"
+              value={rule.folder ?? ""}
+              onChange={(event) =>
+                updateRule(index, { folder: event.target.value || null })
+              }
+            />
+            <button
+              className={secondaryButtonClass}
+              onClick={() => handlePickFolder(index)}
+              type="button"
+            >
+              Choose...
+            </button>
+          </div>
+        </div>
+      ))}
+
+      <div className="flex flex-wrap items-center gap-3">
+        <button
+          className={secondaryButtonClass}
+          onClick={() =>
+            onRulesChange([
+              ...rules,
+              { name: `Rule ${rules.length + 1}`, enabled: true, require: [], folder: null },
+            ])
+          }
+          type="button"
+        >
+          <Plus className="h-4 w-4" />
+          Add rule
+        </button>
+        <button
+          className="flex h-[var(--button-height)] items-center gap-[var(--spacing-sm)] rounded-[var(--radius-md)] bg-[var(--color-accent)] px-[var(--spacing-md)] text-[var(--font-size-sm)] font-medium text-white transition-all duration-[var(--transition-fast)] hover:bg-[var(--color-accent-hover)] disabled:cursor-not-allowed disabled:opacity-60"
+          onClick={onSave}
+          disabled={savePending}
+          type="button"
+        >
+          {savePending ? "Saving..." : "Save rules"}
+        </button>
+        {status && (
+          <span className="text-[var(--font-size-sm)] text-[var(--color-text-secondary)]">
+            {status}
+          </span>
+        )}
+      </div>
+      <p className="text-[var(--font-size-xs)] text-[var(--color-text-secondary)]">
+        Imported tracks skip the inbox when every ticked field is filled in and, if a folder
+        is set, the file is inside it. A rule needs at least one field or a folder.
+      </p>
+    </div>
+  );
+};
@@ -1,6 +1,9 @@
 import { useState } from "react";
 import { ChevronDown } from "lucide-react";
 import { t, type Locale } from "../../i18n";
+import type { InboxRule, WatchedFolder } from "../../utils";
+import { InboxRulesEditor } from "./InboxRulesEditor";
+import { WatchedFoldersEditor } from "./WatchedFoldersEditor";
 
 type SettingsPanelProps = {
   theme: string;
@@ -13,15 +16,38 @@ type SettingsPanelProps = {
   backfillStatus: string | null;
   coverArtBackfillPending: boolean;
   coverArtBackfillStatus: string | null;
+  audioPropertiesBackfillPending: boolean;
+  audioPropertiesBackfillStatus: string | null;
+  artistCreditsBackfillPending: boolean;
+  artistCreditsBackfillStatus: string | null;
+  albumsBackfillPending: boolean;
+  albumsBackfillStatus: string | null;
+  tagWriteRetryPending: boolean;
+  tagWriteRetryStatus: string | null;
   clearSongsPending: boolean;
   seekMode: "fast" | "accurate";
+  filenamePatterns: string[];
+  inboxRules: InboxRule[];
+  inboxRulesSavePending: boolean;
+  inboxRulesStatus: string | null;
+  watchedFolders: WatchedFolder[];
+  watchedFoldersStatus: string | null;
   onThemeChange: (theme: string) => void;
   onLocaleChange: (locale: Locale) => void;
   onSeekModeChange: (mode: "fast" | "accurate") => void;
+  onFilenamePatternsChange: (patterns: string[]) => void;
+  onInboxRulesChange: (rules: InboxRule[]) => void;
+  onSaveInboxRules: () => void;
+  onAddWatchedFolder: (path: string) => void;
+  onRemoveWatchedFolder: (folderId: string) => void;
   onDbPathChange: (value: string) => void;
   onDbFileNameChange: (value: string) => void;
   onBackfillSearchText: () => void;
   onBackfillCoverArt: () => void;
+  onBackfillAudioProperties: () => void;
+  onBackfillArtistCredits: () => void;
+  onBackfillAlbums: () => void;
+  onRetryTagWrites: () => void;
   onClearSongs: () => void;
   onUseDefaultLocation: () => void;
 };
@@ -50,15 +76,38 @@ export const SettingsPanel = ({
   backfillStatus,
   coverArtBackfillPending,
   coverArtBackfillStatus,
+  audioPropertiesBackfillPending,
+  audioPropertiesBackfillStatus,
+  artistCreditsBackfillPending,
+  artistCreditsBackfillStatus,
+  albumsBackfillPending,
+  albumsBackfillStatus,
+  tagWriteRetryPending,
+  tagWriteRetryStatus,
   clearSongsPending,
   seekMode,
+  filenamePatterns,
+  inboxRules,
+  inboxRulesSavePending,
+  inboxRulesStatus,
+  watchedFolders,
+  watchedFoldersStatus,
   onThemeChange,
   onLocaleChange,
   onSeekModeChange,
+  onFilenamePatternsChange,
+  onInboxRulesChange,
+  onSaveInboxRules,
+  onAddWatchedFolder,
+  onRemoveWatchedFolder,
   onDbPathChange,
   onDbFileNameChange,
   onBackfillSearchText,
   onBackfillCoverArt,
+  onBackfillAudioProperties,
+  onBackfillArtistCredits,
+  onBackfillAlbums,
+  onRetryTagWrites,
   onClearSongs,
   onUseDefaultLocation,
 }: SettingsPanelProps) => {
@@ -191,6 +240,49 @@ export const SettingsPanel = ({
               </div>
             </div>
 
+            <div>
+              <h3 className="mb-[var(--spacing-md)] text-[var(--font-size-sm)] font-semibold uppercase tracking-wide text-[var(--color-text-muted)]">
+                Import
+              </h3>
+              <div className="space-y-3">
+                <label className="text-[var(--font-size-sm)] font-medium text-[var(--color-text-primary)]">
+                  Filename Patterns
+                </label>
+                <textarea
+                  className="block min-h-24 w-full max-w-xl rounded-[var(--radius-md)] border border-[var(--color-border)] bg-[var(--color-bg-tertiary)] px-[var(--spacing-md)] py-[var(--spacing-sm)] font-mono text-[var(--font-size-sm)] text-[var(--color-text-primary)] transition-all duration-[var(--transition-fast)] placeholder:text-[var(--color-text-muted)] focus:border-[var(--color-accent)] focus:outline-none focus:ring-4 focus:ring-[var(--color-accent-light)]"
+                  placeholder="%artist%/%album%/%track% - %title%"
+                  value={filenamePatterns.join("\n")}
+                  onChange={(event) =>
+                    onFilenamePatternsChange(event.target.value.split("\n"))
+                  }
+                />
+                <p className="text-[var(--font-size-xs)] text-[var(--color-text-secondary)]">
+                  One pattern per line, tried in order, used to guess tags for untagged files
+                  from their path. Fields: %artist%, %albumartist%, %album%, %title%, %track%,
+                  %disc%, %year%, %genre% and %ignore%.
+                </p>
+                <label className="block pt-2 text-[var(--font-size-sm)] font-medium text-[var(--color-text-primary)]">
+                  Inbox Rules
+                </label>
+                <InboxRulesEditor
+                  rules={inboxRules}
+                  savePending={inboxRulesSavePending}
+                  status={inboxRulesStatus}
+                  onRulesChange={onInboxRulesChange}
+                  onSave={onSaveInboxRules}
+                />
+                <label className="block pt-2 text-[var(--font-size-sm)] font-medium text-[var(--color-text-primary)]">
+                  Watched Folders
+                </label>
+                <WatchedFoldersEditor
+                  folders={watchedFolders}
+                  status={watchedFoldersStatus}
+                  onAddFolder={onAddWatchedFolder}
+                  onRemoveFolder={onRemoveWatchedFolder}
+                />
+              </div>
+            </div>
+
             {/* Maintenance Section */}
             <div>
               <h3 className="mb-[var(--spacing-md)] text-[var(--font-size-sm)] font-semibold uppercase tracking-wide text-[var(--color-text-muted)]">
@@ -272,6 +364,90 @@ export const SettingsPanel = ({
                     Extracts and caches cover art for tracks imported before this feature was added.
                   </p>
                 </div>
+
+                <div>
+                  <div className="flex flex-wrap items-center gap-3">
+                    <button
+                      className="flex h-[var(--button-height)] items-center gap-[var(--spacing-sm)] rounded-[var(--radius-md)] bg-[var(--color-accent)] px-[var(--spacing-md)] text-[var(--font-size-sm)] font-medium text-white transition-all duration-[var(--transition-fast)] hover:bg-[var(--color-accent-hover)] disabled:cursor-not-allowed disabled:opacity-60"
+                      onClick={onBackfillAudioProperties}
+                      disabled={audioPropertiesBackfillPending}
+                      type="button"
+                    >
+                      {audioPropertiesBackfillPending ? "Reading..." : "Read audio properties"}
+                    </button>
+                    {audioPropertiesBackfillStatus && (
+                      <span className="text-[var(--font-size-sm)] text-[var(--color-text-secondary)]">
+                        {audioPropertiesBackfillStatus}
+                      </span>
+                    )}
+                  </div>
+                  <p className="mt-2 text-[var(--font-size-xs)] text-[var(--color-text-secondary)]">
+                    Reads sample rate, bit depth, codec and other audio properties for tracks imported before they were stored.
+                  </p>
+                </div>
+
+                <div>
+                  <div className="flex flex-wrap items-center gap-3">
+                    <button
+                      className="flex h-[var(--button-height)] items-center gap-[var(--spacing-sm)] rounded-[var(--radius-md)] bg-[var(--color-accent)] px-[var(--spacing-md)] text-[var(--font-size-sm)] font-medium text-white transition-all duration-[var(--transition-fast)] hover:bg-[var(--color-accent-hover)] disabled:cursor-not-allowed disabled:opacity-60"
+                      onClick={onBackfillArtistCredits}
+                      disabled={artistCreditsBackfillPending}
+                      type="button"
+                    >
+                      {artistCreditsBackfillPending ? "Crediting..." : "Credit artists"}
+                    </button>
+                    {artistCreditsBackfillStatus && (
+                      <span className="text-[var(--font-size-sm)] text-[var(--color-text-secondary)]">
+                        {artistCreditsBackfillStatus}
+                      </span>
+                    )}
+                  </div>
+                  <p className="mt-2 text-[var(--font-size-xs)] text-[var(--color-text-secondary)]">
+                    Records artist credits for tracks imported before the artist list existed, and removes artists no track credits.
+                  </p>
+                </div>
+
+                <div>
+                  <div className="flex flex-wrap items-center gap-3">
+                    <button
+                      className="flex h-[var(--button-height)] items-center gap-[var(--spacing-sm)] rounded-[var(--radius-md)] bg-[var(--color-accent)] px-[var(--spacing-md)] text-[var(--font-size-sm)] font-medium text-white transition-all duration-[var(--transition-fast)] hover:bg-[var(--color-accent-hover)] disabled:cursor-not-allowed disabled:opacity-60"
+                      onClick={onBackfillAlbums}
+                      disabled={albumsBackfillPending}
+                      type="button"
+                    >
+                      {albumsBackfillPending ? "Grouping..." : "Rebuild albums"}
+                    </button>
+                    {albumsBackfillStatus && (
+                      <span className="text-[var(--font-size-sm)] text-[var(--color-text-secondary)]">
+                        {albumsBackfillStatus}
+                      </span>
+                    )}
+                  </div>
+                  <p className="mt-2 text-[var(--font-size-xs)] text-[var(--color-text-secondary)]">
+                    Groups every track into albums again from its tags, for tracks imported before albums existed or under the older year-based grouping.
+                  </p>
+                </div>
+
+                <div>
+                  <div className="flex flex-wrap items-center gap-3">
+                    <button
+                      className="flex h-[var(--button-height)] items-center gap-[var(--spacing-sm)] rounded-[var(--radius-md)] bg-[var(--color-accent)] px-[var(--spacing-md)] text-[var(--font-size-sm)] font-medium text-white transition-all duration-[var(--transition-fast)] hover:bg-[var(--color-accent-hover)] disabled:cursor-not-allowed disabled:opacity-60"
+                      onClick={onRetryTagWrites}
+                      disabled={tagWriteRetryPending}
+                      type="button"
+                    >
+                      {tagWriteRetryPending ? "Writing..." : "Retry tag writes"}
+                    </button>
+                    {tagWriteRetryStatus && (
+                      <span className="text-[var(--font-size-sm)] text-[var(--color-text-secondary)]">
+                        {tagWriteRetryStatus}
+                      </span>
+                    )}
+                  </div>
+                  <p className="mt-2 text-[var(--font-size-xs)] text-[var(--color-text-secondary)]">
+                    Writes edits that could not be saved to their files, for example because the drive was read-only at the time.
+                  </p>
+                </div>
               </div>
             </div>
           </div>
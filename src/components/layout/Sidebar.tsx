@@ -1,7 +1,9 @@
-import { ChevronLeft, ChevronRight, Clock, Inbox, Library, ListMusic, Music, Plus, Settings } from "lucide-react";
+import { useMemo } from "react";
+import { ChevronLeft, ChevronRight, Clock, Folder, Inbox, Library, ListMusic, Music, Plus, Settings } from "lucide-react";
 import { t } from "../../i18n";
 import { useLibraryStore } from "../../stores";
 import type { LibraryView } from "../../hooks";
+import type { Playlist } from "../../types";
 
 type SidebarProps = {
   currentView: LibraryView;
@@ -19,6 +21,28 @@ type SidebarProps = {
   onPlaylistContextMenu: (event: React.MouseEvent<HTMLButtonElement>, id: string) => void;
 };
 
+// Playlists in display order, each folder followed by its contents
+const flattenPlaylists = (playlists: Playlist[]) => {
+  const ids = new Set(playlists.map((playlist) => playlist.id));
+  const childrenByParent = new Map<string | null, Playlist[]>();
+  for (const playlist of playlists) {
+    const parentId = playlist.parentId && ids.has(playlist.parentId) ? playlist.parentId : null;
+    const siblings = childrenByParent.get(parentId) ?? [];
+    siblings.push(playlist);
+    childrenByParent.set(parentId, siblings);
+  }
+
+  const flattened: { playlist: Playlist; depth: number }[] = [];
+  const visit = (parentId: string | null, depth: number) => {
+    for (const playlist of childrenByParent.get(parentId) ?? []) {
+      flattened.push({ playlist, depth });
+      visit(playlist.id, depth + 1);
+    }
+  };
+  visit(null, 0);
+  return flattened;
+};
+
 export const Sidebar = ({
   currentView,
   draggingPlaylistId,
@@ -38,6 +62,7 @@ export const Sidebar = ({
   const tracks = useLibraryStore((s) => s.tracks);
   const inboxTracks = useLibraryStore((s) => s.inboxTracks);
   const playlists = useLibraryStore((s) => s.playlists);
+  const playlistTree = useMemo(() => flattenPlaylists(playlists), [playlists]);
   const trackCount = tracks.length;
   const inboxCount = inboxTracks.length;
   const isLibrary = currentView === "library";
@@ -163,7 +188,22 @@ export const Sidebar = ({
           </p>
         )}
         
-        {playlists.map((playlist) => {
+        {playlistTree.map(({ playlist, depth }) => {
+          const indent = { paddingLeft: `calc(var(--spacing-md) + ${depth} * var(--spacing-lg))` };
+          if (playlist.isFolder) {
+            return (
+              <button
+                key={playlist.id}
+                className="mb-1 flex w-full items-center gap-[var(--spacing-sm)] rounded-[var(--radius-md)] px-[var(--spacing-md)] py-[var(--spacing-sm)] text-left text-[var(--font-size-sm)] font-medium text-[var(--color-text-muted)]"
+                onContextMenu={(event) => onPlaylistContextMenu(event, playlist.id)}
+                style={indent}
+                type="button"
+              >
+                <Folder className="h-4 w-4 shrink-0" />
+                <span className="truncate">{playlist.name}</span>
+              </button>
+            );
+          }
           const isDropTarget = draggingPlaylistId === playlist.id;
           const isActive = currentView === `playlist:${playlist.id}`;
           return (
@@ -196,6 +236,7 @@ export const Sidebar = ({
                 onPlaylistDrop(event, playlist.id);
               }}
               data-playlist-target={playlist.id}
+              style={indent}
               type="button"
             >
               <span className="truncate">{playlist.name}</span>
@@ -0,0 +1,70 @@
+import { open } from "@tauri-apps/plugin-dialog";
+import { Plus, Trash2 } from "lucide-react";
+import type { WatchedFolder } from "../../utils";
+
+type WatchedFoldersEditorProps = {
+  folders: WatchedFolder[];
+  status: string | null;
+  onAddFolder: (path: string) => void;
+  onRemoveFolder: (folderId: string) => void;
+};
+
+const secondaryButtonClass =
+  "flex h-[var(--button-height)] items-center gap-[var(--spacing-sm)] rounded-[var(--radius-md)] border border-[var(--color-border)] bg-[var(--color-bg-tertiary)] px-[var(--spacing-md)] text-[var(--font-size-sm)] font-medium text-[var(--color-text-primary)] transition-all duration-[var(--transition-fast)] hover:bg-[var(--color-bg-hover)] disabled:cursor-not-allowed disabled:opacity-60";
+
+/** Lists the folders new files are imported from and lets the user add or remove them */
+export const WatchedFoldersEditor = ({
+  folders,
+  status,
+  onAddFolder,
+  onRemoveFolder,
+}: WatchedFoldersEditorProps) => {
+  const handlePickFolder = async () => {
+    const result = await open({ directory: true });
+    if (typeof result === "string") {
+      onAddFolder(result);
+    }
+  };
+
+  return (
+    <div className="space-y-3">
+      {folders.length > 0 && (
+        <ul className="max-w-xl space-y-2">
+          {folders.map((folder) => (
+            <li
+              key={folder.id}
+              className="flex items-center gap-3 rounded-[var(--radius-md)] border border-[var(--color-border)] px-[var(--spacing-md)] py-[var(--spacing-sm)]"
+            >
+              <span className="flex-1 truncate font-mono text-[var(--font-size-sm)] text-[var(--color-text-primary)]">
+                {folder.path}
+              </span>
+              <button
+                className={secondaryButtonClass}
+                onClick={() => onRemoveFolder(folder.id)}
+                type="button"
+                aria-label="Stop watching folder"
+              >
+                <Trash2 className="h-4 w-4" />
+              </button>
+            </li>
+          ))}
+        </ul>
+      )}
+
+      <div className="flex flex-wrap items-center gap-3">
+        <button className={secondaryButtonClass} onClick={handlePickFolder} type="button">
+          <Plus className="h-4 w-4" />
+          Add folder
+        </button>
+        {status && (
+          <span className="text-[var(--font-size-sm)] text-[var(--color-text-secondary)]">
+            {status}
+          </span>
+        )}
+      </div>
+      <p className="text-[var(--font-size-xs)] text-[var(--color-text-secondary)]">
+        Audio files copied into these folders are imported once they finish writing.
+      </p>
+    </div>
+  );
+};
@@ -1,6 +1,8 @@
 export { AppLayout } from "./AppLayout";
+export { InboxRulesEditor } from "./InboxRulesEditor";
 export { LibraryHeader } from "./LibraryHeader";
 export { PlayerBar } from "./PlayerBar";
 export { QueuePanel } from "./QueuePanel";
 export { SettingsPanel } from "./SettingsPanel";
 export { Sidebar } from "./Sidebar";
+export { WatchedFoldersEditor } from "./WatchedFoldersEditor";
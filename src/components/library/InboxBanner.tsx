@@ -4,9 +4,15 @@ type InboxBannerProps = {
   selectedCount: number;
   onAccept: () => void;
   onReject: () => void;
+  onApplyRules: () => void;
 };
 
-export const InboxBanner = ({ selectedCount, onAccept, onReject }: InboxBannerProps) => {
+export const InboxBanner = ({
+  selectedCount,
+  onAccept,
+  onReject,
+  onApplyRules,
+}: InboxBannerProps) => {
   return (
     <div className="px-[var(--spacing-lg)] pb-[var(--spacing-md)]">
       <div className="flex flex-wrap items-center gap-3 rounded-[var(--radius-lg)] bg-[var(--color-bg-primary)] px-5 py-4 text-[var(--font-size-sm)]">
@@ -20,6 +26,12 @@ export const InboxBanner = ({ selectedCount, onAccept, onReject }: InboxBannerPr
           </span>
         </div>
         <div className="ml-auto flex items-center gap-3">
+          <button
+            onClick={onApplyRules}
+            className="flex h-[var(--button-height)] items-center rounded-[var(--radius-md)] border border-[var(--color-border)] bg-[var(--color-bg-tertiary)] px-[var(--spacing-md)] text-[var(--font-size-sm)] font-medium text-[var(--color-text-primary)] transition-all duration-[var(--transition-fast)] hover:bg-[var(--color-bg-hover)]"
+          >
+            {t("inbox.applyRules")}
+          </button>
           <button
             disabled={selectedCount === 0}
             onClick={onAccept}
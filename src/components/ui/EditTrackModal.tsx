@@ -441,6 +441,7 @@ export const EditTrackModal = ({
               value={form.comment}
               placeholder={isBatch ? t("edit.placeholder.keep") : ""}
               onChange={(v) => updateField("comment", v)}
+              multiline
             />
           </div>
         </div>
@@ -481,8 +482,13 @@ type FieldProps = {
   type?: "text" | "number";
   className?: string;
   inputRef?: React.Ref<HTMLInputElement>;
+  /** One value per line, e.g. comments that may contain commas */
+  multiline?: boolean;
 };
 
+const fieldInputClass =
+  "w-full rounded-[var(--radius-md)] border border-[var(--color-border)] bg-[var(--color-bg-secondary)] px-[var(--spacing-md)] py-[var(--spacing-sm)] text-[var(--font-size-sm)] text-[var(--color-text-primary)] placeholder:text-[var(--color-text-muted)] focus:outline-none focus:ring-2 focus:ring-[var(--color-accent)]";
+
 const Field = ({
   label,
   value,
@@ -491,19 +497,30 @@ const Field = ({
   type = "text",
   className,
   inputRef,
+  multiline = false,
 }: FieldProps) => (
   <div className={className}>
     <label className="mb-[var(--spacing-xs)] block text-[var(--font-size-xs)] font-medium text-[var(--color-text-secondary)]">
       {label}
     </label>
-    <input
-      ref={inputRef}
-      className="w-full rounded-[var(--radius-md)] border border-[var(--color-border)] bg-[var(--color-bg-secondary)] px-[var(--spacing-md)] py-[var(--spacing-sm)] text-[var(--font-size-sm)] text-[var(--color-text-primary)] placeholder:text-[var(--color-text-muted)] focus:outline-none focus:ring-2 focus:ring-[var(--color-accent)]"
-      type={type}
-      value={value}
-      placeholder={placeholder}
-      onChange={(e) => onChange(e.target.value)}
-    />
+    {multiline ? (
+      <textarea
+        className={fieldInputClass}
+        rows={2}
+        value={value}
+        placeholder={placeholder}
+        onChange={(e) => onChange(e.target.value)}
+      />
+    ) : (
+      <input
+        ref={inputRef}
+        className={fieldInputClass}
+        type={type}
+        value={value}
+        placeholder={placeholder}
+        onChange={(e) => onChange(e.target.value)}
+      />
+    )}
   </div>
 );
 
@@ -23,6 +23,8 @@ export {
 } from "./useAudioPlayback";
 export { usePlaylistOperations } from "./usePlaylistOperations";
 export { useInboxOperations } from "./useInboxOperations";
+export { useInboxRules } from "./useInboxRules";
+export { useWatchedFolders } from "./useWatchedFolders";
 export {
   useFileImport,
   type ImportProgress,
@@ -13,6 +13,7 @@ import {
   playbackToggle,
   type PlaybackState,
 } from "../utils";
+import { useDbPath } from "./useDbPath";
 
 // Re-export CurrentTrack from store for backwards compatibility
 export type { CurrentTrack } from "../stores";
@@ -33,6 +34,7 @@ type UseAudioPlaybackOptions = {
 
 export const useAudioPlayback = (options: UseAudioPlaybackOptions = {}) => {
   const { onTrackEnd, onMediaControl, seekMode } = options;
+  const resolveDbPath = useDbPath();
 
   // Get state and actions from store
   const isPlaying = usePlaybackStore((s) => s.isPlaying);
@@ -162,7 +164,8 @@ export const useAudioPlayback = (options: UseAudioPlaybackOptions = {}) => {
           track.sourcePath,
           track.durationSeconds,
           track.coverArtPath,
-          track.coverArtThumbPath
+          track.coverArtThumbPath,
+          await resolveDbPath()
         );
         setIsPlaying(true);
         setCurrentPosition(0);
@@ -172,7 +175,13 @@ export const useAudioPlayback = (options: UseAudioPlaybackOptions = {}) => {
         notify.error("Failed to play track");
       }
     },
-    [setIsPlaying, setCurrentPosition, setDuration, setCurrentTrack]
+    [
+      resolveDbPath,
+      setIsPlaying,
+      setCurrentPosition,
+      setDuration,
+      setCurrentTrack,
+    ]
   );
 
   const togglePlay = useCallback(async () => {
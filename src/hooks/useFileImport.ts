@@ -3,13 +3,22 @@ import { useCallback, useEffect, useRef } from "react";
 import { commandManager, type Command } from "../command-manager/commandManager";
 import { useLibraryStore, useUIStore, notify } from "../stores";
 import { useDbPath } from "./useDbPath";
-import { addTracksToPlaylist, createPlaylist, removeLastTracksFromPlaylist, importFiles, importedTrackToTrack } from "../utils";
+import { addTracksToPlaylist, createPlaylist, removeLastTracksFromPlaylist, importFiles, importedTrackToTrack, resumeImportJob, cancelImport, listImportJobs, type ImportReport } from "../utils";
 import type { Playlist } from "../types";
 
 export type ImportProgress = {
   imported: number;
   total: number;
   phase: "scanning" | "importing";
+  currentFile?: string;
+  jobId?: string;
+};
+
+type ImportProgressEvent = {
+  job_id: string;
+  imported: number;
+  total: number;
+  current_file: string | null;
 };
 
 export type PlaylistDropOperation = {
@@ -30,6 +39,7 @@ export const useFileImport = ({ onImportComplete }: UseFileImportArgs = {}) => {
   const playlists = useLibraryStore((s) => s.playlists);
   const setPlaylists = useLibraryStore((s) => s.setPlaylists);
   const setInboxTracks = useLibraryStore((s) => s.setInboxTracks);
+  const setTracks = useLibraryStore((s) => s.setTracks);
   const pendingPlaylistDrop = useUIStore((s) => s.pendingPlaylistDrop);
   const setPendingPlaylistDrop = useUIStore((s) => s.setPendingPlaylistDrop);
   const setImportProgress = useUIStore((s) => s.setImportProgress);
@@ -124,12 +134,22 @@ export const useFileImport = ({ onImportComplete }: UseFileImportArgs = {}) => {
     setPendingPlaylistDrop(null);
   }, [setPendingPlaylistDrop]);
 
-  const handleImportPaths = useCallback(
-    async (paths: string[]) => {
-      if (paths.length === 0) {
-        return;
+  const clearImportProgressSoon = useCallback(
+    (delay: number) => {
+      if (typeof window !== "undefined") {
+        clearProgressTimerRef.current = window.setTimeout(() => {
+          setImportProgress(null);
+          clearProgressTimerRef.current = null;
+        }, delay);
+      } else {
+        setImportProgress(null);
       }
+    },
+    [setImportProgress]
+  );
 
+  const runImport = useCallback(
+    async (start: (dbPath: string) => Promise<ImportReport>) => {
       try {
         if (clearProgressTimerRef.current !== null && typeof window !== "undefined") {
           window.clearTimeout(clearProgressTimerRef.current);
@@ -137,50 +157,95 @@ export const useFileImport = ({ onImportComplete }: UseFileImportArgs = {}) => {
         }
         setImportProgress({ imported: 0, total: 0, phase: "scanning" });
         const resolvedDbPath = await resolveDbPath();
-        const imported = await importFiles(resolvedDbPath, paths);
+        const report = await start(resolvedDbPath);
+        const imported = report.imported;
+        const notImported = report.failed.length + report.unsupported.length;
+        if (notImported > 0) {
+          notify.error(
+            `${notImported} ${notImported === 1 ? "file" : "files"} could not be imported`
+          );
+        }
+        if (report.cancelled) {
+          notify.info("Import cancelled. It can be resumed later.");
+        }
         if (imported.length === 0) {
-          if (typeof window !== "undefined") {
-            clearProgressTimerRef.current = window.setTimeout(() => {
-              setImportProgress(null);
-              clearProgressTimerRef.current = null;
-            }, 500);
-          } else {
-            setImportProgress(null);
-          }
+          clearImportProgressSoon(500);
           return;
         }
 
-        const convertedTracks = imported.map(importedTrackToTrack);
+        // Tracks matched by an inbox rule were accepted during the import
+        const acceptedTracks = imported
+          .filter((track) => track.import_status === "accepted")
+          .map(importedTrackToTrack);
+        const stagedTracks = imported
+          .filter((track) => track.import_status !== "accepted")
+          .map(importedTrackToTrack);
+        if (acceptedTracks.length > 0) {
+          notify.info(
+            `${acceptedTracks.length} ${acceptedTracks.length === 1 ? "track was" : "tracks were"} accepted by inbox rules`
+          );
+        }
         const command: Command = {
           label: `Import ${imported.length} tracks`,
           do: () => {
-            setInboxTracks((current) => [...convertedTracks, ...current]);
+            setInboxTracks((current) => [...stagedTracks, ...current]);
+            setTracks((current) => [...acceptedTracks, ...current]);
           },
           undo: () => {
             const ids = new Set(imported.map((track) => track.id));
             setInboxTracks((current) =>
               current.filter((track) => !ids.has(track.id))
             );
+            setTracks((current) => current.filter((track) => !ids.has(track.id)));
           },
         };
         commandManager.execute(command);
         onImportComplete?.();
-        if (typeof window !== "undefined") {
-          clearProgressTimerRef.current = window.setTimeout(() => {
-            setImportProgress(null);
-            clearProgressTimerRef.current = null;
-          }, 800);
-        } else {
-          setImportProgress(null);
-        }
+        clearImportProgressSoon(800);
       } catch (error) {
         notify.error("Import failed");
         setImportProgress(null);
       }
     },
-    [resolveDbPath, setImportProgress, setInboxTracks, onImportComplete]
+    [
+      resolveDbPath,
+      setImportProgress,
+      setInboxTracks,
+      setTracks,
+      onImportComplete,
+      clearImportProgressSoon,
+    ]
+  );
+
+  const handleImportPaths = useCallback(
+    async (paths: string[]) => {
+      if (paths.length === 0) {
+        return;
+      }
+      await runImport((dbPath) => importFiles(dbPath, paths));
+    },
+    [runImport]
+  );
+
+  const handleResumeImport = useCallback(
+    async (jobId: string) => {
+      await runImport((dbPath) => resumeImportJob(dbPath, jobId));
+    },
+    [runImport]
   );
 
+  const handleCancelImport = useCallback(async () => {
+    const jobId = useUIStore.getState().importProgress?.jobId;
+    if (!jobId) {
+      return;
+    }
+    try {
+      await cancelImport(jobId);
+    } catch (error) {
+      notify.error("Failed to cancel import");
+    }
+  }, []);
+
   const handleCreatePlaylist = useCallback(
     async (name: string) => {
       const trimmed = name.trim();
@@ -254,7 +319,7 @@ export const useFileImport = ({ onImportComplete }: UseFileImportArgs = {}) => {
 
     const setup = async () => {
       try {
-        importUnlistenRef.current = await listen<ImportProgress>(
+        importUnlistenRef.current = await listen<ImportProgressEvent>(
           "muro://import-progress",
           (event) => {
             const payload = event.payload;
@@ -265,6 +330,8 @@ export const useFileImport = ({ onImportComplete }: UseFileImportArgs = {}) => {
               imported: payload.imported,
               total: payload.total,
               phase: "importing",
+              currentFile: payload.current_file ?? undefined,
+              jobId: payload.job_id,
             });
             if (payload.total > 0 && payload.imported >= payload.total) {
               if (clearProgressTimerRef.current !== null && typeof window !== "undefined") {
@@ -294,8 +361,31 @@ export const useFileImport = ({ onImportComplete }: UseFileImportArgs = {}) => {
     // eslint-disable-next-line react-hooks/exhaustive-deps -- setImportProgress is stable, only run once
   }, []);
 
+  // Resume imports that were interrupted by closing the app. Cancelled jobs
+  // are left alone until resumed or discarded explicitly.
+  useEffect(() => {
+    const resumeInterruptedImports = async () => {
+      try {
+        const resolvedDbPath = await resolveDbPath();
+        const jobs = await listImportJobs(resolvedDbPath);
+        for (const job of jobs) {
+          if (job.status === "running" && job.pending > 0) {
+            await handleResumeImport(job.id);
+          }
+        }
+      } catch (error) {
+        notify.error("Failed to resume unfinished import");
+      }
+    };
+
+    void resumeInterruptedImports();
+    // eslint-disable-next-line react-hooks/exhaustive-deps -- only run once on mount
+  }, []);
+
   return {
     handleImportPaths,
+    handleResumeImport,
+    handleCancelImport,
     handlePlaylistDrop,
     handleCreatePlaylist,
     pendingPlaylistDrop,
@@ -2,7 +2,14 @@ import { useCallback } from "react";
 import { commandManager } from "../command-manager/commandManager";
 import { useLibraryStore, useUIStore, notify } from "../stores";
 import { useDbPath } from "./useDbPath";
-import { acceptTracks, rejectTracks, unacceptTracks } from "../utils";
+import {
+  acceptTracks,
+  applyInboxRules,
+  rejectTracks,
+  unacceptTracks,
+  type InboxRulesReport,
+  type OrganizeReport,
+} from "../utils";
 
 export const useInboxOperations = () => {
   // Get state and actions from stores
@@ -13,6 +20,30 @@ export const useInboxOperations = () => {
   const clearSelection = useUIStore((s) => s.clearSelection);
   const resolveDbPath = useDbPath();
 
+  // Accepted tracks may have been filed into the library folder
+  const applyOrganizeReport = useCallback(
+    (report: OrganizeReport | null) => {
+      if (!report) {
+        return;
+      }
+      if (report.moved.length > 0) {
+        const newPaths = new Map(
+          report.moved.map((move) => [move.track_id, move.new_path])
+        );
+        setTracks((current) =>
+          current.map((t) => {
+            const sourcePath = newPaths.get(t.id);
+            return sourcePath ? { ...t, sourcePath } : t;
+          })
+        );
+      }
+      if (report.failed.length > 0) {
+        notify.error(`Failed to organise ${report.failed.length} tracks`);
+      }
+    },
+    [setTracks]
+  );
+
   const handleAcceptTracks = useCallback(async () => {
     const selectedTrackIds = Array.from(selectedIds);
     if (selectedTrackIds.length === 0) {
@@ -31,9 +62,11 @@ export const useInboxOperations = () => {
           current.filter((t) => !selectedTrackIds.includes(t.id))
         );
         setTracks((current) => [...tracksToAccept, ...current]);
-        acceptTracks(resolvedDbPath, selectedTrackIds).catch(() => {
-          notify.error("Failed to accept tracks");
-        });
+        acceptTracks(resolvedDbPath, selectedTrackIds)
+          .then(applyOrganizeReport)
+          .catch(() => {
+            notify.error("Failed to accept tracks");
+          });
       },
       undo: () => {
         setTracks((current) =>
@@ -48,6 +81,7 @@ export const useInboxOperations = () => {
 
     commandManager.execute(command);
   }, [
+    applyOrganizeReport,
     clearSelection,
     resolveDbPath,
     inboxTracks,
@@ -86,8 +120,62 @@ export const useInboxOperations = () => {
     commandManager.execute(command);
   }, [clearSelection, resolveDbPath, inboxTracks, selectedIds, setInboxTracks]);
 
+  const handleApplyInboxRules = useCallback(async () => {
+    const resolvedDbPath = await resolveDbPath();
+    let report: InboxRulesReport;
+    try {
+      report = await applyInboxRules(resolvedDbPath);
+    } catch (error) {
+      notify.error("Failed to apply inbox rules");
+      return;
+    }
+    const acceptedIds = report.accepted;
+    if (acceptedIds.length === 0) {
+      notify.info("No inbox tracks matched the rules");
+      return;
+    }
+
+    const accepted = new Set(acceptedIds);
+    const tracksToAccept = inboxTracks.filter((t) => accepted.has(t.id));
+    let applied = false;
+    const command = {
+      label: `Accept ${acceptedIds.length} tracks by rules`,
+      do: () => {
+        setInboxTracks((current) => current.filter((t) => !accepted.has(t.id)));
+        setTracks((current) => [...tracksToAccept, ...current]);
+        if (!applied) {
+          // The rules already accepted and organised them
+          applied = true;
+          applyOrganizeReport(report.organize);
+          return;
+        }
+        acceptTracks(resolvedDbPath, acceptedIds)
+          .then(applyOrganizeReport)
+          .catch(() => {
+            notify.error("Failed to accept tracks");
+          });
+      },
+      undo: () => {
+        setTracks((current) => current.filter((t) => !accepted.has(t.id)));
+        setInboxTracks((current) => [...tracksToAccept, ...current]);
+        unacceptTracks(resolvedDbPath, acceptedIds).catch(() => {
+          notify.error("Failed to undo accept");
+        });
+      },
+    };
+
+    commandManager.execute(command);
+  }, [
+    applyOrganizeReport,
+    resolveDbPath,
+    inboxTracks,
+    setInboxTracks,
+    setTracks,
+  ]);
+
   return {
     handleAcceptTracks,
     handleRejectTracks,
+    handleApplyInboxRules,
   };
 };
@@ -0,0 +1,56 @@
+import { useCallback, useEffect, useState } from "react";
+import { useDbPath } from "./useDbPath";
+import { getInboxRules, setInboxRules, type InboxRule } from "../utils";
+
+/** Inbox rules as edited in settings, saved back on request */
+export const useInboxRules = () => {
+  const resolveDbPath = useDbPath();
+  const [inboxRules, setInboxRulesState] = useState<InboxRule[]>([]);
+  const [inboxRulesSavePending, setInboxRulesSavePending] = useState(false);
+  const [inboxRulesStatus, setInboxRulesStatus] = useState<string | null>(null);
+
+  useEffect(() => {
+    let active = true;
+    resolveDbPath()
+      .then((dbPath) => getInboxRules(dbPath))
+      .then((rules) => {
+        if (active) {
+          setInboxRulesState(rules);
+        }
+      })
+      .catch((error) => {
+        if (active) {
+          setInboxRulesStatus(error instanceof Error ? error.message : String(error));
+        }
+      });
+    return () => {
+      active = false;
+    };
+  }, [resolveDbPath]);
+
+  const handleInboxRulesChange = useCallback((rules: InboxRule[]) => {
+    setInboxRulesState(rules);
+    setInboxRulesStatus(null);
+  }, []);
+
+  const handleSaveInboxRules = useCallback(async () => {
+    try {
+      setInboxRulesSavePending(true);
+      await setInboxRules(await resolveDbPath(), inboxRules);
+      setInboxRulesStatus("Rules saved.");
+    } catch (error) {
+      // Validation errors come back as plain strings
+      setInboxRulesStatus(error instanceof Error ? error.message : String(error));
+    } finally {
+      setInboxRulesSavePending(false);
+    }
+  }, [inboxRules, resolveDbPath]);
+
+  return {
+    inboxRules,
+    inboxRulesSavePending,
+    inboxRulesStatus,
+    handleInboxRulesChange,
+    handleSaveInboxRules,
+  };
+};
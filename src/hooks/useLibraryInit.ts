@@ -1,14 +1,21 @@
 import { useCallback, useEffect, useState } from "react";
+import { listen } from "@tauri-apps/api/event";
 import { appDataDir, join } from "@tauri-apps/api/path";
 import { useLibraryStore, useSettingsStore, useRecentlyPlayedStore, notify } from "../stores";
 import {
+  backfillAlbums,
+  backfillAudioProperties,
+  backfillArtistCredits,
   backfillCoverArt,
   backfillSearchText,
   clearTracks,
   loadPlaylists,
   loadRecentlyPlayed,
   loadTracks,
+  retryTagWrites,
   importedTrackToTrack,
+  startFolderWatcher,
+  type LibraryChanged,
 } from "../utils";
 import { useDbPath } from "./useDbPath";
 import { confirm } from "@tauri-apps/plugin-dialog";
@@ -31,6 +38,16 @@ export const useLibraryInit = () => {
   const [backfillStatus, setBackfillStatus] = useState<string | null>(null);
   const [coverArtBackfillPending, setCoverArtBackfillPending] = useState(false);
   const [coverArtBackfillStatus, setCoverArtBackfillStatus] = useState<string | null>(null);
+  const [audioPropertiesBackfillPending, setAudioPropertiesBackfillPending] = useState(false);
+  const [audioPropertiesBackfillStatus, setAudioPropertiesBackfillStatus] = useState<
+    string | null
+  >(null);
+  const [artistCreditsBackfillPending, setArtistCreditsBackfillPending] = useState(false);
+  const [artistCreditsBackfillStatus, setArtistCreditsBackfillStatus] = useState<string | null>(null);
+  const [albumsBackfillPending, setAlbumsBackfillPending] = useState(false);
+  const [albumsBackfillStatus, setAlbumsBackfillStatus] = useState<string | null>(null);
+  const [tagWriteRetryPending, setTagWriteRetryPending] = useState(false);
+  const [tagWriteRetryStatus, setTagWriteRetryStatus] = useState<string | null>(null);
   const [clearSongsPending, setClearSongsPending] = useState(false);
 
   // Auto-resolve DB path
@@ -81,9 +98,14 @@ export const useLibraryInit = () => {
             id: playlist.id,
             name: playlist.name,
             trackIds: playlist.track_ids,
+            parentId: playlist.parent_id,
+            isFolder: playlist.is_folder,
           }))
         );
         setRecentlyPlayedTracks(recentlyPlayedSnapshot.map(importedTrackToTrack));
+        startFolderWatcher(resolvedPath).catch(() => {
+          notify.error("Failed to start folder watcher");
+        });
       } catch (error) {
         notify.error("Failed to load library");
       }
@@ -95,6 +117,30 @@ export const useLibraryInit = () => {
     };
   }, [resolveDbPath, setTracks, setInboxTracks, setPlaylists, setRecentlyPlayedTracks]);
 
+  // Tracks imported in the background (e.g. from watched folders)
+  useEffect(() => {
+    let unlisten: (() => void) | null = null;
+
+    const setup = async () => {
+      unlisten = await listen<LibraryChanged>("muro://library-changed", (event) => {
+        const tracks = event.payload?.tracks ?? [];
+        if (tracks.length === 0) {
+          return;
+        }
+        const ids = new Set(tracks.map((track) => track.id));
+        setInboxTracks((current) => [
+          ...tracks.map(importedTrackToTrack),
+          ...current.filter((track) => !ids.has(track.id)),
+        ]);
+      });
+    };
+
+    void setup();
+    return () => {
+      unlisten?.();
+    };
+  }, [setInboxTracks]);
+
   // Backfill handlers
   const handleBackfillSearchText = useCallback(async () => {
     if (!dbPath.trim()) {
@@ -141,6 +187,97 @@ export const useLibraryInit = () => {
     }
   }, [dbPath, resolveDbPath, setTracks, setInboxTracks]);
 
+  const handleBackfillAudioProperties = useCallback(async () => {
+    if (!dbPath.trim()) {
+      setAudioPropertiesBackfillStatus("Enter a database path to run the backfill.");
+      return;
+    }
+
+    try {
+      setAudioPropertiesBackfillPending(true);
+      setAudioPropertiesBackfillStatus("Reading audio properties...");
+      const updated = await backfillAudioProperties(dbPath.trim());
+      setAudioPropertiesBackfillStatus(`Read audio properties for ${updated} tracks.`);
+      const resolvedPath = await resolveDbPath();
+      const snapshot = await loadTracks(resolvedPath);
+      setTracks(snapshot.library.map(importedTrackToTrack));
+      setInboxTracks(snapshot.inbox.map(importedTrackToTrack));
+    } catch (error) {
+      const message =
+        error instanceof Error ? error.message : "Reading audio properties failed.";
+      setAudioPropertiesBackfillStatus(message);
+    } finally {
+      setAudioPropertiesBackfillPending(false);
+    }
+  }, [dbPath, resolveDbPath, setTracks, setInboxTracks]);
+
+  const handleBackfillArtistCredits = useCallback(async () => {
+    if (!dbPath.trim()) {
+      setArtistCreditsBackfillStatus("Enter a database path to run the backfill.");
+      return;
+    }
+
+    try {
+      setArtistCreditsBackfillPending(true);
+      setArtistCreditsBackfillStatus("Crediting artists...");
+      const updated = await backfillArtistCredits(dbPath.trim());
+      setArtistCreditsBackfillStatus(`Credited artists on ${updated} tracks.`);
+    } catch (error) {
+      const message =
+        error instanceof Error ? error.message : "Crediting artists failed.";
+      setArtistCreditsBackfillStatus(message);
+    } finally {
+      setArtistCreditsBackfillPending(false);
+    }
+  }, [dbPath]);
+
+  const handleBackfillAlbums = useCallback(async () => {
+    if (!dbPath.trim()) {
+      setAlbumsBackfillStatus("Enter a database path to run the backfill.");
+      return;
+    }
+
+    try {
+      setAlbumsBackfillPending(true);
+      setAlbumsBackfillStatus("Grouping albums...");
+      const updated = await backfillAlbums(dbPath.trim());
+      setAlbumsBackfillStatus(`Grouped ${updated} tracks into albums.`);
+    } catch (error) {
+      const message =
+        error instanceof Error ? error.message : "Grouping albums failed.";
+      setAlbumsBackfillStatus(message);
+    } finally {
+      setAlbumsBackfillPending(false);
+    }
+  }, [dbPath]);
+
+  const handleRetryTagWrites = useCallback(async () => {
+    if (!dbPath.trim()) {
+      setTagWriteRetryStatus("Enter a database path to retry tag writes.");
+      return;
+    }
+
+    try {
+      setTagWriteRetryPending(true);
+      setTagWriteRetryStatus("Writing tags...");
+      const report = await retryTagWrites(dbPath.trim());
+      if (report.written.length === 0 && report.failed.length === 0) {
+        setTagWriteRetryStatus("All files are in sync.");
+      } else if (report.failed.length === 0) {
+        setTagWriteRetryStatus(`Wrote tags to ${report.written.length} files.`);
+      } else {
+        setTagWriteRetryStatus(
+          `Wrote tags to ${report.written.length} files, ${report.failed.length} still failing: ${report.failed[0].error}`
+        );
+      }
+    } catch (error) {
+      const message = error instanceof Error ? error.message : "Retrying tag writes failed.";
+      setTagWriteRetryStatus(message);
+    } finally {
+      setTagWriteRetryPending(false);
+    }
+  }, [dbPath]);
+
   // Clear songs handler
   const handleClearSongs = useCallback(async () => {
     if (clearSongsPending) {
@@ -177,10 +314,22 @@ export const useLibraryInit = () => {
     backfillStatus,
     coverArtBackfillPending,
     coverArtBackfillStatus,
+    audioPropertiesBackfillPending,
+    audioPropertiesBackfillStatus,
+    artistCreditsBackfillPending,
+    artistCreditsBackfillStatus,
+    albumsBackfillPending,
+    albumsBackfillStatus,
+    tagWriteRetryPending,
+    tagWriteRetryStatus,
     clearSongsPending,
     // Handlers
     handleBackfillSearchText,
     handleBackfillCoverArt,
+    handleBackfillAudioProperties,
+    handleBackfillArtistCredits,
+    handleBackfillAlbums,
+    handleRetryTagWrites,
     handleClearSongs,
   };
 };
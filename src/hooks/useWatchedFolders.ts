@@ -0,0 +1,69 @@
+import { useCallback, useEffect, useState } from "react";
+import { useDbPath } from "./useDbPath";
+import {
+  addWatchedFolder,
+  listWatchedFolders,
+  removeWatchedFolder,
+  type WatchedFolder,
+} from "../utils";
+
+/** Folders the background watcher imports new files from */
+export const useWatchedFolders = () => {
+  const resolveDbPath = useDbPath();
+  const [watchedFolders, setWatchedFolders] = useState<WatchedFolder[]>([]);
+  const [watchedFoldersStatus, setWatchedFoldersStatus] = useState<string | null>(null);
+
+  useEffect(() => {
+    let active = true;
+    resolveDbPath()
+      .then((dbPath) => listWatchedFolders(dbPath))
+      .then((folders) => {
+        if (active) {
+          setWatchedFolders(folders);
+        }
+      })
+      .catch((error) => {
+        if (active) {
+          setWatchedFoldersStatus(error instanceof Error ? error.message : String(error));
+        }
+      });
+    return () => {
+      active = false;
+    };
+  }, [resolveDbPath]);
+
+  const handleAddWatchedFolder = useCallback(
+    async (path: string) => {
+      try {
+        const folder = await addWatchedFolder(await resolveDbPath(), path);
+        setWatchedFolders((current) =>
+          [...current, folder].sort((a, b) => a.path.localeCompare(b.path))
+        );
+        setWatchedFoldersStatus(null);
+      } catch (error) {
+        setWatchedFoldersStatus(error instanceof Error ? error.message : String(error));
+      }
+    },
+    [resolveDbPath]
+  );
+
+  const handleRemoveWatchedFolder = useCallback(
+    async (folderId: string) => {
+      try {
+        await removeWatchedFolder(await resolveDbPath(), folderId);
+        setWatchedFolders((current) => current.filter((folder) => folder.id !== folderId));
+        setWatchedFoldersStatus(null);
+      } catch (error) {
+        setWatchedFoldersStatus(error instanceof Error ? error.message : String(error));
+      }
+    },
+    [resolveDbPath]
+  );
+
+  return {
+    watchedFolders,
+    watchedFoldersStatus,
+    handleAddWatchedFolder,
+    handleRemoveWatchedFolder,
+  };
+};
@@ -2,7 +2,7 @@
 import Essentia from "essentia.js/dist/essentia.js-core.es.js";
 import { EssentiaWASM } from "essentia.js/dist/essentia-wasm.es.js";
 import { toCamelot } from "./camelot";
-import { convertFileSrc } from "@tauri-apps/api/core";
+import { convertFileSrc, invoke } from "@tauri-apps/api/core";
 
 // Default BPM range for electronic/techno
 const DEFAULT_BPM_MIN = 100;
@@ -12,6 +12,9 @@ const DEFAULT_BPM_MAX = 180;
 const ANALYSIS_DURATION_SECONDS = 60;
 const SAMPLE_RATE = 44100;
 
+// Formats WebAudio can't decode; the backend decodes these to a mono WAV
+const BACKEND_DECODED_EXTENSIONS = ["dsf", "dff", "wv", "ape"];
+
 export interface AnalysisResult {
   bpm: number;
   key: string;
@@ -72,11 +75,17 @@ function checkAborted(signal?: AbortSignal): void {
   }
 }
 
-async function decodeAudioFile(
+async function loadAudioBytes(
   filePath: string,
   signal?: AbortSignal
-): Promise<Float32Array> {
-  checkAborted(signal);
+): Promise<ArrayBuffer> {
+  const extension = filePath.split(".").pop()?.toLowerCase() ?? "";
+  if (BACKEND_DECODED_EXTENSIONS.includes(extension)) {
+    return invoke<ArrayBuffer>("decode_for_analysis", {
+      path: filePath,
+      maxSeconds: ANALYSIS_DURATION_SECONDS,
+    });
+  }
 
   // Convert file path to asset URL for Tauri
   const assetUrl = convertFileSrc(filePath);
@@ -89,7 +98,16 @@ async function decodeAudioFile(
 
   checkAborted(signal);
 
-  const arrayBuffer = await response.arrayBuffer();
+  return response.arrayBuffer();
+}
+
+async function decodeAudioFile(
+  filePath: string,
+  signal?: AbortSignal
+): Promise<Float32Array> {
+  checkAborted(signal);
+
+  const arrayBuffer = await loadAudioBytes(filePath, signal);
 
   checkAborted(signal);
 
@@ -115,9 +115,21 @@ export const useLibraryStore = create<LibraryStore>((set) => ({
     })),
 
   deletePlaylist: (id) =>
-    set((state) => ({
-      playlists: state.playlists.filter((p) => p.id !== id),
-    })),
+    set((state) => {
+      // Deleting a folder removes everything inside it, as in the database
+      const removed = new Set([id]);
+      let grew = true;
+      while (grew) {
+        grew = false;
+        for (const p of state.playlists) {
+          if (p.parentId && removed.has(p.parentId) && !removed.has(p.id)) {
+            removed.add(p.id);
+            grew = true;
+          }
+        }
+      }
+      return { playlists: state.playlists.filter((p) => !removed.has(p.id)) };
+    }),
 
   addTracksToPlaylist: (playlistId, trackIds) =>
     set((state) => ({
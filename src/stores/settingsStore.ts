@@ -9,6 +9,7 @@ type SettingsState = {
   dbPath: string;
   dbFileName: string;
   useAutoDbPath: boolean;
+  filenamePatterns: string[];
 };
 
 type SettingsActions = {
@@ -18,6 +19,7 @@ type SettingsActions = {
   setDbPath: (path: string) => void;
   setDbFileName: (name: string) => void;
   setUseAutoDbPath: (auto: boolean) => void;
+  setFilenamePatterns: (patterns: string[]) => void;
 };
 
 export type SettingsStore = SettingsState & SettingsActions;
@@ -32,6 +34,11 @@ export const useSettingsStore = create<SettingsStore>()(
       dbPath: "",
       dbFileName: "muro.db",
       useAutoDbPath: true,
+      filenamePatterns: [
+        "%artist%/%album%/%track% - %title%",
+        "%track% - %artist% - %title%",
+        "%artist% - %title%",
+      ],
 
       // Actions
       setTheme: (theme) => {
@@ -48,6 +55,7 @@ export const useSettingsStore = create<SettingsStore>()(
       setDbPath: (dbPath) => set({ dbPath, useAutoDbPath: false }),
       setDbFileName: (dbFileName) => set({ dbFileName, useAutoDbPath: true }),
       setUseAutoDbPath: (useAutoDbPath) => set({ useAutoDbPath }),
+      setFilenamePatterns: (filenamePatterns) => set({ filenamePatterns }),
     }),
     {
       name: "muro-settings",
@@ -55,6 +63,7 @@ export const useSettingsStore = create<SettingsStore>()(
         theme: state.theme,
         locale: state.locale,
         seekMode: state.seekMode,
+        filenamePatterns: state.filenamePatterns,
       }),
       onRehydrateStorage: () => (state) => {
         if (state) {
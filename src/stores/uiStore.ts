@@ -31,6 +31,8 @@ type UIState = {
     imported: number;
     total: number;
     phase: "scanning" | "importing";
+    currentFile?: string;
+    jobId?: string;
   } | null;
 };
 
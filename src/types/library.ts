@@ -28,6 +28,15 @@ export type Track = {
   discTotal?: number;
   lastPlayedAt?: string;
   playCount: number;
+  isMissing: boolean;
+  sampleRate?: number;
+  bitDepth?: number;
+  channels?: number;
+  codec?: string;
+  container?: string;
+  isLossless?: boolean;
+  bitrateMode?: "cbr" | "vbr";
+  fileSize?: number;
 };
 
 export type TrackMetadataUpdates = {
@@ -54,6 +63,8 @@ export type Playlist = {
   id: string;
   name: string;
   trackIds: string[];
+  parentId?: string | null;
+  isFolder?: boolean;
 };
 
 export type ColumnKey =
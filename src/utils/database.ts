@@ -1,5 +1,283 @@
 import { invoke } from "@tauri-apps/api/core";
-import type { LibrarySnapshot, PlaylistSnapshot } from "./importApi";
+import type {
+  ImportedTrack,
+  ImportIssue,
+  LibrarySnapshot,
+  PlaylistSnapshot,
+} from "./importApi";
+
+// ============================================================================
+// Types
+// ============================================================================
+
+export type RescanSummary = {
+  added: number;
+  updated: number;
+  missing: number;
+  restored: number;
+  unchanged: number;
+  missing_track_ids: string[];
+  errors: ImportIssue[];
+};
+
+export type WatchedFolder = {
+  id: string;
+  path: string;
+  added_at: number;
+};
+
+export type RelinkReport = {
+  dry_run: boolean;
+  missing: number;
+  relinked: {
+    track_id: string;
+    old_path: string;
+    new_path: string;
+    matched_by: "hash" | "size";
+  }[];
+  ambiguous: {
+    track_id: string;
+    old_path: string;
+    matched_by: "hash" | "size" | "tags";
+    candidates: string[];
+  }[];
+  unresolved: string[];
+  errors: ImportIssue[];
+};
+
+export type PlaylistFileFormat = "m3u8" | "pls" | "xspf";
+
+export type UnresolvedEntry = {
+  location: string;
+  title: string | null;
+  duration_seconds: number | null;
+  reason: string;
+};
+
+export type PlaylistImportReport = {
+  playlist_id: string;
+  name: string;
+  total: number;
+  matched: number;
+  imported: number;
+  unresolved: UnresolvedEntry[];
+};
+
+export type CollectionImportReport = {
+  source: "rekordbox" | "traktor";
+  total: number;
+  matched: number;
+  imported: number;
+  cue_points: number;
+  playlists: number;
+  folders: number;
+  unresolved: UnresolvedEntry[];
+};
+
+export type RekordboxExportSummary = {
+  tracks: number;
+  playlists: number;
+  cue_points: number;
+};
+
+export type StatsPolicy = "keep" | "overwrite" | "merge";
+
+export type PlayerImportReport = {
+  source: "rhythmbox" | "strawberry" | "clementine";
+  total: number;
+  matched_by_path: number;
+  matched_by_tags: number;
+  updated: number;
+  kept: number;
+  playlists: number;
+  unresolved: UnresolvedEntry[];
+};
+
+export type FilenameTagField =
+  | "title"
+  | "artist"
+  | "artists"
+  | "album"
+  | "trackNumber"
+  | "discNumber"
+  | "year"
+  | "genre";
+
+export type FilenameTagPreview = {
+  track_id: string;
+  source_path: string;
+  pattern: string | null;
+  tags: {
+    title: string | null;
+    artist: string | null;
+    album_artist: string | null;
+    album: string | null;
+    track_number: number | null;
+    disc_number: number | null;
+    year: number | null;
+    genre: string | null;
+  };
+  changes: { field: FilenameTagField; current: string | null; value: string }[];
+};
+
+export type ScanOptions = {
+  skip_hidden: boolean;
+  exclude: string[];
+};
+
+export type InboxRuleField =
+  | "title"
+  | "artist"
+  | "album"
+  | "album_artist"
+  | "year"
+  | "genre"
+  | "track_number"
+  | "bpm"
+  | "key"
+  | "cover";
+
+export type InboxRule = {
+  name: string;
+  enabled: boolean;
+  require: InboxRuleField[];
+  folder?: string | null;
+};
+
+export type OrganizeMode = "move" | "copy";
+
+export type OrganizeOptions = {
+  enabled: boolean;
+  library_root: string;
+  template: string;
+  mode: OrganizeMode;
+};
+
+export type OrganizeReport = {
+  dry_run: boolean;
+  mode: OrganizeMode;
+  moved: {
+    track_id: string;
+    old_path: string;
+    new_path: string;
+  }[];
+  unchanged: number;
+  failed: {
+    track_id: string;
+    path: string;
+    reason: string;
+  }[];
+};
+
+export type InboxRulesReport = {
+  accepted: string[];
+  organize: OrganizeReport | null;
+};
+
+export type UnsyncedTrack = {
+  track_id: string;
+  title?: string;
+  artist?: string;
+  source_path?: string;
+  error: string;
+  pending_fields: string[];
+};
+
+export type TagWriteRetryReport = {
+  written: string[];
+  failed: UnsyncedTrack[];
+};
+
+export type DuplicateCopy = {
+  track_id: string;
+  title?: string;
+  artist?: string;
+  album?: string;
+  source_path: string;
+  import_status: string;
+  duration_seconds?: number;
+  bitrate_kbps?: number;
+  codec?: string;
+  is_lossless: boolean;
+  sample_rate?: number;
+  bit_depth?: number;
+  tag_score: number;
+  rating?: number;
+  play_count: number;
+  is_missing: boolean;
+};
+
+export type DuplicateCluster = {
+  key: string;
+  exact: boolean;
+  copies: DuplicateCopy[];
+};
+
+export type DuplicateResolution = {
+  kept_id: string;
+  removed: number;
+  play_count: number;
+  rating?: number;
+  playlist_entries: number;
+};
+
+export type ArtistSummary = {
+  id: number;
+  name: string;
+  track_count: number;
+  main_count: number;
+  featured_count: number;
+  album_artist_count: number;
+  remixer_count: number;
+};
+
+export type LyricLine = {
+  time_ms: number;
+  text: string;
+};
+
+export type TrackLyrics = {
+  track_id: string;
+  text: string;
+  lines: LyricLine[];
+  source: "tag" | "sidecar" | "user";
+};
+
+export type LyricLineEvent = {
+  track_id: string;
+  index: number | null;
+  text: string | null;
+};
+
+export type AlbumSummary = {
+  id: string;
+  title: string;
+  album_artist: string | null;
+  year: number | null;
+  is_compilation: boolean;
+  track_count: number;
+  disc_count: number;
+  duration_seconds: number;
+  cover_art_path: string | null;
+  cover_art_thumb_path: string | null;
+  track_ids: string[];
+};
+
+export type CuePoint = {
+  id: string;
+  source: string;
+  kind: "cue" | "hotcue" | "loop" | "fade_in" | "fade_out" | "load" | "grid";
+  slot: number | null;
+  start_seconds: number;
+  end_seconds: number | null;
+  name: string | null;
+  color: string | null;
+};
+
+export type LibraryChanged = {
+  source: string;
+  tracks: ImportedTrack[];
+};
 
 // ============================================================================
 // Library Operations
@@ -14,7 +292,7 @@ export const clearTracks = (dbPath: string) => {
 };
 
 export const acceptTracks = (dbPath: string, trackIds: string[]) => {
-  return invoke<void>("accept_tracks", { dbPath, trackIds });
+  return invoke<OrganizeReport | null>("accept_tracks", { dbPath, trackIds });
 };
 
 export const unacceptTracks = (dbPath: string, trackIds: string[]) => {
@@ -25,6 +303,105 @@ export const rejectTracks = (dbPath: string, trackIds: string[]) => {
   return invoke<void>("reject_tracks", { dbPath, trackIds });
 };
 
+export const getInboxRules = (dbPath: string) => {
+  return invoke<InboxRule[]>("get_inbox_rules", { dbPath });
+};
+
+export const setInboxRules = (dbPath: string, rules: InboxRule[]) => {
+  return invoke<void>("set_inbox_rules", { dbPath, rules });
+};
+
+export const applyInboxRules = (dbPath: string) => {
+  return invoke<InboxRulesReport>("apply_inbox_rules", { dbPath });
+};
+
+export const getOrganizeOptions = (dbPath: string) => {
+  return invoke<OrganizeOptions>("get_organize_options", { dbPath });
+};
+
+export const setOrganizeOptions = (
+  dbPath: string,
+  options: OrganizeOptions
+) => {
+  return invoke<void>("set_organize_options", { dbPath, options });
+};
+
+export const organizeTracks = (
+  dbPath: string,
+  trackIds: string[],
+  dryRun: boolean
+) => {
+  return invoke<OrganizeReport>("organize_tracks", {
+    dbPath,
+    trackIds,
+    dryRun,
+  });
+};
+
+export const undoLastOrganize = (dbPath: string) => {
+  return invoke<OrganizeReport>("undo_last_organize", { dbPath });
+};
+
+export const rescanLibrary = (dbPath: string, roots: string[]) => {
+  return invoke<RescanSummary>("rescan_library", { dbPath, roots });
+};
+
+export const relinkMissingTracks = (
+  dbPath: string,
+  roots: string[],
+  dryRun: boolean
+) => {
+  return invoke<RelinkReport>("relink_missing_tracks", {
+    dbPath,
+    roots,
+    dryRun,
+  });
+};
+
+export const findDuplicates = (dbPath: string) => {
+  return invoke<DuplicateCluster[]>("find_duplicates", { dbPath });
+};
+
+export const resolveDuplicates = (
+  dbPath: string,
+  keepId: string,
+  removeIds: string[]
+) => {
+  return invoke<DuplicateResolution>("resolve_duplicates", {
+    dbPath,
+    keepId,
+    removeIds,
+  });
+};
+
+export const listArtists = (dbPath: string) => {
+  return invoke<ArtistSummary[]>("list_artists", { dbPath });
+};
+
+export const loadAlbums = (dbPath: string) => {
+  return invoke<AlbumSummary[]>("load_albums", { dbPath });
+};
+
+// ============================================================================
+// Watched Folder Operations
+// ============================================================================
+
+export const listWatchedFolders = (dbPath: string) => {
+  return invoke<WatchedFolder[]>("list_watched_folders", { dbPath });
+};
+
+export const addWatchedFolder = (dbPath: string, path: string) => {
+  return invoke<WatchedFolder>("add_watched_folder", { dbPath, path });
+};
+
+export const removeWatchedFolder = (dbPath: string, folderId: string) => {
+  return invoke<void>("remove_watched_folder", { dbPath, folderId });
+};
+
+export const startFolderWatcher = (dbPath: string) => {
+  return invoke<void>("start_folder_watcher", { dbPath });
+};
+
 // ============================================================================
 // Playlist Operations
 // ============================================================================
@@ -72,6 +449,62 @@ export const removeLastTracksFromPlaylist = (
   });
 };
 
+export const importPlaylistFile = (dbPath: string, path: string) => {
+  return invoke<PlaylistImportReport>("import_playlist_file", { dbPath, path });
+};
+
+export const exportPlaylist = (
+  dbPath: string,
+  playlistId: string,
+  destPath: string,
+  format: PlaylistFileFormat,
+  relativePaths: boolean
+) => {
+  return invoke<number>("export_playlist", {
+    dbPath,
+    playlistId,
+    destPath,
+    format,
+    relativePaths,
+  });
+};
+
+// ============================================================================
+// DJ Software Operations
+// ============================================================================
+
+export const importDjCollection = (dbPath: string, path: string) => {
+  return invoke<CollectionImportReport>("import_dj_collection", { dbPath, path });
+};
+
+export const exportRekordboxXml = (dbPath: string, destPath: string) => {
+  return invoke<RekordboxExportSummary>("export_rekordbox_xml", { dbPath, destPath });
+};
+
+export const loadCuePoints = (dbPath: string, trackId: string) => {
+  return invoke<CuePoint[]>("load_cue_points", { dbPath, trackId });
+};
+
+// ============================================================================
+// Music Player Library Operations
+// ============================================================================
+
+export const importPlayerHistory = (dbPath: string, path: string, policy: StatsPolicy) => {
+  return invoke<PlayerImportReport>("import_player_history", { dbPath, path, policy });
+};
+
+// ============================================================================
+// Filename Tag Guessing
+// ============================================================================
+
+export const previewFilenameTags = (dbPath: string, trackIds: string[], patterns: string[]) => {
+  return invoke<FilenameTagPreview[]>("preview_filename_tags", { dbPath, trackIds, patterns });
+};
+
+export const applyFilenameTags = (dbPath: string, trackIds: string[], patterns: string[]) => {
+  return invoke<number>("apply_filename_tags", { dbPath, trackIds, patterns });
+};
+
 // ============================================================================
 // Backfill Operations
 // ============================================================================
@@ -84,12 +517,91 @@ export const backfillCoverArt = (dbPath: string) => {
   return invoke<number>("backfill_cover_art", { dbPath });
 };
 
+export const backfillAudioProperties = (dbPath: string) => {
+  return invoke<number>("backfill_audio_properties", { dbPath });
+};
+
+export const backfillAlbums = (dbPath: string) => {
+  return invoke<number>("backfill_albums", { dbPath });
+};
+
+export const backfillArtistCredits = (dbPath: string) => {
+  return invoke<number>("backfill_artist_credits", { dbPath });
+};
+
+// ============================================================================
+// Tag Writes
+// ============================================================================
+
+export const listUnsyncedTracks = (dbPath: string) => {
+  return invoke<UnsyncedTrack[]>("list_unsynced_tracks", { dbPath });
+};
+
+export const retryTagWrites = (dbPath: string, trackIds?: string[]) => {
+  return invoke<TagWriteRetryReport>("retry_tag_writes", {
+    dbPath,
+    trackIds: trackIds ?? null,
+  });
+};
+
+// ============================================================================
+// Cover Art Settings
+// ============================================================================
+
+export const getCoverArtPatterns = (dbPath: string) => {
+  return invoke<string[]>("get_cover_art_patterns", { dbPath });
+};
+
+export const setCoverArtPatterns = (dbPath: string, patterns: string[]) => {
+  return invoke<void>("set_cover_art_patterns", { dbPath, patterns });
+};
+
+// ============================================================================
+// Folder Scan Settings
+// ============================================================================
+
+export const getScanOptions = (dbPath: string) => {
+  return invoke<ScanOptions>("get_scan_options", { dbPath });
+};
+
+export const setScanOptions = (dbPath: string, options: ScanOptions) => {
+  return invoke<void>("set_scan_options", { dbPath, options });
+};
+
+// ============================================================================
+// Lyrics Operations
+// ============================================================================
+
+export const getLyrics = (dbPath: string, trackId: string) => {
+  return invoke<TrackLyrics | null>("get_lyrics", { dbPath, trackId });
+};
+
+export const saveLyrics = (dbPath: string, trackId: string, text: string) => {
+  return invoke<TrackLyrics | null>("save_lyrics", { dbPath, trackId, text });
+};
+
+export const writeLyricsToFile = (dbPath: string, trackId: string) => {
+  return invoke<void>("write_lyrics_to_file", { dbPath, trackId });
+};
+
+// ============================================================================
+// Compilation Settings
+// ============================================================================
+
+export const getVariousArtistsName = (dbPath: string) => {
+  return invoke<string>("get_various_artists_name", { dbPath });
+};
+
+export const setVariousArtistsName = (dbPath: string, name: string) => {
+  return invoke<void>("set_various_artists_name", { dbPath, name });
+};
+
 // ============================================================================
 // Recently Played Operations
 // ============================================================================
 
 export const loadRecentlyPlayed = (dbPath: string, limit: number = 50) => {
-  return invoke<import("./importApi").ImportedTrack[]>("load_recently_played", {
+  return invoke<ImportedTrack[]>("load_recently_played", {
     dbPath,
     limit,
   });
@@ -33,6 +33,16 @@ export type ImportedTrack = {
   disc_total?: number;
   last_played_at?: string;
   play_count: number;
+  is_missing: boolean;
+  import_status: "staged" | "accepted";
+  sample_rate?: number;
+  bit_depth?: number;
+  channels?: number;
+  codec?: string;
+  container?: string;
+  is_lossless?: boolean;
+  bitrate_mode?: "cbr" | "vbr";
+  file_size?: number;
 };
 
 export type LibrarySnapshot = {
@@ -40,10 +50,44 @@ export type LibrarySnapshot = {
   inbox: ImportedTrack[];
 };
 
+export type ImportIssue = {
+  path: string;
+  reason: string;
+};
+
+export type ImportReport = {
+  job_id: string;
+  cancelled: boolean;
+  imported: ImportedTrack[];
+  skipped_duplicates: ImportIssue[];
+  unsupported: ImportIssue[];
+  failed: ImportIssue[];
+};
+
+export type ImportReportSummary = {
+  job_id: string;
+  cancelled: boolean;
+  created_at: number;
+  imported: number;
+  skipped_duplicates: ImportIssue[];
+  unsupported: ImportIssue[];
+  failed: ImportIssue[];
+};
+
+export type ImportJob = {
+  id: string;
+  created_at: number;
+  status: "running" | "cancelled";
+  total: number;
+  pending: number;
+};
+
 export type PlaylistSnapshot = {
   playlists: {
     id: string;
     name: string;
+    parent_id: string | null;
+    is_folder: boolean;
     track_ids: string[];
   }[];
 };
@@ -53,12 +97,37 @@ export type PlaylistSnapshot = {
 // ============================================================================
 
 export const importFiles = (dbPath: string, paths: string[]) => {
-  return invoke<ImportedTrack[]>("import_files", {
+  return invoke<ImportReport>("import_files", {
     paths,
     dbPath,
   });
 };
 
+export const resumeImportJob = (dbPath: string, jobId: string) => {
+  return invoke<ImportReport>("resume_import_job", {
+    jobId,
+    dbPath,
+  });
+};
+
+export const cancelImport = (jobId: string) => {
+  return invoke<boolean>("cancel_import", { jobId });
+};
+
+export const listImportJobs = (dbPath: string) => {
+  return invoke<ImportJob[]>("list_import_jobs", { dbPath });
+};
+
+export const discardImportJob = (dbPath: string, jobId: string) => {
+  return invoke("discard_import_job", { dbPath, jobId });
+};
+
+export const loadLastImportReport = (dbPath: string) => {
+  return invoke<ImportReportSummary | null>("load_last_import_report", {
+    dbPath,
+  });
+};
+
 // ============================================================================
 // Type Conversion
 // ============================================================================
@@ -94,4 +163,13 @@ export const importedTrackToTrack = (imported: ImportedTrack): Track => ({
   discTotal: imported.disc_total,
   lastPlayedAt: imported.last_played_at,
   playCount: imported.play_count,
+  isMissing: imported.is_missing,
+  sampleRate: imported.sample_rate,
+  bitDepth: imported.bit_depth,
+  channels: imported.channels,
+  codec: imported.codec,
+  container: imported.container,
+  isLossless: imported.is_lossless,
+  bitrateMode: imported.bitrate_mode,
+  fileSize: imported.file_size,
 });
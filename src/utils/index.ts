@@ -22,20 +22,96 @@ export {
   acceptTracks,
   unacceptTracks,
   rejectTracks,
+  getInboxRules,
+  setInboxRules,
+  applyInboxRules,
+  getOrganizeOptions,
+  setOrganizeOptions,
+  organizeTracks,
+  undoLastOrganize,
+  rescanLibrary,
+  relinkMissingTracks,
+  findDuplicates,
+  resolveDuplicates,
+  listArtists,
+  loadAlbums,
+  listWatchedFolders,
+  addWatchedFolder,
+  removeWatchedFolder,
+  startFolderWatcher,
   loadPlaylists,
   createPlaylist,
   deletePlaylist,
   addTracksToPlaylist,
   removeLastTracksFromPlaylist,
+  importPlaylistFile,
+  exportPlaylist,
+  importDjCollection,
+  exportRekordboxXml,
+  loadCuePoints,
+  importPlayerHistory,
+  previewFilenameTags,
+  applyFilenameTags,
   backfillSearchText,
   backfillCoverArt,
+  backfillAudioProperties,
+  listUnsyncedTracks,
+  retryTagWrites,
+  getCoverArtPatterns,
+  setCoverArtPatterns,
+  getScanOptions,
+  setScanOptions,
+  getVariousArtistsName,
+  setVariousArtistsName,
+  getLyrics,
+  saveLyrics,
+  writeLyricsToFile,
   loadRecentlyPlayed,
   recordTrackPlay,
+  type RescanSummary,
+  type RelinkReport,
+  type InboxRuleField,
+  type InboxRule,
+  type OrganizeMode,
+  type OrganizeOptions,
+  type OrganizeReport,
+  type InboxRulesReport,
+  type UnsyncedTrack,
+  type TagWriteRetryReport,
+  type DuplicateCopy,
+  type DuplicateCluster,
+  type DuplicateResolution,
+  type ArtistSummary,
+  type AlbumSummary,
+  type LyricLine,
+  type TrackLyrics,
+  type LyricLineEvent,
+  type PlaylistFileFormat,
+  type PlaylistImportReport,
+  type CollectionImportReport,
+  type RekordboxExportSummary,
+  type CuePoint,
+  type StatsPolicy,
+  type PlayerImportReport,
+  type FilenameTagPreview,
+  type ScanOptions,
+  type UnresolvedEntry,
+  type WatchedFolder,
+  type LibraryChanged,
 } from "./database";
 export {
   importFiles,
+  resumeImportJob,
+  cancelImport,
+  listImportJobs,
+  discardImportJob,
+  loadLastImportReport,
   importedTrackToTrack,
   type ImportedTrack,
+  type ImportIssue,
+  type ImportJob,
+  type ImportReport,
+  type ImportReportSummary,
   type LibrarySnapshot,
   type PlaylistSnapshot,
 } from "./importApi";
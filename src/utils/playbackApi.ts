@@ -32,7 +32,8 @@ export const playbackPlayFile = (
   sourcePath: string,
   durationHint: number,
   coverArtPath?: string,
-  coverArtThumbPath?: string
+  coverArtThumbPath?: string,
+  dbPath?: string
 ) => {
   return invoke<void>("playback_play_file", {
     id,
@@ -43,6 +44,7 @@ export const playbackPlayFile = (
     durationHint,
     coverArtPath,
     coverArtThumbPath,
+    dbPath,
   });
 };
 